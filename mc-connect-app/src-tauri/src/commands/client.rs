@@ -1,12 +1,13 @@
+use mc_connect_core::encryption::{fingerprint, short_fingerprint, CryptoError, RsaPublicKey};
 use mc_connect_core::models::packet::{Protocol, ServerInfoResponsePayload};
-use mc_connect_core::services::ws_client::TunnelStats;
-use mc_connect_core::WsClientService;
-use std::sync::atomic::Ordering;
+use mc_connect_core::services::ws_client::{ConnectionInfo, KnownServers, TunnelStats};
+use mc_connect_core::{t, McConnectError, WsClientService};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, Runtime};
-use tokio::time::{interval, Duration};
+use tokio::net::TcpListener;
 
-use crate::models::{MappingInfo, StatsEvent, TunnelStatus};
+use crate::commands::config::get_known_servers_path;
+use crate::models::{KeyFingerprint, KeyRotatedEvent, MappingInfo, StatsEvent, TunnelStatus};
 use crate::state::{TunnelHandle, STATE};
 use crate::utils::emit_log;
 
@@ -18,17 +19,14 @@ pub async fn get_server_info<R: Runtime>(
     emit_log(
         &app_handle,
         "INFO",
-        format!("サーバー情報を取得中: {}", ws_url),
+        t!("app.fetching_server_info", url = ws_url),
     );
     match WsClientService::get_server_info(&ws_url).await {
         Ok(info) => {
             emit_log(
                 &app_handle,
                 "SUCCESS",
-                format!(
-                    "サーバー情報を取得しました。許可ポート: {}個",
-                    info.allowed_ports.len()
-                ),
+                t!("app.server_info_fetched", count = info.allowed_ports.len()),
             );
             Ok(info)
         }
@@ -36,30 +34,32 @@ pub async fn get_server_info<R: Runtime>(
             emit_log(
                 &app_handle,
                 "ERROR",
-                format!("サーバー情報の取得に失敗: {}", e),
+                t!("app.server_info_failed", error = e),
             );
             Err(e.to_string())
         }
     }
 }
 
+#[tauri::command]
+pub async fn get_key_fingerprint(public_key: String) -> Result<KeyFingerprint, String> {
+    use base64::{engine::general_purpose, Engine as _};
+    let der = general_purpose::STANDARD
+        .decode(public_key.trim())
+        .map_err(|e| t!("app.public_key_decode_failed", error = e))?;
+    RsaPublicKey::from_der(&der).map_err(|e| e.to_string())?;
+    Ok(KeyFingerprint {
+        fingerprint: fingerprint(&der),
+        short_fingerprint: short_fingerprint(&der),
+    })
+}
+
 #[tauri::command]
 pub async fn start_mapping<R: Runtime>(
     app_handle: AppHandle<R>,
     info: MappingInfo,
 ) -> Result<(), String> {
-    emit_log(
-        &app_handle,
-        "INFO",
-        format!("トンネル開始命令を受信: {}", info.id),
-    );
-
-    let mut state = STATE.lock().await;
-
-    if let Some(handle) = state.tunnels.remove(&info.id) {
-        handle.join_handle.abort();
-        handle.stats_handle.abort();
-    }
+    emit_log(&app_handle, "INFO", t!("app.start_requested", id = info.id));
 
     let app = app_handle.clone();
     let mapping_id = info.id.clone();
@@ -70,39 +70,66 @@ pub async fn start_mapping<R: Runtime>(
     let proto_str = info.protocol.clone();
     let ping_interval = info.ping_interval;
     let public_key_str = info.public_key.clone();
+    let trust_on_first_use = info.trust_on_first_use;
 
     emit_log(
         &app,
         "INFO",
-        format!(
-            "トンネルを開始します: [{}] {}:{} -> {} (Ping: {}s)",
-            mapping_id, bind_addr, local_port, ws_url, ping_interval
+        t!(
+            "app.tunnel_starting",
+            id = mapping_id,
+            bind = bind_addr,
+            port = local_port,
+            url = ws_url,
+            ping = ping_interval
         ),
     );
 
+    // "proxy" は固定のポートを持たず、許可されたすべての TCP ポートへ中継する SOCKS5 / HTTP CONNECT プロキシ
     let proto = match proto_str.to_lowercase().as_str() {
-        "tcp" => Protocol::TCP,
-        "udp" => Protocol::UDP,
-        _ => return Err(format!("Unsupported protocol: {}", proto_str)),
+        "tcp" => Some(Protocol::TCP),
+        "udp" => Some(Protocol::UDP),
+        "proxy" => None,
+        _ => return Err(t!("app.unsupported_protocol", protocol = proto_str)),
     };
 
-    // 公開鍵のパース
-    let server_public_key = if let Some(key_str) = public_key_str {
+    // 公開鍵のパース (TOFU の場合はゲートウェイが署名付きで提示した鍵を既知サーバーの記録と照合する)
+    let server_public_key = if trust_on_first_use {
+        let mut known = KnownServers::load(get_known_servers_path()?).map_err(|e| e.to_string())?;
+        let der = WsClientService::trust_on_first_use(&ws_url, &mut known)
+            .await
+            .map_err(|e| t!("app.server_key_verify_failed", error = e))?;
+        Arc::new(RsaPublicKey::from_der(&der).map_err(|e| e.to_string())?)
+    } else if let Some(key_str) = public_key_str {
         if key_str.trim().is_empty() {
-            return Err("公開鍵が空です。".into());
+            return Err(t!("app.public_key_empty"));
         }
         use base64::{engine::general_purpose, Engine as _};
         let der = general_purpose::STANDARD
             .decode(key_str.trim())
-            .map_err(|e| format!("公開鍵のデコードに失敗: {}", e))?;
-        Arc::new(
-            mc_connect_core::encryption::RsaKeyPair::from_public_der(&der)
-                .map_err(|e| e.to_string())?,
-        )
+            .map_err(|e| t!("app.public_key_decode_failed", error = e))?;
+        Arc::new(RsaPublicKey::from_der(&der).map_err(|e| e.to_string())?)
     } else {
-        return Err("公開鍵が設定されていません。".into());
+        return Err(t!("app.public_key_missing"));
     };
 
+    emit_log(
+        &app,
+        "INFO",
+        t!(
+            "app.server_key_fingerprint",
+            id = mapping_id,
+            fingerprint = server_public_key.short_fingerprint()
+        ),
+    );
+
+    let mut state = STATE.lock().await;
+
+    if let Some(handle) = state.tunnels.remove(&info.id) {
+        handle.join_handle.abort();
+        handle.stats_handle.abort();
+    }
+
     let stats = Arc::new(TunnelStats::new());
     let (ping_tx, ping_rx) = tokio::sync::mpsc::unbounded_channel();
 
@@ -110,36 +137,19 @@ pub async fn start_mapping<R: Runtime>(
     let app_stats = app.clone();
     let mapping_id_stats = mapping_id.clone();
 
-    // Stats reporting loop
+    // Stats reporting loop (速度はコアの計測タスクが更新し、計測のたびに通知される)
     let stats_handle = tokio::spawn(async move {
-        let mut interval = interval(Duration::from_millis(1000));
-        let mut last_up = 0;
-        let mut last_down = 0;
-
-        loop {
-            interval.tick().await;
-
-            let cur_up = stats_clone.upload_total.load(Ordering::Relaxed);
-            let cur_down = stats_clone.download_total.load(Ordering::Relaxed);
-
-            let speed_up = cur_up.saturating_sub(last_up);
-            let speed_down = cur_down.saturating_sub(last_down);
-
-            stats_clone.upload_speed.store(speed_up, Ordering::Relaxed);
-            stats_clone
-                .download_speed
-                .store(speed_down, Ordering::Relaxed);
-
-            last_up = cur_up;
-            last_down = cur_down;
-
-            let snapshot = stats_clone.get_snapshot();
+        let mut updates = stats_clone.subscribe();
+        while updates.changed().await.is_ok() {
+            let snapshot = updates.borrow_and_update().clone();
             if app_stats
                 .emit(
                     "tunnel-stats",
                     StatsEvent {
                         id: mapping_id_stats.clone(),
                         stats: snapshot,
+                        gateway: stats_clone.gateway_snapshot(),
+                        connections: stats_clone.connections.list(),
                     },
                 )
                 .is_err()
@@ -149,7 +159,23 @@ pub async fn start_mapping<R: Runtime>(
         }
     });
 
+    let tunnel_stats = Arc::clone(&stats);
     let handle = tokio::spawn(async move {
+        let Some(proto) = proto else {
+            run_proxy(
+                app,
+                mapping_id,
+                bind_addr,
+                local_port,
+                ws_url,
+                stats,
+                ping_rx,
+                server_public_key,
+            )
+            .await;
+            return;
+        };
+
         // Step 1: Handshake (while frontend is still 'loading')
         match WsClientService::check_connectivity(
             &ws_url,
@@ -159,19 +185,33 @@ pub async fn start_mapping<R: Runtime>(
         )
         .await
         {
-            Ok(_) => {
+            Ok(rotated_key) => {
+                // ゲートウェイが鍵をローテーションしている場合は、検証済みの新しい公開鍵に切り替えて UI に保存させる
+                let server_public_key = match rotated_key {
+                    Some(der) => {
+                        use base64::{engine::general_purpose, Engine as _};
+                        let public_key = general_purpose::STANDARD.encode(&der);
+                        emit_log(&app, "WARN", t!("app.server_key_rotated", id = mapping_id));
+                        let _ = app.emit(
+                            "server-key-rotated",
+                            KeyRotatedEvent {
+                                id: mapping_id.clone(),
+                                public_key,
+                            },
+                        );
+                        match RsaPublicKey::from_der(&der) {
+                            Ok(key) => Arc::new(key),
+                            Err(_) => server_public_key,
+                        }
+                    }
+                    None => server_public_key,
+                };
+
                 // Step 2: Handshake Success -> Notify UI
-                let _ = app.emit(
-                    "tunnel-status",
-                    TunnelStatus {
-                        id: mapping_id.clone(),
-                        running: true,
-                        message: "接続完了".into(),
-                    },
-                );
+                emit_connected(&app, &mapping_id);
 
                 // Step 3: Run the server loop
-                if let Err(e) = WsClientService::run_tunnel_server(
+                let result = WsClientService::run_tunnel_server(
                     bind_addr,
                     local_port,
                     ws_url,
@@ -181,52 +221,10 @@ pub async fn start_mapping<R: Runtime>(
                     ping_rx,
                     server_public_key,
                 )
-                .await
-                {
-                    emit_log(
-                        &app,
-                        "ERROR",
-                        format!("トンネルエラー [{}]: {}", mapping_id, e),
-                    );
-                    let _ = app.emit(
-                        "tunnel-status",
-                        TunnelStatus {
-                            id: mapping_id,
-                            running: false,
-                            message: format!("エラー: {}", e),
-                        },
-                    );
-                } else {
-                    emit_log(
-                        &app,
-                        "INFO",
-                        format!("トンネルセッション終了: {}", mapping_id),
-                    );
-                    let _ = app.emit(
-                        "tunnel-status",
-                        TunnelStatus {
-                            id: mapping_id,
-                            running: false,
-                            message: "トンネルが停止しました".into(),
-                        },
-                    );
-                }
-            }
-            Err(e) => {
-                emit_log(
-                    &app,
-                    "ERROR",
-                    format!("接続テスト失敗 [{}]: {}", mapping_id, e),
-                );
-                let _ = app.emit(
-                    "tunnel-status",
-                    TunnelStatus {
-                        id: mapping_id,
-                        running: false,
-                        message: format!("接続失敗: {}", e),
-                    },
-                );
+                .await;
+                emit_session_ended(&app, mapping_id, result);
             }
+            Err(e) => emit_connect_failed(&app, mapping_id, e),
         }
     });
 
@@ -236,11 +234,124 @@ pub async fn start_mapping<R: Runtime>(
             join_handle: handle,
             stats_handle,
             ping_tx,
+            stats: tunnel_stats,
         },
     );
     Ok(())
 }
 
+/// [run_proxy]
+/// SOCKS5 / HTTP CONNECT プロキシとしてマッピングを実行します。
+///
+/// ゲートウェイの許可ポートを取得してから待ち受けを開始し、接続ごとに要求された宛先ポートへトンネルを張ります。
+#[allow(clippy::too_many_arguments)]
+async fn run_proxy<R: Runtime>(
+    app: AppHandle<R>,
+    mapping_id: String,
+    bind_addr: String,
+    local_port: u16,
+    ws_url: String,
+    stats: Arc<TunnelStats>,
+    ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+    server_public_key: Arc<RsaPublicKey>,
+) {
+    // Step 1: 許可ポートの取得と待ち受けの開始 (while frontend is still 'loading')
+    let info = match WsClientService::get_server_info(&ws_url).await {
+        Ok(info) => info,
+        Err(e) => return emit_connect_failed(&app, mapping_id, e),
+    };
+    let listener = match TcpListener::bind(format!("{}:{}", bind_addr, local_port)).await {
+        Ok(listener) => listener,
+        Err(e) => return emit_connect_failed(&app, mapping_id, e.into()),
+    };
+    emit_log(
+        &app,
+        "INFO",
+        t!("client.proxy_started", host = bind_addr, port = local_port),
+    );
+
+    // Step 2: Notify UI
+    emit_connected(&app, &mapping_id);
+
+    // Step 3: Run the proxy loop
+    let result = WsClientService::run_proxy_listener(
+        listener,
+        ws_url,
+        info.allowed_ports,
+        stats,
+        ping_rx,
+        server_public_key,
+    )
+    .await;
+    emit_session_ended(&app, mapping_id, result);
+}
+
+/// ゲートウェイとの接続を確認できたことを UI へ通知します。
+fn emit_connected<R: Runtime>(app: &AppHandle<R>, mapping_id: &str) {
+    let _ = app.emit(
+        "tunnel-status",
+        TunnelStatus {
+            id: mapping_id.to_string(),
+            running: true,
+            message: t!("app.status_connected"),
+            code: None,
+        },
+    );
+}
+
+/// 接続の確認に失敗したことを UI へ通知します。
+fn emit_connect_failed<R: Runtime>(app: &AppHandle<R>, mapping_id: String, e: CryptoError) {
+    emit_log(
+        app,
+        "ERROR",
+        t!("app.connect_test_failed", id = mapping_id, error = e),
+    );
+    let _ = app.emit(
+        "tunnel-status",
+        TunnelStatus {
+            id: mapping_id,
+            running: false,
+            message: t!("app.status_connect_failed", error = e),
+            code: McConnectError::from_boxed(&e).map(McConnectError::code),
+        },
+    );
+}
+
+/// 待ち受けの終了 (エラーまたは停止) を UI へ通知します。
+fn emit_session_ended<R: Runtime>(
+    app: &AppHandle<R>,
+    mapping_id: String,
+    result: Result<(), CryptoError>,
+) {
+    if let Err(e) = result {
+        emit_log(
+            app,
+            "ERROR",
+            t!("app.tunnel_error", id = mapping_id, error = e),
+        );
+        let _ = app.emit(
+            "tunnel-status",
+            TunnelStatus {
+                id: mapping_id,
+                running: false,
+                message: t!("app.status_error", error = e),
+                code: McConnectError::from_boxed(&e).map(McConnectError::code),
+            },
+        );
+    } else {
+        emit_log(app, "INFO", t!("app.tunnel_session_ended", id = mapping_id));
+        let _ = app.emit(
+            "tunnel-status",
+            TunnelStatus {
+                id: mapping_id,
+                running: false,
+                message: t!("app.status_tunnel_stopped"),
+                code: None,
+            },
+        );
+    }
+}
+
 #[tauri::command]
 pub async fn stop_mapping<R: Runtime>(app_handle: AppHandle<R>, id: String) -> Result<(), String> {
     let mut state = STATE.lock().await;
@@ -248,7 +359,7 @@ pub async fn stop_mapping<R: Runtime>(app_handle: AppHandle<R>, id: String) -> R
         emit_log(
             &app_handle,
             "INFO",
-            format!("トンネルを手動で停止しました: {}", id),
+            t!("app.tunnel_stopped_manually", id = id),
         );
         handle.join_handle.abort();
         handle.stats_handle.abort();
@@ -257,7 +368,8 @@ pub async fn stop_mapping<R: Runtime>(app_handle: AppHandle<R>, id: String) -> R
             TunnelStatus {
                 id: id,
                 running: false,
-                message: "停止しました".into(),
+                message: t!("app.status_stopped"),
+                code: None,
             },
         );
     }
@@ -277,6 +389,37 @@ pub async fn trigger_ping(id: String) -> Result<(), String> {
         let _ = handle.ping_tx.send(());
         Ok(())
     } else {
-        Err("Tunnel not running".into())
+        Err(t!("app.tunnel_not_running"))
     }
 }
+
+#[tauri::command]
+pub async fn list_mapping_connections(id: String) -> Result<Vec<ConnectionInfo>, String> {
+    let state = STATE.lock().await;
+    match state.tunnels.get(&id) {
+        Some(handle) => Ok(handle.stats.connections.list()),
+        None => Err(t!("app.tunnel_not_running")),
+    }
+}
+
+#[tauri::command]
+pub async fn close_mapping_connection<R: Runtime>(
+    app_handle: AppHandle<R>,
+    id: String,
+    connection_id: u64,
+) -> Result<(), String> {
+    let state = STATE.lock().await;
+    let handle = state
+        .tunnels
+        .get(&id)
+        .ok_or_else(|| t!("app.tunnel_not_running"))?;
+    if !handle.stats.connections.close(connection_id) {
+        return Err(t!("app.connection_not_found", connection = connection_id));
+    }
+    emit_log(
+        &app_handle,
+        "INFO",
+        t!("app.connection_closed", connection = connection_id, id = id),
+    );
+    Ok(())
+}
@@ -1,9 +1,20 @@
-use crate::models::AppPersistConfig;
+use crate::models::{AppPersistConfig, MappingConfig};
+use crate::utils::emit_log;
+use mc_connect_core::encryption::encrypt_private_key;
+use mc_connect_core::i18n;
+use mc_connect_core::models::packet::{ClientExportConfig, Protocol};
+use mc_connect_core::t;
+use std::collections::HashSet;
 use std::fs;
+use std::net::{TcpListener, UdpSocket};
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Runtime};
 
 const CONFIG_FILE_NAME: &str = "mc-connect-config.json";
+const KNOWN_SERVERS_FILE_NAME: &str = "mc-connect-known-servers.json";
+/// インポート時、リモートと同じポートが使えない場合に続けて試すポートの数
+const IMPORT_PORT_SEARCH_RANGE: u16 = 100;
 
 fn get_config_path<R: Runtime>(_app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
     std::env::current_exe()
@@ -12,29 +23,80 @@ fn get_config_path<R: Runtime>(_app_handle: &AppHandle<R>) -> Result<PathBuf, St
             path.push(CONFIG_FILE_NAME);
             path
         })
-        .map_err(|e| format!("実行ファイルのディレクトリ取得に失敗しました: {}", e))
+        .map_err(|e| t!("app.exe_dir_failed", error = e))
 }
 
+/// TOFU で記録した既知サーバーの保存先 (設定ファイルと同じディレクトリ)
+pub(crate) fn get_known_servers_path() -> Result<PathBuf, String> {
+    std::env::current_exe()
+        .map(|mut path| {
+            path.pop();
+            path.push(KNOWN_SERVERS_FILE_NAME);
+            path
+        })
+        .map_err(|e| t!("app.exe_dir_failed", error = e))
+}
+
+/// [apply_saved_locale]
+/// 保存済みの設定から表示言語を読み込んで適用します。設定がない場合や読み込めない場合は既定 (英語) のままです。
+pub(crate) fn apply_saved_locale<R: Runtime>(app_handle: &AppHandle<R>) {
+    let config = get_config_path(app_handle)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str::<AppPersistConfig>(&json).ok());
+    if let Some(config) = config {
+        i18n::set_locale(config.app_settings.language);
+    }
+}
+
+/// [save_config]
+/// 設定を保存します。
+///
+/// `passphrase` を指定した場合、平文のサーバー秘密鍵は Argon2id + AES-256-GCM で暗号化してから書き出します。
+/// 既に暗号化されている秘密鍵 (`key_encryption` が設定されているもの) はそのまま保存します。
 #[tauri::command]
 pub async fn save_config<R: Runtime>(
     app_handle: AppHandle<R>,
-    config: AppPersistConfig,
+    mut config: AppPersistConfig,
+    passphrase: Option<String>,
 ) -> Result<(), String> {
     let path = get_config_path(&app_handle)?;
 
+    let server = &mut config.server_config;
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+    let private_key = server
+        .private_key
+        .clone()
+        .filter(|_| server.key_encryption.is_none());
+    if let (Some(passphrase), Some(private_key)) = (passphrase, private_key) {
+        // Argon2id の鍵導出は重いため、非同期ランタイムのスレッドを塞がないようにする
+        let (sealed, params) = tauri::async_runtime::spawn_blocking(move || {
+            encrypt_private_key(private_key.trim(), &passphrase)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| t!("app.private_key_encrypt_failed", error = e))?;
+        server.private_key = Some(sealed);
+        server.key_encryption = Some(params);
+    }
+
     // ディレクトリが存在しない場合は作成
     if let Some(parent) = path.parent() {
         if !parent.exists() {
             fs::create_dir_all(parent)
-                .map_err(|e| format!("設定ディレクトリの作成に失敗しました: {}", e))?;
+                .map_err(|e| t!("app.config_dir_create_failed", error = e))?;
         }
     }
 
     let json = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("設定のシリアライズに失敗しました: {}", e))?;
+        .map_err(|e| t!("app.config_serialize_failed", error = e))?;
 
-    fs::write(path, json).map_err(|e| format!("設定ファイルの書き込みに失敗しました: {}", e))?;
+    // 秘密鍵を含むため、CLI と同じく所有者のみ読み書きできる権限で書き込む
+    mc_connect_core::fs::write_private_file(&path, json.as_bytes())
+        .await
+        .map_err(|e| t!("app.config_write_failed", error = e))?;
 
+    i18n::set_locale(config.app_settings.language);
     Ok(())
 }
 
@@ -48,11 +110,111 @@ pub async fn load_config<R: Runtime>(
         return Ok(None);
     }
 
-    let json = fs::read_to_string(path)
-        .map_err(|e| format!("設定ファイルの読み込みに失敗しました: {}", e))?;
+    let json = fs::read_to_string(path).map_err(|e| t!("app.config_read_failed", error = e))?;
 
-    let config: AppPersistConfig = serde_json::from_str(&json)
-        .map_err(|e| format!("設定のデシリアライズに失敗しました: {}", e))?;
+    let config: AppPersistConfig =
+        serde_json::from_str(&json).map_err(|e| t!("app.config_invalid", error = e))?;
 
+    i18n::set_locale(config.app_settings.language);
     Ok(Some(config))
 }
+
+/// [import_client_config]
+/// サーバーが配布した設定ファイル (ClientExportConfig) を検証し、マッピングごとの接続設定を作成します。
+///
+/// ローカルの待ち受けポートはリモートと同じ番号を優先し、使用中の場合や
+/// `reserved_ports` (既存のマッピングが使用するポート) と重なる場合は空いているポートを割り当てます。
+/// 作成した設定は返すだけで、一覧への追加と保存はフロントエンドで行います。
+#[tauri::command]
+pub async fn import_client_config<R: Runtime>(
+    app_handle: AppHandle<R>,
+    config_json: String,
+    reserved_ports: Vec<u16>,
+) -> Result<Vec<MappingConfig>, String> {
+    let config: ClientExportConfig =
+        serde_json::from_str(&config_json).map_err(|e| t!("app.import_invalid_json", error = e))?;
+    config
+        .validate()
+        .map_err(|e| t!("app.import_invalid_config", error = e))?;
+
+    let id_base = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let mut reserved: HashSet<u16> = reserved_ports.into_iter().collect();
+    let mut mappings = Vec::with_capacity(config.mappings.len());
+
+    for (i, mapping) in config.mappings.iter().enumerate() {
+        let protocol = format!("{:?}", mapping.protocol);
+        let local_port =
+            pick_local_port(mapping.port, &mapping.protocol, &reserved).ok_or_else(|| {
+                t!(
+                    "app.import_no_free_port",
+                    protocol = protocol,
+                    port = mapping.port
+                )
+            })?;
+        reserved.insert(local_port);
+        if local_port != mapping.port {
+            emit_log(
+                &app_handle,
+                "WARN",
+                t!(
+                    "app.import_port_reassigned",
+                    protocol = protocol,
+                    port = mapping.port,
+                    local_port = local_port
+                ),
+            );
+        }
+
+        mappings.push(MappingConfig {
+            id: format!("{:x}{:02x}", id_base, i),
+            name: format!("{} ({}:{})", config.name, protocol, mapping.port),
+            ws_url: config.ws_url.trim().to_string(),
+            bind_addr: "127.0.0.1".to_string(),
+            local_port,
+            remote_port: mapping.port,
+            protocol,
+            public_key: Some(config.public_key.trim().to_string()),
+            ping_interval: 5,
+            trust_on_first_use: false,
+        });
+    }
+
+    emit_log(
+        &app_handle,
+        "SUCCESS",
+        t!(
+            "app.config_imported",
+            count = mappings.len(),
+            name = config.name
+        ),
+    );
+    Ok(mappings)
+}
+
+/// リモートのポートから順に、予約されておらずローカルで待ち受けられるポートを探します。
+/// 範囲内に見つからない場合は OS に空きポートを割り当てさせます。
+fn pick_local_port(preferred: u16, protocol: &Protocol, reserved: &HashSet<u16>) -> Option<u16> {
+    let candidates =
+        (0..IMPORT_PORT_SEARCH_RANGE).filter_map(|offset| preferred.checked_add(offset));
+    for port in candidates {
+        if !reserved.contains(&port) && probe_port(port, protocol).is_some() {
+            return Some(port);
+        }
+    }
+    (0..8)
+        .filter_map(|_| probe_port(0, protocol))
+        .find(|port| !reserved.contains(port))
+}
+
+/// 127.0.0.1 の指定ポートで待ち受けられるか試し、実際に割り当てられたポートを返します。
+fn probe_port(port: u16, protocol: &Protocol) -> Option<u16> {
+    let addr = ("127.0.0.1", port);
+    match protocol {
+        Protocol::TCP => TcpListener::bind(addr).and_then(|l| l.local_addr()).ok(),
+        Protocol::UDP => UdpSocket::bind(addr).and_then(|s| s.local_addr()).ok(),
+    }
+    .map(|addr| addr.port())
+}
@@ -1,10 +1,13 @@
-use mc_connect_core::encryption::RsaKeyPair;
-use mc_connect_core::models::packet::{AllowedPort, Protocol as Proto};
+use mc_connect_core::encryption::{decrypt_private_key, RsaKeyPair, RsaPublicKey, ServerKeyring};
+use mc_connect_core::models::packet::{AllowedPort, ClientExportConfig, Protocol as Proto};
+use mc_connect_core::services::proxy::{SessionInfo, SessionRegistry};
+use mc_connect_core::t;
 use std::sync::Arc;
-use tauri::{AppHandle, Runtime};
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::models::StartServerConfig;
-use crate::state::STATE;
+use crate::models::{ExportClientConfigRequest, ServerStatus, StartServerConfig};
+use crate::state::{ServerHandle, STATE};
 use crate::utils::emit_log;
 
 #[tauri::command]
@@ -25,71 +28,152 @@ pub async fn generate_server_keys() -> Result<(String, String), String> {
 pub async fn start_server<R: Runtime>(
     app_handle: AppHandle<R>,
     config: StartServerConfig,
-) -> Result<(), String> {
+) -> Result<ServerStatus, String> {
     let port = config.port;
+    let bind_host = match config.bind_host.trim() {
+        "" => "0.0.0.0".to_string(),
+        host => host.to_string(),
+    };
     let allowed_ports = config.allowed_ports;
-    let private_key_b64 = config.private_key_b64;
+    let private_key_b64 = match config.key_encryption {
+        Some(params) => {
+            let passphrase = config
+                .passphrase
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| t!("app.passphrase_required"))?;
+            let sealed = config.private_key_b64;
+            // Argon2id の鍵導出は重いため、非同期ランタイムのスレッドを塞がないようにする
+            tauri::async_runtime::spawn_blocking(move || {
+                decrypt_private_key(sealed.trim(), &params, &passphrase)
+            })
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| t!("app.private_key_unlock_failed", error = e))?
+        }
+        None => config.private_key_b64,
+    };
     let encryption_type = config.encryption_type;
+    let allow_legacy_key_wrap = config.allow_legacy_key_wrap;
 
     let mut state = STATE.lock().await;
-    if state.server_handle.is_some() {
-        return Err("Server is already running".into());
+    if state.server_handle.as_ref().is_some_and(|h| h.is_running()) {
+        return Err(t!("app.server_already_running"));
     }
 
     use base64::{engine::general_purpose, Engine as _};
 
     if encryption_type != "RSA" {
-        return Err(format!(
-            "暗号化方式 {} は現在バックエンドで未実装です。RSAを使用してください。",
-            encryption_type
+        return Err(t!(
+            "app.encryption_unsupported",
+            encryption = encryption_type
         ));
     }
 
     let der = general_purpose::STANDARD
         .decode(private_key_b64.trim())
-        .map_err(|e| format!("秘密鍵のデコードに失敗: {}", e))?;
-    let key_pair = Arc::new(RsaKeyPair::from_private_der(&der).map_err(|e| e.to_string())?);
-
-    let mut ports = Vec::new();
-    for (p, proto_str) in allowed_ports {
-        let protocol = match proto_str.to_lowercase().as_str() {
-            "tcp" => Proto::TCP,
-            "udp" => Proto::UDP,
-            _ => continue,
-        };
-        ports.push(AllowedPort { port: p, protocol });
-    }
+        .map_err(|e| t!("app.private_key_decode_failed", error = e))?;
+    let key_pair = RsaKeyPair::from_private_der(&der).map_err(|e| e.to_string())?;
+    let mut server_keys = ServerKeyring::new(key_pair);
+    server_keys.set_allow_legacy_key_wrap(allow_legacy_key_wrap);
+    let server_keys = Arc::new(server_keys);
+
+    let ports = parse_allowed_ports(allowed_ports);
 
     let app = app_handle.clone();
     emit_log(
         &app,
         "INFO",
-        format!(
-            "サーバーを起動します (Port: {}, Protocol: {})",
-            port, encryption_type
+        t!(
+            "app.server_starting",
+            host = bind_host,
+            port = port,
+            encryption = encryption_type
         ),
     );
 
-    let handle = tokio::spawn(async move {
-        // Actix server usually needs to run on its own thread if we want it to be responsive
-        // and not block the tokio executor, but HttpServer::run().await is fine in tokio.
-        // If we want to be able to stop it via JoinHandle::abort, it needs to be awaited here.
-        match mc_connect_core::start_server("0.0.0.0", port, ports, key_pair).await {
-            Ok(_) => emit_log(&app, "INFO", "サーバーが終了しました".into()),
-            Err(e) => emit_log(&app, "ERROR", format!("サーバーエラー: {}", e)),
+    let sessions = Arc::new(SessionRegistry::new());
+    let options = mc_connect_core::GatewayOptions {
+        sessions: Some(Arc::clone(&sessions)),
+        ..Default::default()
+    };
+
+    // バインドはここで行い、ポートの使用中などの失敗はコマンドの結果として返す
+    let bound = mc_connect_core::bind_server(&bind_host, port, ports, server_keys, options);
+    let (server, addr) = bound.map_err(|e| {
+        let message = t!(
+            "app.server_bind_failed",
+            host = bind_host,
+            port = port,
+            error = e
+        );
+        emit_log(&app, "ERROR", message.clone());
+        message
+    })?;
+
+    let status = ServerStatus {
+        running: true,
+        address: Some(addr.to_string()),
+        message: t!("app.server_listening", address = addr),
+    };
+    emit_log(&app, "SUCCESS", status.message.clone());
+    let _ = app.emit("server-status", status.clone());
+
+    let join_handle = tokio::spawn(async move {
+        // JoinHandle::abort で停止できるよう、サーバーはこのタスク内で await する
+        let (level, message) = match server.await {
+            Ok(_) => ("INFO", t!("app.server_exited")),
+            Err(e) => ("ERROR", t!("app.server_error", error = e)),
+        };
+        emit_log(&app, level, message.clone());
+        let _ = app.emit(
+            "server-status",
+            ServerStatus {
+                running: false,
+                address: None,
+                message,
+            },
+        );
+    });
+
+    // セッションの接続・切断・通信量の変化を UI へ転送する
+    let mut events = sessions.subscribe();
+    let app = app_handle.clone();
+    let events_handle = tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let _ = app.emit("server-session", event);
+                }
+                // 取りこぼした通知は、一覧 (list_server_sessions) の再取得で補う
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
         }
     });
 
-    state.server_handle = Some(handle);
-    Ok(())
+    state.server_handle = Some(ServerHandle {
+        join_handle,
+        events_handle,
+        sessions,
+    });
+    Ok(status)
 }
 
 #[tauri::command]
 pub async fn stop_server<R: Runtime>(app_handle: AppHandle<R>) -> Result<(), String> {
     let mut state = STATE.lock().await;
     if let Some(handle) = state.server_handle.take() {
-        handle.abort();
-        emit_log(&app_handle, "INFO", "サーバーを停止しました".into());
+        handle.join_handle.abort();
+        handle.events_handle.abort();
+        emit_log(&app_handle, "INFO", t!("app.server_stopped"));
+        let _ = app_handle.emit(
+            "server-status",
+            ServerStatus {
+                running: false,
+                address: None,
+                message: t!("app.server_stopped"),
+            },
+        );
     }
     Ok(())
 }
@@ -97,5 +181,74 @@ pub async fn stop_server<R: Runtime>(app_handle: AppHandle<R>) -> Result<(), Str
 #[tauri::command]
 pub async fn is_server_running() -> bool {
     let state = STATE.lock().await;
-    state.server_handle.is_some()
+    state.server_handle.as_ref().is_some_and(|h| h.is_running())
+}
+
+/// [export_client_config]
+/// サーバーモードの設定から、クライアントへ配布する設定 (CLI の `server --export` と同じ形式) を作成します。
+/// 接続先の URL は公開用ホストとポートから組み立てます。
+#[tauri::command]
+pub async fn export_client_config(
+    request: ExportClientConfigRequest,
+) -> Result<ClientExportConfig, String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let public_host = request
+        .public_host
+        .as_deref()
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+        .ok_or_else(|| t!("app.public_host_missing"))?;
+    let public_key = request
+        .public_key
+        .as_deref()
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .ok_or_else(|| t!("app.public_key_missing"))?;
+    // 配布後にクライアントが接続できないことがないよう、公開鍵として読めるか確認する
+    let der = general_purpose::STANDARD
+        .decode(public_key)
+        .map_err(|e| t!("app.public_key_decode_failed", error = e))?;
+    RsaPublicKey::from_der(&der).map_err(|e| e.to_string())?;
+
+    let mappings = parse_allowed_ports(request.allowed_ports);
+    if mappings.is_empty() {
+        return Err(t!("app.export_no_ports"));
+    }
+
+    let port = request.public_port.unwrap_or(request.listen_port);
+    Ok(ClientExportConfig {
+        name: request
+            .name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| "Server Connection".to_string()),
+        ws_url: ClientExportConfig::gateway_ws_url(public_host, port, request.public_tls),
+        mappings,
+        public_key: public_key.to_string(),
+        encryption_type: request.encryption_type,
+    })
+}
+
+#[tauri::command]
+pub async fn list_server_sessions() -> Vec<SessionInfo> {
+    let state = STATE.lock().await;
+    match &state.server_handle {
+        Some(handle) if handle.is_running() => handle.sessions.list(),
+        _ => Vec::new(),
+    }
+}
+
+/// UI から受け取った `(ポート, "TCP" / "UDP")` の一覧を変換します。不明なプロトコルは読み飛ばします。
+fn parse_allowed_ports(allowed_ports: Vec<(u16, String)>) -> Vec<AllowedPort> {
+    allowed_ports
+        .into_iter()
+        .filter_map(|(port, protocol)| {
+            let protocol = match protocol.to_lowercase().as_str() {
+                "tcp" => Proto::TCP,
+                "udp" => Proto::UDP,
+                _ => return None,
+            };
+            Some(AllowedPort { port, protocol })
+        })
+        .collect()
 }
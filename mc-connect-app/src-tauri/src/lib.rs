@@ -3,6 +3,7 @@ mod models;
 mod state;
 mod utils;
 
+use mc_connect_core::t;
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
@@ -26,19 +27,26 @@ pub fn run() {
             commands::stop_mapping,
             commands::is_mapping_running,
             commands::trigger_ping,
+            commands::list_mapping_connections,
+            commands::close_mapping_connection,
+            commands::get_key_fingerprint,
             commands::generate_server_keys,
             commands::start_server,
             commands::stop_server,
             commands::is_server_running,
+            commands::list_server_sessions,
+            commands::export_client_config,
+            commands::import_client_config,
             commands::save_config,
             commands::load_config
         ])
         .setup(|app| {
             crate::utils::init_logger(app.handle().clone());
+            // トレイメニューは起動時の言語で作成する
+            commands::config::apply_saved_locale(app.handle());
 
-            let quit_i = MenuItem::with_id(app, "quit", "McConnect を終了", true, None::<&str>)?;
-            let show_i =
-                MenuItem::with_id(app, "show", "ダッシュボードを開く", true, None::<&str>)?;
+            let quit_i = MenuItem::with_id(app, "quit", t!("app.tray_quit"), true, None::<&str>)?;
+            let show_i = MenuItem::with_id(app, "show", t!("app.tray_show"), true, None::<&str>)?;
             let menu = Menu::with_items(app, &[&show_i, &quit_i])?;
 
             let _tray = TrayIconBuilder::new()
@@ -1,4 +1,6 @@
-use mc_connect_core::models::packet::StatsPayload;
+use mc_connect_core::i18n::Locale;
+use mc_connect_core::models::packet::{ErrorCode, KeyEncryption, StatsPayload};
+use mc_connect_core::services::ws_client::ConnectionInfo;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -9,9 +11,13 @@ pub struct MappingInfo {
     pub bind_addr: String,
     pub local_port: u16,
     pub remote_port: u16,
+    /// `tcp`、`udp`、または許可されたすべての TCP ポートへ中継する SOCKS5 / HTTP CONNECT プロキシの `proxy`。
+    /// `proxy` の場合、`remote_port` は使用しません。
     pub protocol: String,
     pub ping_interval: u64,
     pub public_key: Option<String>,
+    #[serde(default)]
+    pub trust_on_first_use: bool,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -20,7 +26,42 @@ pub struct StartServerConfig {
     pub port: u16,
     pub allowed_ports: Vec<(u16, String)>,
     pub private_key_b64: String,
+    /// 秘密鍵をパスフレーズで暗号化している場合の KDF パラメータ。設定されている場合は `passphrase` が必要です。
+    #[serde(default)]
+    pub key_encryption: Option<KeyEncryption>,
+    /// 暗号化された秘密鍵を復号するパスフレーズ
+    #[serde(default)]
+    pub passphrase: Option<String>,
     pub encryption_type: String,
+    /// PKCS#1 v1.5 で共通鍵をラップする旧クライアントを受け付けるかどうか
+    #[serde(default)]
+    pub allow_legacy_key_wrap: bool,
+    /// 待ち受けるアドレス。省略した場合はすべてのインターフェース (0.0.0.0) で待ち受けます。
+    #[serde(default = "default_bind_host")]
+    pub bind_host: String,
+}
+
+fn default_bind_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+/// クライアント配布用設定の作成に使用する、サーバーモードの設定
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportClientConfigRequest {
+    /// 配布する設定の表示名
+    pub name: Option<String>,
+    pub listen_port: u16,
+    /// クライアントが接続するホスト。`wss://` などのスキームを付けることもできます。
+    pub public_host: Option<String>,
+    /// クライアントが接続するポート。省略した場合は待ち受けポートを使用します。
+    pub public_port: Option<u16>,
+    /// 公開用ホストで TLS を終端している (リバースプロキシなど) 場合は true。配布する URL を `wss://` にします。
+    #[serde(default)]
+    pub public_tls: bool,
+    pub public_key: Option<String>,
+    pub encryption_type: String,
+    pub allowed_ports: Vec<(u16, String)>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -35,15 +76,23 @@ pub struct MappingConfig {
     pub protocol: String,
     pub public_key: Option<String>,
     pub ping_interval: u64,
+    #[serde(default)]
+    pub trust_on_first_use: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SavedServerConfig {
     pub listen_port: u16,
+    #[serde(default)]
+    pub bind_host: Option<String>,
     pub public_host: Option<String>,
     pub public_port: Option<u16>,
+    /// サーバーの秘密鍵（Base64）。`key_encryption` が設定されている場合は暗号文です。
     pub private_key: Option<String>,
+    /// 秘密鍵をパスフレーズで暗号化している場合の KDF パラメータ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_encryption: Option<KeyEncryption>,
     pub public_key: Option<String>,
     pub encryption_type: String,
     pub allowed_ports: Vec<(u16, String)>,
@@ -53,6 +102,9 @@ pub struct SavedServerConfig {
 #[serde(rename_all = "camelCase")]
 pub struct AppSettings {
     pub server_mode_enabled: bool,
+    /// バックエンドのメッセージ (ログ・エラー・トレイメニュー) の言語
+    #[serde(default)]
+    pub language: Locale,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -74,6 +126,11 @@ pub struct LogEntry {
 pub struct StatsEvent {
     pub id: String,
     pub stats: StatsPayload,
+    /// ゲートウェイから届いた、ゲートウェイ側で観測した統計情報
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<StatsPayload>,
+    /// 中継中のローカル接続ごとの統計情報
+    pub connections: Vec<ConnectionInfo>,
 }
 
 #[derive(Serialize, Clone)]
@@ -81,4 +138,32 @@ pub struct TunnelStatus {
     pub id: String,
     pub running: bool,
     pub message: String,
+    /// 失敗した場合の理由を表すコード。UI で再試行か設定の見直しかを案内するために使用します。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+}
+
+/// サーバーモードの状態変更通知 (`server-status` イベント)
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    pub running: bool,
+    /// 待ち受けているアドレス。停止している場合は None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRotatedEvent {
+    pub id: String,
+    pub public_key: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyFingerprint {
+    pub fingerprint: String,
+    pub short_fingerprint: String,
 }
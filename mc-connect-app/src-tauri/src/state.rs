@@ -1,5 +1,7 @@
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use mc_connect_core::services::proxy::SessionRegistry;
+use mc_connect_core::services::ws_client::TunnelStats;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -7,12 +9,26 @@ pub struct TunnelHandle {
     pub join_handle: tokio::task::JoinHandle<()>,
     pub stats_handle: tokio::task::JoinHandle<()>, // 統計情報報告ループ用
     pub ping_tx: tokio::sync::mpsc::UnboundedSender<()>,
+    pub stats: Arc<TunnelStats>, // 接続ごとの統計情報の参照・切断用
+}
+
+pub struct ServerHandle {
+    pub join_handle: tokio::task::JoinHandle<()>,
+    pub events_handle: tokio::task::JoinHandle<()>, // セッションイベントの転送ループ用
+    pub sessions: Arc<SessionRegistry>,             // 中継中のセッションの参照用
+}
+
+impl ServerHandle {
+    /// サーバーが終了していないかどうか (バインド後にエラーで終了した場合も false)
+    pub fn is_running(&self) -> bool {
+        !self.join_handle.is_finished()
+    }
 }
 
 #[derive(Default)]
 pub struct AppState {
     pub tunnels: HashMap<String, TunnelHandle>,
-    pub server_handle: Option<tokio::task::JoinHandle<()>>,
+    pub server_handle: Option<ServerHandle>,
 }
 
 pub static STATE: Lazy<Arc<Mutex<AppState>>> =
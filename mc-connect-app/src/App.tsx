@@ -26,10 +26,10 @@ export default function App() {
   const [currentView, setCurrentView] = useState<View>("dashboard");
 
   // マッピングデータの操作用フック
-  const { mappings, setMappings, startMapping, stopMapping, triggerPing, updateMapping, deleteMappings, importConfig } = useMappings();
+  const { mappings, setMappings, startMapping, stopMapping, triggerPing, closeConnection, updateMapping, deleteMappings, importConfig } = useMappings();
 
   // サーバー操作用フック
-  const { settings, setSettings, serverConfig, setServerConfig, isGeneratingKeys, generateKeys, startServer, stopServer } = useServer();
+  const { settings, setSettings, serverConfig, setServerConfig, sessions, isGeneratingKeys, generateKeys, startServer, stopServer, exportConfig } = useServer();
 
   // ログデータの操作用フック
   const { logs, logEndRef } = useLogs(currentView);
@@ -58,9 +58,11 @@ export default function App() {
             setServerConfig(prev => ({
               ...prev,
               listenPort: config.serverConfig.listenPort,
+              bindHost: config.serverConfig.bindHost ?? prev.bindHost,
               publicHost: config.serverConfig.publicHost,
               publicPort: config.serverConfig.publicPort,
               privateKey: config.serverConfig.privateKey,
+              keyEncryption: config.serverConfig.keyEncryption,
               publicKey: config.serverConfig.publicKey,
               encryptionType: config.serverConfig.encryptionType,
               allowedPorts: config.serverConfig.allowedPorts.map((p: any) => ({ port: p[0], protocol: p[1] }))
@@ -170,6 +172,7 @@ export default function App() {
                 setSelectedIds={setSelectedIds}
                 onToggleConnect={handleToggleConnect}
                 onTriggerPing={triggerPing}
+                onCloseConnection={closeConnection}
                 onEdit={handleEdit}
                 onDeleteSelected={handleDeleteSelected}
                 onToggleSelect={handleToggleSelect}
@@ -180,9 +183,11 @@ export default function App() {
               <ServerPage
                 key="server"
                 config={serverConfig}
+                sessions={sessions}
                 onConfigChange={setServerConfig}
                 onStart={startServer}
                 onStop={stopServer}
+                onExport={exportConfig}
                 onGenerateKeys={generateKeys}
                 isGeneratingKeys={isGeneratingKeys}
               />
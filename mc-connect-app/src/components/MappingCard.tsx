@@ -1,6 +1,6 @@
 import { useState, useEffect } from "react";
 import { motion } from "framer-motion";
-import { Globe, Play, Square, ArrowUpCircle, ArrowDownCircle, Activity, RefreshCw, Clock } from "lucide-react";
+import { Globe, Play, Square, ArrowUpCircle, ArrowDownCircle, Activity, RefreshCw, Clock, X } from "lucide-react";
 import { Mapping } from "../types";
 
 /**
@@ -17,6 +17,8 @@ interface MappingCardProps {
     onSelect: (id: string) => void;
     /** PING計測をトリガーする時のコールバック */
     onTriggerPing: (id: string) => void;
+    /** 接続を 1 つだけ切断する時のコールバック */
+    onCloseConnection: (id: string, connectionId: number) => void;
     /** 編集ボタンが押された時のコールバック */
     onEdit: (mapping: Mapping) => void;
     /** 接続状態の切り替え（開始/停止）が押された時のコールバック */
@@ -31,6 +33,7 @@ export const MappingCard = ({
     isDeleteMode,
     isSelected,
     onSelect,
+    onCloseConnection,
     onEdit,
     onToggleConnect
 }: MappingCardProps) => {
@@ -119,7 +122,7 @@ export const MappingCard = ({
                             {/* 接続設定の詳細情報（プロトコル、ポート等） */}
                             <div className="flex items-center flex-wrap gap-2 text-xs font-bold leading-none mb-2">
                                 <span className="px-2 py-0.5 rounded-md bg-slate-900 text-white tracking-widest text-[9px] uppercase">{mapping.protocol}</span>
-                                <span className="text-slate-400 font-mono">Port: <span className="text-slate-700">{mapping.remotePort}</span></span>
+                                <span className="text-slate-400 font-mono">Port: <span className="text-slate-700">{mapping.protocol === "PROXY" ? "*" : mapping.remotePort}</span></span>
                                 <span className="text-slate-300 hidden md:inline">|</span>
                                 <span className="text-slate-400 font-mono hidden md:inline">{mapping.bindAddr}:{mapping.localPort}</span>
                             </div>
@@ -129,6 +132,12 @@ export const MappingCard = ({
                                     {mapping.statusMessage}
                                 </div>
                             )}
+                            {/* 失敗理由に応じた案内（再試行 / 設定の見直し） */}
+                            {!mapping.isRunning && mapping.errorCode && mapping.error && (
+                                <div className="text-[10px] font-bold text-red-500">
+                                    {mapping.error}
+                                </div>
+                            )}
                         </div>
                     </div>
 
@@ -202,6 +211,39 @@ export const MappingCard = ({
                             </div>
                             <span className="text-base font-black font-mono text-amber-600 leading-none">{mapping.stats.rtt_ms !== undefined ? `${mapping.stats.rtt_ms}ms` : "--"}</span>
                         </div>
+                        {/* ゲートウェイ側の統計：対応しているゲートウェイのみ */}
+                        {mapping.gatewayStats && (
+                            <div className="col-span-2 md:col-span-3 flex flex-wrap gap-x-6 gap-y-1 border-t border-slate-200 pt-3 text-[10px] font-bold text-slate-400 font-mono">
+                                <span className="uppercase tracking-[0.15em]">ゲートウェイ</span>
+                                <span>送信 {formatBytes(mapping.gatewayStats.upload_total)}</span>
+                                <span>受信 {formatBytes(mapping.gatewayStats.download_total)}</span>
+                                <span>接続 {mapping.gatewayStats.connect_latency_ms !== undefined ? `${mapping.gatewayStats.connect_latency_ms}ms` : "--"}</span>
+                                <span>待機 {formatBytes(mapping.gatewayStats.queue_depth ?? 0)}</span>
+                            </div>
+                        )}
+                        {/* 接続ごとの内訳：中継中の接続がある場合のみ */}
+                        {mapping.connections && mapping.connections.length > 0 && (
+                            <div className="col-span-2 md:col-span-3 border-t border-slate-200 pt-3 space-y-1.5">
+                                <span className="text-[10px] font-black text-slate-400 uppercase tracking-[0.15em]">接続中 {mapping.connections.length}件</span>
+                                {mapping.connections.map(conn => (
+                                    <div key={conn.id} className="flex items-center gap-4 text-[11px] font-mono text-slate-600">
+                                        <span className="flex-1 truncate">{conn.peerAddr}</span>
+                                        <span>↑ {formatBytes(conn.uploadTotal)}</span>
+                                        <span>↓ {formatBytes(conn.downloadTotal)}</span>
+                                        <span className="text-amber-600">{conn.rttMs !== undefined ? `${conn.rttMs}ms` : "--"}</span>
+                                        <span className="text-slate-400">{conn.state}</span>
+                                        <button
+                                            onClick={(e) => { e.stopPropagation(); onCloseConnection(mapping.id, conn.id); }}
+                                            disabled={conn.state === "closing"}
+                                            className="p-1 rounded-lg text-slate-400 hover:text-red-500 hover:bg-red-50 disabled:opacity-30"
+                                            title="この接続を切断"
+                                        >
+                                            <X size={12} />
+                                        </button>
+                                    </div>
+                                ))}
+                            </div>
+                        )}
                     </div>
                 )}
             </div>
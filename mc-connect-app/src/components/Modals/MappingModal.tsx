@@ -1,6 +1,8 @@
+import { useEffect, useState } from "react";
 import { motion, AnimatePresence } from "framer-motion";
 import { X } from "lucide-react";
-import { Mapping } from "../../types";
+import { invoke } from "@tauri-apps/api/core";
+import { KeyFingerprint, Mapping } from "../../types";
 
 /**
  * マップ設定（マッピング）編集・新規作成用モーダルのプロパティ定義
@@ -34,6 +36,20 @@ export const MappingModal = ({
     onChange,
     submitLabel
 }: MappingModalProps) => {
+    const [keyFingerprint, setKeyFingerprint] = useState<KeyFingerprint | null>(null);
+
+    // 入力された公開鍵のフィンガープリントを表示し、サーバー管理者と照合できるようにする
+    useEffect(() => {
+        const publicKey = mapping.publicKey?.trim();
+        if (!publicKey) {
+            setKeyFingerprint(null);
+            return;
+        }
+        invoke<KeyFingerprint>("get_key_fingerprint", { publicKey })
+            .then(setKeyFingerprint)
+            .catch(() => setKeyFingerprint(null));
+    }, [mapping.publicKey]);
+
     return (
         <AnimatePresence>
             {isOpen && (
@@ -88,22 +104,24 @@ export const MappingModal = ({
                                     <label className="text-[10px] font-black text-slate-400 uppercase tracking-widest block mb-2 px-1">プロトコル</label>
                                     <select
                                         value={mapping.protocol || "TCP"}
-                                        onChange={event => onChange({ ...mapping, protocol: event.target.value as "TCP" | "UDP" })}
+                                        onChange={event => onChange({ ...mapping, protocol: event.target.value as Mapping["protocol"] })}
                                         className="w-full bg-slate-50 border-2 border-slate-100 p-4 rounded-2xl font-black outline-none cursor-pointer appearance-none"
                                         style={{ backgroundImage: 'url("data:image/svg+xml,%3Csvg xmlns=\'http://www.w3.org/2000/svg\' fill=\'none\' viewBox=\'0 0 24 24\' stroke=\'%2316a34a\'%3E%3Cpath stroke-linecap=\'round\' stroke-linejoin=\'round\' stroke-width=\'3\' d=\'M19 9l-7 7-7-7\'%3E%3C/path%3E%3C/svg%3E")', backgroundPosition: 'right 1rem center', backgroundSize: '1.2em', backgroundRepeat: 'no-repeat' }}
                                     >
                                         <option>TCP</option>
                                         <option>UDP</option>
+                                        <option value="PROXY">SOCKS5 / HTTP</option>
                                     </select>
                                 </div>
                                 <div>
                                     <label className="text-[10px] font-black text-slate-400 uppercase tracking-widest block mb-2 px-1">外部ポート</label>
                                     <input
                                         type="number"
-                                        value={mapping.remotePort || ""}
+                                        value={mapping.protocol === "PROXY" ? "" : mapping.remotePort || ""}
                                         onChange={event => onChange({ ...mapping, remotePort: Number(event.target.value) })}
-                                        className="w-full bg-slate-50 border-2 border-slate-100 p-4 rounded-2xl font-mono font-black outline-none"
-                                        placeholder="25565"
+                                        disabled={mapping.protocol === "PROXY"}
+                                        className="w-full bg-slate-50 border-2 border-slate-100 p-4 rounded-2xl font-mono font-black outline-none disabled:opacity-50"
+                                        placeholder={mapping.protocol === "PROXY" ? "許可ポートすべて" : "25565"}
                                     />
                                 </div>
                             </div>
@@ -138,7 +156,22 @@ export const MappingModal = ({
                                     onChange={event => onChange({ ...mapping, publicKey: event.target.value })}
                                     className="w-full bg-slate-50 border-2 border-slate-100 p-4 rounded-2xl font-mono text-[10px] h-24 focus:border-[#16a34a] focus:bg-white outline-none transition-all resize-none"
                                     placeholder="Base64 encoded public key..."
+                                    disabled={mapping.trustOnFirstUse}
                                 />
+                                {keyFingerprint && !mapping.trustOnFirstUse && (
+                                    <p className="text-[10px] font-mono text-slate-400 mt-2 px-1 break-all" title={keyFingerprint.fingerprint}>
+                                        {keyFingerprint.shortFingerprint}
+                                    </p>
+                                )}
+                                <label className="flex items-center gap-2 mt-3 px-1 text-xs font-bold text-slate-500 cursor-pointer">
+                                    <input
+                                        type="checkbox"
+                                        checked={mapping.trustOnFirstUse ?? false}
+                                        onChange={event => onChange({ ...mapping, trustOnFirstUse: event.target.checked })}
+                                        className="accent-[#16a34a]"
+                                    />
+                                    初回接続時の鍵を記録して信頼する (TOFU)
+                                </label>
                             </div>
 
                             {/* フッター：アクションボタン */}
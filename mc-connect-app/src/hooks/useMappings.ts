@@ -1,7 +1,24 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { Mapping, TunnelStatusEvent, StatsPayload } from "../types";
+import { Mapping, TunnelStatusEvent, StatsPayload, KeyRotatedEvent, ConnectionInfo, ErrorCode, ImportedMapping } from "../types";
+
+/**
+ * 接続失敗の理由コードから、再試行すべきか設定を見直すべきかの案内を返します
+ */
+const describeFailure = (code?: ErrorCode): string => {
+    switch (code) {
+        case "ws_connect_failed":
+        case "quota_exceeded":
+        case "target_unreachable":
+            return "接続失敗（しばらくしてから再試行してください）";
+        case undefined:
+        case "unknown":
+            return "接続失敗";
+        default:
+            return "接続失敗（接続先の設定を確認してください）";
+    }
+};
 
 /**
  * 接続設定（マッピング）の一覧管理、保存、およびバックエンドとの通信を制御するカスタムフック
@@ -16,7 +33,8 @@ export const useMappings = () => {
     useEffect(() => {
         // トンネルの実行状態（開始/停止/エラー）のイベントをリッスン
         const unlistenStatusPromise = listen<TunnelStatusEvent>("tunnel-status", (event) => {
-            const isErrorMessage = !event.payload.running && event.payload.message.toLowerCase().includes("error");
+            const { running, message, code } = event.payload;
+            const isErrorMessage = !running && (code !== undefined || message.toLowerCase().includes("error"));
 
             setMappings(prevMappings => prevMappings.map(mapping =>
                 mapping.id === event.payload.id
@@ -25,7 +43,8 @@ export const useMappings = () => {
                         isRunning: event.payload.running,
                         statusMessage: event.payload.message,
                         loading: false,
-                        error: isErrorMessage ? "接続失敗" : mapping.error,
+                        error: isErrorMessage ? describeFailure(code) : mapping.error,
+                        errorCode: isErrorMessage ? code : undefined,
                         hasFailed: isErrorMessage ? true : mapping.hasFailed,
                         stats: event.payload.running ? mapping.stats : undefined,
                         startedAt: event.payload.running ? (mapping.startedAt || Date.now()) : undefined,
@@ -44,7 +63,7 @@ export const useMappings = () => {
         });
 
         // 通信統計データ（速度、遅延等）のイベントをリッスン
-        const unlistenStatsPromise = listen<{ id: string, stats: StatsPayload }>("tunnel-stats", (event) => {
+        const unlistenStatsPromise = listen<{ id: string, stats: StatsPayload, gateway?: StatsPayload, connections: ConnectionInfo[] }>("tunnel-stats", (event) => {
             setMappings(prevMappings => prevMappings.map(mapping => {
                 if (mapping.id === event.payload.id) {
                     const history = mapping.speedHistory || { up: [], down: [] };
@@ -60,6 +79,8 @@ export const useMappings = () => {
                     return {
                         ...mapping,
                         stats: newStats,
+                        gatewayStats: event.payload.gateway,
+                        connections: event.payload.connections,
                         speedHistory: { up: newUploadHistory, down: newDownloadHistory },
                         latencyHistory: newLatencyHistory
                     };
@@ -68,10 +89,20 @@ export const useMappings = () => {
             }));
         });
 
+        // サーバー鍵のローテーション通知（署名検証済みの新しい公開鍵）をリッスン
+        const unlistenKeyRotatedPromise = listen<KeyRotatedEvent>("server-key-rotated", (event) => {
+            setMappings(prevMappings => prevMappings.map(mapping =>
+                mapping.id === event.payload.id
+                    ? { ...mapping, publicKey: event.payload.publicKey }
+                    : mapping
+            ));
+        });
+
         // クリーンアップ：イベントリスナーの解除
         return () => {
             unlistenStatusPromise.then(unlistenFn => unlistenFn());
             unlistenStatsPromise.then(unlistenFn => unlistenFn());
+            unlistenKeyRotatedPromise.then(unlistenFn => unlistenFn());
         };
     }, []);
 
@@ -97,7 +128,8 @@ export const useMappings = () => {
                     remotePort: mapping.remotePort,
                     protocol: mapping.protocol,
                     pingInterval: mapping.pingInterval,
-                    publicKey: mapping.publicKey
+                    publicKey: mapping.publicKey,
+                    trustOnFirstUse: mapping.trustOnFirstUse ?? false
                 }
             });
         } catch (error) {
@@ -136,6 +168,19 @@ export const useMappings = () => {
     };
 
 
+    /**
+     * マッピング内の接続を 1 つだけ切断する
+     * @param id 対象のマッピングID
+     * @param connectionId 切断する接続のID
+     */
+    const closeConnection = async (id: string, connectionId: number) => {
+        try {
+            await invoke("close_mapping_connection", { id, connectionId });
+        } catch (error) {
+            console.error("Close connection failed", error);
+        }
+    };
+
     /**
      * 既存のマッピング情報を更新する
      * @param updatedMapping 更新後のマッピングデータ
@@ -154,27 +199,18 @@ export const useMappings = () => {
 
     /**
      * 設定ファイル（JSON）からマッピングを一括インポートする
+     * 検証とローカルポートの割り当てはバックエンドで行う
      * @param configJson インポートするJSON文字列
      */
-    const importConfig = (configJson: string) => {
+    const importConfig = async (configJson: string) => {
         try {
-            const config = JSON.parse(configJson);
-            const { name, ws_url, mappings: importedMappings, public_key } = config;
-
-            if (!importedMappings || !Array.isArray(importedMappings)) {
-                throw new Error("Invalid config format: mappings must be an array");
-            }
+            const imported = await invoke<ImportedMapping[]>("import_client_config", {
+                configJson,
+                reservedPorts: mappings.map(m => m.localPort)
+            });
 
-            const newMappings: Mapping[] = importedMappings.map((m: any) => ({
-                id: Math.random().toString(36).substr(2, 9),
-                name: `${name} (${m.protocol}:${m.port})`,
-                wsUrl: ws_url || "ws://localhost:8080/ws",
-                bindAddr: "127.0.0.1",
-                localPort: m.port,
-                remotePort: m.port,
-                protocol: (m.protocol || "TCP").toUpperCase() as "TCP" | "UDP",
-                publicKey: public_key,
-                pingInterval: 5,
+            const newMappings: Mapping[] = imported.map(m => ({
+                ...m,
                 isRunning: false,
                 statusMessage: "インポート済み",
                 loading: false,
@@ -187,7 +223,7 @@ export const useMappings = () => {
             return true;
         } catch (error) {
             console.error("Import failed", error);
-            alert("インポートに失敗しました。ファイル形式を確認してください。");
+            alert(`インポートに失敗しました: ${error}`);
             return false;
         }
     };
@@ -198,6 +234,7 @@ export const useMappings = () => {
         startMapping,
         stopMapping,
         triggerPing,
+        closeConnection,
         updateMapping,
         deleteMappings,
         importConfig
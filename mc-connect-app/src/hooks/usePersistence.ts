@@ -22,24 +22,29 @@ export const usePersistence = (
                     remotePort: m.remotePort,
                     protocol: m.protocol,
                     publicKey: m.publicKey,
-                    pingInterval: m.pingInterval
+                    pingInterval: m.pingInterval,
+                    trustOnFirstUse: m.trustOnFirstUse ?? false
                 })),
                 serverConfig: {
                     listenPort: serverConfig.listenPort,
+                    bindHost: serverConfig.bindHost,
                     publicHost: serverConfig.publicHost,
                     publicPort: serverConfig.publicPort,
                     privateKey: serverConfig.privateKey,
+                    keyEncryption: serverConfig.keyEncryption,
                     publicKey: serverConfig.publicKey,
                     encryptionType: serverConfig.encryptionType,
                     allowedPorts: serverConfig.allowedPorts.map(p => [p.port, p.protocol])
                 },
                 appSettings: {
-                    serverModeEnabled: appSettings.serverModeEnabled
+                    serverModeEnabled: appSettings.serverModeEnabled,
+                    language: appSettings.language
                 }
             };
 
             try {
-                await invoke("save_config", { config });
+                // パスフレーズがある場合、平文の秘密鍵はバックエンドで暗号化してから保存される
+                await invoke("save_config", { config, passphrase: serverConfig.passphrase || null });
             } catch (error) {
                 console.error("Failed to save config:", error);
             }
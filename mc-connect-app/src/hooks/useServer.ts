@@ -1,14 +1,18 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { ServerConfig, AppSettings } from "../types";
+import { listen } from "@tauri-apps/api/event";
+import { ServerConfig, AppSettings, ClientExportConfig, ServerSession, ServerSessionEvent, ServerStatusEvent } from "../types";
 
 export const useServer = () => {
-    const [settings, setSettings] = useState<AppSettings>({ serverModeEnabled: false });
+    const [settings, setSettings] = useState<AppSettings>({ serverModeEnabled: false, language: "en" });
 
     const [isGeneratingKeys, setIsGeneratingKeys] = useState(false);
 
+    const [sessions, setSessions] = useState<ServerSession[]>([]);
+
     const [serverConfig, setServerConfig] = useState<ServerConfig>({
         isRunning: false,
+        bindHost: "0.0.0.0",
         listenPort: 8080,
         publicHost: "",
         publicPort: undefined,
@@ -21,7 +25,34 @@ export const useServer = () => {
     useEffect(() => {
         invoke<boolean>("is_server_running").then(running => {
             setServerConfig(prev => ({ ...prev, isRunning: running }));
+            if (running) {
+                invoke<ServerSession[]>("list_server_sessions").then(setSessions);
+            }
+        });
+
+        // サーバーの起動・停止（エラーによる終了を含む）の通知
+        const unlistenStatusPromise = listen<ServerStatusEvent>("server-status", (event) => {
+            const { running, address } = event.payload;
+            setServerConfig(prev => ({ ...prev, isRunning: running, listenAddress: address }));
+            if (!running) {
+                setSessions([]);
+            }
+        });
+
+        // セッションの接続・通信量の更新・切断の通知
+        const unlistenSessionPromise = listen<ServerSessionEvent>("server-session", (event) => {
+            const { kind, session } = event.payload;
+            setSessions(prev => {
+                const others = prev.filter(s => s.id !== session.id);
+                if (kind === "disconnected") return others;
+                return [...others, session].sort((a, b) => a.id - b.id);
+            });
         });
+
+        return () => {
+            unlistenStatusPromise.then(unlistenFn => unlistenFn());
+            unlistenSessionPromise.then(unlistenFn => unlistenFn());
+        };
     }, []);
 
     const generateKeys = async () => {
@@ -36,7 +67,7 @@ export const useServer = () => {
             // but generate_server_keys 2048bit is usually fast on modern PCs.
             // Still, 4096bit can take a bit.
             const [priv, pub] = await invoke<[string, string]>("generate_server_keys");
-            setServerConfig(prev => ({ ...prev, privateKey: priv, publicKey: pub }));
+            setServerConfig(prev => ({ ...prev, privateKey: priv, publicKey: pub, keyEncryption: undefined }));
             return true;
         } catch (error) {
             console.error("Key generation failed", error);
@@ -52,16 +83,25 @@ export const useServer = () => {
             alert("サーバーを起動する前に鍵を生成してください。");
             return;
         }
+        if (serverConfig.keyEncryption && !serverConfig.passphrase) {
+            alert("秘密鍵は暗号化されています。パスフレーズを入力してください。");
+            return;
+        }
         try {
-            await invoke("start_server", {
+            // バインドに失敗した場合（ポートの使用中など）はここで例外になる
+            const status = await invoke<ServerStatusEvent>("start_server", {
                 config: {
                     port: serverConfig.listenPort,
+                    bindHost: serverConfig.bindHost || "0.0.0.0",
                     allowedPorts: serverConfig.allowedPorts.map(p => [p.port, p.protocol]),
                     privateKeyB64: serverConfig.privateKey,
+                    keyEncryption: serverConfig.keyEncryption ?? null,
+                    passphrase: serverConfig.passphrase || null,
                     encryptionType: serverConfig.encryptionType
                 }
             });
-            setServerConfig(prev => ({ ...prev, isRunning: true }));
+            setServerConfig(prev => ({ ...prev, isRunning: status.running, listenAddress: status.address }));
+            setSessions([]);
         } catch (error) {
             alert(`サーバー起動失敗: ${error}`);
         }
@@ -76,15 +116,21 @@ export const useServer = () => {
         }
     };
 
-    const exportConfig = () => {
-        if (!serverConfig.publicKey) return null;
-        const config = {
-            name: "Server Connection",
-            ws_url: `ws://localhost:${serverConfig.listenPort}/ws`,
-            mappings: serverConfig.allowedPorts,
-            public_key: serverConfig.publicKey,
-            encryption_type: serverConfig.encryptionType
-        };
+    /**
+     * クライアント配布用の設定を作成する（URL は公開用ホスト・ポートから組み立てる）
+     * 公開用ホストや公開鍵が未設定の場合は例外になる
+     */
+    const exportConfig = async () => {
+        const config = await invoke<ClientExportConfig>("export_client_config", {
+            request: {
+                listenPort: serverConfig.listenPort,
+                publicHost: serverConfig.publicHost,
+                publicPort: serverConfig.publicPort,
+                publicKey: serverConfig.publicKey,
+                encryptionType: serverConfig.encryptionType,
+                allowedPorts: serverConfig.allowedPorts.map(p => [p.port, p.protocol])
+            }
+        });
         return JSON.stringify(config, null, 2);
     };
 
@@ -93,6 +139,7 @@ export const useServer = () => {
         setSettings,
         serverConfig,
         setServerConfig,
+        sessions,
         isGeneratingKeys,
         generateKeys,
         startServer,
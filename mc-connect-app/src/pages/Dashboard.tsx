@@ -21,6 +21,8 @@ interface DashboardProps {
     onToggleConnect: (event: React.MouseEvent, mapping: Mapping) => void;
     /** 導通確認（PING）を実行するコールバック */
     onTriggerPing: (id: string) => void;
+    /** マッピング内の接続を 1 つだけ切断するコールバック */
+    onCloseConnection: (id: string, connectionId: number) => void;
     /** 編集画面を開くコールバック */
     onEdit: (mapping: Mapping) => void;
     /** 選択されたマッピングを一括削除するコールバック */
@@ -28,7 +30,7 @@ interface DashboardProps {
     /** 単一マッピングの選択状態を反転させるコールバック */
     onToggleSelect: (id: string) => void;
     /** 設定ファイルをインポートする関数 */
-    onImportConfig: (configJson: string) => boolean;
+    onImportConfig: (configJson: string) => Promise<boolean>;
 }
 
 /**
@@ -42,6 +44,7 @@ export const Dashboard = ({
     setSelectedIds,
     onToggleConnect,
     onTriggerPing,
+    onCloseConnection,
     onEdit,
     onDeleteSelected,
     onToggleSelect,
@@ -55,9 +58,9 @@ export const Dashboard = ({
         if (!file) return;
 
         const reader = new FileReader();
-        reader.onload = (e) => {
+        reader.onload = async (e) => {
             const content = e.target?.result as string;
-            if (onImportConfig(content)) {
+            if (await onImportConfig(content)) {
                 alert("設定をインポートしました。");
             }
         };
@@ -151,6 +154,7 @@ export const Dashboard = ({
                             isSelected={selectedIds.includes(mapping.id)}
                             onSelect={onToggleSelect}
                             onTriggerPing={onTriggerPing}
+                            onCloseConnection={onCloseConnection}
                             onEdit={onEdit}
                             onToggleConnect={onToggleConnect}
                         />
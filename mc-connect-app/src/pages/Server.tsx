@@ -1,20 +1,35 @@
 import { useState } from "react";
 import { motion } from "framer-motion";
-import { Server, Play, Square, Key, Share2, Plus, Trash2, ShieldCheck, Globe, Settings as SettingsIcon, RefreshCw, Zap } from "lucide-react";
-import { ServerConfig } from "../types";
+import { Server, Play, Square, Key, Share2, Plus, Trash2, ShieldCheck, Globe, Settings as SettingsIcon, RefreshCw, Zap, Users } from "lucide-react";
+import { ServerConfig, ServerSession } from "../types";
 import { PortModal } from "../components/Modals/PortModal";
 import { ConfirmModal } from "../components/Modals/ConfirmModal";
 
 interface ServerPageProps {
     config: ServerConfig;
+    sessions: ServerSession[];
     isGeneratingKeys: boolean;
     onConfigChange: (config: ServerConfig) => void;
     onStart: () => void;
     onStop: () => void;
+    onExport: () => Promise<string>;
     onGenerateKeys: () => void;
 }
 
-export const ServerPage = ({ config, isGeneratingKeys, onConfigChange, onStart, onStop, onGenerateKeys }: ServerPageProps) => {
+/**
+ * バイト数を読みやすい形式にフォーマットする関数
+ * @param bytes フォーマット対象のバイト数
+ */
+const formatBytes = (bytes: number) => {
+    if (!bytes || bytes === 0) return "0.00 MB";
+    const mb = bytes / (1024 * 1024);
+    if (mb < 0.1) {
+        return (bytes / 1024).toFixed(2) + " KB";
+    }
+    return mb.toFixed(2) + " MB";
+};
+
+export const ServerPage = ({ config, sessions, isGeneratingKeys, onConfigChange, onStart, onStop, onExport, onGenerateKeys }: ServerPageProps) => {
     const [isPortModalOpen, setIsPortModalOpen] = useState(false);
     const [isConfirmModalOpen, setIsConfirmModalOpen] = useState(false);
 
@@ -33,15 +48,15 @@ export const ServerPage = ({ config, isGeneratingKeys, onConfigChange, onStart,
             alert("公開鍵がありません。先に鍵を生成してください。");
             return;
         }
-        const exportData = {
-            name: "Server Connection",
-            ws_url: `ws://${config.publicHost || 'YOUR_IP'}:${config.publicPort || config.listenPort}/ws`,
-            mappings: config.allowedPorts,
-            public_key: config.publicKey,
-            encryption_type: config.encryptionType
-        };
 
-        const jsonString = JSON.stringify(exportData, null, 2);
+        // 接続先 URL の組み立てと公開鍵の検証はバックエンドで行う
+        let jsonString: string;
+        try {
+            jsonString = await onExport();
+        } catch (error) {
+            alert(`設定の書き出しに失敗しました: ${error}`);
+            return;
+        }
 
         // File System Access API を試行 (救済策・デスクトップブラウザ向け)
         if ('showSaveFilePicker' in window) {
@@ -103,7 +118,9 @@ export const ServerPage = ({ config, isGeneratingKeys, onConfigChange, onStart,
                                     {config.isRunning ? 'サーバー稼働中' : 'サーバー停止中'}
                                 </h3>
                                 <p className="text-sm text-slate-400 font-bold">
-                                    {config.isRunning ? '外部からの接続を待ち受けています' : '設定を確認して起動してください'}
+                                    {config.isRunning
+                                        ? `外部からの接続を待ち受けています${config.listenAddress ? ` (${config.listenAddress})` : ''}`
+                                        : '設定を確認して起動してください'}
                                 </p>
                             </div>
                         </div>
@@ -122,6 +139,34 @@ export const ServerPage = ({ config, isGeneratingKeys, onConfigChange, onStart,
                     </div>
                 </section>
 
+                {/* 中継中のセッション */}
+                {config.isRunning && (
+                    <section className="bg-white rounded-[2rem] border border-slate-200 p-8 shadow-sm space-y-4">
+                        <h4 className="text-[10px] font-black text-slate-400 uppercase tracking-widest px-1 flex items-center gap-2">
+                            <Users size={12} /> 接続中のクライアント ({sessions.length})
+                        </h4>
+                        {sessions.length === 0 ? (
+                            <p className="text-xs text-slate-300 font-bold italic px-1">No active sessions</p>
+                        ) : (
+                            <div className="space-y-2 max-h-64 overflow-y-auto pr-2">
+                                {sessions.map(session => (
+                                    <div key={session.id} className="flex items-center justify-between p-3 bg-slate-50 rounded-xl border border-slate-100 text-xs font-bold">
+                                        <div className="flex items-center gap-3">
+                                            <span className="px-2 py-0.5 bg-slate-900 text-white text-[9px] font-black rounded uppercase tracking-wider">{session.protocol}</span>
+                                            <span className="font-mono text-slate-700">{session.port}</span>
+                                            <span className="font-mono text-slate-400">{session.peerAddr}</span>
+                                        </div>
+                                        <div className="flex items-center gap-4 font-mono text-slate-500">
+                                            <span>↑ {formatBytes(session.uploadTotal)}</span>
+                                            <span>↓ {formatBytes(session.downloadTotal)}</span>
+                                        </div>
+                                    </div>
+                                ))}
+                            </div>
+                        )}
+                    </section>
+                )}
+
                 <div className="grid grid-cols-1 lg:grid-cols-2 gap-8">
                     {/* 基本設定 */}
                     <section className="bg-white rounded-[2rem] border border-slate-200 p-8 shadow-sm space-y-6">
@@ -130,6 +175,18 @@ export const ServerPage = ({ config, isGeneratingKeys, onConfigChange, onStart,
                         </h4>
 
                         <div className="space-y-4">
+                            <div>
+                                <label className="text-xs font-black text-slate-500 block mb-2 px-1">待ち受けアドレス</label>
+                                <input
+                                    type="text"
+                                    value={config.bindHost ?? ''}
+                                    onChange={e => onConfigChange({ ...config, bindHost: e.target.value })}
+                                    disabled={config.isRunning}
+                                    className="w-full bg-slate-50 border-2 border-slate-100 p-4 rounded-2xl font-mono font-bold focus:border-[#16a34a] outline-none disabled:opacity-50"
+                                    placeholder="0.0.0.0"
+                                />
+                            </div>
+
                             <div>
                                 <label className="text-xs font-black text-slate-500 block mb-2 px-1">待ち受けポート (WebSocket)</label>
                                 <input
@@ -150,7 +207,7 @@ export const ServerPage = ({ config, isGeneratingKeys, onConfigChange, onStart,
                                         value={config.publicHost || ''}
                                         onChange={e => onConfigChange({ ...config, publicHost: e.target.value })}
                                         className="w-full bg-slate-50 border-2 border-slate-100 p-4 rounded-2xl font-bold focus:border-[#16a34a] outline-none"
-                                        placeholder="example.com"
+                                        placeholder="example.com (TLS 終端時は wss://example.com)"
                                     />
                                 </div>
                                 <div>
@@ -227,6 +284,20 @@ export const ServerPage = ({ config, isGeneratingKeys, onConfigChange, onStart,
                                 </div>
                             </div>
 
+                            <div>
+                                <label className="text-xs font-black text-slate-500 block mb-2 px-1">
+                                    秘密鍵のパスフレーズ {config.keyEncryption ? "（暗号化済み）" : "（任意）"}
+                                </label>
+                                <input
+                                    type="password"
+                                    value={config.passphrase ?? ''}
+                                    onChange={e => onConfigChange({ ...config, passphrase: e.target.value })}
+                                    disabled={config.isRunning}
+                                    className="w-full bg-slate-50 border-2 border-slate-100 p-4 rounded-2xl font-mono font-bold focus:border-[#16a34a] outline-none disabled:opacity-50"
+                                    placeholder={config.keyEncryption ? "起動時に入力してください" : "設定すると秘密鍵を暗号化して保存します"}
+                                />
+                            </div>
+
                             {config.publicKey ? (
                                 <div className="p-4 bg-slate-50 rounded-2xl border border-slate-100 group relative">
                                     <p className="text-[9px] font-black text-slate-400 uppercase mb-2">公開鍵（配布用）</p>
@@ -1,6 +1,11 @@
 import { motion } from "framer-motion";
-import { Settings as SettingsIcon, Server, ShieldCheck } from "lucide-react";
-import { AppSettings } from "../types";
+import { Settings as SettingsIcon, Server, ShieldCheck, Languages } from "lucide-react";
+import { AppSettings, Locale } from "../types";
+
+const LANGUAGES: { value: Locale; label: string }[] = [
+    { value: "en", label: "English" },
+    { value: "ja", label: "日本語" },
+];
 
 interface SettingsPageProps {
     settings: AppSettings;
@@ -63,6 +68,38 @@ export const SettingsPage = ({ settings, onSettingsChange }: SettingsPageProps)
                         </div>
                     )}
                 </section>
+
+                <section className="bg-white rounded-3xl border border-slate-200 p-8 shadow-sm">
+                    <div className="flex items-center gap-4 mb-6">
+                        <div className="p-3 bg-blue-50 text-blue-500 rounded-2xl">
+                            <Languages size={24} />
+                        </div>
+                        <div>
+                            <h3 className="text-lg font-black text-slate-900">メッセージの言語</h3>
+                            <p className="text-sm text-slate-400 font-bold">ログやエラーメッセージの表示言語を選択します</p>
+                        </div>
+                    </div>
+
+                    <div className="flex gap-3 p-4 bg-slate-50 rounded-2xl border border-slate-100">
+                        {LANGUAGES.map(({ value, label }) => (
+                            <button
+                                key={value}
+                                onClick={() => onSettingsChange({ ...settings, language: value })}
+                                className={`
+                                    flex-1 py-3 rounded-xl font-bold transition-colors
+                                    ${settings.language === value
+                                        ? 'bg-[#16a34a] text-white shadow-sm'
+                                        : 'bg-white text-slate-600 border border-slate-200 hover:bg-slate-100'}
+                                `}
+                            >
+                                {label}
+                            </button>
+                        ))}
+                    </div>
+                    <p className="mt-3 text-xs text-slate-400 font-bold">
+                        トレイメニューの表示はアプリの再起動後に切り替わります。
+                    </p>
+                </section>
             </div>
         </motion.div>
     );
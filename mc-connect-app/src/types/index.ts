@@ -12,12 +12,14 @@ export interface Mapping {
     bindAddr: string;
     /** ローカルで待ち受けるポート番号 */
     localPort: number;
-    /** 接続先（外部）のポート番号 */
+    /** 接続先（外部）のポート番号（"PROXY" の場合は使用しない） */
     remotePort: number;
-    /** 使用プロトコル（"TCP" または "UDP"） */
-    protocol: "TCP" | "UDP";
+    /** 使用プロトコル（"TCP"、"UDP"、または許可されたすべての TCP ポートへ中継する SOCKS5 / HTTP CONNECT プロキシの "PROXY"） */
+    protocol: "TCP" | "UDP" | "PROXY";
     /** プロキシサーバーの公開鍵（暗号化用） */
     publicKey?: string;
+    /** 初回接続時にゲートウェイが提示した鍵を記録して信頼する（TOFU） */
+    trustOnFirstUse?: boolean;
     /** PING送信の間隔（秒） */
     pingInterval: number;
     /** 現在トンネルが実行中かどうか */
@@ -26,12 +28,18 @@ export interface Mapping {
     statusMessage: string;
     /** エラーが発生している場合のメッセージ */
     error?: string;
+    /** 直近の接続失敗の理由コード */
+    errorCode?: ErrorCode;
     /** 処理中（開始/停止中）フラグ */
     loading?: boolean;
     /** 起動失敗フラグ（アニメーション等に使用） */
     hasFailed?: boolean;
     /** 通信統計データ */
     stats?: StatsPayload;
+    /** ゲートウェイ側で観測した統計データ */
+    gatewayStats?: StatsPayload;
+    /** 中継中のローカル接続ごとの統計データ */
+    connections?: ConnectionInfo[];
     /** 通信速度の履歴（20件分） */
     speedHistory?: { up: number[], down: number[] };
     /** 遅延（PING）の履歴（20件分） */
@@ -40,6 +48,33 @@ export interface Mapping {
     startedAt?: number;
 }
 
+/**
+ * 設定ファイルのインポートでバックエンドが作成する接続設定（Rust側の MappingConfig）
+ */
+export type ImportedMapping = Pick<Mapping,
+    "id" | "name" | "wsUrl" | "bindAddr" | "localPort" | "remotePort" |
+    "protocol" | "publicKey" | "trustOnFirstUse" | "pingInterval">;
+
+/**
+ * マッピング内のローカル接続 1 つ分の統計情報
+ */
+export interface ConnectionInfo {
+    /** マッピング内で接続を識別する ID */
+    id: number;
+    /** 接続元のアドレス */
+    peerAddr: string;
+    /** 接続を受け付けた時刻（UNIX 秒） */
+    startedAt: number;
+    /** この接続で送信したバイト数 */
+    uploadTotal: number;
+    /** この接続で受信したバイト数 */
+    downloadTotal: number;
+    /** この接続のラウンドトリップタイム（ミリ秒） */
+    rttMs?: number;
+    /** 接続の状態 */
+    state: "connecting" | "active" | "closing";
+}
+
 /**
  * 転送速度や統計情報を表すペイロード
  */
@@ -54,6 +89,10 @@ export interface StatsPayload {
     download_speed: number;
     /** ラウンドトリップタイム（ミリ秒） */
     rtt_ms?: number;
+    /** ゲートウェイからターゲットへの接続にかかった時間（ミリ秒、ゲートウェイのみ） */
+    connect_latency_ms?: number;
+    /** ゲートウェイでターゲットへの書き込みを待っているバイト数（ゲートウェイのみ） */
+    queue_depth?: number;
 }
 
 /**
@@ -66,6 +105,42 @@ export interface TunnelStatusEvent {
     running: boolean;
     /** 状態メッセージ */
     message: string;
+    /** 失敗した場合の理由コード（バックエンドの ErrorCode） */
+    code?: ErrorCode;
+}
+
+/**
+ * 接続失敗の理由コード（バックエンドの ErrorCode と同じ表記）
+ */
+export type ErrorCode =
+    | "invalid_url"
+    | "ws_connect_failed"
+    | "handshake_failed"
+    | "port_not_allowed"
+    | "quota_exceeded"
+    | "target_unreachable"
+    | "decrypt_failed"
+    | "protocol_mismatch"
+    | "unknown";
+
+/**
+ * サーバー鍵のローテーション通知イベント（署名検証済み）
+ */
+export interface KeyRotatedEvent {
+    /** 対象マッピングID */
+    id: string;
+    /** 新しい公開鍵 (DER/Base64) */
+    publicKey: string;
+}
+
+/**
+ * 公開鍵のフィンガープリント（get_key_fingerprint の戻り値）
+ */
+export interface KeyFingerprint {
+    /** SHA-256 フィンガープリント（aa:bb:... 形式） */
+    fingerprint: string;
+    /** 照合用の短縮形（SHA256:xxxx-xxxx-xxxx-xxxx 形式） */
+    shortFingerprint: string;
 }
 
 /**
@@ -91,14 +166,22 @@ export type View = "dashboard" | "server" | "console" | "settings";
 export interface ServerConfig {
     /** サーバーが実行中かどうか */
     isRunning: boolean;
+    /** 実際に待ち受けているアドレス（実行中のみ） */
+    listenAddress?: string;
+    /** 待ち受けアドレス（0.0.0.0 ですべてのインターフェース） */
+    bindHost?: string;
     /** 待ち受けポート */
     listenPort: number;
     /** 公開用ホスト (IP/ドメイン) */
     publicHost?: string;
     /** 公開用ポート */
     publicPort?: number;
-    /** 秘密鍵 (PEM) */
+    /** 秘密鍵 (DER/Base64)。keyEncryption が設定されている場合は暗号文 */
     privateKey?: string;
+    /** 秘密鍵をパスフレーズで暗号化している場合の KDF パラメータ（バックエンドが設定する） */
+    keyEncryption?: unknown;
+    /** 秘密鍵のパスフレーズ（メモリ上のみで保持し、保存しない） */
+    passphrase?: string;
     /** 公開鍵 (DER/Base64) */
     publicKey?: string;
     /** 暗号化方式 */
@@ -107,11 +190,75 @@ export interface ServerConfig {
     allowedPorts: { port: number; protocol: "TCP" | "UDP" }[];
 }
 
+/**
+ * クライアント配布用の設定ファイル（CLI の server --export と同じ形式）
+ */
+export interface ClientExportConfig {
+    name: string;
+    ws_url: string;
+    mappings: { port: number; protocol: "TCP" | "UDP" }[];
+    public_key: string;
+    encryption_type: string;
+}
+
+/**
+ * サーバーモードの状態変更通知イベント
+ */
+export interface ServerStatusEvent {
+    /** 実行中かどうか */
+    running: boolean;
+    /** 待ち受けているアドレス（実行中のみ） */
+    address?: string;
+    /** 状態メッセージ */
+    message: string;
+}
+
+/**
+ * サーバーモードで中継中のセッション 1 つ分の情報
+ */
+export interface ServerSession {
+    /** セッション ID（監査ログの session_id と同じ値） */
+    id: number;
+    /** 接続元のアドレス */
+    peerAddr: string;
+    /** 中継を開始した時刻（UNIX 秒） */
+    startedAt: number;
+    /** クライアントが使用したサーバー鍵のフィンガープリント */
+    keyFingerprint: string;
+    /** 中継先のポート */
+    port: number;
+    /** 中継先のプロトコル */
+    protocol: "TCP" | "UDP";
+    /** クライアントから受信したバイト数 */
+    uploadTotal: number;
+    /** クライアントへ送信したバイト数 */
+    downloadTotal: number;
+}
+
+/**
+ * サーバーモードのセッションの接続・通信量・切断の通知イベント
+ */
+export interface ServerSessionEvent {
+    /** イベントの種類 */
+    kind: "connected" | "traffic" | "disconnected";
+    /** 対象セッション */
+    session: ServerSession;
+    /** 切断理由（切断時のみ） */
+    reason?: string;
+}
+
 /**
  * アプリ全体のユーザー設定
  */
 export interface AppSettings {
     /** サーバーモードを有効にするかどうか */
     serverModeEnabled: boolean;
+    /** バックエンド (ログ・エラー) のメッセージの言語 */
+    language: Locale;
 }
 
+/**
+ * バックエンドのメッセージの言語
+ */
+export type Locale = "en" | "ja";
+
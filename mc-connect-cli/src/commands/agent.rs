@@ -0,0 +1,55 @@
+use crate::commands::server::load_server_keyring;
+use crate::utils::parse_allowed_ports;
+use anyhow::Result;
+use log::info;
+use mc_connect_core::HostAgentService;
+use mc_connect_core::encryption::CryptoKeyPair;
+use mc_connect_core::services::relay::agent_id;
+use mc_connect_core::t;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// `agent`: NAT の内側からリレーへ接続し、リレー経由でサーバーを公開します。
+///
+/// 受け入れ用のポートは開けず、エージェントからリレーへ WebSocket で接続します。
+/// 鍵と許可ポートは `server` と同じサーバー設定を使用し、クライアントはリレーの
+/// `/ws/{エージェント ID}` に、このサーバーの公開鍵で接続します。
+pub async fn run_agent(
+    config_path: PathBuf,
+    relay_url: String,
+    allowed_ports_str: Option<String>,
+    passphrase_file: Option<String>,
+    allow_legacy_key_wrap: bool,
+) -> Result<()> {
+    let (config, keyring) = load_server_keyring(
+        &config_path,
+        passphrase_file.as_deref(),
+        allow_legacy_key_wrap,
+    )
+    .await?;
+    let parsed_ports = parse_allowed_ports(&allowed_ports_str.unwrap_or(config.allowed_ports))?;
+
+    let id = agent_id(&keyring.current().fingerprint());
+    info!("====================================================");
+    info!("{}", t!("cli.agent_id", agent = id));
+    info!(
+        "{}",
+        t!(
+            "agent.client_url",
+            url = HostAgentService::client_url(&relay_url, &id)
+        )
+    );
+    info!(
+        "{}",
+        t!(
+            "cli.fingerprint",
+            short = keyring.current().short_fingerprint(),
+            full = keyring.current().fingerprint()
+        )
+    );
+    info!("====================================================");
+
+    HostAgentService::run(relay_url, parsed_ports, Arc::new(keyring))
+        .await
+        .map_err(|e| anyhow::anyhow!(t!("cli.agent_error", error = e)))
+}
@@ -1,10 +1,15 @@
 use anyhow::{Context, Result};
-use log::{error, info};
-use mc_connect_core::WsClientService;
-use mc_connect_core::encryption::RsaKeyPair;
+use log::{error, info, warn};
+use mc_connect_core::encryption::CryptoError;
+use mc_connect_core::encryption::{RsaPublicKey, fingerprint, short_fingerprint};
 use mc_connect_core::models::packet::{ClientExportConfig, Protocol};
+use mc_connect_core::services::ws_client::{KnownServers, TunnelStats};
+use mc_connect_core::{McConnectError, WsClientService, t};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run_client(
     local_port: u16,
     remote_port: u16,
@@ -13,70 +18,170 @@ pub async fn run_client(
     list_ports: bool,
     public_key: Option<String>,
     config: Option<String>,
+    tofu: bool,
+    known_servers_path: PathBuf,
+    proxy_port: Option<u16>,
+    status_interval: Option<u64>,
 ) -> Result<()> {
     let mut final_ws_url = ws_url;
     let mut final_pub_key = public_key;
+    // 公開鍵を設定ファイルから読み込んだ場合、鍵ローテーション時に書き戻すために保持する
+    let mut key_source: Option<(String, ClientExportConfig)> = None;
 
     // 設定ファイルからの読み込み
     if let Some(path) = config {
-        let content = std::fs::read_to_string(&path)
-            .context(format!("設定ファイル {} の読み取りに失敗しました", path))?;
-        let cfg: ClientExportConfig = serde_json::from_str(&content)
-            .context("設定ファイルのフォーマットが正しくありません")?;
+        let content =
+            std::fs::read_to_string(&path).context(t!("cli.config_read_failed", path = path))?;
+        let cfg: ClientExportConfig =
+            serde_json::from_str(&content).context(t!("cli.config_invalid"))?;
 
         if final_ws_url.is_none() {
-            final_ws_url = Some(cfg.ws_url);
+            final_ws_url = Some(cfg.ws_url.clone());
         }
-        if final_pub_key.is_none() {
-            final_pub_key = Some(cfg.public_key);
+        if final_pub_key.is_none() && !tofu {
+            final_pub_key = Some(cfg.public_key.clone());
+            key_source = Some((path, cfg));
         }
     }
 
-    let ws_url_str =
-        final_ws_url.ok_or_else(|| anyhow::anyhow!("--ws-url または --config が必要です"))?;
-    let pub_key_str =
-        final_pub_key.ok_or_else(|| anyhow::anyhow!("--public-key または --config が必要です"))?;
+    let ws_url_str = final_ws_url.ok_or_else(|| anyhow::anyhow!(t!("cli.ws_url_required")))?;
 
     let proto = match protocol_str.to_lowercase().as_str() {
         "tcp" => Protocol::TCP,
         "udp" => Protocol::UDP,
-        _ => return Err(anyhow::anyhow!("Unsupported protocol: {}", protocol_str)),
+        _ => {
+            return Err(anyhow::anyhow!(t!(
+                "cli.unsupported_protocol",
+                value = protocol_str
+            )));
+        }
     };
 
     if list_ports {
-        info!("Fetching allowed ports from {}...", ws_url_str);
+        info!("{}", t!("cli.fetching_ports", url = ws_url_str));
         match WsClientService::get_server_info(&ws_url_str).await {
             Ok(info) => {
-                println!("Server Version: {}", info.server_version);
-                println!("Allowed Ports:");
+                println!(
+                    "{}",
+                    t!("cli.server_version", version = info.server_version)
+                );
+                match &info.protocol_info {
+                    Some(p) => println!(
+                        "{}",
+                        t!(
+                            "cli.protocol_version",
+                            version = p.version,
+                            capabilities = p.capabilities.join(", ")
+                        )
+                    ),
+                    None => println!("{}", t!("cli.protocol_version_legacy")),
+                }
+                println!("{}", t!("cli.allowed_ports"));
                 for p in info.allowed_ports {
                     println!("  - {}: {:?}", p.port, p.protocol);
                 }
             }
             Err(e) => {
-                error!("Failed to fetch server info: {}", e);
+                error!("{}", t!("cli.server_info_failed", error = e));
             }
         }
         return Ok(());
     }
 
-    let pub_key_bytes =
+    // TOFU の場合、鍵ローテーションは trust_on_first_use が検証して既知サーバーの記録に反映する
+    let pub_key_bytes = if tofu {
+        let mut known = KnownServers::load(&known_servers_path)
+            .map_err(|e| anyhow::anyhow!(t!("cli.known_servers_load_failed", error = e)))?;
+        WsClientService::trust_on_first_use(&ws_url_str, &mut known)
+            .await
+            .map_err(|e| anyhow::anyhow!(t!("cli.server_key_verify_failed", error = e)))?
+    } else {
+        let pub_key_str =
+            final_pub_key.ok_or_else(|| anyhow::anyhow!(t!("cli.public_key_required")))?;
         base64::Engine::decode(&base64::engine::general_purpose::STANDARD, pub_key_str)
-            .context("公開鍵の Base64 デコードに失敗しました")?;
+            .context(t!("cli.public_key_decode_failed"))?
+    };
+
+    info!(
+        "{}",
+        t!(
+            "cli.server_fingerprint",
+            short = short_fingerprint(&pub_key_bytes),
+            full = fingerprint(&pub_key_bytes)
+        )
+    );
 
-    let rsa_pub_key = Arc::new(
-        RsaKeyPair::from_public_der(&pub_key_bytes)
-            .map_err(|e| anyhow::anyhow!("公開鍵の読み込みに失敗しました: {}", e))?,
+    let mut rsa_pub_key = Arc::new(
+        RsaPublicKey::from_der(&pub_key_bytes)
+            .map_err(|e| anyhow::anyhow!(t!("cli.public_key_load_failed", error = e)))?,
     );
 
+    // プロキシモードでは宛先ポートが接続ごとに決まるため、固定ポートでの接続テストは行わない
+    if let Some(proxy_port) = proxy_port {
+        info!(
+            "{}",
+            t!("cli.proxy_starting", local = proxy_port, url = ws_url_str)
+        );
+        let stats = Arc::new(TunnelStats::new());
+        spawn_status_report(&stats, status_interval);
+        let (_ping_tx, ping_rx) = tokio::sync::mpsc::unbounded_channel();
+        return WsClientService::run_proxy_server(
+            "127.0.0.1".into(),
+            proxy_port,
+            ws_url_str,
+            stats,
+            ping_rx,
+            rsa_pub_key,
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!(t!("cli.client_error", error = e)));
+    }
+
     info!(
-        "Starting secure client tunnel: local {} -> ws {} -> remote {} ({:?})",
-        local_port, ws_url_str, remote_port, proto
+        "{}",
+        t!(
+            "cli.tunnel_starting",
+            local = local_port,
+            url = ws_url_str,
+            remote = remote_port,
+            protocol = format!("{:?}", proto)
+        )
     );
-    let stats = Arc::new(mc_connect_core::services::ws_client::TunnelStats::new());
+    let rotated_key = WsClientService::check_connectivity(
+        &ws_url_str,
+        remote_port,
+        proto.clone(),
+        Arc::clone(&rsa_pub_key),
+    )
+    .await
+    .map_err(|e| connect_error(&e))?;
+
+    if let Some(new_key) = rotated_key {
+        let new_key_b64 =
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &new_key);
+        warn!("{}", t!("cli.key_rotated", key = new_key_b64));
+        match key_source {
+            Some((path, mut cfg)) => {
+                cfg.public_key = new_key_b64;
+                std::fs::write(&path, serde_json::to_string_pretty(&cfg)?)
+                    .context(t!("cli.config_update_failed", path = path))?;
+                info!("{}", t!("cli.config_key_updated", path = path));
+            }
+            None => {
+                warn!("{}", t!("cli.replace_public_key"))
+            }
+        }
+        rsa_pub_key = Arc::new(
+            RsaPublicKey::from_der(&new_key)
+                .map_err(|e| anyhow::anyhow!(t!("cli.public_key_load_failed", error = e)))?,
+        );
+    }
+
+    let stats = Arc::new(TunnelStats::new());
+    spawn_status_report(&stats, status_interval);
     let (_ping_tx, ping_rx) = tokio::sync::mpsc::unbounded_channel();
 
-    WsClientService::start_tunnel_with_protocol(
+    WsClientService::run_tunnel_server(
         "127.0.0.1".into(),
         local_port,
         ws_url_str,
@@ -87,7 +192,92 @@ pub async fn run_client(
         rsa_pub_key,
     )
     .await
-    .map_err(|e| anyhow::anyhow!("Client error: {}", e))?;
+    .map_err(|e| anyhow::anyhow!(t!("cli.client_error", error = e)))?;
 
     Ok(())
 }
+
+/// [connect_error]
+/// 接続テストの失敗を、再試行すべきか設定を見直すべきかの案内を添えたエラーに変換します。
+fn connect_error(e: &CryptoError) -> anyhow::Error {
+    let Some(err) = McConnectError::from_boxed(e) else {
+        return anyhow::anyhow!(t!("cli.client_error", error = e));
+    };
+    let hint = if err.is_retryable() {
+        t!("cli.hint_retry")
+    } else {
+        t!("cli.hint_check_config")
+    };
+    anyhow::anyhow!(t!(
+        "cli.client_error_with_code",
+        code = err.code().as_str(),
+        error = err,
+        hint = hint
+    ))
+}
+
+/// [spawn_status_report]
+/// `--status-interval` が指定されている場合、転送量・速度と中継中の接続の一覧を定期的に表示します。
+/// 速度はコアの計測タスクが更新したスナップショットを購読して表示します。
+fn spawn_status_report(stats: &Arc<TunnelStats>, interval_secs: Option<u64>) {
+    let Some(secs) = interval_secs.filter(|s| *s > 0) else {
+        return;
+    };
+    let stats = Arc::clone(stats);
+    let updates = stats.subscribe();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(secs));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let snapshot = updates.borrow().clone();
+            println!(
+                "{}",
+                t!(
+                    "cli.traffic_status",
+                    up = snapshot.upload_total,
+                    up_speed = snapshot.upload_speed,
+                    down = snapshot.download_total,
+                    down_speed = snapshot.download_speed,
+                    rtt = snapshot
+                        .rtt_ms
+                        .map(|r| format!("{}ms", r))
+                        .unwrap_or_else(|| "--".to_string())
+                )
+            );
+            print_connection_status(&stats);
+        }
+    });
+}
+
+fn print_connection_status(stats: &TunnelStats) {
+    let connections = stats.connections.list();
+    let now = mc_connect_core::time::unix_now();
+    println!(
+        "{}",
+        t!("cli.active_connections", count = connections.len())
+    );
+    if connections.is_empty() {
+        return;
+    }
+    println!(
+        "  {:>4}  {:<22}  {:>8}  {:>12}  {:>12}  {:>7}  STATE",
+        "ID", "PEER", "UPTIME", "UPLOAD", "DOWNLOAD", "RTT"
+    );
+    for c in connections {
+        let rtt = c
+            .rtt_ms
+            .map(|r| format!("{}ms", r))
+            .unwrap_or_else(|| "--".to_string());
+        println!(
+            "  {:>4}  {:<22}  {:>7}s  {:>12}  {:>12}  {:>7}  {:?}",
+            c.id,
+            c.peer_addr,
+            now.saturating_sub(c.started_at),
+            c.upload_total,
+            c.download_total,
+            rtt,
+            c.state
+        );
+    }
+}
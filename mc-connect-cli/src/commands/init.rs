@@ -0,0 +1,103 @@
+use crate::utils::{parse_allowed_ports, read_passphrase, save_server_config};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use mc_connect_core::encryption::{
+    KeyGenerator, KeyMaterial, RsaKeyGenerator, decode_key, encrypt_server_config, fingerprint,
+};
+use mc_connect_core::models::packet::ServerConfig;
+use mc_connect_core::t;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// `init`: サーバー設定 (鍵を含む) を設定ディレクトリに一度だけ作成します。
+///
+/// 既に設定が存在する場合、`--force` がなければ上書きしません。
+/// 上書きするとサーバーの鍵が変わり、配布済みの公開鍵が使えなくなるためです。
+#[allow(clippy::too_many_arguments)]
+pub async fn run_init(
+    config_path: PathBuf,
+    host: String,
+    public_host: String,
+    port: u16,
+    allowed_ports_str: String,
+    key_pair_path: Option<String>,
+    encrypt_key: bool,
+    passphrase_file: Option<String>,
+    force: bool,
+) -> Result<()> {
+    if config_path.exists() {
+        if !force {
+            return Err(anyhow::anyhow!(t!(
+                "cli.config_exists",
+                path = config_path.display()
+            )));
+        }
+        warn!(
+            "{}",
+            t!("cli.config_overwrite", path = config_path.display())
+        );
+    }
+
+    // 許可ポートの書式を保存前に検証しておく
+    parse_allowed_ports(&allowed_ports_str)?;
+
+    let (private_der, public_der) = match key_pair_path {
+        Some(path) => {
+            info!("{}", t!("cli.loading_private_key", path = path));
+            let bytes = fs::read(&path)
+                .await
+                .context(t!("cli.key_file_read_failed", path = path))?;
+            let material = decode_key(&bytes)
+                .map_err(|e| anyhow::anyhow!(t!("cli.key_parse_failed", error = e)))?;
+            let public_der = material
+                .public_der()
+                .map_err(|e| anyhow::anyhow!(t!("cli.public_key_derive_failed", error = e)))?;
+            let KeyMaterial::Private(private_der) = material else {
+                return Err(anyhow::anyhow!(t!("cli.public_key_only", path = path)));
+            };
+            (private_der, public_der)
+        }
+        None => {
+            info!("{}", t!("cli.generating_key_pair"));
+            let kp = RsaKeyGenerator::default()
+                .generate()
+                .map_err(|e| anyhow::anyhow!(t!("cli.key_generation_failed", error = e)))?;
+            (kp.private_key_bytes(), kp.public_key_bytes())
+        }
+    };
+
+    let mut config = ServerConfig {
+        bind_host: host,
+        public_host,
+        port,
+        public_key: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &public_der),
+        private_key: base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            &private_der,
+        ),
+        allowed_ports: allowed_ports_str,
+        retired_keys: Vec::new(),
+        key_encryption: None,
+        allow_legacy_key_wrap: false,
+    };
+
+    if encrypt_key {
+        let passphrase = read_passphrase(passphrase_file.as_deref(), true)?;
+        encrypt_server_config(&mut config, &passphrase)
+            .map_err(|e| anyhow::anyhow!(t!("cli.key_encrypt_failed", error = e)))?;
+        info!("{}", t!("cli.saving_encrypted_key"));
+    }
+
+    save_server_config(&config_path, &config).await?;
+    info!("{}", t!("cli.config_created", path = config_path.display()));
+    info!("{}", t!("cli.public_key", key = config.public_key));
+    info!(
+        "{}",
+        t!(
+            "cli.fingerprint_only",
+            fingerprint = fingerprint(&public_der)
+        )
+    );
+    info!("{}", t!("cli.init_next_step"));
+    Ok(())
+}
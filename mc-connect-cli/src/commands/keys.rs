@@ -0,0 +1,418 @@
+use crate::utils::{load_server_config, read_passphrase, save_server_config, write_private_file};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use log::{info, warn};
+use mc_connect_core::encryption::{
+    KeyFormat, KeyGenerator, KeyMaterial, RsaKeyGenerator, decode_key, decrypt_server_config,
+    encode_key, encrypt_server_config, fingerprint, short_fingerprint,
+};
+use mc_connect_core::models::packet::{RetiredKey, ServerConfig};
+use mc_connect_core::t;
+use mc_connect_core::time::unix_now;
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::fs;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// 生成する鍵のアルゴリズム
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum KeyAlgorithm {
+    Rsa,
+}
+
+/// 鍵ファイルの入出力形式
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum KeyFileFormat {
+    Der,
+    Pem,
+    Base64,
+}
+
+impl From<KeyFileFormat> for KeyFormat {
+    fn from(format: KeyFileFormat) -> Self {
+        match format {
+            KeyFileFormat::Der => KeyFormat::Der,
+            KeyFileFormat::Pem => KeyFormat::Pem,
+            KeyFileFormat::Base64 => KeyFormat::Base64,
+        }
+    }
+}
+
+/// `keys generate`: 新しい鍵ペアを生成して秘密鍵をファイルへ書き出します。
+///
+/// 秘密鍵が端末やログに残らないよう、標準出力へは `stdout` を指定した場合のみ書き出します。
+/// 表示するのは公開鍵とフィンガープリントだけです。
+pub async fn generate_key(
+    algorithm: KeyAlgorithm,
+    bits: usize,
+    output: Option<String>,
+    stdout: bool,
+    format: KeyFileFormat,
+) -> Result<()> {
+
+    let generator = match algorithm {
+        KeyAlgorithm::Rsa => rsa_generator(bits)?,
+    };
+
+    info!(
+        "{}",
+        t!(
+            "cli.generating_key",
+            algorithm = format!("{:?}", algorithm),
+            bits = bits
+        )
+    );
+    let kp = generator
+        .generate()
+        .map_err(|e| anyhow::anyhow!(t!("cli.key_generation_failed", error = e)))?;
+
+    let encoded = encode_key(&KeyMaterial::Private(kp.private_key_bytes()), format.into())
+        .map_err(|e| anyhow::anyhow!(t!("cli.private_key_encode_failed", error = e)))?;
+    // 出力先は clap で必須にしているため、標準出力へ書き出すのは `stdout` を指定した場合のみ
+    let output = if stdout { None } else { output };
+    write_output(output.as_deref(), &encoded, true).await?;
+
+    info!(
+        "{}",
+        t!("cli.public_key", key = to_base64(&kp.public_key_bytes()))
+    );
+    info!(
+        "{}",
+        t!(
+            "cli.fingerprint_only",
+            fingerprint = fingerprint(&kp.public_key_bytes())
+        )
+    );
+    Ok(())
+}
+
+/// `keys show`: 鍵ファイルまたはサーバー設定の公開鍵とフィンガープリントを表示します。
+pub async fn show_key(key: Option<String>, config_path: PathBuf) -> Result<()> {
+    if let Some(path) = key {
+        let bytes = fs::read(&path)
+            .await
+            .context(t!("cli.key_file_read_failed", path = path))?;
+        let material = decode_key(&bytes)
+            .map_err(|e| anyhow::anyhow!(t!("cli.key_load_failed", error = e)))?;
+        let public_der = material
+            .public_der()
+            .map_err(|e| anyhow::anyhow!(t!("cli.public_key_derive_failed", error = e)))?;
+
+        let kind = if material.is_private() {
+            t!("cli.key_kind_private")
+        } else {
+            t!("cli.key_kind_public")
+        };
+        println!("{}", t!("cli.show_file", path = path, kind = kind));
+        print_public_key(&to_base64(&public_der), &public_der);
+        return Ok(());
+    }
+
+    let config = load_server_config(&config_path).await?;
+    let public_der = from_base64(&config.public_key, &t!("cli.key_kind_public"))?;
+
+    println!("{}", t!("cli.show_config", path = config_path.display()));
+    print_public_key(&config.public_key, &public_der);
+    if let Some(params) = &config.key_encryption {
+        println!(
+            "{}",
+            t!(
+                "cli.show_encrypted",
+                kdf = params.kdf,
+                memory = params.memory_kib,
+                iterations = params.iterations,
+                parallelism = params.parallelism,
+                cipher = params.cipher
+            )
+        );
+    }
+
+    if !config.retired_keys.is_empty() {
+        let now = unix_now();
+        println!("{}", t!("cli.show_retired_keys"));
+        for retired in &config.retired_keys {
+            let der = from_base64(&retired.public_key, &t!("cli.key_kind_retired"))?;
+            let status = if retired.expires_at > now {
+                t!(
+                    "cli.retired_valid_for",
+                    days = (retired.expires_at - now).div_ceil(SECS_PER_DAY)
+                )
+            } else {
+                t!("cli.retired_expired")
+            };
+            println!("  - {} ({})", fingerprint(&der), status);
+        }
+    }
+    Ok(())
+}
+
+/// `keys convert`: 鍵ファイルの形式を変換します。
+pub async fn convert_key(
+    input: String,
+    to: KeyFileFormat,
+    output: Option<String>,
+    public_only: bool,
+) -> Result<()> {
+    let bytes = fs::read(&input)
+        .await
+        .context(t!("cli.key_file_read_failed", path = input))?;
+    let mut material =
+        decode_key(&bytes).map_err(|e| anyhow::anyhow!(t!("cli.key_load_failed", error = e)))?;
+
+    if public_only {
+        material = KeyMaterial::Public(
+            material
+                .public_der()
+                .map_err(|e| anyhow::anyhow!(t!("cli.public_key_derive_failed", error = e)))?,
+        );
+    }
+
+    let encoded = encode_key(&material, to.into())
+        .map_err(|e| anyhow::anyhow!(t!("cli.key_encode_failed", error = e)))?;
+    write_output(output.as_deref(), &encoded, material.is_private()).await?;
+    info!(
+        "{}",
+        t!(
+            "cli.key_converted",
+            path = input,
+            format = format!("{:?}", to)
+        )
+    );
+    Ok(())
+}
+
+/// `keys import`: 既存の PEM 秘密鍵をサーバー設定に取り込みます。
+///
+/// 設定ファイルが存在しない場合は、既定値で新しい設定ファイルを作成します。
+pub async fn import_key(
+    pem: String,
+    config_path: PathBuf,
+    passphrase_file: Option<String>,
+) -> Result<()> {
+    let text = fs::read_to_string(&pem)
+        .await
+        .context(t!("cli.pem_read_failed", path = pem))?;
+    if !text.trim_start().starts_with("-----BEGIN") {
+        return Err(anyhow::anyhow!(t!("cli.not_pem", path = pem)));
+    }
+    let material = decode_key(text.as_bytes())
+        .map_err(|e| anyhow::anyhow!(t!("cli.pem_load_failed", error = e)))?;
+    let KeyMaterial::Private(private_der) = &material else {
+        return Err(anyhow::anyhow!(t!("cli.pem_public_only", path = pem)));
+    };
+    let public_der = material
+        .public_der()
+        .map_err(|e| anyhow::anyhow!(t!("cli.public_key_derive_failed", error = e)))?;
+
+    let mut passphrase = None;
+    let mut config = if config_path.exists() {
+        let mut config = load_server_config(&config_path).await?;
+        passphrase = unlock_config(&mut config, passphrase_file.as_deref())?;
+        warn!("{}", t!("cli.replacing_key", path = config_path.display()));
+        config
+    } else {
+        info!(
+            "{}",
+            t!("cli.creating_default_config", path = config_path.display())
+        );
+        ServerConfig {
+            bind_host: "0.0.0.0".to_string(),
+            public_host: "127.0.0.1".to_string(),
+            port: 8080,
+            public_key: String::new(),
+            private_key: String::new(),
+            allowed_ports: "25565:tcp".to_string(),
+            retired_keys: Vec::new(),
+            key_encryption: None,
+            allow_legacy_key_wrap: false,
+        }
+    };
+
+    config.public_key = to_base64(&public_der);
+    config.private_key = to_base64(private_der);
+    lock_config(&mut config, passphrase.as_deref())?;
+    save_server_config(&config_path, &config).await?;
+
+    info!(
+        "{}",
+        t!("cli.key_imported", pem = pem, path = config_path.display())
+    );
+    info!(
+        "{}",
+        t!(
+            "cli.fingerprint_only",
+            fingerprint = fingerprint(&public_der)
+        )
+    );
+    Ok(())
+}
+
+/// `keys rotate`: 新しいサーバー鍵を生成し、現在の鍵を猶予期間付きで退役させます。
+///
+/// 退役した鍵は `retired_keys` に有効期限付きで記録され、期限切れのものはこの時点で削除されます。
+pub async fn rotate_key(
+    config_path: PathBuf,
+    grace_days: u64,
+    bits: usize,
+    passphrase_file: Option<String>,
+) -> Result<()> {
+    let mut config = load_server_config(&config_path).await?;
+    let passphrase = unlock_config(&mut config, passphrase_file.as_deref())?;
+    let now = unix_now();
+
+    let old_public_der = from_base64(&config.public_key, &t!("cli.key_kind_public"))?;
+
+    info!("{}", t!("cli.generating_server_key", bits = bits));
+    let kp = rsa_generator(bits)?
+        .generate()
+        .map_err(|e| anyhow::anyhow!(t!("cli.key_generation_failed", error = e)))?;
+
+    config.retired_keys.retain(|k| k.expires_at > now);
+    config.retired_keys.push(RetiredKey {
+        public_key: std::mem::replace(&mut config.public_key, to_base64(&kp.public_key_bytes())),
+        private_key: std::mem::replace(&mut config.private_key, to_base64(&kp.private_key_bytes())),
+        expires_at: now + grace_days * SECS_PER_DAY,
+    });
+    lock_config(&mut config, passphrase.as_deref())?;
+    save_server_config(&config_path, &config).await?;
+
+    info!(
+        "{}",
+        t!(
+            "cli.retired_key_grace",
+            fingerprint = fingerprint(&old_public_der),
+            days = grace_days
+        )
+    );
+    info!("{}", t!("cli.new_public_key", key = config.public_key));
+    info!(
+        "{}",
+        t!(
+            "cli.new_fingerprint",
+            fingerprint = fingerprint(&kp.public_key_bytes())
+        )
+    );
+    Ok(())
+}
+
+/// `keys encrypt`: サーバー設定の秘密鍵 (旧鍵を含む) をパスフレーズで暗号化します。
+pub async fn encrypt_config_key(
+    config_path: PathBuf,
+    passphrase_file: Option<String>,
+) -> Result<()> {
+    let mut config = load_server_config(&config_path).await?;
+    if config.key_encryption.is_some() {
+        return Err(anyhow::anyhow!(t!(
+            "cli.key_already_encrypted",
+            path = config_path.display()
+        )));
+    }
+
+    let passphrase = read_passphrase(passphrase_file.as_deref(), true)?;
+    lock_config(&mut config, Some(&passphrase))?;
+    save_server_config(&config_path, &config).await?;
+    info!("{}", t!("cli.key_encrypted", path = config_path.display()));
+    Ok(())
+}
+
+/// `keys decrypt`: 暗号化されたサーバー設定の秘密鍵を平文に戻します。
+pub async fn decrypt_config_key(
+    config_path: PathBuf,
+    passphrase_file: Option<String>,
+) -> Result<()> {
+    let mut config = load_server_config(&config_path).await?;
+    if unlock_config(&mut config, passphrase_file.as_deref())?.is_none() {
+        info!(
+            "{}",
+            t!("cli.key_not_encrypted", path = config_path.display())
+        );
+        return Ok(());
+    }
+
+    save_server_config(&config_path, &config).await?;
+    warn!("{}", t!("cli.key_decrypted", path = config_path.display()));
+    Ok(())
+}
+
+/// 設定の秘密鍵が暗号化されていれば復号し、再暗号化用にパスフレーズを返します。
+fn unlock_config(
+    config: &mut ServerConfig,
+    passphrase_file: Option<&str>,
+) -> Result<Option<String>> {
+    if config.key_encryption.is_none() {
+        return Ok(None);
+    }
+    let passphrase = read_passphrase(passphrase_file, false)?;
+    decrypt_server_config(config, &passphrase).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(Some(passphrase))
+}
+
+/// パスフレーズが指定されていれば設定の秘密鍵を暗号化します。
+fn lock_config(config: &mut ServerConfig, passphrase: Option<&str>) -> Result<()> {
+    if let Some(passphrase) = passphrase {
+        encrypt_server_config(config, passphrase)
+            .map_err(|e| anyhow::anyhow!(t!("cli.key_encrypt_failed", error = e)))?;
+    }
+    Ok(())
+}
+
+fn rsa_generator(bits: usize) -> Result<RsaKeyGenerator> {
+    if bits < 2048 {
+        return Err(anyhow::anyhow!(t!("cli.rsa_bits_too_small", bits = bits)));
+    }
+    Ok(RsaKeyGenerator { bits })
+}
+
+/// 鍵をファイル、または標準出力へ書き出します。秘密鍵のファイルは所有者のみ読み書きできる権限で作成します。
+async fn write_output(output: Option<&str>, data: &[u8], private: bool) -> Result<()> {
+    match output {
+        Some(path) => {
+            if private {
+                write_private_file(path, data).await?;
+            } else {
+                fs::write(path, data)
+                    .await
+                    .context(t!("cli.write_failed", path = path))?;
+            }
+            info!("{}", t!("cli.written", path = path));
+        }
+        None => {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(data)?;
+            // テキスト形式 (PEM / Base64) の場合のみ末尾に改行を補う
+            if std::str::from_utf8(data).is_ok() && !data.ends_with(b"\n") {
+                writeln!(stdout)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)
+}
+
+fn from_base64(text: &str, what: &str) -> Result<Vec<u8>> {
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, text)
+        .context(t!("cli.base64_decode_failed", what = what))
+}
+
+/// 公開鍵とフィンガープリントを表示します。
+fn print_public_key(public_key_b64: &str, public_der: &[u8]) {
+    println!("{}", t!("cli.show_public_key", key = public_key_b64));
+    println!(
+        "{}",
+        t!(
+            "cli.show_fingerprint",
+            fingerprint = fingerprint(public_der)
+        )
+    );
+    println!(
+        "{}",
+        t!(
+            "cli.show_short_fingerprint",
+            fingerprint = short_fingerprint(public_der)
+        )
+    );
+}
@@ -1,2 +1,7 @@
 pub mod server;
 pub mod client;
+pub mod keys;
+pub mod init;
+pub mod agent;
+pub mod relay;
+pub mod traffic;
@@ -0,0 +1,55 @@
+use crate::commands::server::read_admin_token;
+use crate::utils::parse_byte_size;
+use anyhow::Result;
+use log::info;
+use mc_connect_core::services::relay::RelayLimits;
+use mc_connect_core::{RelayOptions, start_relay};
+use mc_connect_core::t;
+
+/// `relay`: リバーストンネル用のリレーを起動します。
+///
+/// ホストエージェントは `/agent` に接続して公開鍵のフィンガープリントで登録し、
+/// クライアントは `/ws/{エージェント ID}` に接続します。リレーは共通鍵を持たず、
+/// 暗号化された `Message` をそのまま中継します。中継状況は管理用トークンを指定した場合のみ
+/// `/metrics` で確認できます。`max_bytes_per_agent` を超えたエージェントへの中継は打ち切ります。
+pub async fn run_relay(
+    host: String,
+    port: u16,
+    max_agents: Option<usize>,
+    max_streams_per_agent: Option<u32>,
+    max_bytes_per_agent: Option<String>,
+    allowed_agents: Vec<String>,
+    admin_token_file: Option<String>,
+) -> Result<()> {
+    let limits = RelayLimits {
+        max_agents,
+        max_streams_per_agent,
+        allowed_agents,
+        max_bytes_per_agent: max_bytes_per_agent
+            .as_deref()
+            .map(parse_byte_size)
+            .transpose()?,
+        ..RelayLimits::default()
+    };
+    if limits.allowed_agents.is_empty() {
+        info!("{}", t!("cli.relay_all_agents"));
+    } else {
+        info!(
+            "{}",
+            t!(
+                "cli.relay_allowed_agents",
+                count = limits.allowed_agents.len()
+            )
+        );
+    }
+
+    let admin_token = read_admin_token(admin_token_file.as_deref()).await?;
+    if admin_token.is_some() {
+        info!("{}", t!("cli.relay_metrics_enabled"));
+    }
+
+    info!("{}", t!("server.relay_starting", host = host, port = port));
+    start_relay(&host, port, limits, RelayOptions { admin_token })
+        .await
+        .map_err(|e| anyhow::anyhow!(t!("cli.relay_error", error = e)))
+}
@@ -1,145 +1,80 @@
-use crate::utils::parse_allowed_ports;
+use crate::utils::{
+    ADMIN_TOKEN_ENV, load_server_config, parse_allowed_ports, parse_byte_size, read_passphrase,
+};
 use anyhow::{Context, Result};
-use log::info;
-use mc_connect_core::encryption::{CryptoKeyPair, KeyGenerator, RsaKeyGenerator, RsaKeyPair};
+use log::{info, warn};
+use mc_connect_core::encryption::{CryptoKeyPair, ServerKeyring, decrypt_server_config};
 use mc_connect_core::models::packet::{ClientExportConfig, ServerConfig};
-use mc_connect_core::start_server;
-use std::path::Path;
+use mc_connect_core::services::proxy::{AuditLog, TrafficLedger, TrafficQuota};
+use mc_connect_core::{GatewayOptions, start_server, t};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
 
+/// `server`: 保存済みのサーバー設定を読み込んでゲートウェイを起動します。
+///
+/// 設定ファイルは `init` で作成します。ホスト・ポート・許可ポートはコマンドライン引数で
+/// 一時的に上書きできますが、設定ファイルや鍵は変更しません。
+/// `allow_legacy_key_wrap` は設定ファイルの `allow_legacy_key_wrap` が無効でも旧クライアントを受け付けます。
+///
+/// 転送量はクライアントが使用した鍵ごと・許可ポートごとに `traffic_file` へ記録し、
+/// `daily_cap` / `monthly_cap` を超えている間は新しい接続を拒否します。
+/// 書き出すクライアント用の設定の URL は、`public_tls` を指定するか公開用ホストに `wss://` を付けた場合のみ wss になります。
+/// `audit_log` を指定すると、セッションごとの監査記録を JSON Lines で追記します。
+#[allow(clippy::too_many_arguments)]
 pub async fn run_server(
-    host: String,
+    config_path: PathBuf,
+    host: Option<String>,
     public_host: Option<String>,
-    port: u16,
-    allowed_ports_str: String,
+    port: Option<u16>,
+    allowed_ports_str: Option<String>,
     export: Option<String>,
-    key_pair_path: Option<String>,
-    config_path: Option<String>,
+    public_tls: bool,
+    passphrase_file: Option<String>,
+    allow_legacy_key_wrap: bool,
+    traffic_file: PathBuf,
+    daily_cap: Option<String>,
+    monthly_cap: Option<String>,
+    admin_token_file: Option<String>,
+    audit_log: Option<String>,
 ) -> Result<()> {
-    // 最終的に使用する設定値
-    let final_host: String;
-    let final_port: u16;
-    let final_allowed_ports_str: String;
-    let key_pair_obj: RsaKeyPair;
-
-    if let Some(ref path) = config_path {
-        // --- 設定ファイルモード ---
-        info!("設定ファイル {} を読み込んでいます...", path);
-        let content = fs::read_to_string(path)
-            .await
-            .context(format!("設定ファイル {} の読み込みに失敗しました", path))?;
-        let config: ServerConfig = serde_json::from_str(&content)
-            .context("設定ファイルのフォーマットが正しくありません")?;
-
-        final_host = config.bind_host;
-        final_port = config.port;
-        final_allowed_ports_str = config.allowed_ports; // 設定ファイル内の許可ポート設定を使用
-
-        // 秘密鍵の復元
-        let priv_key_bytes = base64::Engine::decode(
-            &base64::engine::general_purpose::STANDARD,
-            &config.private_key,
-        )
-        .context("秘密鍵の Base64 デコードに失敗しました")?;
-        key_pair_obj = RsaKeyPair::from_private_der(&priv_key_bytes)
-            .map_err(|e| anyhow::anyhow!("秘密鍵の読み込みに失敗しました: {}", e))?;
-
-        info!(
-            "設定ファイルから設定をロードしました: Host={}, Port={}",
-            final_host, final_port
-        );
-    } else {
-        // --- CLI モード (default.json 生成モード) ---
-        final_host = host;
-        final_port = port;
-        final_allowed_ports_str = allowed_ports_str; // CLI 引数を使用
-
-        // 鍵の読み込み (key_pair引数があればそれを使用、なければ default.json 用に新規生成 or default.json があればそれを読むべきだが、今回の要件では「コマンドで起動されたときは default.json として出力」なので新規生成して保存の流れ)
-        // ただし、--key-pair が指定されていれば、それはそちらを優先して読み込む形にする（既存ロジック温存）
-        if let Some(ref path_str) = key_pair_path {
-            // 既存の --key-pair ロジック
-            let path = Path::new(path_str);
-            if path.exists() {
-                info!("既存のキーペアを読み込んでいます: {}", path_str);
-                let bytes = fs::read(path).await.context(format!(
-                    "キーファイル {} の読み込みに失敗しました",
-                    path_str
-                ))?;
-                key_pair_obj = RsaKeyPair::from_private_der(&bytes)
-                    .map_err(|e| anyhow::anyhow!("キーのパースに失敗しました: {}", e))?;
-            } else {
-                info!("新しいキーペアを生成しています...");
-                let generator = RsaKeyGenerator::default();
-                let kp = generator
-                    .generate()
-                    .map_err(|e| anyhow::anyhow!("Key generation failed: {}", e))?;
-                let priv_bytes = kp.private_key_bytes();
-                if let Some(parent) = path.parent() {
-                    if !parent.exists() {
-                        fs::create_dir_all(parent).await?;
-                    }
-                }
-                fs::write(path, &priv_bytes).await?;
-                key_pair_obj = RsaKeyPair::from_private_der(&priv_bytes).unwrap();
-            }
-        } else {
-            // 鍵指定なし -> 新規生成して default.json に埋め込む
-            info!("ServerConfig 用のキーペアを生成中...");
-            let generator = RsaKeyGenerator::default();
-            let kp = generator
-                .generate()
-                .map_err(|e| anyhow::anyhow!("Key generation failed: {}", e))?;
-            key_pair_obj = RsaKeyPair::from_private_der(&kp.private_key_bytes()).unwrap();
-        }
-
-        // default.json への保存
-        let pub_key_b64 = base64::Engine::encode(
-            &base64::engine::general_purpose::STANDARD,
-            key_pair_obj.public_key_bytes(),
-        );
-        let priv_key_b64 = base64::Engine::encode(
-            &base64::engine::general_purpose::STANDARD,
-            key_pair_obj.private_key_bytes(),
-        );
-
-        // 公開ホストの決定
-        let resolved_public_host = public_host.as_deref().unwrap_or("127.0.0.1").to_string();
-
-        let server_config = ServerConfig {
-            bind_host: final_host.clone(),
-            public_host: resolved_public_host,
-            port: final_port,
-            public_key: pub_key_b64,
-            private_key: priv_key_b64,
-            allowed_ports: final_allowed_ports_str.clone(),
-        };
-
-        let json_output = serde_json::to_string_pretty(&server_config)?;
-        let default_config_path = "default.json";
-        fs::write(default_config_path, json_output)
-            .await
-            .context("default.json の保存に失敗しました")?;
-        info!("現在の設定を {} に保存しました。", default_config_path);
-    }
+    let quota = TrafficQuota {
+        daily_bytes: daily_cap.as_deref().map(parse_byte_size).transpose()?,
+        monthly_bytes: monthly_cap.as_deref().map(parse_byte_size).transpose()?,
+    };
+    let admin_token = read_admin_token(admin_token_file.as_deref()).await?;
+
+    let (config, keyring) = load_server_keyring(
+        &config_path,
+        passphrase_file.as_deref(),
+        allow_legacy_key_wrap,
+    )
+    .await?;
+
+    // 引数が指定されていれば設定ファイルの値より優先する
+    let final_host = host.unwrap_or(config.bind_host);
+    let final_port = port.unwrap_or(config.port);
+    let final_public_host = public_host.unwrap_or(config.public_host);
+    let final_allowed_ports_str = allowed_ports_str.unwrap_or(config.allowed_ports);
+
+    info!(
+        "{}",
+        t!("cli.config_loaded", host = final_host, port = final_port)
+    );
 
     // allowd_ports のパース
     let parsed_ports = parse_allowed_ports(&final_allowed_ports_str)?;
 
     let pub_key_b64 = base64::Engine::encode(
         &base64::engine::general_purpose::STANDARD,
-        key_pair_obj.public_key_bytes(),
+        keyring.current().public_key_bytes(),
     );
 
     // 設定のエクスポート (Client配布用)
     if let Some(path) = export {
         let export_data = ClientExportConfig {
             name: "Server Connection".to_string(),
-            ws_url: format!(
-                "ws://{}:{}/ws",
-                public_host.unwrap_or_else(|| "127.0.0.1".to_string()),
-                final_port
-            ),
+            ws_url: ClientExportConfig::gateway_ws_url(&final_public_host, final_port, public_tls),
             mappings: parsed_ports.clone(),
             public_key: pub_key_b64.clone(),
             encryption_type: "RSA".to_string(),
@@ -147,21 +82,130 @@ pub async fn run_server(
         let json = serde_json::to_string_pretty(&export_data)?;
         fs::write(&path, json)
             .await
-            .context(format!("設定ファイル {} の書き込みに失敗しました", path))?;
-        info!("クライアント用設定ファイルを書き出しました: {}", path);
+            .context(t!("cli.config_write_failed", path = path))?;
+        info!("{}", t!("cli.client_config_exported", path = path));
     }
 
     info!("====================================================");
-    info!("サーバーの公開鍵:");
+    info!("{}", t!("cli.server_public_key"));
     info!("{}", pub_key_b64);
+    info!("{}", t!("cli.fingerprint_heading"));
+    info!(
+        "{} ({})",
+        keyring.current().short_fingerprint(),
+        keyring.current().fingerprint()
+    );
     info!("====================================================");
 
-    let rsa_pair = Arc::new(key_pair_obj);
-
-    info!("Starting server on {}:{}", final_host, final_port);
-    start_server(&final_host, final_port, parsed_ports, rsa_pair)
-        .await
-        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+    let ledger = TrafficLedger::open(&traffic_file, quota).map_err(|e| {
+        anyhow::anyhow!(t!(
+            "cli.traffic_read_failed",
+            path = traffic_file.display(),
+            error = e
+        ))
+    })?;
+    info!(
+        "{}",
+        t!(
+            "cli.traffic_recording",
+            path = traffic_file.display(),
+            quota = format!("{:?}", quota)
+        )
+    );
+    if admin_token.is_some() {
+        info!("{}", t!("cli.admin_enabled"));
+    }
+    let audit = audit_log
+        .map(|path| {
+            let log = AuditLog::open(&path).map_err(|e| {
+                anyhow::anyhow!(t!("cli.audit_open_failed", path = path, error = e))
+            })?;
+            info!("{}", t!("cli.audit_enabled", path = path));
+            anyhow::Ok(Arc::new(log))
+        })
+        .transpose()?;
+    let options = GatewayOptions {
+        traffic: Some(Arc::new(ledger)),
+        admin_token,
+        audit,
+        sessions: None,
+    };
+
+    info!(
+        "{}",
+        t!(
+            "server.gateway_starting",
+            host = final_host,
+            port = final_port
+        )
+    );
+    start_server(
+        &final_host,
+        final_port,
+        parsed_ports,
+        Arc::new(keyring),
+        options,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!(t!("cli.server_error", error = e)))?;
 
     Ok(())
 }
+
+/// [load_server_keyring]
+/// サーバー設定を読み込み、秘密鍵 (猶予期間中の旧鍵を含む) を復元します。
+///
+/// 秘密鍵が暗号化されている場合はパスフレーズで復号します (ファイルは暗号化されたまま)。
+/// `server` と `agent` で共通の処理です。
+pub async fn load_server_keyring(
+    config_path: &Path,
+    passphrase_file: Option<&str>,
+    allow_legacy_key_wrap: bool,
+) -> Result<(ServerConfig, ServerKeyring)> {
+    if !config_path.exists() {
+        return Err(anyhow::anyhow!(t!(
+            "cli.server_config_missing",
+            path = config_path.display()
+        )));
+    }
+
+    info!("{}", t!("cli.loading_config", path = config_path.display()));
+    let mut config = load_server_config(&config_path).await?;
+
+    // 秘密鍵が暗号化されている場合はパスフレーズで復号する (ファイルは暗号化されたまま)
+    if config.key_encryption.is_some() {
+        info!("{}", t!("cli.decrypting_key"));
+        let passphrase = read_passphrase(passphrase_file, false)?;
+        decrypt_server_config(&mut config, &passphrase).map_err(|e| anyhow::anyhow!("{}", e))?;
+    }
+
+    // 秘密鍵の復元 (猶予期間中の旧鍵を含む)
+    let mut keyring = ServerKeyring::from_server_config(&config)
+        .map_err(|e| anyhow::anyhow!(t!("cli.private_key_load_failed", error = e)))?;
+    if allow_legacy_key_wrap {
+        keyring.set_allow_legacy_key_wrap(true);
+    }
+    if keyring.allows_legacy_key_wrap() {
+        warn!("{}", t!("cli.legacy_key_wrap_enabled"));
+    }
+    let retired_count = keyring.active_retired().count();
+    if retired_count > 0 {
+        info!("{}", t!("cli.retired_keys_accepted", count = retired_count));
+    }
+
+    Ok((config, keyring))
+}
+
+/// [read_admin_token]
+/// 管理用エンドポイントのトークンをファイル、または環境変数から読み込みます。
+/// どちらも指定されていない場合は None を返し、管理用エンドポイントは無効になります。
+pub async fn read_admin_token(token_file: Option<&str>) -> Result<Option<String>> {
+    let token = match token_file {
+        Some(path) => fs::read_to_string(path)
+            .await
+            .context(t!("cli.token_file_read_failed", path = path))?,
+        None => std::env::var(ADMIN_TOKEN_ENV).unwrap_or_default(),
+    };
+    let token = token.trim().to_string();
+    Ok((!token.is_empty()).then_some(token))
+}
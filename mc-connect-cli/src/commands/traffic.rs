@@ -0,0 +1,58 @@
+use anyhow::Result;
+use mc_connect_core::services::proxy::{TrafficCounter, TrafficLedger};
+use mc_connect_core::t;
+use std::path::PathBuf;
+
+/// `traffic`: ゲートウェイが記録した転送量を表示します。
+///
+/// 記録はゲートウェイが定期的に書き出すため、実行中のゲートウェイの最新の値とは
+/// 数秒ずれることがあります。最新の値は管理用エンドポイント `/admin/traffic` で取得できます。
+pub fn run_traffic(traffic_file: PathBuf, json: bool) -> Result<()> {
+    let report = TrafficLedger::load(&traffic_file).map_err(|e| {
+        anyhow::anyhow!(t!(
+            "cli.traffic_read_failed",
+            path = traffic_file.display(),
+            error = e
+        ))
+    })?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("{}", t!("cli.traffic_file", path = traffic_file.display()));
+    println!(
+        "  {:<24}  {:>16}  {:>16}  {:>16}",
+        "", "TODAY", "THIS MONTH", "TOTAL"
+    );
+    print_counter(&t!("cli.traffic_total"), &report.total);
+    println!("{}", t!("cli.traffic_per_key"));
+    for (key, counter) in &report.keys {
+        print_counter(&short(key), counter);
+    }
+    println!("{}", t!("cli.traffic_per_port"));
+    for (port, counter) in &report.ports {
+        print_counter(&port.to_string(), counter);
+    }
+    Ok(())
+}
+
+fn print_counter(label: &str, counter: &TrafficCounter) {
+    println!(
+        "  {:<24}  {:>16}  {:>16}  {:>16}",
+        label,
+        format!("{} ({})", counter.day_bytes, counter.day),
+        format!("{} ({})", counter.month_bytes, counter.month),
+        counter.total_bytes
+    );
+}
+
+/// 表示幅に収まるよう、フィンガープリントの先頭部分だけを表示します。
+fn short(fingerprint: &str) -> String {
+    if fingerprint.len() > 23 {
+        format!("{}…", &fingerprint[..23])
+    } else {
+        fingerprint.to_string()
+    }
+}
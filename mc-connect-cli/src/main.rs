@@ -1,52 +1,156 @@
 mod commands;
 mod utils;
 
+use crate::commands::agent::run_agent;
 use crate::commands::client::run_client;
+use crate::commands::keys::{
+    KeyAlgorithm, KeyFileFormat, convert_key, decrypt_config_key, encrypt_config_key, generate_key,
+    import_key, rotate_key, show_key,
+};
+use crate::commands::init::run_init;
+use crate::commands::relay::run_relay;
 use crate::commands::server::run_server;
+use crate::commands::traffic::run_traffic;
+use crate::utils::{KNOWN_SERVERS_FILE, SERVER_CONFIG_FILE, TRAFFIC_FILE};
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Command, CommandFactory, FromArgMatches, Parser, Subcommand};
+use mc_connect_core::i18n::{self, Locale};
+use mc_connect_core::t;
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "mc-connect-cli")]
-#[command(author, version, about, long_about = Some("McConnect は Minecraft の TCP 通信を WebSocket に変換してトンネルするツールです。"))]
+// 説明文は言語に合わせて localized_command で設定する
+#[command(author, version)]
 struct Cli {
+    #[arg(long, global = true)]
+    config_dir: Option<String>,
+
+    #[arg(long, global = true, value_parser = parse_locale)]
+    lang: Option<Locale>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// サーバーを起動します
-    Server {
-        /// バインド用のアドレス (ローカル)
+    Init {
         #[arg(short = 'H', long, default_value = "0.0.0.0")]
         host: String,
 
-        /// クライアントが接続するための公開ドメインまたはIP
-        #[arg(long)]
-        public_host: Option<String>,
+        #[arg(long, default_value = "127.0.0.1")]
+        public_host: String,
 
-        /// サーバーが待受けるポート番号
         #[arg(short, long, default_value_t = 8080)]
         port: u16,
 
         #[arg(short, long, default_value = "25565:tcp")]
         allowed_ports: String,
 
-        /// 設定を JSON ファイルとして書き出します
+        #[arg(long)]
+        key_pair: Option<String>,
+
+        #[arg(long)]
+        config: Option<String>,
+
+        #[arg(long)]
+        encrypt_key: bool,
+
+        #[arg(long)]
+        passphrase_file: Option<String>,
+
+        #[arg(long)]
+        force: bool,
+    },
+    Server {
+        #[arg(short = 'H', long)]
+        host: Option<String>,
+
+        #[arg(long)]
+        public_host: Option<String>,
+
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        #[arg(short, long)]
+        allowed_ports: Option<String>,
+
         #[arg(short, long)]
         export: Option<String>,
 
-        /// サーバーの秘密鍵/公開鍵ペア。指定しない場合は新規生成します。
         #[arg(long)]
-        key_pair: Option<String>,
+        public_tls: bool,
+
+        #[arg(long)]
+        config: Option<String>,
+
+        #[arg(long)]
+        passphrase_file: Option<String>,
+
+        #[arg(long)]
+        allow_legacy_key_wrap: bool,
+
+        #[arg(long)]
+        traffic_file: Option<String>,
+
+        #[arg(long)]
+        daily_cap: Option<String>,
+
+        #[arg(long)]
+        monthly_cap: Option<String>,
+
+        #[arg(long)]
+        admin_token_file: Option<String>,
+
+        #[arg(long)]
+        audit_log: Option<String>,
+    },
+    Traffic {
+        #[arg(long)]
+        traffic_file: Option<String>,
+
+        #[arg(long)]
+        json: bool,
+    },
+    Agent {
+        #[arg(short, long)]
+        relay_url: String,
+
+        #[arg(short, long)]
+        allowed_ports: Option<String>,
 
-        /// サーバー設定ファイル (JSON)。指定された場合、このファイルから設定を読み込みます。
-        /// 指定がない場合、CLI 引数を使用して起動し、設定を default.json に書き出します。
         #[arg(long)]
         config: Option<String>,
+
+        #[arg(long)]
+        passphrase_file: Option<String>,
+
+        #[arg(long)]
+        allow_legacy_key_wrap: bool,
+    },
+    Relay {
+        #[arg(short = 'H', long, default_value = "0.0.0.0")]
+        host: String,
+
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        #[arg(long)]
+        max_agents: Option<usize>,
+
+        #[arg(long)]
+        max_streams_per_agent: Option<u32>,
+
+        #[arg(long)]
+        max_bytes_per_agent: Option<String>,
+
+        #[arg(long = "allow-agent")]
+        allowed_agents: Vec<String>,
+
+        #[arg(long)]
+        admin_token_file: Option<String>,
     },
-    /// クライアントトンネルを開始します
     Client {
         #[arg(short, long, default_value_t = 25565)]
         local_port: u16,
@@ -57,20 +161,108 @@ enum Commands {
         #[arg(short, long, default_value = "tcp")]
         protocol: String,
 
-        /// プロキシサーバーの WebSocket URL (例: ws://example.com/ws)
         #[arg(short, long)]
         ws_url: Option<String>,
 
         #[arg(long)]
         list_ports: bool,
 
-        /// サーバーの公開鍵（Base64形式）。セキュア接続に必須です。
         #[arg(long)]
         public_key: Option<String>,
 
-        /// JSON 設定ファイルから接続情報を読み込みます
         #[arg(short, long)]
         config: Option<String>,
+
+        #[arg(long, conflicts_with = "public_key")]
+        tofu: bool,
+
+        #[arg(long)]
+        known_servers: Option<String>,
+
+        #[arg(long, conflicts_with_all = ["local_port", "remote_port"])]
+        proxy_port: Option<u16>,
+
+        #[arg(long)]
+        status_interval: Option<u64>,
+    },
+    Keys {
+        #[command(subcommand)]
+        action: KeysAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeysAction {
+    Generate {
+        #[arg(short, long, value_enum, default_value_t = KeyAlgorithm::Rsa)]
+        algorithm: KeyAlgorithm,
+
+        #[arg(short, long, default_value_t = 4096)]
+        bits: usize,
+
+        #[arg(short, long, required_unless_present = "stdout")]
+        output: Option<String>,
+
+        #[arg(long, conflicts_with = "output")]
+        stdout: bool,
+
+        #[arg(short, long, value_enum, default_value_t = KeyFileFormat::Pem)]
+        format: KeyFileFormat,
+    },
+    Show {
+        #[arg(short, long, conflicts_with = "config")]
+        key: Option<String>,
+
+        #[arg(short, long)]
+        config: Option<String>,
+    },
+    Convert {
+        input: String,
+
+        #[arg(short, long, value_enum)]
+        to: KeyFileFormat,
+
+        #[arg(short, long)]
+        output: Option<String>,
+
+        #[arg(long)]
+        public_only: bool,
+    },
+    Import {
+        pem: String,
+
+        #[arg(short, long)]
+        config: Option<String>,
+
+        #[arg(long)]
+        passphrase_file: Option<String>,
+    },
+    Rotate {
+        #[arg(short, long)]
+        config: Option<String>,
+
+        #[arg(short, long, default_value_t = 7)]
+        grace_days: u64,
+
+        #[arg(short, long, default_value_t = 4096)]
+        bits: usize,
+
+        #[arg(long)]
+        passphrase_file: Option<String>,
+    },
+    Encrypt {
+        #[arg(short, long)]
+        config: Option<String>,
+
+        #[arg(long)]
+        passphrase_file: Option<String>,
+    },
+    Decrypt {
+        #[arg(short, long)]
+        config: Option<String>,
+
+        #[arg(long)]
+        passphrase_file: Option<String>,
     },
 }
 
@@ -78,25 +270,114 @@ enum Commands {
 async fn main() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let cli = Cli::parse();
+    // ヘルプは引数の解析中に表示されるため、--lang を先に読み取ってから説明文を作成する
+    i18n::set_locale(requested_locale().unwrap_or_else(Locale::from_env));
+    let matches = localized_command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    i18n::set_locale(cli.lang.unwrap_or_else(Locale::from_env));
+    // ファイル名が明示されていなければ設定ディレクトリ内の既定のファイルを使用する
+    let config_dir = cli.config_dir;
+    let resolve = |path: Option<String>, file_name: &str| -> Result<PathBuf> {
+        match path {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => Ok(utils::config_dir(config_dir.as_deref())?.join(file_name)),
+        }
+    };
     match cli.command {
+        Commands::Init {
+            host,
+            public_host,
+            port,
+            allowed_ports,
+            key_pair,
+            config,
+            encrypt_key,
+            passphrase_file,
+            force,
+        } => {
+            run_init(
+                resolve(config, SERVER_CONFIG_FILE)?,
+                host,
+                public_host,
+                port,
+                allowed_ports,
+                key_pair,
+                encrypt_key,
+                passphrase_file,
+                force,
+            )
+            .await
+        }
         Commands::Server {
             host,
             public_host,
             port,
             allowed_ports,
             export,
-            key_pair,
+            public_tls,
             config,
+            passphrase_file,
+            allow_legacy_key_wrap,
+            traffic_file,
+            daily_cap,
+            monthly_cap,
+            admin_token_file,
+            audit_log,
         } => {
             run_server(
+                resolve(config, SERVER_CONFIG_FILE)?,
                 host,
                 public_host,
                 port,
                 allowed_ports,
                 export,
-                key_pair,
-                config,
+                public_tls,
+                passphrase_file,
+                allow_legacy_key_wrap,
+                resolve(traffic_file, TRAFFIC_FILE)?,
+                daily_cap,
+                monthly_cap,
+                admin_token_file,
+                audit_log,
+            )
+            .await
+        }
+        Commands::Traffic { traffic_file, json } => {
+            run_traffic(resolve(traffic_file, TRAFFIC_FILE)?, json)
+        }
+        Commands::Agent {
+            relay_url,
+            allowed_ports,
+            config,
+            passphrase_file,
+            allow_legacy_key_wrap,
+        } => {
+            run_agent(
+                resolve(config, SERVER_CONFIG_FILE)?,
+                relay_url,
+                allowed_ports,
+                passphrase_file,
+                allow_legacy_key_wrap,
+            )
+            .await
+        }
+        Commands::Relay {
+            host,
+            port,
+            max_agents,
+            max_streams_per_agent,
+            max_bytes_per_agent,
+            allowed_agents,
+            admin_token_file,
+        } => {
+            run_relay(
+                host,
+                port,
+                max_agents,
+                max_streams_per_agent,
+                max_bytes_per_agent,
+                allowed_agents,
+                admin_token_file,
             )
             .await
         }
@@ -108,6 +389,10 @@ async fn main() -> Result<()> {
             list_ports,
             public_key,
             config,
+            tofu,
+            known_servers,
+            proxy_port,
+            status_interval,
         } => {
             run_client(
                 local_port,
@@ -117,8 +402,276 @@ async fn main() -> Result<()> {
                 list_ports,
                 public_key,
                 config,
+                tofu,
+                resolve(known_servers, KNOWN_SERVERS_FILE)?,
+                proxy_port,
+                status_interval,
             )
             .await
         }
+        Commands::Keys { action } => match action {
+            KeysAction::Generate {
+                algorithm,
+                bits,
+                output,
+                stdout,
+                format,
+            } => generate_key(algorithm, bits, output, stdout, format).await,
+            KeysAction::Show { key, config } => {
+                show_key(key, resolve(config, SERVER_CONFIG_FILE)?).await
+            }
+            KeysAction::Convert {
+                input,
+                to,
+                output,
+                public_only,
+            } => convert_key(input, to, output, public_only).await,
+            KeysAction::Import {
+                pem,
+                config,
+                passphrase_file,
+            } => import_key(pem, resolve(config, SERVER_CONFIG_FILE)?, passphrase_file).await,
+            KeysAction::Rotate {
+                config,
+                grace_days,
+                bits,
+                passphrase_file,
+            } => {
+                rotate_key(
+                    resolve(config, SERVER_CONFIG_FILE)?,
+                    grace_days,
+                    bits,
+                    passphrase_file,
+                )
+                .await
+            }
+            KeysAction::Encrypt {
+                config,
+                passphrase_file,
+            } => encrypt_config_key(resolve(config, SERVER_CONFIG_FILE)?, passphrase_file).await,
+            KeysAction::Decrypt {
+                config,
+                passphrase_file,
+            } => decrypt_config_key(resolve(config, SERVER_CONFIG_FILE)?, passphrase_file).await,
+        },
     }
 }
+
+/// [requested_locale]
+/// コマンドラインの `--lang` を、clap による解析の前に読み取ります。
+///
+/// 値が未対応の場合は `None` を返し、エラーの報告は clap による解析に任せます。
+fn requested_locale() -> Option<Locale> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        let value = match arg.strip_prefix("--lang") {
+            Some("") => args.next(),
+            Some(rest) => rest.strip_prefix('=').map(str::to_string),
+            None => continue,
+        };
+        return value.as_deref().and_then(Locale::parse);
+    }
+    None
+}
+
+/// [localized_command]
+/// 現在の言語で、コマンド・サブコマンド・引数の説明文を設定したコマンド定義を作成します。
+fn localized_command() -> Command {
+    let command = Cli::command()
+        .about(t!("cli.about"))
+        .long_about(t!("cli.long_about"));
+    with_arg_help(
+        command,
+        [
+            ("config_dir", t!("cli.arg_config_dir")),
+            ("lang", t!("cli.arg_lang")),
+        ],
+    )
+    .mut_subcommand("init", |c| {
+        with_arg_help(
+            c.about(t!("cli.cmd_init")),
+            [
+                ("host", t!("cli.arg_init_host")),
+                ("public_host", t!("cli.arg_public_host")),
+                ("port", t!("cli.arg_port")),
+                ("key_pair", t!("cli.arg_init_key_pair")),
+                ("config", t!("cli.arg_init_config")),
+                ("encrypt_key", t!("cli.arg_init_encrypt_key")),
+                ("passphrase_file", t!("cli.arg_passphrase_file")),
+                ("force", t!("cli.arg_init_force")),
+            ],
+        )
+    })
+    .mut_subcommand("server", |c| {
+        with_arg_help(
+            c.about(t!("cli.cmd_server")),
+            [
+                ("host", t!("cli.arg_server_host")),
+                ("public_host", t!("cli.arg_public_host")),
+                ("port", t!("cli.arg_port")),
+                ("export", t!("cli.arg_server_export")),
+                ("public_tls", t!("cli.arg_server_public_tls")),
+                ("config", t!("cli.arg_server_config")),
+                ("passphrase_file", t!("cli.arg_passphrase_file")),
+                ("allow_legacy_key_wrap", t!("cli.arg_allow_legacy_key_wrap")),
+                ("traffic_file", t!("cli.arg_traffic_file")),
+                ("daily_cap", t!("cli.arg_server_daily_cap")),
+                ("monthly_cap", t!("cli.arg_server_monthly_cap")),
+                ("admin_token_file", t!("cli.arg_server_admin_token_file")),
+                ("audit_log", t!("cli.arg_server_audit_log")),
+            ],
+        )
+    })
+    .mut_subcommand("traffic", |c| {
+        with_arg_help(
+            c.about(t!("cli.cmd_traffic"))
+                .long_about(t!("cli.cmd_traffic_long")),
+            [
+                ("traffic_file", t!("cli.arg_traffic_file")),
+                ("json", t!("cli.arg_traffic_json")),
+            ],
+        )
+    })
+    .mut_subcommand("agent", |c| {
+        with_arg_help(
+            c.about(t!("cli.cmd_agent")),
+            [
+                ("relay_url", t!("cli.arg_agent_relay_url")),
+                ("allowed_ports", t!("cli.arg_agent_allowed_ports")),
+                ("config", t!("cli.arg_server_config")),
+                ("passphrase_file", t!("cli.arg_passphrase_file")),
+                ("allow_legacy_key_wrap", t!("cli.arg_allow_legacy_key_wrap")),
+            ],
+        )
+    })
+    .mut_subcommand("relay", |c| {
+        with_arg_help(
+            c.about(t!("cli.cmd_relay")),
+            [
+                ("host", t!("cli.arg_relay_host")),
+                ("port", t!("cli.arg_relay_port")),
+                ("max_agents", t!("cli.arg_relay_max_agents")),
+                (
+                    "max_streams_per_agent",
+                    t!("cli.arg_relay_max_streams_per_agent"),
+                ),
+                (
+                    "max_bytes_per_agent",
+                    t!("cli.arg_relay_max_bytes_per_agent"),
+                ),
+                ("allowed_agents", t!("cli.arg_relay_allowed_agents")),
+                ("admin_token_file", t!("cli.arg_relay_admin_token_file")),
+            ],
+        )
+    })
+    .mut_subcommand("client", |c| {
+        with_arg_help(
+            c.about(t!("cli.cmd_client")),
+            [
+                ("ws_url", t!("cli.arg_client_ws_url")),
+                ("public_key", t!("cli.arg_client_public_key")),
+                ("config", t!("cli.arg_client_config")),
+                ("tofu", t!("cli.arg_client_tofu")),
+                ("known_servers", t!("cli.arg_client_known_servers")),
+                ("proxy_port", t!("cli.arg_client_proxy_port")),
+                ("status_interval", t!("cli.arg_client_status_interval")),
+            ],
+        )
+    })
+    .mut_subcommand("keys", |c| {
+        c.about(t!("cli.cmd_keys"))
+            .mut_subcommand("generate", |c| {
+                with_arg_help(
+                    c.about(t!("cli.cmd_keys_generate")),
+                    [
+                        ("algorithm", t!("cli.arg_keys_generate_algorithm")),
+                        ("bits", t!("cli.arg_keys_generate_bits")),
+                        ("output", t!("cli.arg_keys_generate_output")),
+                        ("stdout", t!("cli.arg_keys_generate_stdout")),
+                        ("format", t!("cli.arg_keys_generate_format")),
+                    ],
+                )
+            })
+            .mut_subcommand("show", |c| {
+                with_arg_help(
+                    c.about(t!("cli.cmd_keys_show")),
+                    [
+                        ("key", t!("cli.arg_keys_show_key")),
+                        ("config", t!("cli.arg_keys_show_config")),
+                    ],
+                )
+            })
+            .mut_subcommand("convert", |c| {
+                with_arg_help(
+                    c.about(t!("cli.cmd_keys_convert")),
+                    [
+                        ("input", t!("cli.arg_keys_convert_input")),
+                        ("to", t!("cli.arg_keys_convert_to")),
+                        ("output", t!("cli.arg_keys_convert_output")),
+                        ("public_only", t!("cli.arg_keys_convert_public_only")),
+                    ],
+                )
+            })
+            .mut_subcommand("import", |c| {
+                with_arg_help(
+                    c.about(t!("cli.cmd_keys_import")),
+                    [
+                        ("pem", t!("cli.arg_keys_import_pem")),
+                        ("config", t!("cli.arg_keys_import_config")),
+                        ("passphrase_file", t!("cli.arg_passphrase_file")),
+                    ],
+                )
+            })
+            .mut_subcommand("rotate", |c| {
+                with_arg_help(
+                    c.about(t!("cli.cmd_keys_rotate")),
+                    [
+                        ("config", t!("cli.arg_target_config")),
+                        ("grace_days", t!("cli.arg_keys_rotate_grace_days")),
+                        ("bits", t!("cli.arg_keys_rotate_bits")),
+                        ("passphrase_file", t!("cli.arg_passphrase_file")),
+                    ],
+                )
+            })
+            .mut_subcommand("encrypt", |c| {
+                with_arg_help(
+                    c.about(t!("cli.cmd_keys_encrypt")),
+                    [
+                        ("config", t!("cli.arg_target_config")),
+                        ("passphrase_file", t!("cli.arg_passphrase_file")),
+                    ],
+                )
+            })
+            .mut_subcommand("decrypt", |c| {
+                with_arg_help(
+                    c.about(t!("cli.cmd_keys_decrypt")),
+                    [
+                        ("config", t!("cli.arg_target_config")),
+                        ("passphrase_file", t!("cli.arg_passphrase_file")),
+                    ],
+                )
+            })
+    })
+}
+
+/// [with_arg_help]
+/// 引数 ID と説明文の組を、まとめてコマンドの引数に設定します。
+fn with_arg_help<const N: usize>(command: Command, helps: [(&str, String); N]) -> Command {
+    helps.into_iter().fold(command, |command, (id, help)| {
+        command.mut_arg(id, |arg| arg.help(help))
+    })
+}
+
+fn parse_locale(value: &str) -> std::result::Result<Locale, String> {
+    // 言語を設定する前なので、環境変数から判定した言語で表示する
+    Locale::parse(value).ok_or_else(|| {
+        i18n::tr_in(
+            Locale::from_env(),
+            "cli.unsupported_lang",
+            &[("lang", value.to_string())],
+        )
+    })
+}
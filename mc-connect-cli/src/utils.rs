@@ -1,5 +1,8 @@
 use anyhow::{Result, Context};
-use mc_connect_core::models::packet::{AllowedPort, Protocol};
+use log::info;
+use std::path::{Path, PathBuf};
+use mc_connect_core::models::packet::{AllowedPort, Protocol, ServerConfig};
+use mc_connect_core::t;
 
 pub fn parse_allowed_ports(input: &str) -> Result<Vec<AllowedPort>> {
     let mut ports = Vec::new();
@@ -9,14 +12,19 @@ pub fn parse_allowed_ports(input: &str) -> Result<Vec<AllowedPort>> {
         
         let subparts: Vec<&str> = part.split(':').collect();
         if subparts.len() != 2 {
-            return Err(anyhow::anyhow!("Invalid format: {}. Expected 'port:protocol'", part));
+            return Err(anyhow::anyhow!(t!("cli.invalid_port_format", value = part)));
         }
         
-        let port: u16 = subparts[0].parse().with_context(|| format!("Invalid port: {}", subparts[0]))?;
+        let port: u16 = subparts[0].parse().with_context(|| t!("cli.invalid_port", value = subparts[0]))?;
         let protocol = match subparts[1].to_lowercase().as_str() {
             "tcp" => Protocol::TCP,
             "udp" => Protocol::UDP,
-            _ => return Err(anyhow::anyhow!("Unsupported protocol: {}", subparts[1])),
+            _ => {
+                return Err(anyhow::anyhow!(t!(
+                    "cli.unsupported_protocol",
+                    value = subparts[1]
+                )));
+            }
         };
         
         ports.push(AllowedPort { port, protocol });
@@ -24,3 +32,135 @@ pub fn parse_allowed_ports(input: &str) -> Result<Vec<AllowedPort>> {
     ports.sort_by_key(|p| p.port);
     Ok(ports)
 }
+
+/// パスフレーズを渡すための環境変数名
+pub const PASSPHRASE_ENV: &str = "MC_CONNECT_PASSPHRASE";
+
+/// [read_passphrase]
+/// 秘密鍵の暗号化に使用するパスフレーズを取得します。
+///
+/// `--passphrase-file` で指定されたファイル、環境変数 `MC_CONNECT_PASSPHRASE`、
+/// 対話入力の順に参照します。`confirm` が真の場合、対話入力では確認のため 2 回入力させます。
+pub fn read_passphrase(passphrase_file: Option<&str>, confirm: bool) -> Result<String> {
+    if let Some(path) = passphrase_file {
+        let content = std::fs::read_to_string(path)
+            .context(t!("cli.passphrase_file_read_failed", path = path))?;
+        info!("{}", t!("cli.passphrase_from_file", path = path));
+        return Ok(content.trim_end_matches(['\r', '\n']).to_string());
+    }
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        info!("{}", t!("cli.passphrase_from_env", name = PASSPHRASE_ENV));
+        return Ok(passphrase);
+    }
+
+    let passphrase = rpassword::prompt_password(t!("cli.passphrase_prompt"))
+        .context(t!("cli.passphrase_input_failed"))?;
+    if confirm {
+        let again = rpassword::prompt_password(t!("cli.passphrase_confirm_prompt"))
+            .context(t!("cli.passphrase_input_failed"))?;
+        if passphrase != again {
+            return Err(anyhow::anyhow!(t!("cli.passphrase_mismatch")));
+        }
+    }
+    Ok(passphrase)
+}
+
+/// 設定ディレクトリを上書きするための環境変数名
+pub const CONFIG_DIR_ENV: &str = "MC_CONNECT_CONFIG_DIR";
+/// 設定ディレクトリ内のサーバー設定ファイル名
+pub const SERVER_CONFIG_FILE: &str = "server.json";
+/// 設定ディレクトリ内の転送量の記録ファイル名
+pub const TRAFFIC_FILE: &str = "traffic.json";
+/// 管理用エンドポイントのトークンを渡すための環境変数名
+pub const ADMIN_TOKEN_ENV: &str = "MC_CONNECT_ADMIN_TOKEN";
+
+/// [parse_byte_size]
+/// `500GB` や `1.5TB` のようなサイズ指定をバイト数に変換します。
+/// 単位は KB / MB / GB / TB (1000 倍ずつ) と KiB / MiB / GiB / TiB (1024 倍ずつ) で、省略時はバイトです。
+pub fn parse_byte_size(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| t!("cli.invalid_size", value = input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => {
+            return Err(anyhow::anyhow!(t!(
+                "cli.unsupported_size_unit",
+                unit = unit
+            )));
+        }
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+/// 設定ディレクトリ内の既知サーバー (TOFU) ファイル名
+pub const KNOWN_SERVERS_FILE: &str = "known_servers.json";
+
+/// [config_dir]
+/// 設定ファイルを保存するディレクトリを返します。
+///
+/// `--config-dir`、環境変数 `MC_CONNECT_CONFIG_DIR`、OS 標準の設定ディレクトリ
+/// (Linux では `$XDG_CONFIG_HOME/mc-connect`) の順に参照します。
+pub fn config_dir(override_dir: Option<&str>) -> Result<PathBuf> {
+    if let Some(dir) = override_dir {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Ok(dir) = std::env::var(CONFIG_DIR_ENV)
+        && !dir.is_empty()
+    {
+        return Ok(PathBuf::from(dir));
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("mc-connect"))
+        .ok_or_else(|| anyhow::anyhow!(t!("cli.config_dir_unknown")))
+}
+
+/// [write_private_file]
+/// 秘密鍵を含むデータを、所有者のみ読み書きできる権限で書き込みます。
+/// 処理は [mc_connect_core::fs::write_private_file] に委ね、失敗時は書き込み先のパスを添えたエラーにします。
+pub async fn write_private_file(path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    mc_connect_core::fs::write_private_file(path, data)
+        .await
+        .context(t!("cli.write_failed", path = path.display()))
+}
+
+/// サーバー設定ファイルを読み込みます。
+pub async fn load_server_config(path: impl AsRef<Path>) -> Result<ServerConfig> {
+    let path = path.as_ref();
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .context(t!("cli.config_read_failed", path = path.display()))?;
+    serde_json::from_str(&content).context(t!("cli.config_invalid"))
+}
+
+/// [save_server_config]
+/// サーバー設定ファイルを保存します。
+/// 親ディレクトリがなければ作成し、秘密鍵を含むため [write_private_file] で所有者のみ読み書きできる権限のファイルへ書き込みます。
+pub async fn save_server_config(path: impl AsRef<Path>, config: &ServerConfig) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context(t!("cli.create_dir_failed", path = parent.display()))?;
+    }
+
+    let json = serde_json::to_string_pretty(config)?;
+    write_private_file(path, json.as_bytes())
+        .await
+        .context(t!("cli.config_write_failed", path = path.display()))
+}
@@ -0,0 +1,92 @@
+//! ヘルプの説明文の言語のテスト
+//!
+//! すべてのサブコマンドの `--help` を実際に表示し、選択した言語で出力されることを検証します。
+
+use std::process::Command;
+
+/// 言語の環境変数を `lang` に固定して CLI を実行し、標準出力を返します。
+fn run_cli(lang: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_mc-connect-cli"))
+        .args(args)
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES")
+        .env("LANG", lang)
+        .output()
+        .expect("CLI の実行に失敗しました");
+    assert!(
+        output.status.success(),
+        "{:?} が失敗しました: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// ヘルプの `Commands:` 欄からサブコマンド名を集めます。
+fn subcommands(help: &str) -> Vec<String> {
+    help.lines()
+        .skip_while(|line| line.trim() != "Commands:")
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| *name != "help")
+        .map(str::to_string)
+        .collect()
+}
+
+/// ひらがな・カタカナ・漢字・全角記号を含むかを判定します。
+fn contains_japanese(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(c,
+            '\u{3000}'..='\u{30FF}' | '\u{4E00}'..='\u{9FFF}' | '\u{FF00}'..='\u{FFEF}')
+    })
+}
+
+/// ルートとすべてのサブコマンドのヘルプを、コマンドのパスと組にして返します。
+fn all_help(lang: &str, global_args: &[&str]) -> Vec<(String, String)> {
+    let mut pending = vec![Vec::<String>::new()];
+    let mut helps = Vec::new();
+    while let Some(path) = pending.pop() {
+        let mut args: Vec<&str> = global_args.to_vec();
+        args.extend(path.iter().map(String::as_str));
+        args.push("--help");
+        let help = run_cli(lang, &args);
+        for name in subcommands(&help) {
+            let mut child = path.clone();
+            child.push(name);
+            pending.push(child);
+        }
+        helps.push((path.join(" "), help));
+    }
+    helps
+}
+
+#[test]
+fn english_help_has_no_japanese_text() {
+    // 環境変数が日本語でも --lang en が優先され、環境変数が英語なら指定なしでも英語になる
+    for (lang, global_args) in [("ja_JP.UTF-8", &["--lang", "en"][..]), ("C", &[][..])] {
+        let helps = all_help(lang, global_args);
+        assert!(
+            helps.iter().any(|(path, _)| path == "keys rotate"),
+            "サブコマンドのヘルプを辿れていません"
+        );
+        for (path, help) in helps {
+            assert!(
+                !contains_japanese(&help),
+                "`{}` の英語のヘルプに日本語が含まれています:\n{}",
+                path,
+                help
+            );
+        }
+    }
+}
+
+#[test]
+fn japanese_help_is_localized() {
+    let help = run_cli("C", &["--lang=ja", "client", "--help"]);
+    assert!(
+        contains_japanese(&help),
+        "日本語のヘルプになっていません:\n{}",
+        help
+    );
+}
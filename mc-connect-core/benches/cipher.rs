@@ -0,0 +1,72 @@
+//! 共通鍵暗号エンジンの性能比較
+//!
+//! AES-256-GCM と ChaCha20-Poly1305 で、Data フレームと同じ in-place 暗号化・復号を比較します。
+//! ゲートウェイを動かすホストでどちらが速いか確認するために使用します。
+//! `cargo bench -p mc-connect-core --bench cipher` で実行します。
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use mc_connect_core::encryption::SymmetricAlgorithm;
+use mc_connect_core::models::frame::Direction;
+use std::hint::black_box;
+
+/// TCP の読み取りバッファ (8 KiB) までの代表的なチャンクサイズ
+const SIZES: &[usize] = &[64, 1024, 8192];
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 + 7) as u8).collect()
+}
+
+fn bench_encrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cipher_encrypt");
+    for algorithm in SymmetricAlgorithm::ALL {
+        let engine = algorithm.generate();
+        let nonce = Direction::ClientToServer.nonce(0);
+        for &size in SIZES {
+            let mut buf = payload(size);
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_function(BenchmarkId::new(algorithm.name(), size), |b| {
+                b.iter(|| {
+                    black_box(
+                        engine
+                            .encrypt_in_place_detached(&nonce, &[], &mut buf)
+                            .unwrap(),
+                    )
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_decrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cipher_decrypt");
+    for algorithm in SymmetricAlgorithm::ALL {
+        let engine = algorithm.generate();
+        let nonce = Direction::ClientToServer.nonce(0);
+        for &size in SIZES {
+            let data = payload(size);
+            let mut ciphertext = data.clone();
+            let tag = engine
+                .encrypt_in_place_detached(&nonce, &[], &mut ciphertext)
+                .unwrap();
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(
+                BenchmarkId::new(algorithm.name(), size),
+                &ciphertext,
+                |b, ciphertext| {
+                    b.iter(|| {
+                        let mut buf = ciphertext.clone();
+                        engine
+                            .decrypt_in_place_detached(&nonce, &[], &mut buf, &tag)
+                            .unwrap();
+                        black_box(buf)
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encrypt, bench_decrypt);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+//! Data 転送のエンコード・デコード性能の比較
+//!
+//! 従来の `Message` (MessagePack + ランダムなナンスによるペイロード暗号化) と、
+//! カウンタ方式のナンスを使うコンパクトなバイナリフレームを比較します。
+//! `cargo bench -p mc-connect-core --bench data_frame` で実行します。
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use mc_connect_core::encryption::{AesGcmEngine, SecureContext, SymmetricCrypto};
+use mc_connect_core::models::frame::Direction;
+use mc_connect_core::models::packet::{Command, Message};
+use std::hint::black_box;
+
+/// TCP の読み取りバッファ (8 KiB) までの代表的なチャンクサイズ
+const SIZES: &[usize] = &[64, 1024, 8192];
+
+fn contexts() -> (SecureContext, SecureContext) {
+    let client = AesGcmEngine::new_random();
+    let server = AesGcmEngine::from_key(&client.key_bytes()).unwrap();
+    let mut client =
+        SecureContext::with_crypto(Box::new(client), Direction::ClientToServer).unwrap();
+    let mut server =
+        SecureContext::with_crypto(Box::new(server), Direction::ServerToClient).unwrap();
+    client.compact_data = true;
+    server.compact_data = true;
+    (client, server)
+}
+
+/// 従来の送信経路と同じく、ペイロードをランダムなナンスで暗号化した `Message` をエンコードします。
+fn seal_legacy(engine: &AesGcmEngine, data: &[u8]) -> Vec<u8> {
+    Message::new(Command::Data, engine.encrypt(data).unwrap())
+        .to_vec()
+        .unwrap()
+}
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 + 7) as u8).collect()
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let (mut client, _) = contexts();
+    let legacy = AesGcmEngine::new_random();
+    let mut group = c.benchmark_group("data_encode");
+    for &size in SIZES {
+        let data = payload(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("message", size), &data, |b, data| {
+            b.iter(|| black_box(seal_legacy(&legacy, data)))
+        });
+        group.bench_with_input(BenchmarkId::new("frame", size), &data, |b, data| {
+            b.iter(|| black_box(client.seal_data_frame(data).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let (mut client, mut server) = contexts();
+    let legacy = AesGcmEngine::new_random();
+    let mut group = c.benchmark_group("data_decode");
+    for &size in SIZES {
+        let data = payload(size);
+        let message = seal_legacy(&legacy, &data);
+        let frame = client.seal_data_frame(&data).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("message", size), &message, |b, bin| {
+            b.iter(|| {
+                let msg = Message::from_slice(bin).unwrap();
+                black_box(legacy.decrypt(&msg.payload).unwrap())
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("frame", size), &frame, |b, bin| {
+            b.iter(|| black_box(server.open_data_frame(bin).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
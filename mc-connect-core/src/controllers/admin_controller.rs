@@ -0,0 +1,51 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use log::warn;
+
+use super::GatewayOptions;
+use crate::t;
+
+/// 管理用エンドポイントの認証に使用するヘッダーの接頭辞
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// [traffic]
+/// `GET /admin/traffic`: 鍵ごと・許可ポートごとの転送量と上限を JSON で返します。
+///
+/// `Authorization: Bearer <管理用トークン>` が必要です。
+/// 管理用トークンが設定されていない、または転送量を記録していないゲートウェイでは 404 を返します。
+pub async fn traffic(req: HttpRequest, options: web::Data<GatewayOptions>) -> HttpResponse {
+    let (Some(token), Some(ledger)) = (&options.admin_token, &options.traffic) else {
+        return HttpResponse::NotFound().finish();
+    };
+    if !is_authorized(&req, token) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    HttpResponse::Ok().json(ledger.report())
+}
+
+/// [is_authorized]
+/// `Authorization: Bearer <管理用トークン>` が一致するかを確認します。一致しない場合は警告を記録します。
+///
+/// ゲートウェイの `/admin/...` とリレーの `/metrics` で共通して使用します。
+pub(super) fn is_authorized(req: &HttpRequest, token: &str) -> bool {
+    let provided = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix(BEARER_PREFIX));
+    let authorized = provided.is_some_and(|p| constant_time_eq(p.as_bytes(), token.as_bytes()));
+    if !authorized {
+        warn!(
+            "{}",
+            t!(
+                "server.admin_auth_failed",
+                peer = format!("{:?}", req.peer_addr())
+            )
+        );
+    }
+    authorized
+}
+
+/// トークンの比較にかかる時間から内容を推測されないよう、長さが同じ場合は常に全体を比較します。
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
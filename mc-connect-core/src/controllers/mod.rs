@@ -1,10 +1,40 @@
 pub mod health_controller;
 pub mod ws_controller;
+pub mod relay_controller;
+pub mod admin_controller;
 
+use actix_web::dev::Server;
 use actix_web::{App, HttpServer, web};
 use log::info;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
 use crate::models::packet::AllowedPort;
+use crate::services::proxy::{AuditLog, SessionRegistry, TrafficLedger};
+use crate::services::relay::{RelayLimits, RelayRegistry};
+use crate::t;
+
+/// [GatewayOptions]
+/// ゲートウェイの任意の機能の設定です。既定値ではすべて無効です。
+#[derive(Clone, Default)]
+pub struct GatewayOptions {
+    /// 転送量の記録と上限。None の場合は記録しません。
+    pub traffic: Option<Arc<TrafficLedger>>,
+    /// 管理用エンドポイント (`/admin/...`) のトークン。None の場合は管理用エンドポイントを無効にします。
+    pub admin_token: Option<String>,
+    /// セッションごとの監査ログ (JSON Lines)。None の場合は書き出しません。
+    pub audit: Option<Arc<AuditLog>>,
+    /// 中継中のセッションの一覧。None の場合は記録しません。
+    pub sessions: Option<Arc<SessionRegistry>>,
+}
+
+/// [RelayOptions]
+/// リレーの任意の機能の設定です。既定値ではすべて無効です。
+#[derive(Clone, Default)]
+pub struct RelayOptions {
+    /// `/metrics` のトークン。None の場合は `/metrics` を無効にします。
+    pub admin_token: Option<String>,
+}
 
 /// サーバーを起動するためのメインエントリーポイント
 ///
@@ -12,30 +42,130 @@ use crate::models::packet::AllowedPort;
 /// * `host` - バインドするホスト名 (例: "127.0.0.1")
 /// * `port` - 待受ポート番号
 /// * `allowed_ports` - 許可するターゲットポートのリスト
-/// * `server_key` - サーバーのRSAキーペア
+/// * `server_keys` - サーバーの鍵 (猶予期間中の旧鍵を含む)
+/// * `options` - 転送量の記録や管理用エンドポイントなどの設定
 pub async fn start_server(
     host: &str,
     port: u16,
     allowed_ports: Vec<AllowedPort>,
-    server_key: std::sync::Arc<crate::encryption::RsaKeyPair>,
+    server_keys: std::sync::Arc<crate::encryption::ServerKeyring>,
+    options: GatewayOptions,
 ) -> std::io::Result<()> {
-    info!("McConnect サーバーを起動中: {}:{}", host, port);
-    info!("許可されたポート: {:?}", allowed_ports);
+    let (srv, _) = bind_server(host, port, allowed_ports, server_keys, options)?;
+    srv.await
+}
+
+/// [bind_server]
+/// サーバーをバインドし、実行前の `Server` と実際に待ち受けているアドレスを返します。
+///
+/// `port` に 0 を指定すると OS が空きポートを割り当てるため、テストや
+/// アプリへの組み込みで待受ポートを後から知りたい場合に使用します。
+/// 返された `Server` は await するか spawn するまで接続を処理しません。
+pub fn bind_server(
+    host: &str,
+    port: u16,
+    allowed_ports: Vec<AllowedPort>,
+    server_keys: std::sync::Arc<crate::encryption::ServerKeyring>,
+    options: GatewayOptions,
+) -> std::io::Result<(Server, SocketAddr)> {
+    info!(
+        "{}",
+        t!("server.gateway_starting", host = host, port = port)
+    );
+    info!(
+        "{}",
+        t!(
+            "server.allowed_ports",
+            ports = format!("{:?}", allowed_ports)
+        )
+    );
 
     let allowed_ports = web::Data::new(allowed_ports);
-    let server_key = web::Data::new(server_key);
+    let server_keys = web::Data::new(server_keys);
+    let options = web::Data::new(options);
 
     let srv = HttpServer::new(move || {
         App::new()
             .app_data(allowed_ports.clone())
-            .app_data(server_key.clone())
+            .app_data(server_keys.clone())
+            .app_data(options.clone())
             // ヘルスチェックエンドポイントの登録
             .service(health_controller::health_check)
+            // 管理用エンドポイント (管理用トークンが必要)
+            .route("/admin/traffic", web::get().to(admin_controller::traffic))
             // WebSocket プロキシエンドポイントの登録
             .route("/ws", web::get().to(ws_controller::ws_proxy))
     })
-    .bind((host, port))?
-    .run();
+    .bind((host, port))?;
+
+    let local_addr = srv.addrs().first().copied().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::AddrNotAvailable,
+            t!("server.bind_failed"),
+        )
+    })?;
+    Ok((srv.run(), local_addr))
+}
 
+/// [start_relay]
+/// リバーストンネル用のリレーを起動します。
+///
+/// # 引数
+/// * `host` - バインドするホスト名 (例: "0.0.0.0")
+/// * `port` - 待受ポート番号
+/// * `limits` - エージェント数やエージェントごとのストリーム数・転送量の上限
+/// * `options` - `/metrics` のトークンなどの設定
+pub async fn start_relay(
+    host: &str,
+    port: u16,
+    limits: RelayLimits,
+    options: RelayOptions,
+) -> std::io::Result<()> {
+    let (srv, _, _) = bind_relay(host, port, limits, options)?;
     srv.await
 }
+
+/// [bind_relay]
+/// リレーをバインドし、実行前の `Server`、待ち受けているアドレス、エージェントの登録簿を返します。
+///
+/// ホストエージェントは `/agent` に接続して登録し、クライアントは `/ws/{agent_id}` に接続します。
+/// 中継状況は `/metrics` で JSON として取得できます (管理用トークンが必要です)。
+/// リレーは共通鍵を持たず、クライアントとエージェントの間の暗号化されたバイナリをそのまま中継します。
+pub fn bind_relay(
+    host: &str,
+    port: u16,
+    limits: RelayLimits,
+    options: RelayOptions,
+) -> std::io::Result<(Server, SocketAddr, std::sync::Arc<RelayRegistry>)> {
+    info!("{}", t!("server.relay_starting", host = host, port = port));
+    info!(
+        "{}",
+        t!("server.relay_limits", limits = format!("{:?}", limits))
+    );
+
+    let registry = std::sync::Arc::new(RelayRegistry::new(limits));
+    let registry_data = web::Data::new(registry.clone());
+    let options = web::Data::new(options);
+
+    let srv = HttpServer::new(move || {
+        App::new()
+            .app_data(registry_data.clone())
+            .app_data(options.clone())
+            .service(health_controller::health_check)
+            // 中継状況 (管理用トークンが必要)
+            .route("/metrics", web::get().to(relay_controller::metrics))
+            // ホストエージェントの登録
+            .route("/agent", web::get().to(relay_controller::agent_ws))
+            // クライアントの中継
+            .route("/ws/{agent_id}", web::get().to(relay_controller::client_ws))
+    })
+    .bind((host, port))?;
+
+    let local_addr = srv.addrs().first().copied().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::AddrNotAvailable,
+            t!("server.bind_failed"),
+        )
+    })?;
+    Ok((srv.run(), local_addr, registry))
+}
@@ -0,0 +1,95 @@
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use actix_web_actors::ws;
+use log::{info, warn};
+use std::sync::Arc;
+
+use super::RelayOptions;
+use super::admin_controller::is_authorized;
+use crate::services::relay::{
+    RelayAgentSession, RelayClientSession, RelayRegistry, StreamRejection,
+};
+use crate::t;
+
+/// ホストエージェントの登録を受け付けるハンドラ (`GET /agent`)
+///
+/// WebSocket にアップグレードし、以降の通信を RelayAgentSession アクターに委ねます。
+pub async fn agent_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    registry: web::Data<Arc<RelayRegistry>>,
+) -> Result<HttpResponse, Error> {
+    info!(
+        "{}",
+        t!(
+            "server.agent_request",
+            peer = format!("{:?}", req.peer_addr())
+        )
+    );
+    ws::start(
+        RelayAgentSession::new(registry.get_ref().clone()),
+        &req,
+        stream,
+    )
+}
+
+/// クライアントをエージェントへ中継するハンドラ (`GET /ws/{agent_id}`)
+///
+/// 指定したエージェントが登録されていない場合は 404、
+/// エージェントのストリーム数が上限に達している場合は 503、転送量が上限に達している場合は 429 を返します。
+pub async fn client_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    path: web::Path<String>,
+    registry: web::Data<Arc<RelayRegistry>>,
+) -> Result<HttpResponse, Error> {
+    let agent_id = path.into_inner();
+    let (agent, stats) = match registry.open_stream(&agent_id) {
+        Ok(v) => v,
+        Err(StreamRejection::UnknownAgent) => {
+            warn!("{}", t!("server.unknown_agent", agent = agent_id));
+            return Ok(HttpResponse::NotFound().body(t!("relay.agent_not_found")));
+        }
+        Err(StreamRejection::QuotaExceeded) => {
+            warn!("{}", t!("server.too_many_streams", agent = agent_id));
+            return Ok(HttpResponse::ServiceUnavailable().body(t!("relay.agent_stream_limit")));
+        }
+        Err(StreamRejection::ByteQuotaExceeded) => {
+            warn!(
+                "{}",
+                t!("server.agent_byte_quota_exceeded", agent = agent_id)
+            );
+            return Ok(HttpResponse::TooManyRequests().body(t!("relay.agent_traffic_limit")));
+        }
+    };
+    info!(
+        "{}",
+        t!(
+            "server.relay_request",
+            agent = agent_id,
+            peer = format!("{:?}", req.peer_addr())
+        )
+    );
+    let session = RelayClientSession::new(agent, registry.next_stream_id(), Arc::clone(&stats));
+    ws::start(session, &req, stream).inspect_err(|_| {
+        // アップグレードに失敗した場合はセッションが開始されないため、予約したストリームを戻す
+        stats.close_stream();
+    })
+}
+
+/// リレーの中継状況を JSON で返すハンドラ (`GET /metrics`)
+///
+/// 登録中のエージェント ID と転送量を含むため、`Authorization: Bearer <管理用トークン>` が必要です。
+/// 管理用トークンが設定されていないリレーでは 404 を返します。
+pub async fn metrics(
+    req: HttpRequest,
+    registry: web::Data<Arc<RelayRegistry>>,
+    options: web::Data<RelayOptions>,
+) -> HttpResponse {
+    let Some(token) = &options.admin_token else {
+        return HttpResponse::NotFound().finish();
+    };
+    if !is_authorized(&req, token) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    HttpResponse::Ok().json(registry.metrics())
+}
@@ -4,7 +4,9 @@ use crate::services::proxy::WsProxySession;
 use log::info;
 
 use crate::models::packet::AllowedPort;
-use crate::encryption::RsaKeyPair;
+use crate::encryption::ServerKeyring;
+use super::GatewayOptions;
+use crate::t;
 use std::sync::Arc;
 
 /// WebSocket 通信を開始するためのハンドラ
@@ -15,16 +17,20 @@ pub async fn ws_proxy(
     req: HttpRequest, 
     stream: web::Payload,
     allowed_ports: web::Data<Vec<AllowedPort>>,
-    server_key: web::Data<Arc<RsaKeyPair>>
+    server_keys: web::Data<Arc<ServerKeyring>>,
+    options: web::Data<GatewayOptions>,
 ) -> Result<HttpResponse, Error> {
-    info!("WebSocket へのアップグレード要求を受信: {:?}", req.peer_addr());
+    info!("{}", t!("server.upgrade_request", peer = format!("{:?}", req.peer_addr())));
     
     // Actix アクターを使用して WebSocket セッションを開始
     ws::start(
         WsProxySession::new(
             allowed_ports.get_ref().clone(), 
-            server_key.get_ref().clone()
-        ), 
+            server_keys.get_ref().clone(),
+            options.traffic.clone(),
+        )
+        .with_audit(options.audit.clone(), req.peer_addr())
+        .with_sessions(options.sessions.clone(), req.peer_addr()), 
         &req, 
         stream
     )
@@ -1,10 +1,11 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit, AeadCore},
-    Aes256Gcm, Key, Nonce
+    Aes256Gcm, Key, Nonce, Tag,
+    aead::{Aead, AeadCore, AeadInPlace, KeyInit},
 };
 use rand::RngCore;
 use rand::rngs::OsRng;
-use super::traits::{SymmetricCrypto, CryptoError};
+use super::traits::{SymmetricCrypto, CryptoError, NONCE_LEN, TAG_LEN};
+use crate::t;
 
 pub struct AesGcmEngine {
     cipher: Aes256Gcm,
@@ -25,7 +26,7 @@ impl AesGcmEngine {
 
     pub fn from_key(key_bytes: &[u8]) -> Result<Self, CryptoError> {
         if key_bytes.len() != 32 {
-            return Err("AES-256 の鍵は正確に 32バイトである必要があります。".into());
+            return Err(t!("crypto.invalid_key_length", cipher = "AES-256").into());
         }
         let key = Key::<Aes256Gcm>::from_slice(key_bytes);
         let cipher = Aes256Gcm::new(key);
@@ -37,10 +38,14 @@ impl AesGcmEngine {
 }
 
 impl SymmetricCrypto for AesGcmEngine {
+    fn algorithm_name(&self) -> &str {
+        "AES-256-GCM"
+    }
+
     fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
         let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
         let ciphertext = self.cipher.encrypt(&nonce, plaintext)
-            .map_err(|e| format!("AES-GCM 暗号化に失敗しました: {}", e))?;
+            .map_err(|e| t!("crypto.encrypt_failed", cipher = "AES-GCM", error = e))?;
 
         let mut result = Vec::with_capacity(nonce.len() + ciphertext.len());
         result.extend_from_slice(&nonce);
@@ -50,16 +55,49 @@ impl SymmetricCrypto for AesGcmEngine {
 
     fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
         if data.len() < 12 {
-            return Err("暗号文が短すぎます。Nonce (12バイト) が含まれていません。".into());
+            return Err(t!("crypto.ciphertext_too_short").into());
         }
         let (nonce_bytes, ciphertext) = data.split_at(12);
         let nonce = Nonce::from_slice(nonce_bytes);
         let plaintext = self.cipher.decrypt(nonce, ciphertext)
-            .map_err(|e| format!("AES-GCM 復号に失敗しました: {} (データが改ざんされている可能性があります)", e))?;
+            .map_err(|e| t!("crypto.decrypt_failed", cipher = "AES-GCM", error = e))?;
         Ok(plaintext)
     }
 
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<[u8; TAG_LEN], CryptoError> {
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(Nonce::from_slice(nonce), aad, buffer)
+            .map_err(|e| t!("crypto.encrypt_failed", cipher = "AES-GCM", error = e))?;
+        Ok(tag.into())
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), CryptoError> {
+        if tag.len() != TAG_LEN {
+            return Err(t!("crypto.invalid_tag_length").into());
+        }
+        self.cipher
+            .decrypt_in_place_detached(Nonce::from_slice(nonce), aad, buffer, Tag::from_slice(tag))
+            .map_err(|e| t!("crypto.decrypt_failed", cipher = "AES-GCM", error = e))?;
+        Ok(())
+    }
+
     fn key_bytes(&self) -> Vec<u8> {
         self.key.clone()
     }
+
+    fn with_key(&self, key: &[u8]) -> Result<Box<dyn SymmetricCrypto>, CryptoError> {
+        Ok(Box::new(Self::from_key(key)?))
+    }
 }
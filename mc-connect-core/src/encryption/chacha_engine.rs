@@ -0,0 +1,128 @@
+use super::traits::{CryptoError, NONCE_LEN, SymmetricCrypto, TAG_LEN};
+use crate::t;
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, Nonce, Tag,
+    aead::{Aead, AeadCore, AeadInPlace, KeyInit},
+};
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+/// [ChaCha20Poly1305Engine]
+/// ChaCha20-Poly1305 による共通鍵暗号エンジンです。
+/// AES のハードウェア支援がない環境 (Raspberry Pi などの ARM) では AES-GCM より高速に動作します。
+pub struct ChaCha20Poly1305Engine {
+    cipher: ChaCha20Poly1305,
+    key: Vec<u8>,
+}
+
+impl ChaCha20Poly1305Engine {
+    pub fn new_random() -> Self {
+        let mut key_bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut key_bytes);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key_bytes));
+        Self {
+            cipher,
+            key: key_bytes.to_vec(),
+        }
+    }
+
+    pub fn from_key(key_bytes: &[u8]) -> Result<Self, CryptoError> {
+        if key_bytes.len() != 32 {
+            return Err(t!("crypto.invalid_key_length", cipher = "ChaCha20-Poly1305").into());
+        }
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key_bytes));
+        Ok(Self {
+            cipher,
+            key: key_bytes.to_vec(),
+        })
+    }
+}
+
+impl SymmetricCrypto for ChaCha20Poly1305Engine {
+    fn algorithm_name(&self) -> &str {
+        "ChaCha20-Poly1305"
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher.encrypt(&nonce, plaintext).map_err(|e| {
+            t!(
+                "crypto.encrypt_failed",
+                cipher = "ChaCha20-Poly1305",
+                error = e
+            )
+        })?;
+
+        let mut result = Vec::with_capacity(nonce.len() + ciphertext.len());
+        result.extend_from_slice(&nonce);
+        result.extend_from_slice(&ciphertext);
+        Ok(result)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if data.len() < NONCE_LEN {
+            return Err(t!("crypto.ciphertext_too_short").into());
+        }
+        let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+            .map_err(|e| {
+                t!(
+                    "crypto.decrypt_failed",
+                    cipher = "ChaCha20-Poly1305",
+                    error = e
+                )
+            })?;
+        Ok(plaintext)
+    }
+
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<[u8; TAG_LEN], CryptoError> {
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(Nonce::from_slice(nonce), aad, buffer)
+            .map_err(|e| {
+                t!(
+                    "crypto.encrypt_failed",
+                    cipher = "ChaCha20-Poly1305",
+                    error = e
+                )
+            })?;
+        Ok(tag.into())
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), CryptoError> {
+        if tag.len() != TAG_LEN {
+            return Err(t!("crypto.invalid_tag_length").into());
+        }
+        self.cipher
+            .decrypt_in_place_detached(Nonce::from_slice(nonce), aad, buffer, Tag::from_slice(tag))
+            .map_err(|e| {
+                t!(
+                    "crypto.decrypt_failed",
+                    cipher = "ChaCha20-Poly1305",
+                    error = e
+                )
+            })?;
+        Ok(())
+    }
+
+    fn key_bytes(&self) -> Vec<u8> {
+        self.key.clone()
+    }
+
+    fn with_key(&self, key: &[u8]) -> Result<Box<dyn SymmetricCrypto>, CryptoError> {
+        Ok(Box::new(Self::from_key(key)?))
+    }
+}
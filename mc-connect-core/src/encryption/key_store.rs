@@ -0,0 +1,140 @@
+use argon2::{Argon2, Params, Version};
+use base64::{Engine as _, engine::general_purpose};
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use super::aes_engine::AesGcmEngine;
+use super::traits::{CryptoError, SymmetricCrypto};
+use crate::models::packet::{KeyEncryption, ServerConfig};
+use crate::t;
+
+/// 現在サポートしている KDF の識別子
+pub const KDF_ARGON2ID: &str = "argon2id";
+/// 現在サポートしている暗号方式の識別子
+pub const CIPHER_AES_256_GCM: &str = "aes-256-gcm";
+
+impl KeyEncryption {
+    /// [argon2id]
+    /// ランダムなソルトと既定のコストで Argon2id のパラメータを作成します。
+    /// コストは OWASP の推奨値 (19 MiB, 2 回, 並列度 1) に合わせています。
+    pub fn argon2id() -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            kdf: KDF_ARGON2ID.to_string(),
+            cipher: CIPHER_AES_256_GCM.to_string(),
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+            salt: general_purpose::STANDARD.encode(salt),
+        }
+    }
+
+    /// パスフレーズから AES-256-GCM の鍵を導出します。
+    fn derive_engine(&self, passphrase: &str) -> Result<AesGcmEngine, CryptoError> {
+        if self.kdf != KDF_ARGON2ID || self.cipher != CIPHER_AES_256_GCM {
+            return Err(t!(
+                "crypto.unsupported_key_encryption",
+                kdf = self.kdf,
+                cipher = self.cipher
+            )
+            .into());
+        }
+        let salt = general_purpose::STANDARD.decode(&self.salt)?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| t!("crypto.invalid_argon2_params", error = e))?;
+        let argon2 = Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params);
+
+        let mut key = [0u8; 32];
+        argon2
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| t!("crypto.key_derivation_failed", error = e))?;
+        AesGcmEngine::from_key(&key)
+    }
+}
+
+/// [encrypt_server_config]
+/// `ServerConfig` に含まれる秘密鍵 (旧鍵を含む) をパスフレーズで暗号化します。
+///
+/// 秘密鍵は Argon2id で導出した鍵による AES-256-GCM の暗号文 (Nonce 付き、Base64) に置き換えられ、
+/// 使用した KDF パラメータが `key_encryption` に記録されます。
+pub fn encrypt_server_config(
+    config: &mut ServerConfig,
+    passphrase: &str,
+) -> Result<(), CryptoError> {
+    if config.key_encryption.is_some() {
+        return Err(t!("crypto.key_already_encrypted").into());
+    }
+    if passphrase.is_empty() {
+        return Err(t!("crypto.empty_passphrase").into());
+    }
+
+    let params = KeyEncryption::argon2id();
+    let engine = params.derive_engine(passphrase)?;
+
+    config.private_key = seal(&engine, &config.private_key)?;
+    for retired in &mut config.retired_keys {
+        retired.private_key = seal(&engine, &retired.private_key)?;
+    }
+    config.key_encryption = Some(params);
+    Ok(())
+}
+
+/// [decrypt_server_config]
+/// `encrypt_server_config` で暗号化された秘密鍵を復号し、平文の Base64 に戻します。
+/// 暗号化されていない設定に対しては何もしません。
+pub fn decrypt_server_config(
+    config: &mut ServerConfig,
+    passphrase: &str,
+) -> Result<(), CryptoError> {
+    let Some(params) = &config.key_encryption else {
+        return Ok(());
+    };
+    let engine = params.derive_engine(passphrase)?;
+
+    config.private_key = open(&engine, &config.private_key)?;
+    for retired in &mut config.retired_keys {
+        retired.private_key = open(&engine, &retired.private_key)?;
+    }
+    config.key_encryption = None;
+    Ok(())
+}
+
+/// [encrypt_private_key]
+/// 秘密鍵 1 つ (Base64 の DER) をパスフレーズで暗号化し、暗号文 (Base64) と KDF パラメータを返します。
+/// `ServerConfig` を使わず秘密鍵だけを保存するアプリの設定で使用します。
+pub fn encrypt_private_key(
+    private_key_b64: &str,
+    passphrase: &str,
+) -> Result<(String, KeyEncryption), CryptoError> {
+    if passphrase.is_empty() {
+        return Err(t!("crypto.empty_passphrase").into());
+    }
+    let params = KeyEncryption::argon2id();
+    let engine = params.derive_engine(passphrase)?;
+    Ok((seal(&engine, private_key_b64)?, params))
+}
+
+/// [decrypt_private_key]
+/// [encrypt_private_key] で暗号化された秘密鍵を復号し、平文の Base64 に戻します。
+pub fn decrypt_private_key(
+    sealed_b64: &str,
+    params: &KeyEncryption,
+    passphrase: &str,
+) -> Result<String, CryptoError> {
+    let engine = params.derive_engine(passphrase)?;
+    open(&engine, sealed_b64)
+}
+
+fn seal(engine: &AesGcmEngine, private_key_b64: &str) -> Result<String, CryptoError> {
+    let der = general_purpose::STANDARD.decode(private_key_b64)?;
+    Ok(general_purpose::STANDARD.encode(engine.encrypt(&der)?))
+}
+
+fn open(engine: &AesGcmEngine, sealed_b64: &str) -> Result<String, CryptoError> {
+    let sealed = general_purpose::STANDARD.decode(sealed_b64)?;
+    let der = engine
+        .decrypt(&sealed)
+        .map_err(|_| t!("crypto.key_decrypt_failed"))?;
+    Ok(general_purpose::STANDARD.encode(der))
+}
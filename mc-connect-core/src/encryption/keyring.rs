@@ -0,0 +1,105 @@
+use base64::{Engine as _, engine::general_purpose};
+
+use super::rsa_engine::RsaKeyPair;
+use super::secure_connect::sign_key_rotation;
+use super::traits::{CryptoError, CryptoKeyPair};
+use crate::models::packet::{KeyRotation, ServerConfig};
+use crate::t;
+use crate::time::unix_now;
+
+/// [RetiredServerKey]
+/// ローテーションで退役した旧サーバー鍵です。有効期限までは引き続きハンドシェイクに使用できます。
+pub struct RetiredServerKey {
+    /// 旧鍵ペア
+    pub key: RsaKeyPair,
+    /// 受け付けを終了する時刻 (UNIX 秒)
+    pub expires_at: u64,
+    /// 旧鍵で現在の公開鍵に署名したもの ([sign_key_rotation])。クライアントが新しい鍵を検証するために使用します。
+    pub rotation_signature: Vec<u8>,
+}
+
+/// [ServerKeyring]
+/// ゲートウェイが保持するサーバー鍵の集合です。
+///
+/// 現在の鍵に加えて、猶予期間中の旧鍵を保持します。
+/// 旧鍵でハンドシェイクしてきたクライアントには、新しい公開鍵への移行を促す
+/// [KeyRotation] を返すことができます。
+pub struct ServerKeyring {
+    current: RsaKeyPair,
+    retired: Vec<RetiredServerKey>,
+    allow_legacy_key_wrap: bool,
+}
+
+impl ServerKeyring {
+    /// 現在の鍵のみを持つキーリングを作成します。
+    pub fn new(current: RsaKeyPair) -> Self {
+        Self {
+            current,
+            retired: Vec::new(),
+            allow_legacy_key_wrap: false,
+        }
+    }
+
+    /// PKCS#1 v1.5 でラップされた共通鍵 (旧クライアント) を受け付けるかどうかを設定します。
+    /// パディングオラクル攻撃の恐れがあるため、既定では受け付けません。
+    pub fn set_allow_legacy_key_wrap(&mut self, allow: bool) {
+        self.allow_legacy_key_wrap = allow;
+    }
+
+    /// PKCS#1 v1.5 でラップされた共通鍵を受け付けるかどうかを返します。
+    pub fn allows_legacy_key_wrap(&self) -> bool {
+        self.allow_legacy_key_wrap
+    }
+
+    /// 猶予期間付きの旧鍵を追加します。
+    /// 追加時に旧鍵で現在の公開鍵へ署名 ([sign_key_rotation]) し、ローテーション通知用に保持します。
+    pub fn add_retired(&mut self, key: RsaKeyPair, expires_at: u64) -> Result<(), CryptoError> {
+        let rotation_signature = sign_key_rotation(&key, &self.current.public_key_bytes())?;
+        self.retired.push(RetiredServerKey {
+            key,
+            expires_at,
+            rotation_signature,
+        });
+        Ok(())
+    }
+
+    /// [from_server_config]
+    /// `ServerConfig` に保存された現在の鍵と旧鍵からキーリングを構築します。
+    /// 既に期限切れの旧鍵は読み込みません。
+    /// 秘密鍵が暗号化されている場合は、事前に `decrypt_server_config` で復号しておく必要があります。
+    pub fn from_server_config(config: &ServerConfig) -> Result<Self, CryptoError> {
+        if config.key_encryption.is_some() {
+            return Err(t!("crypto.key_still_encrypted").into());
+        }
+        let current_der = general_purpose::STANDARD.decode(&config.private_key)?;
+        let mut keyring = Self::new(RsaKeyPair::from_private_der(&current_der)?);
+        keyring.set_allow_legacy_key_wrap(config.allow_legacy_key_wrap);
+
+        let now = unix_now();
+        for retired in config.retired_keys.iter().filter(|k| k.expires_at > now) {
+            let der = general_purpose::STANDARD.decode(&retired.private_key)?;
+            keyring.add_retired(RsaKeyPair::from_private_der(&der)?, retired.expires_at)?;
+        }
+        Ok(keyring)
+    }
+
+    /// 現在の鍵ペアを取得します。
+    pub fn current(&self) -> &RsaKeyPair {
+        &self.current
+    }
+
+    /// 期限切れでない旧鍵を取得します。
+    pub fn active_retired(&self) -> impl Iterator<Item = &RetiredServerKey> {
+        let now = unix_now();
+        self.retired.iter().filter(move |k| k.expires_at > now)
+    }
+
+    /// 旧鍵に対応するローテーション通知を作成します。
+    pub fn rotation_notice(&self, retired: &RetiredServerKey) -> KeyRotation {
+        KeyRotation {
+            new_public_key: self.current.public_key_bytes(),
+            signature: retired.rotation_signature.clone(),
+            expires_at: retired.expires_at,
+        }
+    }
+}
@@ -1,12 +1,32 @@
 pub mod traits;
 pub mod rsa_engine;
 pub mod aes_engine;
+pub mod chacha_engine;
+pub mod symmetric;
 pub mod secure_connect;
+pub mod secure_context;
+pub mod key_codec;
+pub mod keyring;
+pub mod key_store;
 
-pub use traits::{CryptoKeyPair, KeyGenerator, Encryptor, Signer, SymmetricCrypto, CryptoError};
-pub use rsa_engine::{RsaKeyPair, RsaKeyGenerator};
+pub use traits::{
+    CryptoKeyPair, KeyGenerator, Encryptor, Decryptor, Signer, Verifier, SymmetricCrypto, CryptoError, NONCE_LEN, TAG_LEN,
+};
+pub use rsa_engine::{RsaKeyPair, RsaPublicKey, RsaKeyGenerator, KeyWrap};
 pub use aes_engine::AesGcmEngine;
-pub use secure_connect::{SecureContext, handle_server_handshake, create_secure_connect_packet};
+pub use chacha_engine::ChaCha20Poly1305Engine;
+pub use symmetric::SymmetricAlgorithm;
+pub use secure_connect::{
+    ServerHandshake, HANDSHAKE_FAILED, handle_server_handshake, create_secure_connect_packet,
+    verify_key_rotation, sign_key_rotation, sign_server_key, verify_server_key, sign_agent_registration,
+    verify_agent_registration, AGENT_REGISTRATION_CONTEXT, KEY_ROTATION_CONTEXT, SERVER_KEY_CHALLENGE_LEN,
+};
+pub use secure_context::{SecureContext, RekeyPolicy};
+pub use keyring::{ServerKeyring, RetiredServerKey};
+pub use key_store::{
+    encrypt_server_config, decrypt_server_config, encrypt_private_key, decrypt_private_key,
+};
+pub use key_codec::{KeyFormat, KeyMaterial, decode_key, encode_key, fingerprint, short_fingerprint};
 
 /// アルゴリズムの種類を指定する列挙型。
 /// 将来的に ED25519 等を追加できるように設計されています。
@@ -1,10 +1,108 @@
-use rsa::{RsaPrivateKey, RsaPublicKey, Pkcs1v15Encrypt, pkcs8::{EncodePublicKey, EncodePrivateKey, DecodePublicKey, DecodePrivateKey}};
+use rsa::{RsaPrivateKey, Oaep, Pkcs1v15Encrypt, pkcs8::{EncodePublicKey, EncodePrivateKey, DecodePublicKey, DecodePrivateKey}};
 use rsa::signature::{Signer as RsaSignatureSigner, Verifier as RsaSignatureVerifier, SignatureEncoding};
 use rsa::pkcs1v15::{SigningKey, VerifyingKey, Signature};
 use rsa::sha2::Sha256;
 use rand::rngs::OsRng;
-use super::traits::{CryptoKeyPair, KeyGenerator, Encryptor, Signer, CryptoError};
+use super::traits::{CryptoKeyPair, KeyGenerator, Encryptor, Decryptor, Signer, Verifier, CryptoError};
+use crate::t;
 
+/// [KeyWrap]
+/// ハンドシェイクで共通鍵を RSA で暗号化 (ラップ) する際のパディング方式です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyWrap {
+    /// RSA-OAEP (SHA-256)。既定の方式です。
+    OaepSha256,
+    /// PKCS#1 v1.5。パディングオラクル攻撃の恐れがあるため、旧クライアントとの互換用にのみ使用します。
+    Pkcs1v15,
+}
+
+impl KeyWrap {
+    /// プロトコル上の名称 (`SecureConnectPayload.key_wrap` に使用) を返します。
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::OaepSha256 => "RSA-OAEP-SHA256",
+            Self::Pkcs1v15 => "RSA-PKCS1-v1_5",
+        }
+    }
+
+    /// 名称から方式を取得します。未知の名称の場合は None を返します。
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::OaepSha256, Self::Pkcs1v15]
+            .into_iter()
+            .find(|w| w.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// [RsaPublicKey]
+/// RSA の公開鍵のみを保持する型です。
+/// クライアントが持つゲートウェイの公開鍵に使用し、暗号化と署名の検証のみを行えます。
+#[derive(Clone)]
+pub struct RsaPublicKey {
+    key: rsa::RsaPublicKey,
+}
+
+impl RsaPublicKey {
+    /// SubjectPublicKeyInfo (DER) 形式の公開鍵を読み込みます。
+    pub fn from_der(der: &[u8]) -> Result<Self, CryptoError> {
+        let key =
+            rsa::RsaPublicKey::from_public_key_der(der).map_err(|e| Box::new(e) as CryptoError)?;
+        Ok(Self { key })
+    }
+
+    /// 公開鍵を SubjectPublicKeyInfo (DER) 形式で取得します。
+    pub fn to_der(&self) -> Vec<u8> {
+        self.key
+            .to_public_key_der()
+            .expect("RSA公開鍵のエンコードに失敗しました")
+            .to_vec()
+    }
+
+    /// 公開鍵の SHA-256 フィンガープリントを `aa:bb:cc:...` 形式で取得します。
+    pub fn fingerprint(&self) -> String {
+        super::key_codec::fingerprint(&self.to_der())
+    }
+
+    /// 鍵長 (法のビット数) を取得します。
+    pub fn bits(&self) -> usize {
+        use rsa::traits::PublicKeyParts;
+        self.key.n().bits()
+    }
+
+    /// 目視での照合向けに短縮したフィンガープリントを取得します。
+    pub fn short_fingerprint(&self) -> String {
+        super::key_codec::short_fingerprint(&self.to_der())
+    }
+
+    /// 指定したパディング方式で暗号化します。
+    pub fn encrypt_with(&self, data: &[u8], wrap: KeyWrap) -> Result<Vec<u8>, CryptoError> {
+        let mut rng = OsRng;
+        let enc_data = match wrap {
+            KeyWrap::OaepSha256 => self.key.encrypt(&mut rng, Oaep::new::<Sha256>(), data),
+            KeyWrap::Pkcs1v15 => self.key.encrypt(&mut rng, Pkcs1v15Encrypt, data),
+        };
+        enc_data.map_err(|e| Box::new(e) as CryptoError)
+    }
+}
+
+/// `Encryptor` としては RSA-OAEP (SHA-256) を使用します。
+impl Encryptor for RsaPublicKey {
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.encrypt_with(data, KeyWrap::OaepSha256)
+    }
+}
+
+impl Verifier for RsaPublicKey {
+    fn verify(&self, data: &[u8], signature_bytes: &[u8]) -> Result<bool, CryptoError> {
+        let verifying_key = VerifyingKey::<Sha256>::new(self.key.clone());
+        let signature = Signature::try_from(signature_bytes)
+            .map_err(|_| t!("crypto.invalid_signature_format"))?;
+
+        Ok(verifying_key.verify(data, &signature).is_ok())
+    }
+}
+
+/// [RsaKeyPair]
+/// RSA の秘密鍵と公開鍵のペアです。ゲートウェイが保持し、共通鍵の復号と署名に使用します。
 pub struct RsaKeyPair {
     private_key: RsaPrivateKey,
     public_key: RsaPublicKey,
@@ -13,14 +111,36 @@ pub struct RsaKeyPair {
 impl RsaKeyPair {
     pub fn from_private_der(der: &[u8]) -> Result<Self, CryptoError> {
         let private_key = RsaPrivateKey::from_pkcs8_der(der).map_err(|e| Box::new(e) as CryptoError)?;
-        let public_key = RsaPublicKey::from(&private_key);
-        Ok(Self { private_key, public_key })
+        Ok(Self::from_private_key(private_key))
     }
 
-    pub fn from_public_der(der: &[u8]) -> Result<Self, CryptoError> {
-        let public_key = RsaPublicKey::from_public_key_der(der).map_err(|e| Box::new(e) as CryptoError)?;
-        let dummy_priv = RsaPrivateKey::new(&mut OsRng, 512).map_err(|e| Box::new(e) as CryptoError)?; 
-        Ok(Self { private_key: dummy_priv, public_key })
+    fn from_private_key(private_key: RsaPrivateKey) -> Self {
+        let public_key = RsaPublicKey {
+            key: rsa::RsaPublicKey::from(&private_key),
+        };
+        Self {
+            private_key,
+            public_key,
+        }
+    }
+
+    /// 公開鍵のみを取り出します。
+    pub fn public_key(&self) -> RsaPublicKey {
+        self.public_key.clone()
+    }
+
+    /// 指定したパディング方式で公開鍵暗号化します。
+    pub fn encrypt_with(&self, data: &[u8], wrap: KeyWrap) -> Result<Vec<u8>, CryptoError> {
+        self.public_key.encrypt_with(data, wrap)
+    }
+
+    /// 指定したパディング方式で秘密鍵復号します。
+    pub fn decrypt_with(&self, data: &[u8], wrap: KeyWrap) -> Result<Vec<u8>, CryptoError> {
+        let dec_data = match wrap {
+            KeyWrap::OaepSha256 => self.private_key.decrypt(Oaep::new::<Sha256>(), data),
+            KeyWrap::Pkcs1v15 => self.private_key.decrypt(Pkcs1v15Encrypt, data),
+        };
+        dec_data.map_err(|e| Box::new(e) as CryptoError)
     }
 }
 
@@ -30,7 +150,7 @@ impl CryptoKeyPair for RsaKeyPair {
     }
 
     fn public_key_bytes(&self) -> Vec<u8> {
-        self.public_key.to_public_key_der().expect("RSA公開鍵のエンコードに失敗しました").to_vec()
+        self.public_key.to_der()
     }
 
     fn private_key_bytes(&self) -> Vec<u8> {
@@ -52,21 +172,20 @@ impl KeyGenerator for RsaKeyGenerator {
     fn generate(&self) -> Result<Box<dyn CryptoKeyPair>, CryptoError> {
         let mut rng = OsRng;
         let private_key = RsaPrivateKey::new(&mut rng, self.bits).map_err(|e| Box::new(e) as CryptoError)?;
-        let public_key = RsaPublicKey::from(&private_key);
-        Ok(Box::new(RsaKeyPair { private_key, public_key }))
+        Ok(Box::new(RsaKeyPair::from_private_key(private_key)))
     }
 }
 
+/// `Encryptor` / `Decryptor` としては RSA-OAEP (SHA-256) を使用します。
 impl Encryptor for RsaKeyPair {
     fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
-        let mut rng = OsRng;
-        let enc_data = self.public_key.encrypt(&mut rng, Pkcs1v15Encrypt, data).map_err(|e| Box::new(e) as CryptoError)?;
-        Ok(enc_data)
+        self.public_key.encrypt(data)
     }
+}
 
+impl Decryptor for RsaKeyPair {
     fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
-        let dec_data = self.private_key.decrypt(Pkcs1v15Encrypt, data).map_err(|e| Box::new(e) as CryptoError)?;
-        Ok(dec_data)
+        self.decrypt_with(data, KeyWrap::OaepSha256)
     }
 }
 
@@ -76,12 +195,10 @@ impl Signer for RsaKeyPair {
         let signature = signing_key.sign(data);
         Ok(signature.to_vec())
     }
+}
 
+impl Verifier for RsaKeyPair {
     fn verify(&self, data: &[u8], signature_bytes: &[u8]) -> Result<bool, CryptoError> {
-        let verifying_key = VerifyingKey::<Sha256>::new(self.public_key.clone());
-        let signature = Signature::try_from(signature_bytes)
-            .map_err(|_| "署名のフォーマットが不正です。")?;
-        
-        Ok(verifying_key.verify(data, &signature).is_ok())
+        self.public_key.verify(data, signature_bytes)
     }
 }
@@ -1,121 +1,347 @@
-use crate::encryption::{AesGcmEngine, Encryptor, SymmetricCrypto, traits::CryptoError};
-use crate::models::packet::{Command, Message, Protocol, SecureConnectPayload};
-use log::{error, info};
-
-/// [SecureContext]
-/// 暗号化セッションの状態を管理し、メッセージの暗号化・復号を行います。
-pub struct SecureContext {
-    /// 確立された共通鍵暗号エンジン。ハンドシェイク前は None です。
-    pub crypto: Option<Box<dyn SymmetricCrypto>>,
-}
-
-impl SecureContext {
-    /// 空のコンテキスト（未初期化状態）を作成します。
-    pub fn new() -> Self {
-        Self { crypto: None }
-    }
+use crate::encryption::{
+    CryptoKeyPair, KeyWrap, RsaKeyPair, RsaPublicKey, SecureContext, ServerKeyring, Signer,
+    SymmetricAlgorithm, Verifier, traits::CryptoError,
+};
+use crate::models::frame::Direction;
+use crate::models::packet::{
+    Command, KeyRotation, Message, Protocol, ProtocolInfo, SecureConnectPayload, SignedServerKey,
+};
+use crate::t;
+use log::{error, info, warn};
 
-    /// [seal_message]
-    /// メッセージのペイロード部分を暗号化します。
-    pub fn seal_message(&self, mut msg: Message) -> Result<Message, CryptoError> {
-        if let Some(crypto) = &self.crypto {
-            msg.payload = crypto.encrypt(&msg.payload)?;
-        }
-        Ok(msg)
-    }
-
-    /// [unseal_message]
-    /// 暗号化されたメッセージのペイロードを復号します。
-    pub fn unseal_message(&self, mut msg: Message) -> Result<Message, CryptoError> {
-        if let Some(crypto) = &self.crypto {
-            msg.payload = crypto.decrypt(&msg.payload)?;
-        }
-        Ok(msg)
-    }
+/// [ServerHandshake]
+/// サーバー側ハンドシェイクの結果です。
+pub struct ServerHandshake {
+    /// 確立された暗号化コンテキスト
+    pub context: SecureContext,
+    /// クライアントが要求したプロトコル
+    pub protocol: Protocol,
+    /// クライアントが要求したターゲットポート
+    pub port: u16,
+    /// クライアントが旧鍵で接続してきた場合の、新しい公開鍵の通知
+    pub key_rotation: Option<KeyRotation>,
+    /// クライアントと合意したプロトコルバージョンと機能
+    pub protocol_info: ProtocolInfo,
+    /// ConnectResponse 以降に使用する共通鍵暗号。
+    /// クライアントが候補を送ってこなかった場合は None で、ハンドシェイクのアルゴリズムを使い続けます。
+    pub algorithm: Option<SymmetricAlgorithm>,
+    /// 共通鍵の復号に使用したサーバー鍵のフィンガープリント。
+    /// クライアントが配布された設定の鍵 (現在の鍵または猶予期間中の旧鍵) を識別するのに使用します。
+    pub key_fingerprint: String,
 }
 
+/// ハンドシェイクに失敗した場合にクライアントへ返すメッセージ。
+/// 失敗の理由 (復号・鍵長・パディングなど) で応答を変えると、パディングオラクル攻撃の手がかりになるため、
+/// 理由によらず常に同じものを返します。詳細はゲートウェイのログにのみ出力します。
+pub const HANDSHAKE_FAILED: &str = "Handshake failed.";
+
 /// [handle_server_handshake]
 /// サーバー側でのセキュアハンドシェイク（同期処理）。
+///
+/// 現在の鍵で共通鍵を復号できない場合は、猶予期間中の旧鍵でも復号を試みます。
+/// 旧鍵で成功した場合は、結果に新しい公開鍵の通知 ([KeyRotation]) が含まれます。
+///
+/// 共通鍵は RSA-OAEP (SHA-256) でラップされている必要があります。
+/// PKCS#1 v1.5 (旧クライアント) は、キーリングで許可されている場合のみ受け付けます。
+/// 失敗した場合は、理由によらず [HANDSHAKE_FAILED] のエラーを返します。
 pub fn handle_server_handshake(
     raw_packet: Message,
-    server_key_pair: &dyn Encryptor,
-) -> Result<(SecureContext, Protocol, u16), CryptoError> {
-    info!("サーバー側ハンドシェイクを開始します...");
+    server_keys: &ServerKeyring,
+) -> Result<ServerHandshake, CryptoError> {
+    info!("{}", t!("handshake.server_starting"));
+    server_handshake(raw_packet, server_keys).map_err(|e| {
+        error!("{}", t!("handshake.server_failed", error = e));
+        HANDSHAKE_FAILED.into()
+    })
+}
 
+/// [server_handshake]
+/// [handle_server_handshake] と同じ処理で、失敗の詳細な理由をそのまま返します。
+/// 理由はクライアントへ返さず、ゲートウェイの監査ログにのみ記録します。
+pub(crate) fn server_handshake(
+    raw_packet: Message,
+    server_keys: &ServerKeyring,
+) -> Result<ServerHandshake, CryptoError> {
     if raw_packet.command != Command::SecureConnect {
-        error!(
-            "初期パケットが SecureConnect ではありません: {:?}",
-            raw_packet.command
-        );
-        return Err("初期パケットは SecureConnect である必要があります。".into());
+        return Err(t!(
+            "handshake.not_secure_connect",
+            command = format!("{:?}", raw_packet.command)
+        )
+        .into());
     }
 
-    let payload: SecureConnectPayload = raw_packet.deserialize_payload().map_err(|e| {
-        error!("SecureConnect ペイロードのデシリアライズに失敗: {}", e);
-        format!("SecureConnect ペイロードの解析に失敗しました: {}", e)
-    })?;
+    let payload: SecureConnectPayload = raw_packet
+        .deserialize_payload()
+        .map_err(|e| t!("handshake.invalid_payload", error = e))?;
 
-    info!("共通鍵を復号中...");
-    let symmetric_key = server_key_pair.decrypt(&payload.encrypted_key)
-        .map_err(|e| {
-            error!("共通鍵の復号に失敗しました。公開鍵・秘密鍵のペアが一致していない可能性があります: {}", e);
-            format!("対称鍵の復号に失敗しました: {}", e)
-        })?;
+    // 旧クライアントはバージョン情報を送ってこないため、v0 として扱う
+    let protocol_info = ProtocolInfo::current().negotiate(payload.protocol_info.as_ref());
+
+    let handshake_algorithm: SymmetricAlgorithm = payload.algorithm.parse()?;
+    let algorithm = negotiate_algorithm(&payload.algorithms)?;
+
+    // 旧クライアントはパディング方式を送ってこず、PKCS#1 v1.5 でラップしている
+    let wrap = match payload.key_wrap.as_deref() {
+        Some(name) => KeyWrap::from_name(name)
+            .ok_or_else(|| t!("handshake.unsupported_key_wrap", name = name))?,
+        None => KeyWrap::Pkcs1v15,
+    };
+    if wrap == KeyWrap::Pkcs1v15 && !server_keys.allows_legacy_key_wrap() {
+        return Err(t!("handshake.legacy_key_wrap_rejected").into());
+    }
 
     info!(
-        "AesGcmEngine を初期化中 (key len: {})...",
-        symmetric_key.len()
+        "{}",
+        t!(
+            "handshake.decrypting_key",
+            algorithm = handshake_algorithm,
+            wrap = wrap.name()
+        )
     );
-    let crypto = AesGcmEngine::from_key(&symmetric_key)?;
+    let open_with = |key: &RsaKeyPair| {
+        key.decrypt_with(&payload.encrypted_key, wrap)
+            .and_then(|symmetric_key| handshake_algorithm.from_key(&symmetric_key))
+    };
 
-    let mut context = SecureContext::new();
-    context.crypto = Some(Box::new(crypto));
+    let (crypto, key_rotation, key_fingerprint) = match open_with(server_keys.current()) {
+        Ok(crypto) => (crypto, None, server_keys.current().fingerprint()),
+        Err(e) => {
+            let retired = server_keys.active_retired().find_map(|retired| {
+                open_with(&retired.key).ok().map(|crypto| {
+                    (
+                        crypto,
+                        server_keys.rotation_notice(retired),
+                        retired.key.fingerprint(),
+                    )
+                })
+            });
+            let Some((crypto, notice, fingerprint)) = retired else {
+                return Err(t!("handshake.key_decrypt_failed", error = e).into());
+            };
+            warn!("{}", t!("handshake.retired_key_used"));
+            (crypto, Some(notice), fingerprint)
+        }
+    };
+
+    let mut context = SecureContext::with_crypto(crypto, Direction::ServerToClient)?;
+    context.apply_protocol(&protocol_info);
 
     info!(
-        "サーバー側ハンドシェイク完了: {:?}:{}",
-        payload.protocol, payload.port
+        "{}",
+        t!(
+            "handshake.server_completed",
+            protocol = format!("{:?}", payload.protocol),
+            port = payload.port,
+            version = protocol_info.version
+        )
     );
-    Ok((context, payload.protocol, payload.port))
+    Ok(ServerHandshake {
+        context,
+        protocol: payload.protocol,
+        port: payload.port,
+        key_rotation,
+        protocol_info,
+        algorithm,
+        key_fingerprint,
+    })
+}
+
+/// [negotiate_algorithm]
+/// クライアントが提示した共通鍵暗号の候補から、セッションで使用するものを選びます。
+/// 未知の名称は読み飛ばし、共通のものが一つもない場合はエラーになります。
+fn negotiate_algorithm(offers: &[String]) -> Result<Option<SymmetricAlgorithm>, CryptoError> {
+    if offers.is_empty() {
+        return Ok(None);
+    }
+    let offers: Vec<SymmetricAlgorithm> = offers.iter().filter_map(|o| o.parse().ok()).collect();
+    let selected = SymmetricAlgorithm::negotiate(&SymmetricAlgorithm::preferred(), &offers)
+        .ok_or_else(|| t!("handshake.no_common_algorithm"))?;
+    info!(
+        "{}",
+        t!("handshake.session_algorithm", algorithm = selected)
+    );
+    Ok(Some(selected))
+}
+
+/// 鍵ローテーションの署名の先頭に付けるコンテキスト。
+/// 同じサーバー鍵で署名する `GetServerInfo` のチャレンジ ([sign_server_key]) と署名の対象を分け、
+/// 一方の署名を他方として扱えないようにします。
+pub const KEY_ROTATION_CONTEXT: &[u8] = b"mc-connect/key-rotation/v1";
+
+/// [sign_key_rotation]
+/// 旧鍵で新しい公開鍵 (DER) に署名し、鍵ローテーション通知の署名を作成します。
+/// 署名の対象は `KEY_ROTATION_CONTEXT || new_public_key` です。
+pub fn sign_key_rotation(
+    old_key: &RsaKeyPair,
+    new_public_key: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    old_key.sign(&[KEY_ROTATION_CONTEXT, new_public_key].concat())
+}
+
+/// [verify_key_rotation]
+/// ゲートウェイから届いた鍵ローテーション通知を、手元の (旧) 公開鍵で検証します。
+/// 署名は [KEY_ROTATION_CONTEXT] を付けた内容に対するもの ([sign_key_rotation]) でなければなりません。
+/// 検証に成功した場合は新しい公開鍵 (DER) を返します。
+pub fn verify_key_rotation(
+    current_public_key: &RsaPublicKey,
+    rotation: &KeyRotation,
+) -> Result<Vec<u8>, CryptoError> {
+    let data = [KEY_ROTATION_CONTEXT, rotation.new_public_key.as_slice()].concat();
+    if !current_public_key.verify(&data, &rotation.signature)? {
+        return Err(t!("handshake.invalid_rotation_signature").into());
+    }
+    Ok(rotation.new_public_key.clone())
+}
+
+/// 公開鍵の提示 ([sign_server_key]) で受け付けるチャレンジの長さ (バイト)。
+/// 長さを固定することで、署名の対象を任意のバイト列にできないようにします。
+pub const SERVER_KEY_CHALLENGE_LEN: usize = 32;
+
+/// [sign_server_key]
+/// クライアントのチャレンジと公開鍵を連結したものに署名し、公開鍵の提示に使用します。
+/// チャレンジの長さが [SERVER_KEY_CHALLENGE_LEN] でない場合はエラーになります。
+pub fn sign_server_key(
+    server_key: &RsaKeyPair,
+    challenge: &[u8],
+) -> Result<SignedServerKey, CryptoError> {
+    if challenge.len() != SERVER_KEY_CHALLENGE_LEN {
+        return Err(t!("handshake.invalid_challenge_length", len = challenge.len()).into());
+    }
+    let public_key = server_key.public_key_bytes();
+    let signature = server_key.sign(&[challenge, public_key.as_slice()].concat())?;
+    Ok(SignedServerKey {
+        public_key,
+        signature,
+    })
+}
+
+/// [verify_server_key]
+/// ゲートウェイが提示した公開鍵が、チャレンジに対して正しく署名されているか検証します。
+/// 検証に成功した場合は公開鍵 (DER) を返します。
+pub fn verify_server_key(
+    signed: &SignedServerKey,
+    challenge: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let key = RsaPublicKey::from_der(&signed.public_key)?;
+    let data = [challenge, signed.public_key.as_slice()].concat();
+    if !key.verify(&data, &signed.signature)? {
+        return Err(t!("handshake.invalid_server_key_signature").into());
+    }
+    Ok(signed.public_key.clone())
+}
+
+/// エージェント登録の署名の先頭に付けるコンテキスト。
+/// ゲートウェイの `GetServerInfo` 応答 (`challenge || public_key`) の署名を登録に流用できないよう、
+/// 署名の対象を用途ごとに分けます。ゲートウェイが署名するチャレンジは [SERVER_KEY_CHALLENGE_LEN]
+/// バイトに固定しているため、このコンテキストから始まる登録用の内容と一致することはありません。
+pub const AGENT_REGISTRATION_CONTEXT: &[u8] = b"mc-connect/agent-register/v1";
+
+/// エージェント登録で署名する内容を組み立てます。
+/// `context || len(relay_id) || relay_id || challenge || public_key` の形式で、
+/// リレー ID の長さを u32 (big endian) で入れて、リレー ID とチャレンジの境界を曖昧にしません。
+fn agent_registration_message(relay_id: &str, challenge: &[u8], public_key: &[u8]) -> Vec<u8> {
+    let relay_id = relay_id.as_bytes();
+    [
+        AGENT_REGISTRATION_CONTEXT,
+        &(relay_id.len() as u32).to_be_bytes(),
+        relay_id,
+        challenge,
+        public_key,
+    ]
+    .concat()
+}
+
+/// [sign_agent_registration]
+/// リレーのチャレンジに対して、ホストエージェントの登録用の署名付き公開鍵を作成します。
+/// 署名には [AGENT_REGISTRATION_CONTEXT] とリレー ID を含め、[sign_server_key] の署名とは区別します。
+pub fn sign_agent_registration(
+    server_key: &RsaKeyPair,
+    relay_id: &str,
+    challenge: &[u8],
+) -> Result<SignedServerKey, CryptoError> {
+    let public_key = server_key.public_key_bytes();
+    let signature = server_key.sign(&agent_registration_message(
+        relay_id,
+        challenge,
+        &public_key,
+    ))?;
+    Ok(SignedServerKey {
+        public_key,
+        signature,
+    })
+}
+
+/// [verify_agent_registration]
+/// ホストエージェントが返した登録用の署名を検証します。
+/// 検証に成功した場合は公開鍵 (DER) を返します。
+pub fn verify_agent_registration(
+    signed: &SignedServerKey,
+    relay_id: &str,
+    challenge: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let key = RsaPublicKey::from_der(&signed.public_key)?;
+    let data = agent_registration_message(relay_id, challenge, &signed.public_key);
+    if !key.verify(&data, &signed.signature)? {
+        return Err(t!("relay.invalid_registration_signature").into());
+    }
+    Ok(signed.public_key.clone())
 }
 
 /// [create_secure_connect_packet]
 /// クライアント側でのセキュア接続要求の構築。
+/// 共通鍵はゲートウェイの公開鍵で RSA-OAEP (SHA-256) を使用してラップします。
 pub fn create_secure_connect_packet(
     protocol: Protocol,
     port: u16,
-    server_public_key: &dyn Encryptor,
+    server_public_key: &RsaPublicKey,
 ) -> Result<(SecureContext, Message), CryptoError> {
     info!(
-        "クライアント側ハンドシェイクパケットを生成中 (Port: {}, Protocol: {:?})...",
-        port, protocol
+        "{}",
+        t!(
+            "handshake.client_building",
+            port = port,
+            protocol = format!("{:?}", protocol)
+        )
     );
 
-    info!("ランダムな共通鍵を生成中...");
-    let aes_engine = AesGcmEngine::new_random();
-    let key_bytes = aes_engine.key_bytes();
+    // 旧ゲートウェイは algorithm を無視して AES-256-GCM で復号するため、ハンドシェイクは常に AES で行い、
+    // セッションで使用したい共通鍵暗号は algorithms で提示する
+    info!("{}", t!("handshake.generating_key"));
+    let handshake_algorithm = SymmetricAlgorithm::Aes256Gcm;
+    let engine = handshake_algorithm.generate();
+    let key_bytes = engine.key_bytes();
 
-    info!("サーバーの公開鍵を使用して共通鍵を暗号化中...");
-    let encrypted_key = server_public_key.encrypt(&key_bytes).map_err(|e| {
-        error!("共通鍵の暗号化に失敗しました: {}", e);
-        e
-    })?;
+    info!("{}", t!("handshake.wrapping_key"));
+    let key_wrap = KeyWrap::OaepSha256;
+    let encrypted_key = server_public_key
+        .encrypt_with(&key_bytes, key_wrap)
+        .map_err(|e| {
+            error!("{}", t!("handshake.wrap_failed", error = e));
+            e
+        })?;
 
     let payload = SecureConnectPayload {
         protocol,
         port,
         encrypted_key,
-        algorithm: "AES-256-GCM".to_string(),
+        algorithm: handshake_algorithm.name().to_string(),
+        algorithms: SymmetricAlgorithm::preferred()
+            .iter()
+            .map(|a| a.name().to_string())
+            .collect(),
+        protocol_info: Some(ProtocolInfo::current()),
+        key_wrap: Some(key_wrap.name().to_string()),
     };
 
-    info!("ハンドシェイクメッセージを構築中...");
+    info!("{}", t!("handshake.building_message"));
     let msg = Message::from_payload(Command::SecureConnect, &payload).map_err(|e| {
-        error!("ハンドシェイクパケットのシリアライズに失敗: {}", e);
-        format!("ハンドシェイクパケットの生成に失敗しました: {}", e)
+        error!("{}", t!("handshake.encode_failed", error = e));
+        t!("handshake.build_failed", error = e)
     })?;
 
-    let mut context = SecureContext::new();
-    context.crypto = Some(Box::new(aes_engine));
+    // フレーム形式や連番検証は、ゲートウェイの応答で対応が確認できてから有効にする
+    let context = SecureContext::with_crypto(engine, Direction::ClientToServer)?;
 
-    info!("クライアント側ハンドシェイク準備完了。");
+    info!("{}", t!("handshake.client_ready"));
     Ok((context, msg))
 }
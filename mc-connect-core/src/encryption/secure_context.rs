@@ -0,0 +1,434 @@
+use crate::t;
+use hkdf::Hkdf;
+use log::info;
+use sha2::Sha256;
+use std::time::{Duration, Instant};
+
+use super::symmetric::SymmetricAlgorithm;
+use super::traits::{CryptoError, NONCE_LEN, SymmetricCrypto, TAG_LEN};
+use crate::models::frame::{DataFrame, Direction, FRAME_HEADER_LEN, is_data_frame, new_data_frame};
+use crate::models::packet::{
+    Command, ConnectResponsePayload, Message, ProtocolInfo, RekeyPayload, capability,
+    encode_payload,
+};
+
+/// [RekeyPolicy]
+/// 共通鍵を自動で更新 (rekey) する条件です。どちらかを満たした時点で更新します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RekeyPolicy {
+    /// 現在の鍵で暗号化したデータ量の上限 (バイト)
+    pub max_bytes: u64,
+    /// 現在の鍵を使用する時間の上限
+    pub max_age: Duration,
+}
+
+impl Default for RekeyPolicy {
+    /// 1 GiB または 1 時間ごとに鍵を更新します。
+    fn default() -> Self {
+        Self {
+            max_bytes: 1 << 30,
+            max_age: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// 送信または受信の一方向分の暗号化状態
+struct CipherState {
+    crypto: Box<dyn SymmetricCrypto>,
+    /// 送信側では次に使用する、受信側では次に受け付けるカウンター
+    counter: u64,
+    /// 鍵の世代。鍵を更新するたびに 1 増えます。
+    generation: u32,
+    /// 現在の鍵で暗号化したバイト数
+    bytes: u64,
+    /// 現在の鍵を使い始めた時刻
+    since: Instant,
+}
+
+impl CipherState {
+    fn new(crypto: Box<dyn SymmetricCrypto>) -> Self {
+        Self {
+            crypto,
+            counter: 0,
+            generation: 0,
+            bytes: 0,
+            since: Instant::now(),
+        }
+    }
+
+    /// 現在の鍵から次の世代の鍵を導出して切り替えます。
+    /// 鍵が変わるため、カウンターは 0 からやり直します。
+    fn rekey(&mut self, direction: Direction) -> Result<(), CryptoError> {
+        let key = derive_next_key(&self.crypto.key_bytes(), b"rekey", direction)?;
+        self.crypto = self.crypto.with_key(&key)?;
+        self.counter = 0;
+        self.generation += 1;
+        self.bytes = 0;
+        self.since = Instant::now();
+        Ok(())
+    }
+
+    /// 現在の鍵から導出した鍵で、別のアルゴリズムのエンジンに切り替えます。
+    fn switch(
+        &mut self,
+        algorithm: SymmetricAlgorithm,
+        direction: Direction,
+    ) -> Result<(), CryptoError> {
+        let label = format!("cipher {}", algorithm.name());
+        let key = derive_next_key(&self.crypto.key_bytes(), label.as_bytes(), direction)?;
+        *self = Self::new(algorithm.from_key(&key)?);
+        Ok(())
+    }
+}
+
+/// HKDF-SHA256 で、現在の鍵から同じ長さの次の鍵を導出します。
+/// 用途と送信方向を info に含め、用途ごと・双方向の鍵が同じにならないようにします。
+fn derive_next_key(key: &[u8], label: &[u8], direction: Direction) -> Result<Vec<u8>, CryptoError> {
+    let info = [
+        b"mc-connect ".as_slice(),
+        label,
+        b" ",
+        &direction.nonce(0)[..4],
+    ]
+    .concat();
+    let mut next = vec![0u8; key.len()];
+    Hkdf::<Sha256>::new(None, key)
+        .expand(&info, &mut next)
+        .map_err(|e| t!("session.key_derivation_failed", error = e))?;
+    Ok(next)
+}
+
+/// [SecureContext]
+/// 暗号化セッションの状態を管理し、メッセージの暗号化・復号を行います。
+///
+/// Nonce は送信方向ごとのプレフィックスとカウンターから組み立てます。
+/// 双方が [capability::SEQUENCE_NONCE] に対応している場合は、受信したメッセージのカウンターが
+/// 連番であることを検証し、リプレイや順序の入れ替えを拒否します。
+#[derive(Default)]
+pub struct SecureContext {
+    /// 送信用の暗号化状態。ハンドシェイク前は None です。
+    send: Option<CipherState>,
+    /// 受信用の暗号化状態。ハンドシェイク前は None です。
+    recv: Option<CipherState>,
+    /// このコンテキストが送信する方向。Nonce の衝突を防ぐために使用します。
+    pub direction: Direction,
+    /// Data をコンパクトなバイナリフレームで送信するかどうか (双方が対応している場合のみ有効)
+    pub compact_data: bool,
+    /// 受信したメッセージのカウンターが連番であることを検証するかどうか
+    pub strict_sequence: bool,
+    /// 制御メッセージのコマンドを AAD として認証するかどうか ([capability::SEQUENCE_NONCE] と同時に有効)
+    pub bind_command: bool,
+    /// 自動で鍵を更新する条件。None の場合は更新しません (相手が対応していない場合など)。
+    pub rekey_policy: Option<RekeyPolicy>,
+}
+
+impl SecureContext {
+    /// 空のコンテキスト（未初期化状態）を作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 指定した共通鍵エンジンと送信方向でコンテキストを作成します。
+    /// ハンドシェイク直後は、送信・受信とも同じ共通鍵を使用します。
+    pub fn with_crypto(
+        crypto: Box<dyn SymmetricCrypto>,
+        direction: Direction,
+    ) -> Result<Self, CryptoError> {
+        let recv = crypto.with_key(&crypto.key_bytes())?;
+        Ok(Self {
+            send: Some(CipherState::new(crypto)),
+            recv: Some(CipherState::new(recv)),
+            direction,
+            ..Self::default()
+        })
+    }
+
+    /// 暗号化が確立されているかどうかを返します。
+    pub fn is_established(&self) -> bool {
+        self.send.is_some()
+    }
+
+    /// [apply_protocol]
+    /// 相手と合意したプロトコルの機能に応じて、フレーム形式・連番検証・コマンドの認証・鍵更新を有効にします。
+    pub fn apply_protocol(&mut self, protocol_info: &ProtocolInfo) {
+        self.compact_data = protocol_info.supports(capability::COMPACT_DATA);
+        self.strict_sequence = protocol_info.supports(capability::SEQUENCE_NONCE);
+        self.bind_command = self.strict_sequence;
+        self.rekey_policy = protocol_info
+            .supports(capability::REKEY)
+            .then(RekeyPolicy::default);
+    }
+
+    /// 現在の送信用の共通鍵暗号の名称を返します。ハンドシェイク前は None です。
+    pub fn algorithm(&self) -> Option<&str> {
+        self.send.as_ref().map(|s| s.crypto.algorithm_name())
+    }
+
+    /// [switch_algorithm]
+    /// 現在の鍵から導出した鍵で、送信・受信とも指定した共通鍵暗号に切り替えます。
+    /// 双方が同じタイミング (ConnectResponse の送受信直後) で呼び出す必要があります。
+    /// 既に同じアルゴリズムを使用している場合は何もしません。
+    pub fn switch_algorithm(&mut self, algorithm: SymmetricAlgorithm) -> Result<(), CryptoError> {
+        if self.algorithm() == Some(algorithm.name()) {
+            return Ok(());
+        }
+        let direction = self.direction;
+        let (Some(send), Some(recv)) = (&mut self.send, &mut self.recv) else {
+            return Err(t!("session.switch_before_handshake").into());
+        };
+        send.switch(algorithm, direction)?;
+        recv.switch(algorithm, direction.reverse())?;
+        info!(
+            "{}",
+            t!("session.algorithm_switched", algorithm = algorithm)
+        );
+        Ok(())
+    }
+
+    /// [apply_connect_response]
+    /// クライアント側で、成功した ConnectResponse の内容 (合意したプロトコルと共通鍵暗号) を適用します。
+    /// 合意したプロトコルの情報を返します。
+    pub fn apply_connect_response(
+        &mut self,
+        response: &ConnectResponsePayload,
+    ) -> Result<ProtocolInfo, CryptoError> {
+        let protocol_info = response
+            .protocol_info
+            .clone()
+            .unwrap_or_else(ProtocolInfo::legacy);
+        self.apply_protocol(&protocol_info);
+        if let Some(name) = &response.algorithm {
+            self.switch_algorithm(name.parse()?)?;
+        }
+        Ok(protocol_info)
+    }
+
+    /// 次に使用する送信用の Nonce を払い出します。
+    fn next_nonce(
+        send: &mut CipherState,
+        direction: Direction,
+    ) -> Result<(u64, [u8; NONCE_LEN]), CryptoError> {
+        let counter = send.counter;
+        send.counter = counter
+            .checked_add(1)
+            .ok_or_else(|| t!("session.nonce_exhausted"))?;
+        Ok((counter, direction.nonce(counter)))
+    }
+
+    /// [check_sequence]
+    /// 受信したメッセージの Nonce が、次に受け付けるべきカウンターかどうかを検証します。
+    fn check_sequence(&self, recv: &CipherState, nonce: &[u8]) -> Result<(), CryptoError> {
+        if !self.strict_sequence {
+            return Ok(());
+        }
+        match self.direction.reverse().counter_of(nonce) {
+            Some(counter) if counter == recv.counter => Ok(()),
+            Some(counter) => Err(t!(
+                "session.replay_detected",
+                expected = recv.counter,
+                received = counter
+            )
+            .into()),
+            None => Err(t!("session.invalid_nonce").into()),
+        }
+    }
+
+    /// 復号に成功したメッセージのカウンターを記録します。
+    /// 連番検証が無効な間も記録しておき、後から有効にした場合に続きから検証できるようにします。
+    fn record_sequence(direction: Direction, recv: &mut CipherState, nonce: &[u8]) {
+        if let Some(counter) = direction.reverse().counter_of(nonce) {
+            recv.counter = counter.saturating_add(1);
+        }
+    }
+
+    /// [command_aad]
+    /// メッセージの暗号化で認証する追加データ (AAD) を返します。
+    ///
+    /// `bind_command` が有効な場合は、平文で送られるコマンドを MessagePack の表現のまま AAD に含め、
+    /// 中継者がコマンドだけを書き換えられないようにします。
+    /// ConnectResponse はクライアントがプロトコルを適用する前に復号するため、常に AAD を含めません。
+    fn command_aad(&self, command: &Command) -> Result<Vec<u8>, CryptoError> {
+        if !self.bind_command || *command == Command::ConnectResponse {
+            return Ok(Vec::new());
+        }
+        Ok(rmp_serde::to_vec(command)?)
+    }
+
+    /// [seal_message]
+    /// メッセージのペイロード部分を暗号化します。
+    ///
+    /// ペイロードは `Nonce || 暗号文 || 認証タグ` の形式で、受信側は Nonce をペイロードから読み取ります。
+    /// Nonce は送信方向とカウンターから組み立てるため、同じ鍵で重複することはありません。
+    /// コマンドは [command_aad] によって認証します。
+    pub fn seal_message(&mut self, mut msg: Message) -> Result<Message, CryptoError> {
+        let direction = self.direction;
+        let aad = self.command_aad(&msg.command)?;
+        if let Some(send) = &mut self.send {
+            let (_, nonce) = Self::next_nonce(send, direction)?;
+            let mut sealed = Vec::with_capacity(NONCE_LEN + msg.payload.len() + TAG_LEN);
+            sealed.extend_from_slice(&nonce);
+            sealed.extend_from_slice(&msg.payload);
+            let tag =
+                send.crypto
+                    .encrypt_in_place_detached(&nonce, &aad, &mut sealed[NONCE_LEN..])?;
+            sealed.extend_from_slice(&tag);
+            send.bytes += msg.payload.len() as u64;
+            msg.payload = sealed;
+        }
+        Ok(msg)
+    }
+
+    /// [unseal_message]
+    /// 暗号化されたメッセージのペイロードを復号します。
+    /// コマンドが書き換えられている場合 ([command_aad]) は認証タグの検証に失敗します。
+    /// 鍵更新 (`Command::Rekey`) を受信した場合は、以降の受信に次の世代の鍵を使用します。
+    pub fn unseal_message(&mut self, mut msg: Message) -> Result<Message, CryptoError> {
+        let Some(recv) = &self.recv else {
+            return Ok(msg);
+        };
+        let nonce: [u8; NONCE_LEN] = msg
+            .payload
+            .get(..NONCE_LEN)
+            .and_then(|n| n.try_into().ok())
+            .ok_or_else(|| t!("crypto.ciphertext_too_short"))?;
+        let tag_start = msg
+            .payload
+            .len()
+            .checked_sub(TAG_LEN)
+            .filter(|&start| start >= NONCE_LEN)
+            .ok_or_else(|| t!("crypto.ciphertext_too_short"))?;
+        self.check_sequence(recv, &nonce)?;
+        let aad = self.command_aad(&msg.command)?;
+
+        let direction = self.direction;
+        let recv = self.recv.as_mut().unwrap();
+        let (sealed, tag) = msg.payload.split_at(tag_start);
+        let mut payload = sealed[NONCE_LEN..].to_vec();
+        recv.crypto
+            .decrypt_in_place_detached(&nonce, &aad, &mut payload, tag)?;
+        msg.payload = payload;
+        Self::record_sequence(direction, recv, &nonce);
+
+        if msg.command == Command::Rekey {
+            let payload: RekeyPayload = msg.deserialize_payload()?;
+            if payload.generation != recv.generation + 1 {
+                return Err(t!(
+                    "session.generation_mismatch",
+                    expected = recv.generation + 1,
+                    received = payload.generation
+                )
+                .into());
+            }
+            recv.rekey(direction.reverse())?;
+            info!(
+                "{}",
+                t!("session.peer_rekeyed", generation = recv.generation)
+            );
+        }
+        Ok(msg)
+    }
+
+    /// [seal_data_frame]
+    /// Data のペイロードを暗号化し、コンパクトなバイナリフレームにします。
+    pub fn seal_data_frame(&mut self, payload: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let direction = self.direction;
+        let send = self
+            .send
+            .as_mut()
+            .ok_or_else(|| t!("session.send_before_handshake"))?;
+        let (counter, nonce) = Self::next_nonce(send, direction)?;
+        let mut frame = new_data_frame(counter, payload);
+        let (header, body) = frame.split_at_mut(FRAME_HEADER_LEN);
+        let tag = send
+            .crypto
+            .encrypt_in_place_detached(&nonce, header, body)?;
+        frame.extend_from_slice(&tag);
+        send.bytes += payload.len() as u64;
+        Ok(frame)
+    }
+
+    /// [open_data_frame]
+    /// 受信した Data フレームを検証・復号し、ペイロードを返します。
+    pub fn open_data_frame(&mut self, bin: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let recv = self
+            .recv
+            .as_ref()
+            .ok_or_else(|| t!("session.recv_before_handshake"))?;
+        let frame = DataFrame::parse(bin)?;
+        let nonce = self.direction.reverse().nonce(frame.counter);
+        self.check_sequence(recv, &nonce)?;
+
+        let direction = self.direction;
+        let recv = self.recv.as_mut().unwrap();
+        let mut payload = frame.ciphertext.to_vec();
+        recv.crypto
+            .decrypt_in_place_detached(&nonce, frame.header, &mut payload, frame.tag)?;
+        Self::record_sequence(direction, recv, &nonce);
+        Ok(payload)
+    }
+
+    /// [seal_to_bytes]
+    /// メッセージを暗号化し、WebSocket で送信するバイナリにします。
+    /// コンパクトなフレームが有効な場合、Data はフレーム形式で送信します。
+    pub fn seal_to_bytes(&mut self, msg: Message) -> Result<Vec<u8>, CryptoError> {
+        if self.compact_data && msg.command == Command::Data {
+            return self.seal_data_frame(&msg.payload);
+        }
+        self.seal_message(msg)?.to_vec()
+    }
+
+    /// [open_bytes]
+    /// WebSocket で受信したバイナリを復号し、メッセージに戻します。
+    /// Data フレームは `Command::Data` のメッセージとして返します。
+    pub fn open_bytes(&mut self, bin: &[u8]) -> Result<Message, CryptoError> {
+        if is_data_frame(bin) {
+            return Ok(Message::new(Command::Data, self.open_data_frame(bin)?));
+        }
+        let msg = Message::from_slice(bin)?;
+        self.unseal_message(msg)
+    }
+
+    /// [rekey_message]
+    /// 鍵更新を通知する `Command::Rekey` を現在の鍵で暗号化して返し、以降の送信を次の世代の鍵に切り替えます。
+    /// 返されたバイナリは、次のメッセージより先に送信する必要があります。
+    pub fn rekey_message(&mut self) -> Result<Vec<u8>, CryptoError> {
+        let generation = self
+            .send
+            .as_ref()
+            .ok_or_else(|| t!("session.rekey_before_handshake"))?
+            .generation
+            + 1;
+        let msg = Message::new(
+            Command::Rekey,
+            encode_payload(&RekeyPayload { generation })?,
+        );
+        let bin = self.seal_to_bytes(msg)?;
+
+        let direction = self.direction;
+        self.send.as_mut().unwrap().rekey(direction)?;
+        info!("{}", t!("session.rekeyed", generation = generation));
+        Ok(bin)
+    }
+
+    /// [take_rekey]
+    /// 鍵更新の条件 ([RekeyPolicy]) を満たしている場合に、[rekey_message] を実行して送信すべきバイナリを返します。
+    pub fn take_rekey(&mut self) -> Result<Option<Vec<u8>>, CryptoError> {
+        let (Some(policy), Some(send)) = (&self.rekey_policy, &self.send) else {
+            return Ok(None);
+        };
+        if send.bytes < policy.max_bytes && send.since.elapsed() < policy.max_age {
+            return Ok(None);
+        }
+        self.rekey_message().map(Some)
+    }
+
+    /// 現在の送信鍵の世代を返します。
+    pub fn send_generation(&self) -> Option<u32> {
+        self.send.as_ref().map(|s| s.generation)
+    }
+
+    /// 現在の受信鍵の世代を返します。
+    pub fn recv_generation(&self) -> Option<u32> {
+        self.recv.as_ref().map(|s| s.generation)
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::aes_engine::AesGcmEngine;
+use super::chacha_engine::ChaCha20Poly1305Engine;
+use super::traits::{CryptoError, SymmetricCrypto};
+use crate::t;
+
+/// [SymmetricAlgorithm]
+/// セッションで使用できる共通鍵暗号の種類です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymmetricAlgorithm {
+    /// AES-256-GCM。AES のハードウェア支援がある環境で高速です。
+    Aes256Gcm,
+    /// ChaCha20-Poly1305。ハードウェア支援のない環境でも高速です。
+    ChaCha20Poly1305,
+}
+
+impl SymmetricAlgorithm {
+    /// サポートしているすべてのアルゴリズム
+    pub const ALL: [SymmetricAlgorithm; 2] = [Self::Aes256Gcm, Self::ChaCha20Poly1305];
+
+    /// プロトコル上の名称 (`SecureConnectPayload.algorithm` に使用) を返します。
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Aes256Gcm => "AES-256-GCM",
+            Self::ChaCha20Poly1305 => "ChaCha20-Poly1305",
+        }
+    }
+
+    /// ランダムな鍵でエンジンを作成します。
+    pub fn generate(self) -> Box<dyn SymmetricCrypto> {
+        match self {
+            Self::Aes256Gcm => Box::new(AesGcmEngine::new_random()),
+            Self::ChaCha20Poly1305 => Box::new(ChaCha20Poly1305Engine::new_random()),
+        }
+    }
+
+    /// 指定した鍵でエンジンを作成します。
+    pub fn from_key(self, key: &[u8]) -> Result<Box<dyn SymmetricCrypto>, CryptoError> {
+        Ok(match self {
+            Self::Aes256Gcm => Box::new(AesGcmEngine::from_key(key)?),
+            Self::ChaCha20Poly1305 => Box::new(ChaCha20Poly1305Engine::from_key(key)?),
+        })
+    }
+
+    /// [preferred]
+    /// この環境での推奨順にアルゴリズムを返します。
+    /// AES のハードウェア支援がない場合は ChaCha20-Poly1305 を優先します。
+    pub fn preferred() -> Vec<SymmetricAlgorithm> {
+        if has_aes_acceleration() {
+            vec![Self::Aes256Gcm, Self::ChaCha20Poly1305]
+        } else {
+            vec![Self::ChaCha20Poly1305, Self::Aes256Gcm]
+        }
+    }
+
+    /// [negotiate]
+    /// ゲートウェイとクライアントの推奨順から、セッションで使用するアルゴリズムを決定します。
+    ///
+    /// 双方が対応しているもののうち、双方の順位の合計が最も小さいものを選びます。
+    /// 同順位の場合は、多数のクライアントを処理するゲートウェイの推奨を優先します。
+    pub fn negotiate(
+        server: &[SymmetricAlgorithm],
+        client: &[SymmetricAlgorithm],
+    ) -> Option<SymmetricAlgorithm> {
+        server
+            .iter()
+            .enumerate()
+            .filter_map(|(server_rank, algorithm)| {
+                let client_rank = client.iter().position(|c| c == algorithm)?;
+                Some((server_rank + client_rank, server_rank, *algorithm))
+            })
+            .min_by_key(|&(total, server_rank, _)| (total, server_rank))
+            .map(|(_, _, algorithm)| algorithm)
+    }
+}
+
+impl fmt::Display for SymmetricAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SymmetricAlgorithm {
+    type Err = CryptoError;
+
+    /// 名称からアルゴリズムを取得します。大文字・小文字は区別しません。
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| t!("crypto.unsupported_algorithm", name = name).into())
+    }
+}
+
+/// AES のハードウェア支援 (AES-NI / ARMv8 Crypto Extensions) が使用できるかどうかを返します。
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn has_aes_acceleration() -> bool {
+    std::arch::is_x86_feature_detected!("aes") && std::arch::is_x86_feature_detected!("pclmulqdq")
+}
+
+/// AES のハードウェア支援 (AES-NI / ARMv8 Crypto Extensions) が使用できるかどうかを返します。
+#[cfg(target_arch = "aarch64")]
+fn has_aes_acceleration() -> bool {
+    std::arch::is_aarch64_feature_detected!("aes")
+}
+
+/// AES のハードウェア支援 (AES-NI / ARMv8 Crypto Extensions) が使用できるかどうかを返します。
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn has_aes_acceleration() -> bool {
+    false
+}
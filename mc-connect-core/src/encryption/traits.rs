@@ -3,6 +3,11 @@ use std::error::Error;
 /// Boxed error type that is safe to send between threads.
 pub type CryptoError = Box<dyn Error + Send + Sync>;
 
+/// AEAD の Nonce の長さ (バイト)
+pub const NONCE_LEN: usize = 12;
+/// AEAD の認証タグの長さ (バイト)
+pub const TAG_LEN: usize = 16;
+
 /// [CryptoKeyPair]
 /// 公開鍵暗号（非対称鍵暗号）の鍵ペアを扱うための共通インターフェースです。
 pub trait CryptoKeyPair: Send + Sync {
@@ -14,6 +19,16 @@ pub trait CryptoKeyPair: Send + Sync {
 
     /// 秘密鍵をバイト列として取得します。
     fn private_key_bytes(&self) -> Vec<u8>;
+
+    /// 公開鍵の SHA-256 フィンガープリントを `aa:bb:cc:...` 形式で取得します。
+    fn fingerprint(&self) -> String {
+        super::key_codec::fingerprint(&self.public_key_bytes())
+    }
+
+    /// 目視での照合向けに短縮したフィンガープリント (例: `SHA256:a65d-a406-28b0-98f0`) を取得します。
+    fn short_fingerprint(&self) -> String {
+        super::key_codec::short_fingerprint(&self.public_key_bytes())
+    }
 }
 
 /// [KeyGenerator]
@@ -24,34 +39,66 @@ pub trait KeyGenerator {
 }
 
 /// [Encryptor]
-/// データを暗号化・復号するための基本的な機能を提供します。
+/// データを暗号化するための機能を提供します。公開鍵のみでも使用できます。
 pub trait Encryptor {
     /// プレーンテキストを暗号化し、暗号文を返します。
     fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError>;
-    
+}
+
+/// [Decryptor]
+/// 暗号文を復号するための機能を提供します。秘密鍵が必要です。
+pub trait Decryptor {
     /// 暗号文を復号し、元のプレーンテキストを返します。
     fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError>;
 }
 
+/// [Verifier]
+/// 署名を検証するための機能を提供します。公開鍵のみでも使用できます。
+pub trait Verifier {
+    /// データと署名を照合し、正当なものか検証します。
+    fn verify(&self, data: &[u8], signature: &[u8]) -> Result<bool, CryptoError>;
+}
+
 /// [Signer]
-/// データの署名作成と検証を行うための機能を提供します。
-pub trait Signer {
+/// データの署名を作成するための機能を提供します。秘密鍵が必要です。
+pub trait Signer: Verifier {
     /// 指定されたデータに対してデジタル署名を作成します。
     fn sign(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError>;
-    
-    /// データと署名を照合し、正当なものか検証します。
-    fn verify(&self, data: &[u8], signature: &[u8]) -> Result<bool, CryptoError>;
 }
 
 /// [SymmetricCrypto]
 /// 共通鍵（対称鍵）を用いて高速にデータを暗号化・復号するためのインターフェースです。
 pub trait SymmetricCrypto: Send + Sync {
+    /// 使用しているアルゴリズムの名称 (例: "AES-256-GCM") を取得します。
+    fn algorithm_name(&self) -> &str;
+
     /// プレーンテキストを共通鍵で暗号化します。
     fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError>;
 
     /// 暗号文を共通鍵で復号します。
     fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CryptoError>;
 
+    /// 指定した Nonce と追加認証データ (AAD) で `buffer` をその場で暗号化し、認証タグを返します。
+    /// Nonce は同じ鍵で二度使用してはいけません。
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<[u8; TAG_LEN], CryptoError>;
+
+    /// `encrypt_in_place_detached` で暗号化した `buffer` を、認証タグを検証しながらその場で復号します。
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), CryptoError>;
+
     /// 現在設定されている共通鍵の生バイト列を取得します。
     fn key_bytes(&self) -> Vec<u8>;
+
+    /// 同じアルゴリズムで、別の共通鍵を使用するエンジンを作成します (鍵更新で使用)。
+    fn with_key(&self, key: &[u8]) -> Result<Box<dyn SymmetricCrypto>, CryptoError>;
 }
@@ -0,0 +1,95 @@
+//! 接続処理のエラー型
+//!
+//! コアの多くの関数は [CryptoError] (`Box<dyn Error + Send + Sync>`) を返しますが、
+//! ゲートウェイへの接続やハンドシェイクの失敗は [McConnectError] として返します。
+//! 呼び出し側は [McConnectError::from_boxed] で取り出し、[ErrorCode] によって
+//! 再試行するか設定を見直すかを判断したり、メッセージを翻訳したりできます。
+
+use std::error::Error;
+use std::fmt;
+
+use crate::encryption::CryptoError;
+use crate::models::packet::{ConnectResponsePayload, ErrorCode};
+use crate::t;
+
+/// [McConnectError]
+/// ゲートウェイへの接続・ハンドシェイクで発生するエラーの種類です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McConnectError {
+    /// ゲートウェイの URL が不正
+    InvalidUrl(String),
+    /// ゲートウェイへの WebSocket 接続に失敗した
+    WsConnectFailed(String),
+    /// ゲートウェイがハンドシェイクを拒否した。`code` はゲートウェイが返した理由です。
+    HandshakeRejected { code: ErrorCode, message: String },
+    /// 要求したポート・プロトコルがゲートウェイで許可されていない
+    PortNotAllowed(String),
+    /// ゲートウェイからターゲットへ接続できなかった
+    TargetUnreachable(String),
+    /// 受信したパケットを復号できなかった
+    DecryptFailed(String),
+    /// 想定外のパケットを受信した、または接続が途中で閉じられた
+    ProtocolMismatch(String),
+}
+
+impl McConnectError {
+    /// [from_response]
+    /// ゲートウェイから届いた失敗の ConnectResponse をエラーに変換します。
+    /// コードを送ってこない旧バージョンのゲートウェイの場合は [ErrorCode::Unknown] として扱います。
+    /// メッセージはコードと引数から現在の言語で組み立て直します。
+    pub fn from_response(res: &ConnectResponsePayload) -> Self {
+        let message = res.localized_message();
+        match res.code.unwrap_or(ErrorCode::Unknown) {
+            ErrorCode::PortNotAllowed => Self::PortNotAllowed(message),
+            ErrorCode::TargetUnreachable => Self::TargetUnreachable(message),
+            code => Self::HandshakeRejected { code, message },
+        }
+    }
+
+    /// [from_boxed]
+    /// [CryptoError] の中身が [McConnectError] であれば取り出します。
+    pub fn from_boxed(err: &CryptoError) -> Option<&Self> {
+        err.downcast_ref::<Self>()
+    }
+
+    /// [code]
+    /// エラーの種類を表すコードを返します。
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidUrl(_) => ErrorCode::InvalidUrl,
+            Self::WsConnectFailed(_) => ErrorCode::WsConnectFailed,
+            Self::HandshakeRejected { code, .. } => *code,
+            Self::PortNotAllowed(_) => ErrorCode::PortNotAllowed,
+            Self::TargetUnreachable(_) => ErrorCode::TargetUnreachable,
+            Self::DecryptFailed(_) => ErrorCode::DecryptFailed,
+            Self::ProtocolMismatch(_) => ErrorCode::ProtocolMismatch,
+        }
+    }
+
+    /// [is_retryable]
+    /// 時間をおいて再試行すれば成功する可能性があるかどうかを返します。
+    pub fn is_retryable(&self) -> bool {
+        self.code().is_retryable()
+    }
+}
+
+impl fmt::Display for McConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::InvalidUrl(e) => t!("error.invalid_url", detail = e),
+            Self::WsConnectFailed(e) => t!("error.ws_connect_failed", detail = e),
+            Self::HandshakeRejected { code, message } => t!(
+                "error.handshake_rejected",
+                code = code.as_str(),
+                detail = message
+            ),
+            Self::PortNotAllowed(e) => t!("error.port_not_allowed", detail = e),
+            Self::TargetUnreachable(e) => t!("error.target_unreachable", detail = e),
+            Self::DecryptFailed(e) => t!("error.decrypt_failed", detail = e),
+            Self::ProtocolMismatch(e) => t!("error.protocol_mismatch", detail = e),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl Error for McConnectError {}
@@ -0,0 +1,25 @@
+//! ファイルに関する共通の処理
+
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+
+/// [write_private_file]
+/// 秘密鍵を含むデータをファイルへ書き込みます。
+///
+/// Unix では所有者のみ読み書きできる権限 (0600) でファイルを作成し、既存のファイルも書き込む前に
+/// 同じ権限へ絞るため、書き込み中に他のユーザーから読まれることはありません。
+pub async fn write_private_file(path: impl AsRef<Path>, data: &[u8]) -> std::io::Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+    file.write_all(data).await?;
+    file.flush().await
+}
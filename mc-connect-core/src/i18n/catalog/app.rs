@@ -0,0 +1,241 @@
+//! デスクトップアプリ (Tauri コマンド) のメッセージ
+
+use super::{Entry, msg};
+
+pub(super) const MESSAGES: &[Entry] = &[
+    msg(
+        "app.exe_dir_failed",
+        "Failed to locate the executable directory: {error}",
+        "実行ファイルのディレクトリ取得に失敗しました: {error}",
+    ),
+    msg(
+        "app.config_dir_create_failed",
+        "Failed to create the config directory: {error}",
+        "設定ディレクトリの作成に失敗しました: {error}",
+    ),
+    msg(
+        "app.config_serialize_failed",
+        "Failed to serialize the config: {error}",
+        "設定のシリアライズに失敗しました: {error}",
+    ),
+    msg(
+        "app.config_write_failed",
+        "Failed to write the config file: {error}",
+        "設定ファイルの書き込みに失敗しました: {error}",
+    ),
+    msg(
+        "app.config_read_failed",
+        "Failed to read the config file: {error}",
+        "設定ファイルの読み込みに失敗しました: {error}",
+    ),
+    msg(
+        "app.config_invalid",
+        "Failed to parse the config: {error}",
+        "設定のデシリアライズに失敗しました: {error}",
+    ),
+    msg("app.tray_quit", "Quit McConnect", "McConnect を終了"),
+    msg("app.tray_show", "Open dashboard", "ダッシュボードを開く"),
+    msg(
+        "app.fetching_server_info",
+        "Fetching server info: {url}",
+        "サーバー情報を取得中: {url}",
+    ),
+    msg(
+        "app.server_info_fetched",
+        "Fetched server info. Allowed ports: {count}",
+        "サーバー情報を取得しました。許可ポート: {count}個",
+    ),
+    msg(
+        "app.server_info_failed",
+        "Failed to fetch server info: {error}",
+        "サーバー情報の取得に失敗: {error}",
+    ),
+    msg(
+        "app.public_key_decode_failed",
+        "Failed to decode the public key: {error}",
+        "公開鍵のデコードに失敗: {error}",
+    ),
+    msg(
+        "app.start_requested",
+        "Received request to start tunnel: {id}",
+        "トンネル開始命令を受信: {id}",
+    ),
+    msg(
+        "app.tunnel_starting",
+        "Starting tunnel: [{id}] {bind}:{port} -> {url} (Ping: {ping}s)",
+        "トンネルを開始します: [{id}] {bind}:{port} -> {url} (Ping: {ping}s)",
+    ),
+    msg(
+        "app.unsupported_protocol",
+        "Unsupported protocol: {protocol}",
+        "未対応のプロトコルです: {protocol}",
+    ),
+    msg(
+        "app.server_key_verify_failed",
+        "Failed to verify the server key: {error}",
+        "サーバー鍵の検証に失敗: {error}",
+    ),
+    msg(
+        "app.public_key_empty",
+        "The public key is empty.",
+        "公開鍵が空です。",
+    ),
+    msg(
+        "app.public_key_missing",
+        "No public key is configured.",
+        "公開鍵が設定されていません。",
+    ),
+    msg(
+        "app.server_key_fingerprint",
+        "Server key fingerprint [{id}]: {fingerprint}",
+        "サーバー鍵のフィンガープリント [{id}]: {fingerprint}",
+    ),
+    msg(
+        "app.server_key_rotated",
+        "The server key was rotated [{id}]. Updating the public key.",
+        "サーバー鍵がローテーションされました [{id}]。公開鍵を更新します。",
+    ),
+    msg("app.status_connected", "Connected", "接続完了"),
+    msg(
+        "app.tunnel_error",
+        "Tunnel error [{id}]: {error}",
+        "トンネルエラー [{id}]: {error}",
+    ),
+    msg("app.status_error", "Error: {error}", "エラー: {error}"),
+    msg(
+        "app.tunnel_session_ended",
+        "Tunnel session ended: {id}",
+        "トンネルセッション終了: {id}",
+    ),
+    msg(
+        "app.status_tunnel_stopped",
+        "Tunnel stopped",
+        "トンネルが停止しました",
+    ),
+    msg(
+        "app.connect_test_failed",
+        "Connection test failed [{id}]: {error}",
+        "接続テスト失敗 [{id}]: {error}",
+    ),
+    msg(
+        "app.status_connect_failed",
+        "Connection failed: {error}",
+        "接続失敗: {error}",
+    ),
+    msg(
+        "app.tunnel_stopped_manually",
+        "Tunnel stopped manually: {id}",
+        "トンネルを手動で停止しました: {id}",
+    ),
+    msg("app.status_stopped", "Stopped", "停止しました"),
+    msg(
+        "app.tunnel_not_running",
+        "Tunnel not running",
+        "トンネルは起動していません",
+    ),
+    msg(
+        "app.connection_not_found",
+        "Connection {connection} not found",
+        "接続 {connection} が見つかりません",
+    ),
+    msg(
+        "app.connection_closed",
+        "Closed connection {connection} [{id}]",
+        "接続 {connection} を切断しました [{id}]",
+    ),
+    msg(
+        "app.server_already_running",
+        "Server is already running",
+        "サーバーは既に起動しています",
+    ),
+    msg(
+        "app.encryption_unsupported",
+        "Encryption type {encryption} is not implemented in the backend yet. Use RSA.",
+        "暗号化方式 {encryption} は現在バックエンドで未実装です。RSAを使用してください。",
+    ),
+    msg(
+        "app.private_key_decode_failed",
+        "Failed to decode the private key: {error}",
+        "秘密鍵のデコードに失敗: {error}",
+    ),
+    msg(
+        "app.passphrase_required",
+        "The private key is encrypted. Enter the passphrase to start the server.",
+        "秘密鍵は暗号化されています。サーバーを起動するにはパスフレーズを入力してください。",
+    ),
+    msg(
+        "app.private_key_unlock_failed",
+        "Could not decrypt the private key. Check the passphrase: {error}",
+        "秘密鍵を復号できませんでした。パスフレーズを確認してください: {error}",
+    ),
+    msg(
+        "app.private_key_encrypt_failed",
+        "Failed to encrypt the private key: {error}",
+        "秘密鍵の暗号化に失敗しました: {error}",
+    ),
+    msg(
+        "app.server_starting",
+        "Starting server ({host}:{port}, Protocol: {encryption})",
+        "サーバーを起動します ({host}:{port}, Protocol: {encryption})",
+    ),
+    msg(
+        "app.server_exited",
+        "Server exited",
+        "サーバーが終了しました",
+    ),
+    msg(
+        "app.server_error",
+        "Server error: {error}",
+        "サーバーエラー: {error}",
+    ),
+    msg(
+        "app.server_stopped",
+        "Server stopped",
+        "サーバーを停止しました",
+    ),
+    msg(
+        "app.server_bind_failed",
+        "Failed to listen on {host}:{port}: {error}",
+        "{host}:{port} で待ち受けできませんでした: {error}",
+    ),
+    msg(
+        "app.server_listening",
+        "Server is listening on {address}",
+        "サーバーが {address} で待ち受けを開始しました",
+    ),
+    msg(
+        "app.public_host_missing",
+        "No public host is configured.",
+        "公開用ホストが設定されていません。",
+    ),
+    msg(
+        "app.export_no_ports",
+        "No allowed ports are configured.",
+        "許可するポートが設定されていません。",
+    ),
+    msg(
+        "app.import_invalid_json",
+        "Could not read the config file: {error}",
+        "設定ファイルを読み込めません: {error}",
+    ),
+    msg(
+        "app.import_invalid_config",
+        "Invalid config file: {error}",
+        "設定ファイルが不正です: {error}",
+    ),
+    msg(
+        "app.import_no_free_port",
+        "No free local port is available for {protocol}:{port}",
+        "{protocol}:{port} に使用できるローカルポートがありません",
+    ),
+    msg(
+        "app.import_port_reassigned",
+        "Local port {port} is in use; {protocol}:{port} will listen on {local_port}",
+        "ローカルポート {port} は使用中のため、{protocol}:{port} は {local_port} で待ち受けます",
+    ),
+    msg(
+        "app.config_imported",
+        "Imported {count} mappings from {name}",
+        "{name} から {count} 件のマッピングをインポートしました",
+    ),
+];
@@ -0,0 +1,910 @@
+//! CLI のメッセージ
+
+use super::{Entry, msg};
+
+pub(super) const MESSAGES: &[Entry] = &[
+    msg(
+        "cli.about",
+        "Tunnels Minecraft traffic over WebSocket.",
+        "Minecraft の通信を WebSocket でトンネルします。",
+    ),
+    msg(
+        "cli.long_about",
+        "McConnect is a tool that converts Minecraft TCP traffic to WebSocket and tunnels it.",
+        "McConnect は Minecraft の TCP 通信を WebSocket に変換してトンネルするツールです。",
+    ),
+    msg(
+        "cli.arg_config_dir",
+        "Configuration directory. Defaults to the MC_CONNECT_CONFIG_DIR environment variable, or the OS standard location (~/.config/mc-connect on Linux).",
+        "設定ディレクトリ。省略時は環境変数 MC_CONNECT_CONFIG_DIR、なければ OS 標準の場所 (Linux では ~/.config/mc-connect) です。",
+    ),
+    msg(
+        "cli.arg_lang",
+        "Message language (en / ja). Defaults to the language detected from LANG and similar variables, or English.",
+        "メッセージの言語 (en / ja)。省略時は環境変数 LANG などから判定し、判定できなければ英語です。",
+    ),
+    msg(
+        "cli.arg_passphrase_file",
+        "File containing the passphrase. Defaults to the MC_CONNECT_PASSPHRASE environment variable, or an interactive prompt",
+        "パスフレーズを記載したファイル。省略時は環境変数 MC_CONNECT_PASSPHRASE、なければ対話入力を使用します",
+    ),
+    msg(
+        "cli.arg_allow_legacy_key_wrap",
+        "Also accept old clients that wrap the session key with PKCS#1 v1.5 (deprecated; use only during migration)",
+        "PKCS#1 v1.5 で共通鍵をラップする旧クライアントも受け付けます (非推奨。移行期間中のみ使用してください)",
+    ),
+    msg(
+        "cli.arg_public_host",
+        "Public domain or IP that clients connect to",
+        "クライアントが接続するための公開ドメインまたはIP",
+    ),
+    msg(
+        "cli.arg_port",
+        "Port the server listens on",
+        "サーバーが待受けるポート番号",
+    ),
+    msg(
+        "cli.arg_traffic_file",
+        "Traffic record file. Defaults to traffic.json in the configuration directory.",
+        "転送量の記録ファイル。指定しない場合は設定ディレクトリの traffic.json です。",
+    ),
+    msg(
+        "cli.arg_server_config",
+        "Server configuration file (JSON). Defaults to server.json in the configuration directory.",
+        "サーバー設定ファイル (JSON)。指定しない場合は設定ディレクトリの server.json を読み込みます。",
+    ),
+    msg(
+        "cli.arg_target_config",
+        "Server configuration file (JSON) to operate on",
+        "対象のサーバー設定ファイル (JSON)",
+    ),
+    msg(
+        "cli.cmd_init",
+        "Create the server configuration and key in the configuration directory",
+        "サーバー設定と鍵を設定ディレクトリに作成します",
+    ),
+    msg(
+        "cli.arg_init_host",
+        "Address to bind to (local)",
+        "バインド用のアドレス (ローカル)",
+    ),
+    msg(
+        "cli.arg_init_key_pair",
+        "Existing private key file (DER / PEM / Base64). A new key is generated if omitted.",
+        "既存の秘密鍵ファイル (DER / PEM / Base64)。指定しない場合は新規生成します。",
+    ),
+    msg(
+        "cli.arg_init_config",
+        "Server configuration file to create. Defaults to server.json in the configuration directory.",
+        "作成するサーバー設定ファイル。指定しない場合は設定ディレクトリの server.json です。",
+    ),
+    msg(
+        "cli.arg_init_encrypt_key",
+        "Encrypt the saved private key with a passphrase",
+        "保存する秘密鍵をパスフレーズで暗号化します",
+    ),
+    msg(
+        "cli.arg_init_force",
+        "Overwrite the existing configuration and key",
+        "既存の設定と鍵を上書きします",
+    ),
+    msg(
+        "cli.cmd_server",
+        "Start the server with the saved server configuration",
+        "保存済みのサーバー設定でサーバーを起動します",
+    ),
+    msg(
+        "cli.arg_server_host",
+        "Address to bind to (local). Overrides the value in the configuration file",
+        "バインド用のアドレス (ローカル)。指定した場合は設定ファイルの値より優先します",
+    ),
+    msg(
+        "cli.arg_server_export",
+        "Export the settings as a JSON file",
+        "設定を JSON ファイルとして書き出します",
+    ),
+    msg(
+        "cli.arg_server_public_tls",
+        "Set when TLS is terminated in front of the public host (e.g. by a reverse proxy). Exports the URL as wss://",
+        "公開用ホストで TLS を終端している (リバースプロキシなど) 場合に指定します。書き出す URL を wss:// にします",
+    ),
+    msg(
+        "cli.arg_server_daily_cap",
+        "Traffic cap per day in UTC (e.g. 20GB). New connections are refused once exceeded",
+        "1 日 (UTC) あたりの転送量の上限 (例: 20GB)。超えると新しい接続を拒否します",
+    ),
+    msg(
+        "cli.arg_server_monthly_cap",
+        "Traffic cap per month in UTC (e.g. 1TB). New connections are refused once exceeded",
+        "1 か月 (UTC) あたりの転送量の上限 (例: 1TB)。超えると新しい接続を拒否します",
+    ),
+    msg(
+        "cli.arg_server_admin_token_file",
+        "File containing the token for the admin endpoint (/admin/traffic). Defaults to the MC_CONNECT_ADMIN_TOKEN environment variable; without either, the admin endpoint is disabled",
+        "管理用エンドポイント (/admin/traffic) のトークンを記載したファイル。省略時は環境変数 MC_CONNECT_ADMIN_TOKEN を使用し、どちらもなければ管理用エンドポイントは無効です",
+    ),
+    msg(
+        "cli.arg_server_audit_log",
+        "File to append the per-session audit log (JSON Lines) to. Records the peer, handshake result and traffic",
+        "セッションごとの監査ログ (JSON Lines) の追記先。接続元やハンドシェイクの結果、転送量を記録します",
+    ),
+    msg(
+        "cli.cmd_traffic",
+        "Show the traffic recorded by the gateway (per key and per allowed port)",
+        "ゲートウェイが記録した転送量 (鍵ごと・許可ポートごと) を表示します",
+    ),
+    msg(
+        "cli.cmd_traffic_long",
+        "Show the traffic recorded by the gateway (per key and per allowed port)\n\nTraffic per key is the total for each server key; clients sharing a key are not told apart",
+        "ゲートウェイが記録した転送量 (鍵ごと・許可ポートごと) を表示します\n\n鍵ごとの転送量はサーバー鍵単位の合計で、同じ鍵を使うクライアントは区別しません",
+    ),
+    msg(
+        "cli.arg_traffic_json",
+        "Output as JSON",
+        "JSON で出力します",
+    ),
+    msg(
+        "cli.cmd_agent",
+        "Connect to a relay from behind NAT and publish the server through it (reverse tunnel)",
+        "NAT の内側からリレーへ接続し、リレー経由でサーバーを公開します (リバーストンネル)",
+    ),
+    msg(
+        "cli.arg_agent_relay_url",
+        "WebSocket URL of the relay (e.g. ws://relay.example.com:8080)",
+        "リレーの WebSocket URL (例: ws://relay.example.com:8080)",
+    ),
+    msg(
+        "cli.arg_agent_allowed_ports",
+        "Allowed ports. Overrides the value in the configuration file",
+        "許可ポート。指定した場合は設定ファイルの値より優先します",
+    ),
+    msg(
+        "cli.cmd_relay",
+        "Start a relay for reverse tunnels",
+        "リバーストンネル用のリレーを起動します",
+    ),
+    msg(
+        "cli.arg_relay_host",
+        "Address to bind to",
+        "バインド用のアドレス",
+    ),
+    msg(
+        "cli.arg_relay_port",
+        "Port the relay listens on",
+        "リレーが待受けるポート番号",
+    ),
+    msg(
+        "cli.arg_relay_max_agents",
+        "Number of agents that can be registered at once. Unlimited if omitted.",
+        "同時に登録できるエージェントの数。指定しない場合は無制限です。",
+    ),
+    msg(
+        "cli.arg_relay_max_streams_per_agent",
+        "Number of client connections each agent can relay at once. Unlimited if omitted.",
+        "エージェントごとに同時に中継できるクライアント接続の数。指定しない場合は無制限です。",
+    ),
+    msg(
+        "cli.arg_relay_max_bytes_per_agent",
+        "Traffic each agent can relay (e.g. 50GB), counted since the relay started. Relaying stops once exceeded",
+        "エージェントごとに中継できる転送量 (例: 50GB)。リレーの起動中の累計で、超えると中継を打ち切ります",
+    ),
+    msg(
+        "cli.arg_relay_allowed_agents",
+        "Fingerprint of an agent allowed to register (repeatable). All agents are accepted if omitted.",
+        "登録を許可するエージェントのフィンガープリント (複数指定可)。指定しない場合はすべて受け付けます。",
+    ),
+    msg(
+        "cli.arg_relay_admin_token_file",
+        "File containing the token for the relay status (/metrics). Defaults to the MC_CONNECT_ADMIN_TOKEN environment variable; without either, /metrics is disabled",
+        "中継状況 (/metrics) のトークンを記載したファイル。省略時は環境変数 MC_CONNECT_ADMIN_TOKEN を使用し、どちらもなければ /metrics は無効です",
+    ),
+    msg(
+        "cli.cmd_client",
+        "Start a client tunnel",
+        "クライアントトンネルを開始します",
+    ),
+    msg(
+        "cli.arg_client_ws_url",
+        "WebSocket URL of the proxy server (e.g. ws://example.com/ws)",
+        "プロキシサーバーの WebSocket URL (例: ws://example.com/ws)",
+    ),
+    msg(
+        "cli.arg_client_public_key",
+        "Public key of the server (Base64). Required for a secure connection.",
+        "サーバーの公開鍵（Base64形式）。セキュア接続に必須です。",
+    ),
+    msg(
+        "cli.arg_client_config",
+        "Load the connection settings from a JSON configuration file",
+        "JSON 設定ファイルから接続情報を読み込みます",
+    ),
+    msg(
+        "cli.arg_client_tofu",
+        "Record and trust the key the gateway presents on first connection instead of specifying a public key (TOFU)",
+        "公開鍵を指定せず、初回接続時にゲートウェイが提示した鍵を記録して信頼します (TOFU)",
+    ),
+    msg(
+        "cli.arg_client_known_servers",
+        "Where to store the known servers recorded by TOFU. Defaults to known_servers.json in the configuration directory.",
+        "TOFU で記録した既知サーバーの保存先。指定しない場合は設定ディレクトリの known_servers.json です。",
+    ),
+    msg(
+        "cli.arg_client_proxy_port",
+        "Port to listen on as a SOCKS5 / HTTP CONNECT proxy instead of a fixed port. Opens a tunnel to the gateway's allowed port matching the requested destination port.",
+        "固定ポートの代わりに、SOCKS5 / HTTP CONNECT プロキシとして待ち受けるポート。要求された宛先ポートに応じて、ゲートウェイの許可ポートへトンネルを張ります。",
+    ),
+    msg(
+        "cli.arg_client_status_interval",
+        "Every given number of seconds, show the traffic and throughput and the relayed connections (peer, traffic, RTT, state)",
+        "指定した秒数ごとに、転送量と速度、中継中の接続 (接続元・通信量・RTT・状態) の一覧を表示します",
+    ),
+    msg(
+        "cli.cmd_keys",
+        "Generate, show, convert, import and rotate keys",
+        "鍵の生成・表示・変換・取り込み・ローテーションを行います",
+    ),
+    msg(
+        "cli.cmd_keys_generate",
+        "Generate a new key pair and write out the private key",
+        "新しい鍵ペアを生成し、秘密鍵を書き出します",
+    ),
+    msg(
+        "cli.arg_keys_generate_algorithm",
+        "Key algorithm",
+        "鍵のアルゴリズム",
+    ),
+    msg(
+        "cli.arg_keys_generate_bits",
+        "Key length (bits)",
+        "鍵長 (ビット)",
+    ),
+    msg(
+        "cli.arg_keys_generate_output",
+        "Output file for the private key. Created readable and writable by the owner only",
+        "秘密鍵の出力先ファイル。所有者のみ読み書きできる権限で作成します",
+    ),
+    msg(
+        "cli.arg_keys_generate_stdout",
+        "Write the private key to standard output instead of a file",
+        "秘密鍵をファイルではなく標準出力に書き出します",
+    ),
+    msg(
+        "cli.arg_keys_generate_format",
+        "Output format",
+        "出力形式",
+    ),
+    msg(
+        "cli.cmd_keys_show",
+        "Show the public key and its fingerprint",
+        "公開鍵とフィンガープリントを表示します",
+    ),
+    msg(
+        "cli.arg_keys_show_key",
+        "Key file (DER / PEM / Base64, private or public)",
+        "鍵ファイル (DER / PEM / Base64、秘密鍵・公開鍵のどちらでも可)",
+    ),
+    msg(
+        "cli.arg_keys_show_config",
+        "Server configuration file (JSON). Without --key, server.json in the configuration directory is used",
+        "サーバー設定ファイル (JSON)。--key がない場合は設定ディレクトリの server.json を参照します",
+    ),
+    msg(
+        "cli.cmd_keys_convert",
+        "Convert a key file between DER / PEM / Base64",
+        "鍵ファイルの形式を DER / PEM / Base64 の間で変換します",
+    ),
+    msg(
+        "cli.arg_keys_convert_input",
+        "Key file to convert (format detected automatically)",
+        "変換元の鍵ファイル (形式は自動判別)",
+    ),
+    msg(
+        "cli.arg_keys_convert_to",
+        "Format to convert to",
+        "変換後の形式",
+    ),
+    msg(
+        "cli.arg_keys_convert_output",
+        "Output file. Written to standard output if omitted",
+        "出力先ファイル。指定しない場合は標準出力に書き出します",
+    ),
+    msg(
+        "cli.arg_keys_convert_public_only",
+        "Output only the public part of a private key",
+        "秘密鍵から公開鍵部分だけを取り出して出力します",
+    ),
+    msg(
+        "cli.cmd_keys_import",
+        "Import an existing PEM private key into the server configuration",
+        "既存の PEM 秘密鍵をサーバー設定に取り込みます",
+    ),
+    msg(
+        "cli.arg_keys_import_pem",
+        "PEM file to import (PKCS#8 or PKCS#1)",
+        "取り込む PEM ファイル (PKCS#8 または PKCS#1)",
+    ),
+    msg(
+        "cli.arg_keys_import_config",
+        "Server configuration file (JSON) to write to",
+        "書き込み先のサーバー設定ファイル (JSON)",
+    ),
+    msg(
+        "cli.cmd_keys_rotate",
+        "Generate a new server key and retire the old one with a grace period",
+        "サーバー鍵を新しく生成し、旧鍵を猶予期間付きで退役させます",
+    ),
+    msg(
+        "cli.arg_keys_rotate_grace_days",
+        "Number of days the old key is still accepted",
+        "旧鍵を引き続き受け付ける日数",
+    ),
+    msg(
+        "cli.arg_keys_rotate_bits",
+        "Key length of the new key (bits)",
+        "新しい鍵の鍵長 (ビット)",
+    ),
+    msg(
+        "cli.cmd_keys_encrypt",
+        "Encrypt the private key in the server configuration with a passphrase (Argon2id + AES-256-GCM)",
+        "サーバー設定の秘密鍵をパスフレーズで暗号化します (Argon2id + AES-256-GCM)",
+    ),
+    msg(
+        "cli.cmd_keys_decrypt",
+        "Turn the encrypted private key in the server configuration back into plain text",
+        "暗号化されたサーバー設定の秘密鍵を平文に戻します",
+    ),
+    msg(
+        "cli.unsupported_lang",
+        "Unsupported language: {lang} (en / ja)",
+        "未対応の言語です: {lang} (en / ja)",
+    ),
+    msg(
+        "cli.invalid_port_format",
+        "Invalid format: {value}. Expected 'port:protocol'",
+        "形式が正しくありません: {value}。'ポート:プロトコル' の形式で指定してください",
+    ),
+    msg(
+        "cli.invalid_port",
+        "Invalid port: {value}",
+        "ポート番号が正しくありません: {value}",
+    ),
+    msg(
+        "cli.unsupported_protocol",
+        "Unsupported protocol: {value}",
+        "未対応のプロトコルです: {value}",
+    ),
+    msg(
+        "cli.passphrase_file_read_failed",
+        "Failed to read the passphrase file {path}",
+        "パスフレーズファイル {path} の読み込みに失敗しました",
+    ),
+    msg(
+        "cli.passphrase_from_file",
+        "Read the passphrase from {path}.",
+        "パスフレーズを {path} から読み込みました。",
+    ),
+    msg(
+        "cli.passphrase_from_env",
+        "Read the passphrase from the environment variable {name}.",
+        "パスフレーズを環境変数 {name} から読み込みました。",
+    ),
+    msg("cli.passphrase_prompt", "Passphrase: ", "パスフレーズ: "),
+    msg(
+        "cli.passphrase_confirm_prompt",
+        "Passphrase (confirm): ",
+        "パスフレーズ (確認): ",
+    ),
+    msg(
+        "cli.passphrase_input_failed",
+        "Failed to read the passphrase",
+        "パスフレーズの入力に失敗しました",
+    ),
+    msg(
+        "cli.passphrase_mismatch",
+        "The passphrases do not match",
+        "パスフレーズが一致しません",
+    ),
+    msg(
+        "cli.invalid_size",
+        "Invalid size: {value}",
+        "サイズの指定が正しくありません: {value}",
+    ),
+    msg(
+        "cli.unsupported_size_unit",
+        "Unsupported size unit: {unit}",
+        "未対応のサイズの単位です: {unit}",
+    ),
+    msg(
+        "cli.config_dir_unknown",
+        "Cannot determine the config directory. Specify --config-dir",
+        "設定ディレクトリを特定できません。--config-dir を指定してください",
+    ),
+    msg(
+        "cli.config_read_failed",
+        "Failed to read the config file {path}",
+        "設定ファイル {path} の読み込みに失敗しました",
+    ),
+    msg(
+        "cli.config_invalid",
+        "The config file format is invalid",
+        "設定ファイルのフォーマットが正しくありません",
+    ),
+    msg(
+        "cli.create_dir_failed",
+        "Failed to create the directory {path}",
+        "ディレクトリ {path} の作成に失敗しました",
+    ),
+    msg(
+        "cli.config_write_failed",
+        "Failed to save the config file {path}",
+        "設定ファイル {path} の保存に失敗しました",
+    ),
+    msg(
+        "cli.agent_id",
+        "Agent ID: {agent}",
+        "エージェント ID: {agent}",
+    ),
+    msg(
+        "cli.fingerprint",
+        "Fingerprint: {short} ({full})",
+        "フィンガープリント: {short} ({full})",
+    ),
+    msg(
+        "cli.agent_error",
+        "Agent error: {error}",
+        "エージェントのエラー: {error}",
+    ),
+    msg(
+        "cli.relay_all_agents",
+        "Accepting registrations from all agents.",
+        "すべてのエージェントの登録を受け付けます。",
+    ),
+    msg(
+        "cli.relay_allowed_agents",
+        "Accepting registrations only from {count} allowed agent(s).",
+        "許可したエージェント {count} 個のみ登録を受け付けます。",
+    ),
+    msg(
+        "cli.relay_metrics_enabled",
+        "Enabled the metrics endpoint /metrics.",
+        "中継状況のエンドポイント /metrics を有効にしました。",
+    ),
+    msg(
+        "cli.relay_error",
+        "Relay error: {error}",
+        "リレーのエラー: {error}",
+    ),
+    msg(
+        "cli.traffic_read_failed",
+        "Failed to read the traffic record {path}: {error}",
+        "転送量の記録 {path} の読み込みに失敗しました: {error}",
+    ),
+    msg(
+        "cli.traffic_file",
+        "Traffic file: {path}",
+        "転送量の記録: {path}",
+    ),
+    msg("cli.traffic_total", "Total", "合計"),
+    msg("cli.traffic_per_key", "Per key:", "鍵ごと:"),
+    msg("cli.traffic_per_port", "Per port:", "ポートごと:"),
+    msg(
+        "cli.ws_url_required",
+        "--ws-url or --config is required",
+        "--ws-url または --config が必要です",
+    ),
+    msg(
+        "cli.fetching_ports",
+        "Fetching allowed ports from {url}...",
+        "{url} から許可ポートを取得しています...",
+    ),
+    msg(
+        "cli.server_version",
+        "Server Version: {version}",
+        "サーバーのバージョン: {version}",
+    ),
+    msg(
+        "cli.protocol_version",
+        "Protocol Version: v{version} ({capabilities})",
+        "プロトコルのバージョン: v{version} ({capabilities})",
+    ),
+    msg(
+        "cli.protocol_version_legacy",
+        "Protocol Version: v0 (legacy)",
+        "プロトコルのバージョン: v0 (旧バージョン)",
+    ),
+    msg("cli.allowed_ports", "Allowed Ports:", "許可されたポート:"),
+    msg(
+        "cli.server_info_failed",
+        "Failed to fetch server info: {error}",
+        "サーバー情報の取得に失敗しました: {error}",
+    ),
+    msg(
+        "cli.known_servers_load_failed",
+        "Failed to load known servers: {error}",
+        "既知サーバーの読み込みに失敗しました: {error}",
+    ),
+    msg(
+        "cli.server_key_verify_failed",
+        "Failed to verify the server key: {error}",
+        "サーバー鍵の検証に失敗しました: {error}",
+    ),
+    msg(
+        "cli.public_key_required",
+        "One of --public-key, --config or --tofu is required",
+        "--public-key、--config または --tofu のいずれかが必要です",
+    ),
+    msg(
+        "cli.public_key_decode_failed",
+        "Failed to decode the public key from Base64",
+        "公開鍵の Base64 デコードに失敗しました",
+    ),
+    msg(
+        "cli.server_fingerprint",
+        "Server key fingerprint: {short} ({full})",
+        "サーバー鍵のフィンガープリント: {short} ({full})",
+    ),
+    msg(
+        "cli.public_key_load_failed",
+        "Failed to load the public key: {error}",
+        "公開鍵の読み込みに失敗しました: {error}",
+    ),
+    msg(
+        "cli.proxy_starting",
+        "Starting SOCKS5 / HTTP CONNECT proxy: local {local} -> ws {url}",
+        "SOCKS5 / HTTP CONNECT プロキシを開始します: ローカル {local} -> ws {url}",
+    ),
+    msg(
+        "cli.client_error",
+        "Client error: {error}",
+        "クライアントのエラー: {error}",
+    ),
+    msg(
+        "cli.tunnel_starting",
+        "Starting secure client tunnel: local {local} -> ws {url} -> remote {remote} ({protocol})",
+        "セキュアトンネルを開始します: ローカル {local} -> ws {url} -> リモート {remote} ({protocol})",
+    ),
+    msg(
+        "cli.key_rotated",
+        "The server key has been rotated. New public key: {key}",
+        "サーバー鍵がローテーションされました。新しい公開鍵: {key}",
+    ),
+    msg(
+        "cli.config_update_failed",
+        "Failed to update the config file {path}",
+        "設定ファイル {path} の更新に失敗しました",
+    ),
+    msg(
+        "cli.config_key_updated",
+        "Updated the public key in {path} to the new key.",
+        "{path} の公開鍵を新しい鍵に更新しました。",
+    ),
+    msg(
+        "cli.replace_public_key",
+        "Replace the public key passed to --public-key with the new key above.",
+        "--public-key に指定している公開鍵を上記の新しい鍵に置き換えてください。",
+    ),
+    msg(
+        "cli.hint_retry",
+        "This may be a temporary failure. Please try again later.",
+        "一時的な失敗の可能性があります。しばらくしてから再試行してください。",
+    ),
+    msg(
+        "cli.hint_check_config",
+        "Check the gateway URL, public key and port settings.",
+        "ゲートウェイの URL・公開鍵・ポートの設定を確認してください。",
+    ),
+    msg(
+        "cli.client_error_with_code",
+        "Client error [{code}]: {error}\n{hint}",
+        "クライアントのエラー [{code}]: {error}\n{hint}",
+    ),
+    msg(
+        "cli.traffic_status",
+        "Traffic: up {up} bytes ({up_speed} B/s), down {down} bytes ({down_speed} B/s), rtt {rtt}",
+        "転送量: 上り {up} bytes ({up_speed} B/s)、下り {down} bytes ({down_speed} B/s)、RTT {rtt}",
+    ),
+    msg(
+        "cli.active_connections",
+        "Active connections: {count}",
+        "中継中の接続: {count}",
+    ),
+    msg(
+        "cli.config_exists",
+        "{path} already exists. Pass --force to recreate the server key",
+        "{path} は既に存在します。サーバー鍵を作り直す場合は --force を指定してください",
+    ),
+    msg(
+        "cli.config_overwrite",
+        "Overwriting {path}. The server key will change, so clients need the new public key.",
+        "{path} を上書きします。サーバー鍵が変わるため、クライアントには新しい公開鍵を配布し直す必要があります。",
+    ),
+    msg(
+        "cli.loading_private_key",
+        "Loading the existing private key: {path}",
+        "既存の秘密鍵を読み込んでいます: {path}",
+    ),
+    msg(
+        "cli.key_file_read_failed",
+        "Failed to read the key file {path}",
+        "キーファイル {path} の読み込みに失敗しました",
+    ),
+    msg(
+        "cli.key_parse_failed",
+        "Failed to parse the key: {error}",
+        "キーのパースに失敗しました: {error}",
+    ),
+    msg(
+        "cli.public_key_derive_failed",
+        "Failed to derive the public key: {error}",
+        "公開鍵の導出に失敗しました: {error}",
+    ),
+    msg(
+        "cli.public_key_only",
+        "{path} contains only a public key. Specify a private key",
+        "{path} には公開鍵しか含まれていません。秘密鍵を指定してください",
+    ),
+    msg(
+        "cli.generating_key_pair",
+        "Generating a new key pair...",
+        "新しいキーペアを生成しています...",
+    ),
+    msg(
+        "cli.key_generation_failed",
+        "Key generation failed: {error}",
+        "鍵の生成に失敗しました: {error}",
+    ),
+    msg(
+        "cli.key_encrypt_failed",
+        "Failed to encrypt the private key: {error}",
+        "秘密鍵の暗号化に失敗しました: {error}",
+    ),
+    msg(
+        "cli.saving_encrypted_key",
+        "Saving the private key encrypted with the passphrase.",
+        "秘密鍵をパスフレーズで暗号化して保存します。",
+    ),
+    msg(
+        "cli.config_created",
+        "Created the server config: {path}",
+        "サーバー設定を作成しました: {path}",
+    ),
+    msg("cli.public_key", "Public key: {key}", "公開鍵: {key}"),
+    msg(
+        "cli.fingerprint_only",
+        "Fingerprint: {fingerprint}",
+        "フィンガープリント: {fingerprint}",
+    ),
+    msg(
+        "cli.init_next_step",
+        "Run `mc-connect-cli server` to start the server with this config.",
+        "`mc-connect-cli server` で、この設定を使用してサーバーを起動できます。",
+    ),
+    msg(
+        "cli.config_loaded",
+        "Loaded settings from the config file: Host={host}, Port={port}",
+        "設定ファイルから設定をロードしました: Host={host}, Port={port}",
+    ),
+    msg(
+        "cli.client_config_exported",
+        "Wrote the client config file: {path}",
+        "クライアント用設定ファイルを書き出しました: {path}",
+    ),
+    msg(
+        "cli.server_public_key",
+        "Server public key:",
+        "サーバーの公開鍵:",
+    ),
+    msg(
+        "cli.fingerprint_heading",
+        "Fingerprint:",
+        "フィンガープリント:",
+    ),
+    msg(
+        "cli.traffic_recording",
+        "Recording traffic to {path} (quota: {quota})",
+        "転送量を {path} に記録します (上限: {quota})",
+    ),
+    msg(
+        "cli.admin_enabled",
+        "Enabled the admin endpoint /admin/traffic.",
+        "管理用エンドポイント /admin/traffic を有効にしました。",
+    ),
+    msg(
+        "cli.audit_open_failed",
+        "Could not open the audit log {path}: {error}",
+        "監査ログ {path} を開けませんでした: {error}",
+    ),
+    msg(
+        "cli.audit_enabled",
+        "Appending session audit records to {path}.",
+        "セッションの監査記録を {path} に追記します。",
+    ),
+    msg(
+        "cli.server_error",
+        "Server error: {error}",
+        "サーバーのエラー: {error}",
+    ),
+    msg(
+        "cli.server_config_missing",
+        "Server config {path} not found. Create it with `mc-connect-cli init` or pass an existing config with --config",
+        "サーバー設定 {path} が見つかりません。`mc-connect-cli init` で作成するか、--config で既存の設定ファイルを指定してください",
+    ),
+    msg(
+        "cli.loading_config",
+        "Loading the config file {path}...",
+        "設定ファイル {path} を読み込んでいます...",
+    ),
+    msg(
+        "cli.decrypting_key",
+        "The private key is encrypted. Decrypting it with the passphrase.",
+        "秘密鍵は暗号化されています。パスフレーズで復号します。",
+    ),
+    msg(
+        "cli.private_key_load_failed",
+        "Failed to load the private key: {error}",
+        "秘密鍵の読み込みに失敗しました: {error}",
+    ),
+    msg(
+        "cli.legacy_key_wrap_enabled",
+        "Accepting old clients that wrap the session key with PKCS#1 v1.5. Disable this once all clients are updated.",
+        "PKCS#1 v1.5 で共通鍵をラップする旧クライアントを受け付けます。すべてのクライアントを更新したら無効にしてください。",
+    ),
+    msg(
+        "cli.retired_keys_accepted",
+        "Still accepting {count} retiring key(s) in their grace period.",
+        "猶予期間中の旧鍵 {count} 個も引き続き受け付けます。",
+    ),
+    msg(
+        "cli.token_file_read_failed",
+        "Failed to read the token file {path}",
+        "トークンファイル {path} の読み込みに失敗しました",
+    ),
+    msg(
+        "cli.generating_key",
+        "Generating a {algorithm} {bits}-bit key pair...",
+        "{algorithm} {bits} ビットの鍵ペアを生成しています...",
+    ),
+    msg(
+        "cli.private_key_encode_failed",
+        "Failed to encode the private key: {error}",
+        "秘密鍵のエンコードに失敗しました: {error}",
+    ),
+    msg(
+        "cli.key_load_failed",
+        "Failed to load the key: {error}",
+        "鍵の読み込みに失敗しました: {error}",
+    ),
+    msg("cli.key_kind_private", "private key", "秘密鍵"),
+    msg("cli.key_kind_public", "public key", "公開鍵"),
+    msg("cli.key_kind_retired", "retired public key", "旧公開鍵"),
+    msg(
+        "cli.show_file",
+        "File: {path} ({kind})",
+        "ファイル: {path} ({kind})",
+    ),
+    msg("cli.show_config", "Config: {path}", "設定: {path}"),
+    msg("cli.show_public_key", "Public Key: {key}", "公開鍵: {key}"),
+    msg(
+        "cli.show_fingerprint",
+        "Fingerprint: {fingerprint}",
+        "フィンガープリント: {fingerprint}",
+    ),
+    msg(
+        "cli.show_short_fingerprint",
+        "Short Fingerprint: {fingerprint}",
+        "短縮フィンガープリント: {fingerprint}",
+    ),
+    msg(
+        "cli.show_encrypted",
+        "Private Key: encrypted ({kdf}, m={memory}KiB, t={iterations}, p={parallelism}, {cipher})",
+        "秘密鍵: 暗号化済み ({kdf}, m={memory}KiB, t={iterations}, p={parallelism}, {cipher})",
+    ),
+    msg("cli.show_retired_keys", "Retired Keys:", "退役した鍵:"),
+    msg(
+        "cli.retired_valid_for",
+        "valid for {days} more day(s)",
+        "あと {days} 日有効",
+    ),
+    msg("cli.retired_expired", "expired", "期限切れ"),
+    msg(
+        "cli.key_encode_failed",
+        "Failed to encode the key: {error}",
+        "鍵のエンコードに失敗しました: {error}",
+    ),
+    msg(
+        "cli.key_converted",
+        "Converted {path} to {format} format.",
+        "{path} を {format} 形式に変換しました。",
+    ),
+    msg(
+        "cli.pem_read_failed",
+        "Failed to read the PEM file {path}",
+        "PEM ファイル {path} の読み込みに失敗しました",
+    ),
+    msg(
+        "cli.not_pem",
+        "{path} is not in PEM format",
+        "{path} は PEM 形式ではありません",
+    ),
+    msg(
+        "cli.pem_load_failed",
+        "Failed to load the PEM: {error}",
+        "PEM の読み込みに失敗しました: {error}",
+    ),
+    msg(
+        "cli.pem_public_only",
+        "{path} contains only a public key. Specify a private key PEM",
+        "{path} には公開鍵しか含まれていません。秘密鍵の PEM を指定してください",
+    ),
+    msg(
+        "cli.replacing_key",
+        "Replacing the existing key in {path}. Clients need the new public key.",
+        "{path} の既存の鍵を置き換えます。クライアントには新しい公開鍵を配布し直す必要があります。",
+    ),
+    msg(
+        "cli.creating_default_config",
+        "{path} does not exist. Creating it with default values.",
+        "{path} が存在しないため、既定値で新規作成します。",
+    ),
+    msg(
+        "cli.key_imported",
+        "Imported {pem} into {path}.",
+        "{pem} を {path} に取り込みました。",
+    ),
+    msg(
+        "cli.generating_server_key",
+        "Generating a new server key (RSA {bits} bits)...",
+        "新しいサーバー鍵 (RSA {bits} ビット) を生成しています...",
+    ),
+    msg(
+        "cli.retired_key_grace",
+        "The old key {fingerprint} stays valid for {days} day(s).",
+        "旧鍵 {fingerprint} は {days} 日間有効です。",
+    ),
+    msg(
+        "cli.new_public_key",
+        "New public key: {key}",
+        "新しい公開鍵: {key}",
+    ),
+    msg(
+        "cli.new_fingerprint",
+        "New fingerprint: {fingerprint}",
+        "新しいフィンガープリント: {fingerprint}",
+    ),
+    msg(
+        "cli.key_already_encrypted",
+        "The private key in {path} is already encrypted",
+        "{path} の秘密鍵は既に暗号化されています",
+    ),
+    msg(
+        "cli.key_encrypted",
+        "Encrypted the private key in {path}.",
+        "{path} の秘密鍵を暗号化しました。",
+    ),
+    msg(
+        "cli.key_not_encrypted",
+        "The private key in {path} is not encrypted.",
+        "{path} の秘密鍵は暗号化されていません。",
+    ),
+    msg(
+        "cli.key_decrypted",
+        "Saved the private key in {path} as plain text. Handle the file with care.",
+        "{path} の秘密鍵を平文で保存しました。ファイルの取り扱いに注意してください。",
+    ),
+    msg(
+        "cli.rsa_bits_too_small",
+        "The RSA key length must be at least 2048 bits (given: {bits})",
+        "RSA の鍵長は 2048 ビット以上を指定してください (指定値: {bits})",
+    ),
+    msg(
+        "cli.write_failed",
+        "Failed to write to {path}",
+        "{path} への書き込みに失敗しました",
+    ),
+    msg("cli.written", "Wrote {path}.", "{path} に書き出しました。"),
+    msg(
+        "cli.base64_decode_failed",
+        "Failed to decode the {what} from Base64",
+        "{what}の Base64 デコードに失敗しました",
+    ),
+];
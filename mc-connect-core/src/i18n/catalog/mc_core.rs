@@ -0,0 +1,1183 @@
+//! コアライブラリ (ゲートウェイ・クライアント・暗号化) のメッセージ
+
+use super::{Entry, msg};
+
+pub(super) const MESSAGES: &[Entry] = &[
+    msg(
+        "response.invalid_url",
+        "The gateway URL is invalid.",
+        "ゲートウェイの URL が不正です。",
+    ),
+    msg(
+        "response.ws_connect_failed",
+        "Could not connect to the gateway.",
+        "ゲートウェイに接続できませんでした。",
+    ),
+    msg(
+        "response.handshake_failed",
+        "Handshake failed.",
+        "ハンドシェイクに失敗しました。",
+    ),
+    msg(
+        "response.port_not_allowed",
+        "Unauthorized access to port {port}: {protocol}",
+        "ポート {port} ({protocol}) への接続はゲートウェイで許可されていません。",
+    ),
+    msg(
+        "response.quota_exceeded_daily",
+        "Daily traffic quota exceeded ({used} / {limit} bytes). Try again after 00:00 UTC.",
+        "1 日あたりの転送量の上限に達しています ({used} / {limit} バイト)。UTC 0 時以降に再試行してください。",
+    ),
+    msg(
+        "response.quota_exceeded_monthly",
+        "Monthly traffic quota exceeded ({used} / {limit} bytes).",
+        "1 か月あたりの転送量の上限に達しています ({used} / {limit} バイト)。",
+    ),
+    msg(
+        "response.target_unreachable",
+        "The gateway could not connect to the target: {reason}",
+        "ゲートウェイからターゲットへ接続できませんでした: {reason}",
+    ),
+    msg(
+        "response.decrypt_failed",
+        "Failed to decrypt the packet.",
+        "パケットを復号できませんでした。",
+    ),
+    msg(
+        "response.protocol_mismatch",
+        "Secure connection is required.",
+        "セキュア接続が必要です。",
+    ),
+    msg(
+        "response.unknown",
+        "Connection failed.",
+        "接続に失敗しました。",
+    ),
+
+    msg(
+        "gateway.client_closed",
+        "Client closed the WebSocket connection: {reason}",
+        "クライアントが WebSocket 接続を閉じました: {reason}",
+    ),
+    msg(
+        "gateway.data_frame_decrypt_failed",
+        "Failed to decrypt a Data frame (mismatched session key or tampering): {error}. Closing the session.",
+        "Data フレームの復号に失敗しました (共通鍵の不一致や改ざんの可能性): {error}。セッションを終了します。",
+    ),
+    msg(
+        "gateway.packet_decode_failed",
+        "Failed to deserialize a McConnect packet: {error}",
+        "McConnect パケットのデシリアライズに失敗: {error}",
+    ),
+    msg(
+        "gateway.packet_decrypt_failed",
+        "Failed to decrypt a packet (mismatched session key or tampering): {error}. Closing the session.",
+        "パケットの復号に失敗しました (共通鍵の不一致や改ざんの可能性): {error}。セッションを終了します。",
+    ),
+    msg(
+        "gateway.secure_connect_received",
+        "Received a SecureConnect request. Starting the handshake.",
+        "SecureConnect リクエストを受信しました。ハンドシェイクを開始します。",
+    ),
+    msg(
+        "gateway.secure_connect_duplicate",
+        "Received SecureConnect on an already initialized session. Ignoring.",
+        "既に初期化済みのセッションで SecureConnect を受信しました。無視します。",
+    ),
+    msg(
+        "gateway.plain_connect_rejected",
+        "Received an unencrypted Connect request. This gateway only accepts secure connections.",
+        "暗号化されていない接続要求 (Connect) を受信しました。本サーバーはセキュア接続のみを許可します。",
+    ),
+    msg(
+        "gateway.client_disconnect",
+        "The client requested to disconnect.",
+        "クライアントから明示的な切断要求を受信しました。",
+    ),
+    msg(
+        "gateway.server_info_request",
+        "Answering a server info request (GetServerInfo).",
+        "サーバー情報の問い合わせ (GetServerInfo) に応答します。",
+    ),
+    msg(
+        "gateway.sign_key_failed",
+        "Failed to sign the server public key: {error}",
+        "サーバー公開鍵への署名に失敗: {error}",
+    ),
+    msg(
+        "gateway.client_rekeyed",
+        "The client renewed the session key.",
+        "クライアントが共通鍵を更新しました。",
+    ),
+    msg(
+        "gateway.unknown_command",
+        "Received an unknown command. Ignoring.",
+        "未知のコマンドを受信しました。無視します。",
+    ),
+    msg(
+        "gateway.unhandled_command",
+        "Received an unimplemented or unknown command: {command}",
+        "未実装または未知のコマンドを受信しました: {command}",
+    ),
+    msg(
+        "gateway.target_forward_failed",
+        "Failed to forward data to the TCP target. The connection may have been closed.",
+        "TCP ターゲットへのデータ転送に失敗しました。接続が切断されている可能性があります。",
+    ),
+    msg(
+        "gateway.data_before_connect",
+        "Received a Data packet before the TCP connection was established. Ignoring.",
+        "Data パケットを受信しましたが、TCP 接続が確立されていません。無視します。",
+    ),
+    msg(
+        "gateway.secure_connect_parsing",
+        "Parsing the SecureConnect request...",
+        "SecureConnect リクエストを解析中...",
+    ),
+    msg(
+        "gateway.handshake_failed",
+        "Secure handshake failed: {error}",
+        "セキュアハンドシェイクに失敗しました: {error}",
+    ),
+    msg(
+        "gateway.handshake_succeeded",
+        "Handshake succeeded. Protocol: {protocol}, port: {port}, protocol version: v{version}",
+        "ハンドシェイクに成功しました。プロトコル: {protocol}, ポート: {port}, プロトコルバージョン: v{version}",
+    ),
+    msg(
+        "gateway.port_blocked",
+        "Blocked a request to a port that is not allowed: {port}:{protocol}",
+        "不許可なポートへのアクセス要求をブロックしました: {port}:{protocol}",
+    ),
+    msg(
+        "gateway.quota_rejected",
+        "Rejecting the connection because the traffic quota has been reached: {reason}",
+        "転送量の上限に達しているため接続を拒否します: {reason}",
+    ),
+    msg(
+        "gateway.target_connecting",
+        "Connecting to the target (127.0.0.1:{port}) over TCP...",
+        "ターゲット (127.0.0.1:{port}) への TCP 接続を試行します...",
+    ),
+    msg(
+        "gateway.target_connected",
+        "Connected to the target TCP server.",
+        "ターゲットの TCP サーバーに接続しました。",
+    ),
+    msg(
+        "gateway.target_connect_failed",
+        "Failed to connect to the target: {error}",
+        "ターゲットへの接続に失敗しました: {error}",
+    ),
+    msg(
+        "gateway.target_write_error",
+        "TCP target write error: {error}",
+        "TCP ターゲットへの書き込みエラー: {error}",
+    ),
+    msg(
+        "gateway.target_read_error",
+        "TCP target read error: {error}",
+        "TCP ターゲットからの読み取りエラー: {error}",
+    ),
+    msg(
+        "gateway.algorithm_switch_failed",
+        "Failed to switch the cipher: {error}",
+        "共通鍵暗号の切り替えに失敗しました: {error}",
+    ),
+    msg(
+        "gateway.bridge_established",
+        "Handshake completed. Secure bridge established.",
+        "ハンドシェイクが完了しました。セキュアな中継を開始します。",
+    ),
+    msg(
+        "gateway.target_disconnected",
+        "The TCP target disconnected. Closing the session.",
+        "TCP ターゲットが切断されました。セッションを終了します。",
+    ),
+    msg(
+        "gateway.closing_with_error",
+        "Closing the session due to an error: {reason}",
+        "エラーのためセッションを終了します: {reason}",
+    ),
+    msg(
+        "gateway.packet_encrypt_error",
+        "Packet encryption error: {error}",
+        "パケットの暗号化エラー: {error}",
+    ),
+    msg(
+        "gateway.rekey_error",
+        "Rekey error: {error}",
+        "共通鍵の更新エラー: {error}",
+    ),
+    msg(
+        "gateway.stats_encode_error",
+        "Stats serialization error: {error}",
+        "統計情報のシリアライズエラー: {error}",
+    ),
+    msg(
+        "gateway.session_started",
+        "WebSocket session {id} started. Waiting for a SecureConnect packet...",
+        "WebSocket セッション {id} を開始しました。SecureConnect パケットを待機しています...",
+    ),
+    msg(
+        "gateway.handshake_timeout",
+        "Handshake timed out (30s). Closing the connection.",
+        "ハンドシェイクがタイムアウトしました (30 秒)。接続を閉じます。",
+    ),
+    msg(
+        "gateway.session_stopped",
+        "WebSocket session {id} stopped. Cleaning up resources...",
+        "WebSocket セッション {id} を終了しました。リソースを解放しています...",
+    ),
+
+    msg(
+        "traffic.parse_failed",
+        "Failed to parse the traffic file: {error}",
+        "転送量ファイルの解析に失敗しました: {error}",
+    ),
+    msg(
+        "traffic.encode_failed",
+        "Failed to serialize the traffic record: {error}",
+        "転送量の記録のシリアライズに失敗しました: {error}",
+    ),
+    msg(
+        "traffic.save_failed",
+        "Could not save the traffic record ({path}): {error}",
+        "転送量の記録を保存できませんでした ({path}): {error}",
+    ),
+
+    msg(
+        "audit.encode_failed",
+        "Failed to serialize the audit record: {error}",
+        "監査記録のシリアライズに失敗しました: {error}",
+    ),
+    msg(
+        "audit.write_failed",
+        "Could not write the audit record: {error}",
+        "監査記録を書き込めませんでした: {error}",
+    ),
+
+    msg(
+        "relay.encode_failed",
+        "Failed to serialize relay message: {error}",
+        "リレーメッセージのシリアライズに失敗: {error}",
+    ),
+    msg(
+        "relay.invalid_registration_signature",
+        "The agent's registration has an invalid signature.",
+        "エージェントの登録の署名が不正です。",
+    ),
+    msg(
+        "relay.registration_invalid",
+        "Rejected agent registration: {error}",
+        "エージェントの登録を拒否しました: {error}",
+    ),
+    msg(
+        "relay.registration_failed",
+        "Registration failed.",
+        "登録に失敗しました。",
+    ),
+    msg(
+        "relay.agent_not_found",
+        "Agent not found",
+        "エージェントが見つかりません",
+    ),
+    msg(
+        "relay.agent_stream_limit",
+        "Too many streams for this agent",
+        "このエージェントのストリーム数が上限に達しています",
+    ),
+    msg(
+        "relay.agent_traffic_limit",
+        "Traffic quota exceeded for this agent",
+        "このエージェントの転送量が上限に達しています",
+    ),
+    msg(
+        "relay.agent_key_too_short",
+        "The agent key is too short ({bits} bits; at least {min} bits are required).",
+        "エージェントの鍵が短すぎます ({bits} ビット。{min} ビット以上が必要です)。",
+    ),
+    msg(
+        "relay.registration_rejected",
+        "Rejected registration of agent {agent}: {reason}",
+        "エージェント {agent} の登録を拒否しました: {reason}",
+    ),
+    msg(
+        "relay.agent_reconnected",
+        "Agent {agent} reconnected. Closing the previous connection.",
+        "エージェント {agent} が再接続しました。以前の接続を閉じます。",
+    ),
+    msg(
+        "relay.agent_registered",
+        "Registered agent {agent}.",
+        "エージェント {agent} を登録しました。",
+    ),
+    msg(
+        "relay.agent_connected",
+        "Host agent connected. Verifying its public key...",
+        "ホストエージェントが接続しました。公開鍵の確認を開始します...",
+    ),
+    msg(
+        "relay.registration_timeout",
+        "Agent registration timed out (30s). Closing the connection.",
+        "エージェントの登録がタイムアウトしました (30s)。接続を閉じます。",
+    ),
+    msg(
+        "relay.byte_quota_exceeded",
+        "Agent {agent} reached its traffic quota. Closing stream {stream}.",
+        "エージェント {agent} の転送量が上限に達したため、ストリーム {stream} を閉じます。",
+    ),
+    msg(
+        "relay.agent_disconnected",
+        "Connection of agent {agent} closed.",
+        "エージェント {agent} の接続が終了しました。",
+    ),
+    msg(
+        "relay.decode_failed",
+        "Failed to deserialize packet from agent: {error}",
+        "エージェントからのパケットのデシリアライズに失敗: {error}",
+    ),
+    msg(
+        "relay.unexpected_command",
+        "Received unexpected command from agent: {command}",
+        "エージェントから想定外のコマンドを受信しました: {command}",
+    ),
+    msg(
+        "relay.stream_started",
+        "Started relay stream {stream}.",
+        "リレーのストリーム {stream} を開始しました。",
+    ),
+    msg(
+        "relay.stream_finished",
+        "Finished relay stream {stream}.",
+        "リレーのストリーム {stream} を終了しました。",
+    ),
+    msg(
+        "relay.agent_not_allowed",
+        "Agent {agent} is not allowed.",
+        "エージェント {agent} は許可されていません。",
+    ),
+    msg(
+        "relay.too_many_agents",
+        "The maximum number of registered agents has been reached.",
+        "登録できるエージェント数の上限に達しています。",
+    ),
+
+    msg(
+        "server.agent_request",
+        "Received host agent connection request: {peer}",
+        "ホストエージェントの接続要求を受信: {peer}",
+    ),
+    msg(
+        "server.unknown_agent",
+        "Rejected connection request for unregistered agent: {agent}",
+        "未登録のエージェントへの接続要求を拒否しました: {agent}",
+    ),
+    msg(
+        "server.too_many_streams",
+        "Rejected connection request: agent {agent} has reached its stream limit.",
+        "エージェント {agent} のストリーム数が上限に達しているため、接続要求を拒否しました。",
+    ),
+    msg(
+        "server.agent_byte_quota_exceeded",
+        "Rejected connection request: agent {agent} has reached its traffic quota.",
+        "エージェント {agent} の転送量が上限に達しているため、接続要求を拒否しました。",
+    ),
+    msg(
+        "server.relay_request",
+        "Received relay request for agent {agent}: {peer}",
+        "エージェント {agent} への中継要求を受信: {peer}",
+    ),
+    msg(
+        "server.admin_auth_failed",
+        "Authentication to the admin endpoint failed: {peer}",
+        "管理用エンドポイントへの認証に失敗しました: {peer}",
+    ),
+    msg(
+        "server.gateway_starting",
+        "Starting McConnect server: {host}:{port}",
+        "McConnect サーバーを起動中: {host}:{port}",
+    ),
+    msg(
+        "server.allowed_ports",
+        "Allowed ports: {ports}",
+        "許可されたポート: {ports}",
+    ),
+    msg(
+        "server.relay_starting",
+        "Starting McConnect relay: {host}:{port}",
+        "McConnect リレーを起動中: {host}:{port}",
+    ),
+    msg("server.relay_limits", "Limits: {limits}", "上限: {limits}"),
+    msg(
+        "server.bind_failed",
+        "Failed to bind",
+        "バインドに失敗しました",
+    ),
+    msg(
+        "server.upgrade_request",
+        "Received WebSocket upgrade request: {peer}",
+        "WebSocket へのアップグレード要求を受信: {peer}",
+    ),
+
+    msg(
+        "crypto.invalid_signature_format",
+        "The signature format is invalid.",
+        "署名のフォーマットが不正です。",
+    ),
+    msg(
+        "crypto.invalid_key_length",
+        "The {cipher} key must be exactly 32 bytes.",
+        "{cipher} の鍵は正確に 32バイトである必要があります。",
+    ),
+    msg(
+        "crypto.encrypt_failed",
+        "{cipher} encryption failed: {error}",
+        "{cipher} 暗号化に失敗しました: {error}",
+    ),
+    msg(
+        "crypto.ciphertext_too_short",
+        "The ciphertext is too short and does not contain a 12-byte nonce.",
+        "暗号文が短すぎます。Nonce (12バイト) が含まれていません。",
+    ),
+    msg(
+        "crypto.decrypt_failed",
+        "{cipher} decryption failed: {error} (the data may have been tampered with)",
+        "{cipher} 復号に失敗しました: {error} (データが改ざんされている可能性があります)",
+    ),
+    msg(
+        "crypto.invalid_tag_length",
+        "The authentication tag length is invalid.",
+        "認証タグの長さが不正です。",
+    ),
+    msg(
+        "crypto.key_still_encrypted",
+        "The private key is encrypted. Decrypt it with the passphrase first.",
+        "秘密鍵が暗号化されています。先にパスフレーズで復号してください。",
+    ),
+    msg(
+        "crypto.unsupported_pem_label",
+        "Unsupported PEM label. Use PRIVATE KEY, RSA PRIVATE KEY or PUBLIC KEY.",
+        "未対応の PEM ラベルです。PRIVATE KEY / RSA PRIVATE KEY / PUBLIC KEY のいずれかを指定してください。",
+    ),
+    msg(
+        "crypto.unrecognized_key",
+        "Could not parse the data as an RSA private key (PKCS#8) or public key (SPKI).",
+        "RSA の秘密鍵 (PKCS#8) または公開鍵 (SPKI) として解釈できませんでした。",
+    ),
+    msg(
+        "crypto.unsupported_algorithm",
+        "Unsupported symmetric cipher: {name}",
+        "未対応の共通鍵暗号です: {name}",
+    ),
+    msg(
+        "crypto.unsupported_key_encryption",
+        "Unsupported key encryption scheme (kdf: {kdf}, cipher: {cipher})",
+        "未対応の鍵暗号化方式です (kdf: {kdf}, cipher: {cipher})",
+    ),
+    msg(
+        "crypto.invalid_argon2_params",
+        "Invalid Argon2 parameters: {error}",
+        "Argon2 のパラメータが不正です: {error}",
+    ),
+    msg(
+        "crypto.key_derivation_failed",
+        "Failed to derive a key from the passphrase: {error}",
+        "パスフレーズからの鍵導出に失敗しました: {error}",
+    ),
+    msg(
+        "crypto.key_already_encrypted",
+        "The private key is already encrypted.",
+        "秘密鍵は既に暗号化されています。",
+    ),
+    msg(
+        "crypto.empty_passphrase",
+        "The passphrase is empty.",
+        "パスフレーズが空です。",
+    ),
+    msg(
+        "crypto.key_decrypt_failed",
+        "Failed to decrypt the private key. The passphrase is wrong or the config file is corrupted.",
+        "秘密鍵の復号に失敗しました。パスフレーズが正しくないか、設定ファイルが破損しています。",
+    ),
+
+    msg(
+        "handshake.server_starting",
+        "Starting server-side handshake...",
+        "サーバー側ハンドシェイクを開始します...",
+    ),
+    msg(
+        "handshake.server_failed",
+        "Secure handshake failed: {error}",
+        "セキュアハンドシェイクに失敗しました: {error}",
+    ),
+    msg(
+        "handshake.not_secure_connect",
+        "The initial packet is not SecureConnect: {command}",
+        "初期パケットが SecureConnect ではありません: {command}",
+    ),
+    msg(
+        "handshake.invalid_payload",
+        "Failed to parse the SecureConnect payload: {error}",
+        "SecureConnect ペイロードの解析に失敗しました: {error}",
+    ),
+    msg(
+        "handshake.unsupported_key_wrap",
+        "Unsupported key wrap padding: {name}",
+        "未対応のパディング方式です: {name}",
+    ),
+    msg(
+        "handshake.legacy_key_wrap_rejected",
+        "Session keys wrapped with PKCS#1 v1.5 are not accepted (enable allow_legacy_key_wrap to allow old clients).",
+        "PKCS#1 v1.5 でラップされた共通鍵は受け付けません (旧クライアントを許可するには allow_legacy_key_wrap を有効にしてください)。",
+    ),
+    msg(
+        "handshake.decrypting_key",
+        "Decrypting session key ({algorithm}, {wrap})...",
+        "共通鍵 ({algorithm}, {wrap}) を復号中...",
+    ),
+    msg(
+        "handshake.key_decrypt_failed",
+        "Failed to decrypt the session key. The public and private keys may not match: {error}",
+        "共通鍵の復号に失敗しました。公開鍵・秘密鍵のペアが一致していない可能性があります: {error}",
+    ),
+    msg(
+        "handshake.retired_key_used",
+        "Handshake used a retiring key. Notifying the client of the new public key.",
+        "退役予定の旧鍵でハンドシェイクされました。クライアントに新しい公開鍵を通知します。",
+    ),
+    msg(
+        "handshake.server_completed",
+        "Server-side handshake completed: {protocol}:{port} (protocol v{version})",
+        "サーバー側ハンドシェイク完了: {protocol}:{port} (プロトコル v{version})",
+    ),
+    msg(
+        "handshake.no_common_algorithm",
+        "No symmetric cipher is shared with the client.",
+        "クライアントと共通の共通鍵暗号がありません。",
+    ),
+    msg(
+        "handshake.session_algorithm",
+        "Session cipher: {algorithm}",
+        "セッションの共通鍵暗号: {algorithm}",
+    ),
+    msg(
+        "handshake.invalid_rotation_signature",
+        "The key rotation notice has an invalid signature. The new public key will not be used.",
+        "鍵ローテーション通知の署名が不正です。新しい公開鍵は採用しません。",
+    ),
+    msg(
+        "handshake.invalid_challenge_length",
+        "The challenge must be 32 bytes long (got {len} bytes).",
+        "チャレンジは 32 バイトである必要があります ({len} バイトでした)。",
+    ),
+    msg(
+        "handshake.invalid_server_key_signature",
+        "The public key presented by the gateway has an invalid signature.",
+        "ゲートウェイが提示した公開鍵の署名が不正です。",
+    ),
+    msg(
+        "handshake.client_building",
+        "Building client-side handshake packet (Port: {port}, Protocol: {protocol})...",
+        "クライアント側ハンドシェイクパケットを生成中 (Port: {port}, Protocol: {protocol})...",
+    ),
+    msg(
+        "handshake.generating_key",
+        "Generating a random session key...",
+        "ランダムな共通鍵を生成中...",
+    ),
+    msg(
+        "handshake.wrapping_key",
+        "Encrypting the session key with the server public key (RSA-OAEP)...",
+        "サーバーの公開鍵を使用して共通鍵を暗号化中 (RSA-OAEP)...",
+    ),
+    msg(
+        "handshake.wrap_failed",
+        "Failed to encrypt the session key: {error}",
+        "共通鍵の暗号化に失敗しました: {error}",
+    ),
+    msg(
+        "handshake.building_message",
+        "Building handshake message...",
+        "ハンドシェイクメッセージを構築中...",
+    ),
+    msg(
+        "handshake.encode_failed",
+        "Failed to serialize the handshake packet: {error}",
+        "ハンドシェイクパケットのシリアライズに失敗: {error}",
+    ),
+    msg(
+        "handshake.build_failed",
+        "Failed to build the handshake packet: {error}",
+        "ハンドシェイクパケットの生成に失敗しました: {error}",
+    ),
+    msg(
+        "handshake.client_ready",
+        "Client-side handshake ready.",
+        "クライアント側ハンドシェイク準備完了。",
+    ),
+
+    msg(
+        "session.key_derivation_failed",
+        "Key derivation failed: {error}",
+        "鍵の導出に失敗しました: {error}",
+    ),
+    msg(
+        "session.switch_before_handshake",
+        "Cannot switch the symmetric cipher before the handshake.",
+        "ハンドシェイク前は共通鍵暗号を切り替えられません。",
+    ),
+    msg(
+        "session.algorithm_switched",
+        "Switched the symmetric cipher to {algorithm}.",
+        "共通鍵暗号を {algorithm} に切り替えました。",
+    ),
+    msg(
+        "session.nonce_exhausted",
+        "The nonce counter has reached its limit.",
+        "Nonce のカウンターが上限に達しました。",
+    ),
+    msg(
+        "session.replay_detected",
+        "Detected a replayed or reordered message (expected: {expected}, received: {received})",
+        "メッセージのリプレイまたは順序の入れ替えを検出しました (期待: {expected}, 受信: {received})",
+    ),
+    msg(
+        "session.invalid_nonce",
+        "The message nonce is invalid.",
+        "メッセージの Nonce が不正です。",
+    ),
+    msg(
+        "session.generation_mismatch",
+        "Key generation mismatch (expected: {expected}, received: {received})",
+        "鍵の世代が一致しません (期待: {expected}, 受信: {received})",
+    ),
+    msg(
+        "session.peer_rekeyed",
+        "The peer rotated its sending key (generation: {generation})",
+        "相手の送信鍵が更新されました (世代: {generation})",
+    ),
+    msg(
+        "session.send_before_handshake",
+        "Cannot send a Data frame before the handshake.",
+        "ハンドシェイク前は Data フレームを送信できません。",
+    ),
+    msg(
+        "session.recv_before_handshake",
+        "Received a Data frame before the handshake.",
+        "ハンドシェイク前に Data フレームを受信しました。",
+    ),
+    msg(
+        "session.rekey_before_handshake",
+        "Cannot rotate keys before the handshake.",
+        "ハンドシェイク前は鍵を更新できません。",
+    ),
+    msg(
+        "session.rekeyed",
+        "Rotated the sending key (generation: {generation})",
+        "送信鍵を更新しました (世代: {generation})",
+    ),
+
+    msg(
+        "frame.not_data_frame",
+        "Not a Data frame.",
+        "Data フレームではありません。",
+    ),
+    msg(
+        "frame.too_short",
+        "The Data frame is too short.",
+        "Data フレームが短すぎます。",
+    ),
+
+    msg(
+        "known_servers.parse_failed",
+        "Failed to parse the known servers file: {error}",
+        "既知サーバーファイルの解析に失敗しました: {error}",
+    ),
+    msg(
+        "known_servers.fingerprint_mismatch",
+        "The public key of {url} does not match the recorded one (recorded: {known}, presented: {presented}). Refusing to connect because the server may be impersonated.",
+        "{url} の公開鍵が記録と一致しません (記録: {known}, 提示: {presented})。なりすましの可能性があるため接続を拒否します。",
+    ),
+
+    msg(
+        "proxy.socks_no_auth_unsupported",
+        "The SOCKS5 client does not support the no-authentication method.",
+        "SOCKS5 クライアントが認証なしに対応していません。",
+    ),
+    msg(
+        "proxy.socks_bad_version",
+        "Invalid SOCKS version: {version}",
+        "SOCKS のバージョンが不正です: {version}",
+    ),
+    msg(
+        "proxy.socks_bad_address_type",
+        "Unsupported SOCKS address type: {atyp}",
+        "未対応の SOCKS アドレス形式です: {atyp}",
+    ),
+    msg(
+        "proxy.socks_bad_command",
+        "Unsupported SOCKS command: {command}",
+        "未対応の SOCKS コマンドです: {command}",
+    ),
+    msg(
+        "proxy.http_header_too_large",
+        "The HTTP request header is too large.",
+        "HTTP リクエストヘッダーが大きすぎます。",
+    ),
+    msg(
+        "proxy.http_bad_method",
+        "HTTP method other than CONNECT: {method}",
+        "CONNECT 以外の HTTP メソッドです: {method}",
+    ),
+    msg(
+        "proxy.http_bad_target",
+        "Invalid CONNECT target: {request}",
+        "CONNECT の宛先が不正です: {request}",
+    ),
+    msg(
+        "proxy.request_timeout",
+        "The proxy request was not received within {seconds} seconds.",
+        "{seconds} 秒以内にプロキシの接続要求を受信できませんでした。",
+    ),
+    msg(
+        "proxy.request",
+        "{kind} requested a connection to {host}:{port}.",
+        "{kind} で {host}:{port} への接続が要求されました。",
+    ),
+    msg(
+        "proxy.port_not_allowed",
+        "Port {port} is not allowed by the gateway.",
+        "ポート {port} はゲートウェイで許可されていません。",
+    ),
+    msg(
+        "proxy.port_rejected",
+        "Port not allowed: {port}",
+        "許可されていないポートです: {port}",
+    ),
+
+    msg(
+        "tunnel.invalid_url",
+        "Failed to parse the URL: {error}",
+        "URLの解析に失敗しました: {error}",
+    ),
+    msg(
+        "tunnel.connecting",
+        "Opening WebSocket connection: {url}",
+        "WebSocket 接続を開始します: {url}",
+    ),
+    msg(
+        "tunnel.ws_connect_failed",
+        "WebSocket connection failed: {error}",
+        "WebSocket 接続自体に失敗しました: {error}",
+    ),
+    msg(
+        "tunnel.ws_connected",
+        "WebSocket connection established.",
+        "WebSocket 接続が確立されました。",
+    ),
+    msg(
+        "tunnel.handshake_starting",
+        "Starting secure handshake (Target Port: {port}, Protocol: {protocol})",
+        "セキュアハンドシェイクを開始します (Target Port: {port}, Protocol: {protocol})",
+    ),
+    msg(
+        "tunnel.sending_handshake",
+        "Sending SecureConnect packet...",
+        "SecureConnect パケットを送信します...",
+    ),
+    msg(
+        "tunnel.send_failed",
+        "Failed to send the packet: {error}",
+        "パケットの送信に失敗しました: {error}",
+    ),
+    msg(
+        "tunnel.waiting_response",
+        "Waiting for the gateway response...",
+        "ゲートウェイからの応答を待機中...",
+    ),
+    msg(
+        "tunnel.response_decode_failed",
+        "Failed to decode the response message: {error}",
+        "応答メッセージのデコードに失敗: {error}",
+    ),
+    msg(
+        "tunnel.response_received",
+        "Received the response packet. Decrypting...",
+        "応答パケットを受信しました。復号を試みます...",
+    ),
+    msg(
+        "tunnel.response_decrypt_failed",
+        "Failed to decrypt the response message: {error}",
+        "応答メッセージの復号に失敗: {error}",
+    ),
+    msg(
+        "tunnel.response_payload_invalid",
+        "Failed to deserialize the ConnectResponse payload: {error}",
+        "ConnectResponse ペイロードのデシリアライズに失敗: {error}",
+    ),
+    msg(
+        "tunnel.rejected",
+        "The gateway rejected the connection: {message}",
+        "ゲートウェイが接続を拒否しました: {message}",
+    ),
+    msg(
+        "tunnel.algorithm_unusable",
+        "Cannot apply the cipher selected by the gateway: {error}",
+        "ゲートウェイが選んだ共通鍵暗号を適用できません: {error}",
+    ),
+    msg(
+        "tunnel.established",
+        "Secure handshake succeeded. The encrypted tunnel is active (protocol v{version}, {algorithm}).",
+        "セキュアハンドシェイクに成功しました。暗号化トンネルが有効です (プロトコル v{version}, {algorithm})。",
+    ),
+    msg(
+        "tunnel.unexpected_response",
+        "Protocol error: received a packet other than ConnectResponse: {command}",
+        "プロトコルエラー: ConnectResponse 以外のパケットを受信しました: {command}",
+    ),
+    msg(
+        "tunnel.expected_connect_response",
+        "Expected ConnectResponse after SecureConnect",
+        "SecureConnect の後に ConnectResponse を受信しませんでした",
+    ),
+    msg(
+        "tunnel.ws_error",
+        "WebSocket error: {error}",
+        "WebSocket でエラーが発生しました: {error}",
+    ),
+    msg(
+        "tunnel.closed_during_handshake",
+        "The server closed the connection during the handshake.",
+        "ハンドシェイク中にサーバーによって接続が閉じられました。",
+    ),
+    msg(
+        "tunnel.tcp_read_error",
+        "TCP read error: {error}",
+        "TCP の読み込みエラー: {error}",
+    ),
+    msg(
+        "tunnel.decrypt_error",
+        "Packet decryption error: {error}. Closing tunnel.",
+        "パケットの復号エラー: {error}。トンネルを閉じます。",
+    ),
+    msg(
+        "tunnel.tcp_write_error",
+        "TCP write error: {error}",
+        "TCP の書き込みエラー: {error}",
+    ),
+    msg(
+        "tunnel.gateway_disconnect",
+        "Gateway requested disconnection from secure tunnel.",
+        "ゲートウェイがセキュアトンネルの切断を要求しました。",
+    ),
+    msg(
+        "tunnel.gateway_rekeyed",
+        "Gateway rotated its session key.",
+        "ゲートウェイがセッション鍵を更新しました。",
+    ),
+    msg(
+        "tunnel.unknown_command",
+        "Ignoring unknown command from gateway.",
+        "ゲートウェイからの未知のコマンドを無視します。",
+    ),
+    msg(
+        "tunnel.ws_read_error",
+        "WebSocket read error: {error}",
+        "WebSocket の読み込みエラー: {error}",
+    ),
+    msg(
+        "tunnel.encrypt_error",
+        "Packet encryption error: {error}",
+        "パケットの暗号化エラー: {error}",
+    ),
+    msg(
+        "tunnel.ws_send_error",
+        "WebSocket send error: {error}",
+        "WebSocket の送信エラー: {error}",
+    ),
+    msg(
+        "tunnel.rekey_error",
+        "Rekey error: {error}",
+        "鍵の更新エラー: {error}",
+    ),
+    msg(
+        "tunnel.closing_connection",
+        "Closing connection {id} on request.",
+        "要求に応じて接続 {id} を閉じます。",
+    ),
+    msg(
+        "tunnel.closed",
+        "Secure tunnel session closed.",
+        "セキュアトンネルのセッションが終了しました。",
+    ),
+
+    msg(
+        "client.checking_gateway",
+        "Checking secure connection to the gateway: {url}...",
+        "ゲートウェイへのセキュア接続を確認中: {url}...",
+    ),
+    msg(
+        "client.gateway_ready",
+        "Secure handshake with the gateway succeeded. Ready.",
+        "ゲートウェイとのセキュアハンドシェイクに成功しました。準備完了です。",
+    ),
+    msg(
+        "client.listener_started",
+        "Started TCP listener: {host}:{port}",
+        "TCP リスナーを開始しました: {host}:{port}",
+    ),
+    msg(
+        "client.proxy_started",
+        "Started SOCKS5 / HTTP CONNECT proxy: {host}:{port}",
+        "SOCKS5 / HTTP CONNECT プロキシを開始しました: {host}:{port}",
+    ),
+    msg(
+        "client.new_connection",
+        "New local connection: {addr}",
+        "新規ローカル接続: {addr}",
+    ),
+    msg(
+        "client.session_failed",
+        "Tunnel session ended abnormally ({addr}): {error}",
+        "トンネルセッションが異常終了しました ({addr}): {error}",
+    ),
+    msg(
+        "client.accept_error",
+        "TCP accept error: {error}",
+        "TCP accept エラー: {error}",
+    ),
+    msg(
+        "client.check_starting",
+        "Starting connection test to the gateway: {url}",
+        "ゲートウェイへの接続テストを開始します: {url}",
+    ),
+    msg(
+        "client.invalid_url",
+        "Failed to parse the URL ({url}): {error}",
+        "URLの解析に失敗しました ({url}): {error}",
+    ),
+    msg(
+        "client.ws_connecting",
+        "Connecting WebSocket...",
+        "WebSocket 接続中...",
+    ),
+    msg(
+        "client.ws_connected",
+        "WebSocket connected. Trying the secure handshake...",
+        "WebSocket 接続に成功しました。セキュアハンドシェイクを試行します...",
+    ),
+    msg(
+        "client.response_received",
+        "Received response data ({bytes} bytes). Decrypting...",
+        "応答データを受信しました ({bytes} bytes)。復号中...",
+    ),
+    msg(
+        "client.check_succeeded",
+        "Secure connection test succeeded (protocol v{version}, {algorithm}).",
+        "セキュア接続テストに成功しました (プロトコル v{version}, {algorithm})。",
+    ),
+    msg(
+        "client.key_rotation_received",
+        "The gateway announced a new public key. Verifying its signature...",
+        "ゲートウェイから新しい公開鍵が通知されました。署名を検証します...",
+    ),
+    msg(
+        "client.key_rotation_verified",
+        "Verified the signature of the new public key.",
+        "新しい公開鍵の署名を確認しました。",
+    ),
+    msg(
+        "client.fetching_info",
+        "Fetching server info: {url}",
+        "サーバー情報を取得しています: {url}",
+    ),
+    msg(
+        "client.sending_info_request",
+        "Sending GetServerInfo packet...",
+        "GetServerInfo パケットを送信中...",
+    ),
+    msg(
+        "client.server_key_verified",
+        "Verified the gateway public key: {fingerprint}",
+        "ゲートウェイの公開鍵を確認しました: {fingerprint}",
+    ),
+    msg(
+        "client.info_received",
+        "Fetched server info successfully.",
+        "サーバー情報を正常に取得しました。",
+    ),
+    msg(
+        "client.info_failed",
+        "Failed to fetch server info.",
+        "サーバー情報の取得に失敗しました。",
+    ),
+    msg(
+        "client.no_signed_key",
+        "The gateway did not present a signed public key. It may be an old version without TOFU support.",
+        "ゲートウェイが署名付きの公開鍵を提示しませんでした。TOFU に対応していない古いバージョンの可能性があります。",
+    ),
+    msg(
+        "client.first_use",
+        "Connecting to this gateway for the first time. Recorded its public key: {fingerprint}",
+        "初めて接続するゲートウェイです。公開鍵を記録しました: {fingerprint}",
+    ),
+    msg(
+        "client.key_trusted",
+        "The public key matches the recorded one.",
+        "記録済みの公開鍵と一致しました。",
+    ),
+    msg(
+        "client.known_key_rotated",
+        "The gateway rotated its key. Verified with the recorded key and updated the record: {old} -> {new}",
+        "ゲートウェイが鍵をローテーションしました。記録済みの鍵で検証し、記録を更新しました: {old} -> {new}",
+    ),
+    msg(
+        "client.rotation_not_confirmed",
+        "Could not confirm a key rotation signed by the recorded key: {error}",
+        "記録済みの鍵で署名された鍵ローテーションを確認できませんでした: {error}",
+    ),
+    msg(
+        "client.rotation_no_port",
+        "The gateway allows no ports to perform a handshake with.",
+        "ハンドシェイクに使用できる許可ポートがゲートウェイにありません。",
+    ),
+    msg(
+        "client.rotation_key_differs",
+        "The rotation notice names a different key: {rotated}",
+        "ローテーション通知が別の鍵を指しています: {rotated}",
+    ),
+    msg(
+        "client.rotation_missing",
+        "The gateway accepted the recorded key without a rotation notice.",
+        "ゲートウェイは記録済みの鍵をローテーション通知なしで受け付けました。",
+    ),
+
+    msg(
+        "agent.gateway_started",
+        "Started local gateway: {url}",
+        "ローカルゲートウェイを起動しました: {url}",
+    ),
+    msg(
+        "agent.relay_closed",
+        "Connection to the relay closed.",
+        "リレーとの接続が終了しました。",
+    ),
+    msg(
+        "agent.relay_error",
+        "Error while communicating with the relay: {error}",
+        "リレーとの通信でエラーが発生しました: {error}",
+    ),
+    msg(
+        "agent.reconnecting",
+        "Reconnecting to the relay in {seconds} seconds...",
+        "{seconds} 秒後にリレーへ再接続します...",
+    ),
+    msg(
+        "agent.connecting",
+        "Connecting to the relay: {url}",
+        "リレーに接続しています: {url}",
+    ),
+    msg(
+        "agent.unexpected_command",
+        "Received unexpected command from the relay: {command}",
+        "リレーから想定外のコマンドを受信しました: {command}",
+    ),
+    msg(
+        "agent.registration_failed",
+        "Failed to register with the relay: {message}",
+        "リレーへの登録に失敗しました: {message}",
+    ),
+    msg(
+        "agent.registered",
+        "Registered with the relay. Agent ID: {agent}",
+        "リレーに登録しました。エージェント ID: {agent}",
+    ),
+    msg(
+        "agent.client_url",
+        "Clients connect to: {url}",
+        "クライアントの接続先: {url}",
+    ),
+    msg(
+        "agent.decode_failed",
+        "Failed to deserialize packet from the relay: {error}",
+        "リレーからのパケットのデシリアライズに失敗: {error}",
+    ),
+    msg(
+        "agent.closed_during_registration",
+        "The relay closed the connection during registration.",
+        "登録中にリレーによって接続が閉じられました。",
+    ),
+    msg(
+        "agent.gateway_send_failed",
+        "Failed to send to the local gateway: {error}",
+        "ローカルゲートウェイへの送信に失敗しました: {error}",
+    ),
+    msg(
+        "agent.gateway_recv_failed",
+        "Failed to receive from the local gateway: {error}",
+        "ローカルゲートウェイからの受信に失敗しました: {error}",
+    ),
+    msg(
+        "agent.gateway_connect_failed",
+        "Failed to connect to the local gateway: {error}",
+        "ローカルゲートウェイへの接続に失敗しました: {error}",
+    ),
+
+    msg(
+        "error.invalid_url",
+        "Invalid gateway URL: {detail}",
+        "ゲートウェイの URL が不正です: {detail}",
+    ),
+    msg(
+        "error.ws_connect_failed",
+        "WebSocket connection to the gateway failed: {detail}",
+        "ゲートウェイへの WebSocket 接続に失敗しました: {detail}",
+    ),
+    msg(
+        "error.handshake_rejected",
+        "The gateway rejected the connection ({code}): {detail}",
+        "ゲートウェイが接続を拒否しました ({code}): {detail}",
+    ),
+    msg(
+        "error.port_not_allowed",
+        "Port not allowed by the gateway: {detail}",
+        "ゲートウェイで許可されていないポートです: {detail}",
+    ),
+    msg(
+        "error.target_unreachable",
+        "The gateway cannot reach the target: {detail}",
+        "ゲートウェイからターゲットへ接続できません: {detail}",
+    ),
+    msg(
+        "error.decrypt_failed",
+        "Failed to decrypt a packet: {detail}",
+        "パケットの復号に失敗しました: {detail}",
+    ),
+    msg(
+        "error.protocol_mismatch",
+        "Protocol mismatch: {detail}",
+        "プロトコルが一致しません: {detail}",
+    ),
+
+    msg(
+        "export.unsupported_url",
+        "{url} is not a ws:// or wss:// URL",
+        "{url} は ws:// または wss:// の URL ではありません",
+    ),
+    msg(
+        "export.public_key_invalid",
+        "Invalid public key: {error}",
+        "公開鍵が不正です: {error}",
+    ),
+    msg(
+        "export.no_mappings",
+        "The config contains no mappings.",
+        "設定にマッピングがありません。",
+    ),
+    msg(
+        "export.invalid_port",
+        "Invalid port in mappings: {port}",
+        "マッピングのポートが不正です: {port}",
+    ),
+];
@@ -0,0 +1,55 @@
+//! メッセージカタログ
+//!
+//! キーは `領域.名前` の形式で、領域ごとにファイルを分けています。
+//! テンプレートの `{name}` は、英語と日本語で同じ名前を使用してください。
+
+mod app;
+mod cli;
+mod mc_core;
+
+use super::Locale;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// [Entry]
+/// 1 つのメッセージの英語と日本語のテンプレートです。
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub key: &'static str,
+    pub en: &'static str,
+    pub ja: &'static str,
+}
+
+impl Entry {
+    /// 指定した言語のテンプレートを返します。
+    pub fn text(&self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::En => self.en,
+            Locale::Ja => self.ja,
+        }
+    }
+}
+
+/// カタログの項目を作成します。
+const fn msg(key: &'static str, en: &'static str, ja: &'static str) -> Entry {
+    Entry { key, en, ja }
+}
+
+/// [entries]
+/// カタログのすべての項目を返します。
+pub fn entries() -> impl Iterator<Item = &'static Entry> {
+    mc_core::MESSAGES
+        .iter()
+        .chain(cli::MESSAGES)
+        .chain(app::MESSAGES)
+}
+
+/// [lookup]
+/// キーに対応する項目を返します。
+pub fn lookup(key: &str) -> Option<&'static Entry> {
+    static INDEX: OnceLock<HashMap<&'static str, &'static Entry>> = OnceLock::new();
+    INDEX
+        .get_or_init(|| entries().map(|entry| (entry.key, entry)).collect())
+        .get(key)
+        .copied()
+}
@@ -0,0 +1,140 @@
+//! ユーザー向けメッセージの翻訳 (英語 / 日本語)
+//!
+//! メッセージはキーで [catalog] から引き、`{name}` の形式の引数を埋め込んで表示します。
+//! コア・CLI・アプリのメッセージはすべてこのカタログで管理し、[t!](crate::t) マクロで取得します。
+//!
+//! 表示する言語はプロセス全体で 1 つで、[set_locale] で切り替えます。
+//! CLI は `--lang` か環境変数 `LANG` から、アプリは設定 (`AppSettings`) から設定します。
+
+pub mod catalog;
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 現在の言語 ([Locale] の判別値)
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+/// [Locale]
+/// メッセージを表示する言語です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// 英語 (既定)
+    #[default]
+    En = 0,
+    /// 日本語
+    Ja = 1,
+}
+
+impl Locale {
+    /// [parse]
+    /// `ja`, `ja_JP.UTF-8`, `en-US` などの表記から言語を判定します。対応していない言語の場合は None を返します。
+    pub fn parse(value: &str) -> Option<Self> {
+        let lang = value
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match lang.as_str() {
+            "ja" => Some(Self::Ja),
+            "en" | "c" | "posix" => Some(Self::En),
+            _ => None,
+        }
+    }
+
+    /// [from_env]
+    /// 環境変数 `LC_ALL`, `LC_MESSAGES`, `LANG` の順に確認し、最初に設定されている値から言語を判定します。
+    /// 対応していない言語や、いずれも設定されていない場合は英語を返します。
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    /// [code]
+    /// 言語コード (`en` / `ja`) を返します。
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Ja => "ja",
+        }
+    }
+}
+
+/// [set_locale]
+/// プロセス全体の表示言語を設定します。
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+/// [locale]
+/// 現在の表示言語を返します。
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::Ja,
+        _ => Locale::En,
+    }
+}
+
+/// [tr]
+/// 現在の言語でメッセージを取得します。通常は [t!](crate::t) マクロから呼び出します。
+pub fn tr(key: &str, args: &[(&str, String)]) -> String {
+    tr_in(locale(), key, args)
+}
+
+/// [tr_in]
+/// 指定した言語でメッセージを取得します。カタログにないキーの場合はキーをそのまま返します。
+///
+/// ゲートウェイがクライアントへ送るメッセージなど、表示言語によらず英語で残したい場合に使用します。
+pub fn tr_in(locale: Locale, key: &str, args: &[(&str, String)]) -> String {
+    match catalog::lookup(key) {
+        Some(entry) => render(entry.text(locale), args),
+        None => {
+            log::warn!("Missing message in catalog: {}", key);
+            key.to_string()
+        }
+    }
+}
+
+/// [render]
+/// テンプレート中の `{name}` を引数の値で置き換えます。対応する引数がない部分はそのまま残します。
+pub fn render(template: &str, args: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter().find(|(n, _)| *n == name).map(|(_, v)| (v, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// [t!]
+/// 現在の言語でカタログのメッセージを取得します。
+///
+/// ```ignore
+/// t!("gateway.client_closed", reason = format!("{:?}", reason))
+/// ```
+#[macro_export]
+macro_rules! t {
+    ($key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::tr($key, &[$((stringify!($name), ($value).to_string())),*])
+    };
+}
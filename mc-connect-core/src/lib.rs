@@ -6,10 +6,17 @@ pub mod controllers;
 pub mod services;
 pub mod models;
 pub mod encryption;
+pub mod error;
+pub mod i18n;
+pub mod time;
+pub mod fs;
 
 // 主要な機能を外部に再公開
-pub use controllers::start_server;
+pub use error::McConnectError;
+pub use models::packet::ErrorCode;
+pub use controllers::{GatewayOptions, RelayOptions, bind_relay, bind_server, start_relay, start_server};
 pub use services::ws_client::WsClientService;
+pub use services::host_agent::HostAgentService;
 
 // ネットワーク処理の低レイヤーモジュール
 pub mod tcp;
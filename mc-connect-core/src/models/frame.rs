@@ -0,0 +1,122 @@
+//! Data 転送用のコンパクトなバイナリフレーム
+//!
+//! 制御メッセージは MessagePack の [Message](super::packet::Message) で送りますが、
+//! 転送量の大半を占める Data はシリアライズを省いた次の形式で送ります。
+//!
+//! ```text
+//! +------+----------------+---------------------------+----------+
+//! | type | counter (u64)  | ciphertext                | tag      |
+//! | 1 B  | 8 B big endian | payload と同じ長さ         | 16 B     |
+//! +------+----------------+---------------------------+----------+
+//! ```
+//!
+//! Nonce は送信方向ごとのプレフィックス (4 バイト) とカウンターから組み立てるため、
+//! フレームにはカウンターのみを載せます。先頭 9 バイトのヘッダーは追加認証データ (AAD) として認証されます。
+//! MessagePack の `Message` は常に配列 (0x90 台) で始まるため、先頭 1 バイトで区別できます。
+//!
+//! 双方が [capability::COMPACT_DATA](super::packet::capability::COMPACT_DATA) をサポートする場合のみ使用します。
+
+use crate::encryption::{NONCE_LEN, TAG_LEN};
+use crate::t;
+
+/// Data フレームの種別
+pub const FRAME_DATA: u8 = 0x01;
+
+/// フレームヘッダー (種別 + カウンター) の長さ
+pub const FRAME_HEADER_LEN: usize = 1 + 8;
+
+/// [Direction]
+/// 暗号化メッセージの送信方向。
+/// 双方が同じ共通鍵でカウンターを 0 から数えるため、Nonce の先頭に方向を含めて衝突を防ぎます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    /// クライアントからゲートウェイへ
+    #[default]
+    ClientToServer,
+    /// ゲートウェイからクライアントへ
+    ServerToClient,
+}
+
+impl Direction {
+    /// 逆方向 (相手が送信する方向) を返します。
+    pub fn reverse(self) -> Self {
+        match self {
+            Self::ClientToServer => Self::ServerToClient,
+            Self::ServerToClient => Self::ClientToServer,
+        }
+    }
+
+    /// カウンターから、この方向の Nonce を組み立てます。
+    pub fn nonce(self, counter: u64) -> [u8; NONCE_LEN] {
+        let prefix: &[u8; 4] = match self {
+            Self::ClientToServer => b"c2s\0",
+            Self::ServerToClient => b"s2c\0",
+        };
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..4].copy_from_slice(prefix);
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
+
+    /// Nonce がこの方向のものであれば、そのカウンターを返します。
+    /// 旧実装のランダムな Nonce など、この方向のものでない場合は None を返します。
+    pub fn counter_of(self, nonce: &[u8]) -> Option<u64> {
+        let nonce: &[u8; NONCE_LEN] = nonce.try_into().ok()?;
+        let expected = self.nonce(0);
+        if nonce[..4] != expected[..4] {
+            return None;
+        }
+        Some(u64::from_be_bytes(nonce[4..].try_into().unwrap()))
+    }
+}
+
+/// 受信したバイナリが Data フレームかどうかを判定します。
+pub fn is_data_frame(bin: &[u8]) -> bool {
+    bin.first() == Some(&FRAME_DATA)
+}
+
+/// [DataFrame]
+/// 受信した Data フレームを各部分に分割したものです。
+pub struct DataFrame<'a> {
+    /// 送信側のカウンター
+    pub counter: u64,
+    /// AAD として認証されるヘッダー
+    pub header: &'a [u8],
+    /// 暗号文
+    pub ciphertext: &'a [u8],
+    /// 認証タグ
+    pub tag: &'a [u8],
+}
+
+impl<'a> DataFrame<'a> {
+    /// [parse]
+    /// Data フレームを分割します。長さや種別が不正な場合はエラーになります。
+    pub fn parse(bin: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if !is_data_frame(bin) {
+            return Err(t!("frame.not_data_frame").into());
+        }
+        if bin.len() < FRAME_HEADER_LEN + TAG_LEN {
+            return Err(t!("frame.too_short").into());
+        }
+        let (header, rest) = bin.split_at(FRAME_HEADER_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let counter = u64::from_be_bytes(header[1..].try_into().unwrap());
+        Ok(Self {
+            counter,
+            header,
+            ciphertext,
+            tag,
+        })
+    }
+}
+
+/// [new_data_frame]
+/// ヘッダーと平文を書き込んだ送信用のバッファを作成します。
+/// 認証タグの分も含めて 1 回の確保で済むよう、容量を確保しておきます。
+pub fn new_data_frame(counter: u64, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len() + TAG_LEN);
+    frame.push(FRAME_DATA);
+    frame.extend_from_slice(&counter.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
@@ -1 +1,3 @@
 pub mod packet;
+pub mod frame;
+pub mod relay;
@@ -1,4 +1,41 @@
+use base64::{Engine as _, engine::general_purpose};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::encryption::{CryptoError, RsaPublicKey};
+use crate::error::McConnectError;
+use crate::i18n::{self, Locale};
+use crate::t;
+
+/// 現在のプロトコルバージョン。
+/// バージョン情報を送ってこない旧実装 (0.1.0 以前) はバージョン 0 として扱います。
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// ハンドシェイクで交換する機能 (capability) の識別子
+pub mod capability {
+    /// 旧鍵での接続時に ConnectResponse で新しい公開鍵を通知できる
+    pub const KEY_ROTATION: &str = "key-rotation";
+    /// GetServerInfo のチャレンジに対して署名付きの公開鍵を返せる
+    pub const SIGNED_SERVER_KEY: &str = "signed-server-key";
+    /// Data をコンパクトなバイナリフレーム ([crate::models::frame]) で送受信できる
+    pub const COMPACT_DATA: &str = "compact-data";
+    /// Nonce を送信方向ごとの連番で払い出し、受信側でリプレイや順序の入れ替えを拒否できる
+    pub const SEQUENCE_NONCE: &str = "sequence-nonce";
+    /// `Command::Rekey` による共通鍵の更新に対応している
+    pub const REKEY: &str = "rekey";
+    /// ゲートウェイが記録したセッションの統計情報を `Command::Stats` で定期的に受け取れる
+    pub const SESSION_STATS: &str = "session-stats";
+
+    /// この実装がサポートする機能の一覧
+    pub const SUPPORTED: &[&str] = &[
+        KEY_ROTATION,
+        SIGNED_SERVER_KEY,
+        COMPACT_DATA,
+        SEQUENCE_NONCE,
+        REKEY,
+        SESSION_STATS,
+    ];
+}
 
 /// 通信プロトコルの種類を定義します。
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -37,10 +74,30 @@ pub enum Command {
     /// セキュア接続初期化要求 (Client -> Server)
     /// 公開鍵で暗号化された共通鍵を含みます。
     SecureConnect,
+    /// 共通鍵の更新通知 (双方向)
+    /// 送信側はこのメッセージ以降、次の世代の鍵で暗号化します。
+    Rekey,
+    /// リレー上のストリームの開始通知 (Relay -> Agent)
+    /// クライアントがリレーに接続したことをホストエージェントへ伝えます。ペイロードは [crate::models::relay::RelayStreamPayload] です。
+    RelayOpen,
+    /// リレー上のストリームの終了通知 (双方向)
+    /// クライアント、またはエージェント側のセッションが切断されたことを伝えます。
+    RelayClose,
+    /// エージェント登録のチャレンジ (Relay -> Agent)
+    /// ペイロードは [crate::models::relay::AgentChallengePayload] です。
+    AgentChallenge,
+    /// エージェントの登録要求 (Agent -> Relay)
+    /// ペイロードは [crate::models::relay::AgentRegisterPayload] です。
+    AgentRegister,
+    /// 未知のコマンド (受信専用)
+    /// 新しいバージョンの相手が追加したコマンドはこれにデコードされ、受信側は無視します。
+    /// 新しいコマンドは必ずこの手前に追加してください。
+    #[serde(other)]
+    Unknown,
 }
 
 /// 統計情報を伝える構造体
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct StatsPayload {
     /// アップロードされた累計バイト数
     pub upload_total: u64,
@@ -52,6 +109,12 @@ pub struct StatsPayload {
     pub download_speed: u64,
     /// 直近の RTT (ミリ秒)
     pub rtt_ms: Option<u64>,
+    /// ゲートウェイがターゲットへ接続するまでにかかった時間 (ミリ秒)。ゲートウェイからの報告のみ含みます。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_latency_ms: Option<u64>,
+    /// ゲートウェイでターゲットへの書き込みを待っているバイト数。ゲートウェイからの報告のみ含みます。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_depth: Option<u64>,
 }
 
 /// 許可されたポートの情報
@@ -81,8 +144,73 @@ pub struct SecureConnectPayload {
     pub port: u16,
     /// サーバーの公開鍵で暗号化された対称鍵（共通鍵）
     pub encrypted_key: Vec<u8>,
-    /// 使用する共通鍵暗号アルゴリズム（例: "AES-256-GCM"）
+    /// `encrypted_key` の共通鍵暗号アルゴリズム（例: "AES-256-GCM"）。ConnectResponse までの暗号化に使用します。
     pub algorithm: String,
+    /// クライアントがセッションで使用したい共通鍵暗号の一覧 (推奨順)。旧クライアントは送信しません。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub algorithms: Vec<String>,
+    /// クライアントのプロトコルバージョンと機能。旧クライアントは送信しません。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_info: Option<ProtocolInfo>,
+    /// `encrypted_key` のパディング方式 (例: "RSA-OAEP-SHA256")。
+    /// 旧クライアントは送信せず、PKCS#1 v1.5 でラップしています。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_wrap: Option<String>,
+}
+
+/// [ProtocolInfo]
+/// ハンドシェイクで交換するプロトコルバージョンと機能の一覧
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProtocolInfo {
+    /// プロトコルバージョン
+    pub version: u16,
+    /// サポートしている機能 ([capability] の識別子)。未知の識別子は無視されます。
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+impl ProtocolInfo {
+    /// この実装のプロトコルバージョンと機能を返します。
+    pub fn current() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            capabilities: capability::SUPPORTED
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+        }
+    }
+
+    /// バージョン情報を送ってこない旧実装を表します。
+    pub fn legacy() -> Self {
+        Self {
+            version: 0,
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// 指定した機能をサポートしているかどうかを返します。
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// [negotiate]
+    /// 相手の情報と突き合わせ、双方が使えるバージョン (小さい方) と機能 (共通部分) を返します。
+    /// 相手が情報を送ってこなかった場合は旧実装として扱います。
+    pub fn negotiate(&self, peer: Option<&ProtocolInfo>) -> ProtocolInfo {
+        let Some(peer) = peer else {
+            return Self::legacy();
+        };
+        Self {
+            version: self.version.min(peer.version),
+            capabilities: self
+                .capabilities
+                .iter()
+                .filter(|c| peer.supports(c))
+                .cloned()
+                .collect(),
+        }
+    }
 }
 
 /// 接続初期化の成否を伝える構造体
@@ -92,6 +220,167 @@ pub struct ConnectResponsePayload {
     pub success: bool,
     /// 失敗時のエラー理由などのメッセージ
     pub message: String,
+    /// クライアントが退役予定の旧鍵で接続してきた場合の、新しい公開鍵の通知
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_rotation: Option<KeyRotation>,
+    /// サーバーが決定した、このセッションで使用するプロトコルバージョンと機能
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_info: Option<ProtocolInfo>,
+    /// サーバーが選んだ、ConnectResponse 以降に使用する共通鍵暗号。
+    /// クライアントが `algorithms` を送らなかった場合は含めず、ハンドシェイクのアルゴリズムを使い続けます。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    /// 失敗時の理由を表す機械可読なコード。UI や CLI での分岐や翻訳に使用します。
+    /// 旧バージョンのゲートウェイは送ってこないため、その場合は `message` のみで判断します。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    /// `code` のメッセージに埋め込む引数 (ポート番号など)。クライアントはこれを使って自分の言語で表示します。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+impl ConnectResponsePayload {
+    /// [failure]
+    /// 失敗を伝える応答を作成します。
+    ///
+    /// `message` には旧クライアント向けに英語のメッセージを入れ、
+    /// 新しいクライアントは `code` と `params` から自分の言語でメッセージを組み立てます。
+    pub fn failure(code: ErrorCode, params: &[(&str, String)]) -> Self {
+        Self {
+            success: false,
+            message: i18n::tr_in(Locale::En, code.message_key(params), params),
+            key_rotation: None,
+            protocol_info: None,
+            algorithm: None,
+            code: Some(code),
+            params: params
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// [localized_message]
+    /// 失敗の理由を現在の言語で返します。
+    /// コードを送ってこない旧バージョンのゲートウェイや未知のコードの場合は `message` をそのまま返します。
+    pub fn localized_message(&self) -> String {
+        match self.code {
+            Some(code) if code != ErrorCode::Unknown => {
+                let params: Vec<(&str, String)> = self
+                    .params
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.clone()))
+                    .collect();
+                i18n::tr(code.message_key(&params), &params)
+            }
+            _ => self.message.clone(),
+        }
+    }
+}
+
+/// [ErrorCode]
+/// 接続の失敗理由を表す機械可読なコードです。
+///
+/// ConnectResponse でゲートウェイからクライアントへ送られるほか、
+/// クライアント側で発生したエラー ([crate::error::McConnectError]) にも付与されます。
+/// 未知のコードは [ErrorCode::Unknown] として読み込まれます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// ゲートウェイの URL が不正
+    InvalidUrl,
+    /// ゲートウェイへの WebSocket 接続に失敗した
+    WsConnectFailed,
+    /// ゲートウェイが共通鍵を復号できなかった (公開鍵の不一致など)
+    HandshakeFailed,
+    /// 要求したポート・プロトコルがゲートウェイで許可されていない
+    PortNotAllowed,
+    /// ゲートウェイの転送量が上限に達している
+    QuotaExceeded,
+    /// ゲートウェイからターゲットへ接続できなかった
+    TargetUnreachable,
+    /// 受信したパケットを復号できなかった
+    DecryptFailed,
+    /// プロトコルの不一致 (想定外のパケット、非暗号化接続など)
+    ProtocolMismatch,
+    /// このバージョンが知らないコード
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    /// [as_str]
+    /// ワイヤー上の表記 (snake_case) を返します。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidUrl => "invalid_url",
+            ErrorCode::WsConnectFailed => "ws_connect_failed",
+            ErrorCode::HandshakeFailed => "handshake_failed",
+            ErrorCode::PortNotAllowed => "port_not_allowed",
+            ErrorCode::QuotaExceeded => "quota_exceeded",
+            ErrorCode::TargetUnreachable => "target_unreachable",
+            ErrorCode::DecryptFailed => "decrypt_failed",
+            ErrorCode::ProtocolMismatch => "protocol_mismatch",
+            ErrorCode::Unknown => "unknown",
+        }
+    }
+
+    /// [message_key]
+    /// ConnectResponse で伝えるメッセージのカタログのキーを返します。
+    /// 転送量の上限は `period` (`daily` / `monthly`) によって文面を切り替えます。
+    pub fn message_key(&self, params: &[(&str, String)]) -> &'static str {
+        match self {
+            ErrorCode::InvalidUrl => "response.invalid_url",
+            ErrorCode::WsConnectFailed => "response.ws_connect_failed",
+            ErrorCode::HandshakeFailed => "response.handshake_failed",
+            ErrorCode::PortNotAllowed => "response.port_not_allowed",
+            ErrorCode::QuotaExceeded => {
+                let monthly = params
+                    .iter()
+                    .any(|(name, value)| *name == "period" && value == "monthly");
+                if monthly {
+                    "response.quota_exceeded_monthly"
+                } else {
+                    "response.quota_exceeded_daily"
+                }
+            }
+            ErrorCode::TargetUnreachable => "response.target_unreachable",
+            ErrorCode::DecryptFailed => "response.decrypt_failed",
+            ErrorCode::ProtocolMismatch => "response.protocol_mismatch",
+            ErrorCode::Unknown => "response.unknown",
+        }
+    }
+
+    /// [is_retryable]
+    /// 時間をおいて再試行すれば成功する可能性がある失敗かどうかを返します。
+    /// false の場合は、URL や公開鍵、ポートなどの設定を見直す必要があります。
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::WsConnectFailed | ErrorCode::QuotaExceeded | ErrorCode::TargetUnreachable
+        )
+    }
+}
+
+/// サーバー鍵のローテーション通知
+///
+/// 新しい公開鍵に旧鍵で署名したものを含みます。
+/// クライアントは手元の (旧) 公開鍵で署名を検証してから、保存している公開鍵を更新します。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeyRotation {
+    /// 新しいサーバー公開鍵 (DER)
+    pub new_public_key: Vec<u8>,
+    /// 旧鍵による `new_public_key` への署名
+    pub signature: Vec<u8>,
+    /// 旧鍵の受け付けが終了する時刻 (UNIX 秒)
+    pub expires_at: u64,
+}
+
+/// サーバー情報の問い合わせ (GetServerInfo) に添えるペイロード
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServerInfoRequestPayload {
+    /// クライアントが生成したランダムなチャレンジ。サーバーはこれを含めて公開鍵に署名します。
+    pub challenge: Vec<u8>,
 }
 
 /// サーバーの構成情報を伝える構造体
@@ -101,6 +390,21 @@ pub struct ServerInfoResponsePayload {
     pub server_version: String,
     /// 許可されているポートの一覧
     pub allowed_ports: Vec<AllowedPort>,
+    /// チャレンジ付きで問い合わせた場合の、署名付きサーバー公開鍵
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_key: Option<SignedServerKey>,
+    /// サーバーのプロトコルバージョンと機能
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_info: Option<ProtocolInfo>,
+}
+
+/// 秘密鍵の所持を証明する署名付きのサーバー公開鍵
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SignedServerKey {
+    /// サーバー公開鍵 (DER)
+    pub public_key: Vec<u8>,
+    /// `challenge || public_key` に対するサーバー秘密鍵の署名
+    pub signature: Vec<u8>,
 }
 
 /// クライアントへの配布用設定ファイル構造体
@@ -126,6 +430,66 @@ pub struct ClientExportConfig {
     pub encryption_type: String,
 }
 
+impl ClientExportConfig {
+    /// [validate]
+    /// 読み込んだ設定を検証し、接続先のサーバー公開鍵を返します。
+    ///
+    /// URL が `ws://` / `wss://` として読めること、公開鍵が RSA 公開鍵として読めること、
+    /// マッピングが 1 つ以上あり、ポートが 0 でないことを確認します。
+    pub fn validate(&self) -> Result<RsaPublicKey, CryptoError> {
+        let url = url::Url::parse(self.ws_url.trim())
+            .map_err(|e| McConnectError::InvalidUrl(e.to_string()))?;
+        if !matches!(url.scheme(), "ws" | "wss") || url.host().is_none() {
+            let detail = t!("export.unsupported_url", url = self.ws_url);
+            return Err(McConnectError::InvalidUrl(detail).into());
+        }
+
+        let der = general_purpose::STANDARD
+            .decode(self.public_key.trim())
+            .map_err(|e| t!("export.public_key_invalid", error = e))?;
+        let public_key =
+            RsaPublicKey::from_der(&der).map_err(|e| t!("export.public_key_invalid", error = e))?;
+
+        if self.mappings.is_empty() {
+            return Err(t!("export.no_mappings").into());
+        }
+        if let Some(mapping) = self.mappings.iter().find(|m| m.port == 0) {
+            return Err(t!("export.invalid_port", port = mapping.port).into());
+        }
+        Ok(public_key)
+    }
+
+    /// [gateway_ws_url]
+    /// 公開用ホストとポートから、クライアントが接続する WebSocket の URL を組み立てます。
+    ///
+    /// 既定では ws を使用し、`host` に `wss://` や `https://` のスキームを付けた場合か、`tls` が true の場合のみ
+    /// wss を使用します。ポート番号からは推測しません (443 で TLS を終端していない構成もあるため)。
+    /// スキームの既定ポート (ws: 80 / wss: 443) は省略します。
+    pub fn gateway_ws_url(host: &str, port: u16, tls: bool) -> String {
+        let host = host.trim();
+        let (secure, host) = match host.split_once("://") {
+            Some((scheme, rest)) => (
+                tls || scheme.eq_ignore_ascii_case("wss") || scheme.eq_ignore_ascii_case("https"),
+                rest,
+            ),
+            None => (tls, host),
+        };
+        let host = host.trim_end_matches('/');
+        // IPv6 アドレスはポートと区別できるよう角括弧で囲む
+        let host = if host.contains(':') && !host.starts_with('[') {
+            format!("[{}]", host)
+        } else {
+            host.to_string()
+        };
+        let (scheme, default_port) = if secure { ("wss", 443) } else { ("ws", 80) };
+        if port == default_port {
+            format!("{}://{}/ws", scheme, host)
+        } else {
+            format!("{}://{}:{}/ws", scheme, host, port)
+        }
+    }
+}
+
 /// サーバー側の設定保存用構造体 (秘密鍵を含む)
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ServerConfig {
@@ -137,10 +501,55 @@ pub struct ServerConfig {
     pub port: u16,
     /// サーバーの公開鍵（Base64）
     pub public_key: String,
-    /// サーバーの秘密鍵（Base64）
+    /// サーバーの秘密鍵（Base64）。`key_encryption` が設定されている場合は暗号文です。
     pub private_key: String,
     /// 許可ポート設定
     pub allowed_ports: String,
+    /// ローテーションで退役した旧鍵の一覧。猶予期間が切れるまで保持されます。
+    #[serde(default)]
+    pub retired_keys: Vec<RetiredKey>,
+    /// 秘密鍵をパスフレーズで暗号化している場合の KDF パラメータ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_encryption: Option<KeyEncryption>,
+    /// PKCS#1 v1.5 で共通鍵をラップする旧クライアントを受け付けるかどうか。
+    /// パディングオラクル攻撃の恐れがあるため、旧クライアントの移行期間中のみ有効にしてください。
+    #[serde(default)]
+    pub allow_legacy_key_wrap: bool,
+}
+
+/// 秘密鍵の暗号化に使用した KDF と暗号方式のパラメータ
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeyEncryption {
+    /// 鍵導出関数 (現在は "argon2id" のみ)
+    pub kdf: String,
+    /// 暗号方式 (現在は "aes-256-gcm" のみ)
+    pub cipher: String,
+    /// Argon2 のメモリコスト (KiB)
+    pub memory_kib: u32,
+    /// Argon2 の反復回数
+    pub iterations: u32,
+    /// Argon2 の並列度
+    pub parallelism: u32,
+    /// ソルト（Base64）
+    pub salt: String,
+}
+
+/// 鍵ローテーション後も猶予期間中は受け付ける旧サーバー鍵
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetiredKey {
+    /// 旧公開鍵（Base64）
+    pub public_key: String,
+    /// 旧秘密鍵（Base64）
+    pub private_key: String,
+    /// 受け付けを終了する時刻 (UNIX 秒)
+    pub expires_at: u64,
+}
+
+/// 鍵更新 (Rekey) で使用するペイロード
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RekeyPayload {
+    /// 更新後の鍵の世代
+    pub generation: u32,
 }
 
 /// Ping/Pong で使用するペイロード
@@ -152,6 +561,9 @@ pub struct PingPayload {
 
 /// McConnect ネットワーク上を流れる基本の「コンテナ」構造体。
 /// すべてのパケットはこの形式に MessagePack でラップされて通信されます。
+///
+/// 旧バージョンとの互換性のため、このコンテナのフィールドは変更しないでください。
+/// 拡張は新しい `Command` か、各ペイロード構造体へのフィールド追加で行います。
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Message {
     /// どのような命令かを示すコマンド種別
@@ -173,8 +585,7 @@ impl Message {
         command: Command,
         payload: &T,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let serialized = rmp_serde::to_vec(payload)?;
-        Ok(Self::new(command, serialized))
+        Ok(Self::new(command, encode_payload(payload)?))
     }
 
     /// Message コンテナ全体を MessagePack 形式のバイナリに変換します。
@@ -189,9 +600,23 @@ impl Message {
     }
 
     /// Message の payload 部分を特定の構造体にデシリアライズします。
+    /// 配列形式 (旧実装) とマップ形式のどちらのペイロードも読み込めます。
     pub fn deserialize_payload<'a, T: Deserialize<'a>>(
         &'a self,
     ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
         Ok(rmp_serde::from_slice(&self.payload)?)
     }
 }
+
+/// [encode_payload]
+/// ペイロード構造体をフィールド名付き (マップ形式) の MessagePack にシリアライズします。
+///
+/// 配列形式では末尾にフィールドを追加すると旧実装がデコードに失敗しますが、
+/// マップ形式であれば旧実装は未知のフィールドを無視できます。
+/// 逆に旧実装からの配列形式のペイロードは、`#[serde(default)]` を付けた追加フィールドを
+/// 既定値として読み込めます。
+pub fn encode_payload<T: Serialize>(
+    payload: &T,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(rmp_serde::to_vec_named(payload)?)
+}
@@ -0,0 +1,72 @@
+//! リバーストンネル用のリレーとホストエージェントの間の通信形式
+//!
+//! ホストエージェントはリレーへ 1 本の WebSocket で接続し、その上に複数のクライアントの
+//! ストリームを多重化します。クライアントが送受信するバイナリ (暗号化済みの `Message` や
+//! Data フレーム) は、次の形式でストリーム ID を付けてそのまま運びます。
+//!
+//! ```text
+//! +------+--------------------+------------------------------+
+//! | type | stream_id (u32)    | クライアントのバイナリ (不透明) |
+//! | 1 B  | 4 B big endian     | 可変長                        |
+//! +------+--------------------+------------------------------+
+//! ```
+//!
+//! 登録時は、リレーが `Command::AgentChallenge` でリレー ID とチャレンジを送り、エージェントは
+//! `Command::AgentRegister` で登録用の署名付き公開鍵を返します。この署名はゲートウェイの
+//! `GetServerInfo` 応答とは別の形式で、クライアント向けの応答を登録に流用することはできません。
+//!
+//! ストリームの開始・終了は `Command::RelayOpen` / `Command::RelayClose` の `Message` で通知します。
+//! リレーは共通鍵を持たず、クライアントのバイナリを復号しません。
+
+use serde::{Deserialize, Serialize};
+
+use super::packet::SignedServerKey;
+
+/// リレーフレームの種別。[FRAME_DATA](super::frame::FRAME_DATA) や `Message` の先頭バイトと重なりません。
+pub const FRAME_RELAY: u8 = 0x02;
+
+/// リレーフレームのヘッダー (種別 + ストリーム ID) の長さ
+pub const RELAY_HEADER_LEN: usize = 1 + 4;
+
+/// `Command::RelayOpen` / `Command::RelayClose` で使用するペイロード
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelayStreamPayload {
+    /// リレーが割り当てたストリーム ID
+    pub stream_id: u32,
+}
+
+/// `Command::AgentChallenge` で使用するペイロード (Relay -> Agent)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentChallengePayload {
+    /// リレーの識別子。署名に含め、別のリレー向けの署名と区別します。
+    pub relay_id: String,
+    /// リレーが生成したランダムなチャレンジ
+    pub challenge: Vec<u8>,
+}
+
+/// `Command::AgentRegister` で使用するペイロード (Agent -> Relay)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentRegisterPayload {
+    /// [sign_agent_registration](crate::encryption::sign_agent_registration) で作成した署名付きの公開鍵
+    pub signed_key: SignedServerKey,
+}
+
+/// [new_relay_frame]
+/// クライアントのバイナリにストリーム ID を付けたリレーフレームを作成します。
+pub fn new_relay_frame(stream_id: u32, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(RELAY_HEADER_LEN + data.len());
+    frame.push(FRAME_RELAY);
+    frame.extend_from_slice(&stream_id.to_be_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// [parse_relay_frame]
+/// リレーフレームをストリーム ID と中身に分割します。リレーフレームでない場合は None を返します。
+pub fn parse_relay_frame(bin: &[u8]) -> Option<(u32, &[u8])> {
+    if bin.first() != Some(&FRAME_RELAY) || bin.len() < RELAY_HEADER_LEN {
+        return None;
+    }
+    let stream_id = u32::from_be_bytes(bin[1..RELAY_HEADER_LEN].try_into().unwrap());
+    Some((stream_id, &bin[RELAY_HEADER_LEN..]))
+}
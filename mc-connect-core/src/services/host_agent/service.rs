@@ -0,0 +1,290 @@
+use futures_util::{SinkExt, StreamExt};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, interval, sleep};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use url::Url;
+
+use crate::controllers::{GatewayOptions, bind_server};
+use crate::encryption::{CryptoError, ServerKeyring, sign_agent_registration};
+use crate::models::packet::{AllowedPort, Command, ConnectResponsePayload, Message};
+use crate::models::relay::{
+    AgentChallengePayload, AgentRegisterPayload, RelayStreamPayload, new_relay_frame,
+    parse_relay_frame,
+};
+use crate::t;
+
+/// リレーとの接続が切れた後、再接続を試みるまでの待ち時間
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// リレーとの接続を維持するための WebSocket Ping の間隔
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// ストリームの中継タスクからリレーへの送信要求
+enum AgentOutput {
+    /// ローカルゲートウェイから届いたバイナリ
+    Frame(u32, Vec<u8>),
+    /// ローカルゲートウェイとの接続が終了した
+    Closed(u32),
+}
+
+/// [HostAgentService]
+/// NAT の内側からリレーへ接続し、リレー経由のクライアントを受け付けるホストエージェントです。
+///
+/// エージェントはループバックで通常のゲートウェイを起動し、リレーから届いたストリームごとに
+/// そのゲートウェイへ WebSocket で接続して中継します。ハンドシェイクや許可ポートの確認は
+/// ゲートウェイがそのまま行うため、リレーは暗号化されたバイナリしか扱いません。
+pub struct HostAgentService;
+
+impl HostAgentService {
+    /// [run]
+    /// ローカルゲートウェイを起動し、リレーへの接続を維持します。
+    /// リレーとの接続が切れた場合は、一定時間待ってから再接続します。
+    pub async fn run(
+        relay_url: String,
+        allowed_ports: Vec<AllowedPort>,
+        server_keys: Arc<ServerKeyring>,
+    ) -> Result<(), CryptoError> {
+        let (server, addr) = bind_server(
+            "127.0.0.1",
+            0,
+            allowed_ports,
+            Arc::clone(&server_keys),
+            GatewayOptions::default(),
+        )?;
+        tokio::spawn(server);
+        let gateway_url = format!("ws://{}/ws", addr);
+        info!("{}", t!("agent.gateway_started", url = gateway_url));
+
+        loop {
+            match Self::serve(&relay_url, &gateway_url, &server_keys).await {
+                Ok(()) => warn!("{}", t!("agent.relay_closed")),
+                Err(e) => error!("{}", t!("agent.relay_error", error = e)),
+            }
+            info!(
+                "{}",
+                t!("agent.reconnecting", seconds = RECONNECT_DELAY.as_secs())
+            );
+            sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    /// [agent_url]
+    /// リレーの URL (例: ws://relay.example.com:8080) から、エージェントの登録先 URL を作成します。
+    pub fn agent_url(relay_url: &str) -> String {
+        format!("{}/agent", relay_url.trim_end_matches('/'))
+    }
+
+    /// [client_url]
+    /// リレーの URL とエージェント ID から、クライアントが接続する URL を作成します。
+    pub fn client_url(relay_url: &str, agent_id: &str) -> String {
+        format!("{}/ws/{}", relay_url.trim_end_matches('/'), agent_id)
+    }
+
+    /// [serve]
+    /// リレーへ 1 回接続して登録し、接続が切れるまでストリームを中継します。
+    async fn serve(
+        relay_url: &str,
+        gateway_url: &str,
+        server_keys: &ServerKeyring,
+    ) -> Result<(), CryptoError> {
+        let url = Url::parse(&Self::agent_url(relay_url))?;
+        info!("{}", t!("agent.connecting", url = url));
+        let (ws_stream, _) = connect_async(url).await?;
+        let (mut ws_write, mut ws_read) = ws_stream.split();
+
+        // 1. リレーのチャレンジに登録用の署名付き公開鍵で応答し、鍵の所持を証明する
+        let challenge = match Self::next_message(&mut ws_read).await? {
+            msg if msg.command == Command::AgentChallenge => {
+                msg.deserialize_payload::<AgentChallengePayload>()?
+            }
+            msg => {
+                return Err(t!(
+                    "agent.unexpected_command",
+                    command = format!("{:?}", msg.command)
+                )
+                .into());
+            }
+        };
+        let req = AgentRegisterPayload {
+            signed_key: sign_agent_registration(
+                server_keys.current(),
+                &challenge.relay_id,
+                &challenge.challenge,
+            )?,
+        };
+        let bin = Message::from_payload(Command::AgentRegister, &req)?.to_vec()?;
+        ws_write.send(WsMessage::Binary(bin)).await?;
+
+        // 2. 登録結果を確認する
+        let msg = Self::next_message(&mut ws_read).await?;
+        if msg.command != Command::ConnectResponse {
+            return Err(t!(
+                "agent.unexpected_command",
+                command = format!("{:?}", msg.command)
+            )
+            .into());
+        }
+        let res: ConnectResponsePayload = msg.deserialize_payload()?;
+        if !res.success {
+            return Err(t!("agent.registration_failed", message = res.message).into());
+        }
+        info!("{}", t!("agent.registered", agent = res.message));
+        info!(
+            "{}",
+            t!(
+                "agent.client_url",
+                url = Self::client_url(relay_url, &res.message)
+            )
+        );
+
+        // 3. ストリームの中継
+        let (out_tx, mut out_rx) = mpsc::unbounded_channel::<AgentOutput>();
+        let mut streams = HashMap::<u32, mpsc::UnboundedSender<Vec<u8>>>::new();
+        let mut keepalive = interval(KEEPALIVE_INTERVAL);
+
+        loop {
+            tokio::select! {
+                msg = ws_read.next() => {
+                    let bin = match msg {
+                        Some(Ok(WsMessage::Binary(bin))) => bin,
+                        Some(Ok(WsMessage::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => return Err(e.into()),
+                    };
+
+                    if let Some((stream_id, data)) = parse_relay_frame(&bin) {
+                        if let Some(tx) = streams.get(&stream_id) {
+                            let _ = tx.send(data.to_vec());
+                        }
+                        continue;
+                    }
+
+                    let packet = match Message::from_slice(&bin) {
+                        Ok(p) => p,
+                        Err(e) => {
+                            error!("{}", t!("agent.decode_failed", error = e));
+                            continue;
+                        }
+                    };
+                    let Ok(payload) = packet.deserialize_payload::<RelayStreamPayload>() else {
+                        warn!(
+                            "{}",
+                            t!(
+                                "agent.unexpected_command",
+                                command = format!("{:?}", packet.command)
+                            )
+                        );
+                        continue;
+                    };
+                    match packet.command {
+                        Command::RelayOpen => {
+                            let (tx, rx) = mpsc::unbounded_channel();
+                            streams.insert(payload.stream_id, tx);
+                            tokio::spawn(bridge_stream(
+                                payload.stream_id,
+                                gateway_url.to_string(),
+                                rx,
+                                out_tx.clone(),
+                            ));
+                        }
+                        Command::RelayClose => {
+                            // 送信側を閉じると中継タスクがローカルゲートウェイとの接続を閉じる
+                            streams.remove(&payload.stream_id);
+                        }
+                        _ => warn!(
+                            "{}",
+                            t!(
+                                "agent.unexpected_command",
+                                command = format!("{:?}", packet.command)
+                            )
+                        ),
+                    }
+                }
+
+                Some(output) = out_rx.recv() => {
+                    let bin = match output {
+                        AgentOutput::Frame(stream_id, data) => new_relay_frame(stream_id, &data),
+                        AgentOutput::Closed(stream_id) => {
+                            // リレー側から閉じたストリームには通知しない
+                            if streams.remove(&stream_id).is_none() {
+                                continue;
+                            }
+                            let payload = RelayStreamPayload { stream_id };
+                            Message::from_payload(Command::RelayClose, &payload)?.to_vec()?
+                        }
+                    };
+                    ws_write.send(WsMessage::Binary(bin)).await?;
+                }
+
+                _ = keepalive.tick() => {
+                    ws_write.send(WsMessage::Ping(Vec::new())).await?;
+                }
+            }
+        }
+    }
+
+    /// リレーから次の `Message` を受信します。
+    async fn next_message<S>(ws_read: &mut S) -> Result<Message, CryptoError>
+    where
+        S: StreamExt<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        loop {
+            match ws_read.next().await {
+                Some(Ok(WsMessage::Binary(bin))) => return Message::from_slice(&bin),
+                Some(Ok(WsMessage::Close(_))) | None => {
+                    return Err(t!("agent.closed_during_registration").into());
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// [bridge_stream]
+/// リレー上のストリーム 1 つを、ローカルゲートウェイへの WebSocket 接続と中継します。
+/// どちらかが切断されると終了し、リレーへ終了を通知します。
+async fn bridge_stream(
+    stream_id: u32,
+    gateway_url: String,
+    mut from_relay: mpsc::UnboundedReceiver<Vec<u8>>,
+    to_relay: mpsc::UnboundedSender<AgentOutput>,
+) {
+    match connect_async(gateway_url.as_str()).await {
+        Ok((ws_stream, _)) => {
+            let (mut gw_write, mut gw_read) = ws_stream.split();
+            loop {
+                tokio::select! {
+                    data = from_relay.recv() => {
+                        let Some(data) = data else { break };
+                        if let Err(e) = gw_write.send(WsMessage::Binary(data)).await {
+                            error!("{}", t!("agent.gateway_send_failed", error = e));
+                            break;
+                        }
+                    }
+                    msg = gw_read.next() => {
+                        match msg {
+                            Some(Ok(WsMessage::Binary(bin))) => {
+                                if to_relay.send(AgentOutput::Frame(stream_id, bin)).is_err() {
+                                    break;
+                                }
+                            }
+                            Some(Ok(WsMessage::Close(_))) | None => break,
+                            Some(Ok(_)) => {}
+                            Some(Err(e)) => {
+                                error!("{}", t!("agent.gateway_recv_failed", error = e));
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            let _ = gw_write.close().await;
+        }
+        Err(e) => error!("{}", t!("agent.gateway_connect_failed", error = e)),
+    }
+    let _ = to_relay.send(AgentOutput::Closed(stream_id));
+}
@@ -1,2 +1,4 @@
 pub mod proxy;
 pub mod ws_client;
+pub mod relay;
+pub mod host_agent;
@@ -0,0 +1,302 @@
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::encryption::CryptoError;
+use crate::models::packet::{ErrorCode, Protocol};
+use crate::t;
+
+/// プロセス内で一意なセッション ID の採番用カウンタ
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// [HandshakeOutcome]
+/// セッションのハンドシェイクの結果です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HandshakeOutcome {
+    /// SecureConnect を受信する前に切断された (サーバー情報の問い合わせのみなど)
+    #[default]
+    None,
+    /// ハンドシェイクに成功し、ターゲットへの接続を試行した
+    Success,
+    /// 共通鍵の復号や検証に失敗した
+    Failed,
+    /// ハンドシェイクには成功したが、許可ポートや転送量の上限により拒否した
+    Rejected,
+}
+
+/// [AuditReason]
+/// ハンドシェイクの失敗・拒否やセッションの終了の理由を表す、監査ログ用の安定したコードです。
+///
+/// 監査ログを集計・検索するツールが言語や文面の変更に左右されないよう、snake_case の固定の値で書き出します。
+/// 人が読むための詳細はコードとは別に `*_detail` に記録します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditReason {
+    /// 共通鍵の復号や検証に失敗した ([ErrorCode::HandshakeFailed])
+    HandshakeFailed,
+    /// 要求したポート・プロトコルが許可されていない ([ErrorCode::PortNotAllowed])
+    PortNotAllowed,
+    /// 転送量が上限に達している ([ErrorCode::QuotaExceeded])
+    QuotaExceeded,
+    /// ターゲットへ接続できなかった ([ErrorCode::TargetUnreachable])
+    TargetUnreachable,
+    /// 非暗号化接続などの想定外のパケットを受信した ([ErrorCode::ProtocolMismatch])
+    ProtocolMismatch,
+    /// ハンドシェイクが時間内に完了しなかった
+    HandshakeTimeout,
+    /// クライアントが WebSocket を閉じた
+    ClientClosed,
+    /// クライアントが Disconnect を送った
+    ClientDisconnected,
+    /// Data フレームを復号できなかった
+    DataFrameDecryptFailed,
+    /// パケットを復号できなかった
+    PacketDecryptFailed,
+    /// ハンドシェイク後の共通鍵暗号の切り替えに失敗した
+    AlgorithmSwitchFailed,
+    /// 共通鍵の更新に失敗した
+    RekeyFailed,
+    /// ターゲットへの書き込みに失敗した
+    TargetWriteFailed,
+    /// ターゲットが接続を閉じた
+    TargetClosed,
+    /// 理由を記録せずに終了した (WebSocket が閉じられずに切れた場合など)
+    ConnectionLost,
+    /// このバージョンが知らない理由
+    #[serde(other)]
+    Unknown,
+}
+
+impl AuditReason {
+    /// [as_str]
+    /// 監査ログ上の表記 (snake_case) を返します。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditReason::HandshakeFailed => "handshake_failed",
+            AuditReason::PortNotAllowed => "port_not_allowed",
+            AuditReason::QuotaExceeded => "quota_exceeded",
+            AuditReason::TargetUnreachable => "target_unreachable",
+            AuditReason::ProtocolMismatch => "protocol_mismatch",
+            AuditReason::HandshakeTimeout => "handshake_timeout",
+            AuditReason::ClientClosed => "client_closed",
+            AuditReason::ClientDisconnected => "client_disconnected",
+            AuditReason::DataFrameDecryptFailed => "data_frame_decrypt_failed",
+            AuditReason::PacketDecryptFailed => "packet_decrypt_failed",
+            AuditReason::AlgorithmSwitchFailed => "algorithm_switch_failed",
+            AuditReason::RekeyFailed => "rekey_failed",
+            AuditReason::TargetWriteFailed => "target_write_failed",
+            AuditReason::TargetClosed => "target_closed",
+            AuditReason::ConnectionLost => "connection_lost",
+            AuditReason::Unknown => "unknown",
+        }
+    }
+}
+
+impl From<ErrorCode> for AuditReason {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::HandshakeFailed => AuditReason::HandshakeFailed,
+            ErrorCode::PortNotAllowed => AuditReason::PortNotAllowed,
+            ErrorCode::QuotaExceeded => AuditReason::QuotaExceeded,
+            ErrorCode::TargetUnreachable => AuditReason::TargetUnreachable,
+            ErrorCode::ProtocolMismatch => AuditReason::ProtocolMismatch,
+            ErrorCode::DecryptFailed => AuditReason::PacketDecryptFailed,
+            ErrorCode::InvalidUrl | ErrorCode::WsConnectFailed | ErrorCode::Unknown => {
+                AuditReason::Unknown
+            }
+        }
+    }
+}
+
+/// [AuditRecord]
+/// 1 セッションにつき 1 行、監査ログへ書き出す記録です。
+/// 向きはクライアントから見た名前に揃えています (`bytes_up` はクライアント → ターゲット)。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// ゲートウェイのプロセス内で一意なセッション ID
+    pub session_id: u64,
+    /// 接続元の IP アドレス。取得できなかった場合は None です。
+    pub peer_ip: Option<String>,
+    /// セッション開始時刻 (UNIX エポックからのミリ秒)
+    pub started_at_ms: u64,
+    /// セッションの継続時間 (ミリ秒)
+    pub duration_ms: u64,
+    /// クライアントが使用したサーバー鍵のフィンガープリント
+    pub key_fingerprint: Option<String>,
+    /// クライアントが要求したターゲットポート
+    pub port: Option<u16>,
+    /// クライアントが要求したプロトコル
+    pub protocol: Option<Protocol>,
+    /// ハンドシェイクの結果
+    pub handshake: HandshakeOutcome,
+    /// ハンドシェイクが失敗・拒否された理由
+    pub handshake_reason: Option<AuditReason>,
+    /// ハンドシェイクが失敗・拒否された理由の詳細 (人が読むための文面)
+    pub handshake_detail: Option<String>,
+    /// クライアントから受信し、ターゲットへ渡したバイト数
+    pub bytes_up: u64,
+    /// ターゲットから受信し、クライアントへ送ったバイト数
+    pub bytes_down: u64,
+    /// セッションが終了した理由。終了前は None です。
+    pub close_reason: Option<AuditReason>,
+    /// セッションが終了した理由の詳細 (人が読むための文面)
+    pub close_detail: Option<String>,
+}
+
+/// [AuditLog]
+/// セッションの監査記録を JSON Lines 形式で追記するログです。
+///
+/// 記録は 1 行ずつ書き込んで flush するため、ゲートウェイが異常終了しても
+/// それまでに終了したセッションの記録は失われません。
+pub struct AuditLog {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AuditLog {
+    /// [open]
+    /// ファイルを追記モードで開きます。ファイルや親ディレクトリがない場合は作成します。
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CryptoError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::from_writer(file))
+    }
+
+    /// [from_writer]
+    /// 任意の書き込み先へ記録するログを作成します。標準出力への出力やテストで使用します。
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// [write]
+    /// 記録を 1 行の JSON として書き込みます。書き込みに失敗してもセッションには影響させません。
+    pub fn write(&self, record: &AuditRecord) {
+        let mut line = match serde_json::to_vec(record) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("{}", t!("audit.encode_failed", error = e));
+                return;
+            }
+        };
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = writer.write_all(&line).and_then(|_| writer.flush()) {
+            warn!("{}", t!("audit.write_failed", error = e));
+        }
+    }
+}
+
+/// [SessionAudit]
+/// 1 セッション分の監査記録を、セッションの進行に合わせて組み立てます。
+/// 監査ログが設定されていない場合も ID の採番と記録は行い、書き出しのみを省略します。
+pub struct SessionAudit {
+    log: Option<Arc<AuditLog>>,
+    started: Instant,
+    record: AuditRecord,
+}
+
+impl Default for SessionAudit {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl SessionAudit {
+    /// 新しいセッション ID を採番し、開始時刻と接続元を記録します。
+    pub fn new(log: Option<Arc<AuditLog>>, peer_addr: Option<SocketAddr>) -> Self {
+        let started_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            log,
+            started: Instant::now(),
+            record: AuditRecord {
+                session_id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+                peer_ip: peer_addr.map(|addr| addr.ip().to_string()),
+                started_at_ms,
+                ..AuditRecord::default()
+            },
+        }
+    }
+
+    /// このセッションの ID
+    pub fn session_id(&self) -> u64 {
+        self.record.session_id
+    }
+
+    /// [handshake_succeeded]
+    /// ハンドシェイクの成功と、クライアントが使用した鍵・要求した接続先を記録します。
+    pub fn handshake_succeeded(&mut self, key_fingerprint: &str, port: u16, protocol: Protocol) {
+        self.record.handshake = HandshakeOutcome::Success;
+        self.record.key_fingerprint = Some(key_fingerprint.to_string());
+        self.record.port = Some(port);
+        self.record.protocol = Some(protocol);
+    }
+
+    /// [handshake_failed]
+    /// ハンドシェイクの失敗を記録します。`detail` にはクライアントへ返さない詳細な理由を指定できます。
+    pub fn handshake_failed(&mut self, detail: impl Into<String>) {
+        self.record.handshake = HandshakeOutcome::Failed;
+        self.record.handshake_reason = Some(AuditReason::HandshakeFailed);
+        self.record.handshake_detail = Some(detail.into());
+    }
+
+    /// [handshake_rejected]
+    /// ハンドシェイク後に接続を拒否したことを、クライアントへ返したコードとあわせて記録します。
+    pub fn handshake_rejected(&mut self, code: ErrorCode, detail: impl Into<String>) {
+        self.record.handshake = HandshakeOutcome::Rejected;
+        self.record.handshake_reason = Some(code.into());
+        self.record.handshake_detail = Some(detail.into());
+    }
+
+    /// [set_close_reason]
+    /// セッションが終了する理由を記録します。最初に記録した理由を優先します。
+    pub fn set_close_reason(&mut self, reason: AuditReason) {
+        self.set_close_reason_with_detail(reason, None::<String>);
+    }
+
+    /// [set_close_reason_with_detail]
+    /// セッションが終了する理由を、エラーの内容などの詳細とあわせて記録します。最初に記録した理由を優先します。
+    pub fn set_close_reason_with_detail(
+        &mut self,
+        reason: AuditReason,
+        detail: Option<impl Into<String>>,
+    ) {
+        if self.record.close_reason.is_none() {
+            self.record.close_reason = Some(reason);
+            self.record.close_detail = detail.map(Into::into);
+        }
+    }
+
+    /// 記録したセッションの終了理由。終了前は [AuditReason::ConnectionLost] を返します。
+    pub fn close_reason(&self) -> AuditReason {
+        self.record
+            .close_reason
+            .unwrap_or(AuditReason::ConnectionLost)
+    }
+
+    /// [finish]
+    /// 転送量と継続時間を記録し、監査ログへ書き出します。
+    pub fn finish(&mut self, bytes_up: u64, bytes_down: u64) {
+        self.record.bytes_up = bytes_up;
+        self.record.bytes_down = bytes_down;
+        self.record.duration_ms = self.started.elapsed().as_millis() as u64;
+        // 理由を記録せずに終了した場合は、WebSocket が閉じられずに切れたものとして扱う
+        self.set_close_reason(AuditReason::ConnectionLost);
+        if let Some(log) = &self.log {
+            log.write(&self.record);
+        }
+    }
+}
@@ -1,14 +1,21 @@
 use actix::prelude::*;
 use actix_web_actors::ws;
 use log::{error, info, warn};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
 
-use super::session::WsProxySession;
-use crate::encryption::handle_server_handshake;
+use super::audit::AuditReason;
+use super::session::{STATS_INTERVAL, WsProxySession};
+use crate::encryption::secure_connect::server_handshake;
+use crate::encryption::sign_server_key;
+use crate::models::frame::is_data_frame;
+use crate::t;
 use crate::models::packet::{
-    Command, ConnectResponsePayload, Message, Protocol, ServerInfoResponsePayload,
+    Command, ConnectResponsePayload, ErrorCode, Message, ProtocolInfo, ServerInfoRequestPayload,
+    ServerInfoResponsePayload, capability, encode_payload,
 };
 
 /// [StreamHandler<ws::Message>]
@@ -23,7 +30,11 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsProxySession {
                 return;
             }
             Ok(ws::Message::Close(reason)) => {
-                info!("Client closed WebSocket connection: {:?}", reason);
+                info!(
+                    "{}",
+                    t!("gateway.client_closed", reason = format!("{:?}", reason))
+                );
+                self.audit.set_close_reason(AuditReason::ClientClosed);
                 ctx.close(reason);
                 ctx.stop();
                 return;
@@ -31,11 +42,27 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsProxySession {
             _ => return, // テキストメッセージなどはサポート外
         };
 
+        // Data フレームはシリアライズを省いた形式で届くため、先に判定して復号する
+        if is_data_frame(&bin) {
+            match self.secure_context.open_data_frame(&bin) {
+                Ok(data) => self.forward_to_target(data, ctx),
+                Err(e) => {
+                    error!("{}", t!("gateway.data_frame_decrypt_failed", error = e));
+                    self.audit.set_close_reason_with_detail(
+                        AuditReason::DataFrameDecryptFailed,
+                        Some(e.to_string()),
+                    );
+                    ctx.stop();
+                }
+            }
+            return;
+        }
+
         // アプリケーションレイヤーのパケット (MessagePack) をデシリアライズ
         let mut packet = match Message::from_slice(&bin) {
             Ok(p) => p,
             Err(e) => {
-                error!("McConnect パケットのデシリアライズに失敗: {}", e);
+                error!("{}", t!("gateway.packet_decode_failed", error = e));
                 return;
             }
         };
@@ -45,9 +72,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsProxySession {
             packet = match self.secure_context.unseal_message(packet) {
                 Ok(m) => m,
                 Err(e) => {
-                    error!(
-                        "パケットの復号に失敗しました (共通鍵の不一致や改ざんの可能性): {}. セッションを終了します。",
-                        e
+                    error!("{}", t!("gateway.packet_decrypt_failed", error = e));
+                    self.audit.set_close_reason_with_detail(
+                        AuditReason::PacketDecryptFailed,
+                        Some(e.to_string()),
                     );
                     ctx.stop();
                     return;
@@ -58,58 +86,66 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsProxySession {
         // コマンドごとの処理振り分け
         match packet.command {
             Command::SecureConnect => {
-                info!("SecureConnect リクエストを受信しました。ハンドシェイクを開始します。");
+                info!("{}", t!("gateway.secure_connect_received"));
                 if self.initialized {
-                    warn!(
-                        "既に初期化済みのセッションで SecureConnect を受信しました。無視します。"
-                    );
+                    warn!("{}", t!("gateway.secure_connect_duplicate"));
                     return;
                 }
                 self.handle_secure_connect(packet, ctx);
             }
             Command::Connect => {
-                error!(
-                    "暗号化されていない接続要求 (Connect) を受信しました。本サーバーはセキュア接続のみを許可します。"
+                error!("{}", t!("gateway.plain_connect_rejected"));
+                self.stop_with_error(
+                    ctx,
+                    ConnectResponsePayload::failure(ErrorCode::ProtocolMismatch, &[]),
                 );
-                self.stop_with_error(ctx, "Secure connection is required.".to_string());
-            }
-            Command::Data => {
-                if let Some(tx) = &self.tcp_tx {
-                    if tx.send(packet.payload).is_err() {
-                        error!(
-                            "TCP ターゲットへのデータ転送に失敗しました。接続が切断されている可能性があります。"
-                        );
-                        ctx.stop();
-                    }
-                } else {
-                    warn!(
-                        "Data パケットを受信しましたが、TCP 接続が確立されていません。無視します。"
-                    );
-                }
             }
+            Command::Data => self.forward_to_target(packet.payload, ctx),
             Command::Disconnect => {
-                info!("クライアントから明示的な切断要求を受信しました。");
+                info!("{}", t!("gateway.client_disconnect"));
+                self.audit.set_close_reason(AuditReason::ClientDisconnected);
                 ctx.stop();
             }
             Command::GetServerInfo => {
-                info!("サーバー情報の問い合わせ (GetServerInfo) に応答します。");
+                info!("{}", t!("gateway.server_info_request"));
+                // チャレンジ付きの問い合わせには、署名付きの公開鍵もあわせて返す (TOFU 用)
+                let server_key = packet
+                    .deserialize_payload::<ServerInfoRequestPayload>()
+                    .ok()
+                    .and_then(|req| {
+                        sign_server_key(self.server_keys.current(), &req.challenge)
+                            .map_err(|e| error!("{}", t!("gateway.sign_key_failed", error = e)))
+                            .ok()
+                    });
                 let res = ServerInfoResponsePayload {
                     server_version: env!("CARGO_PKG_VERSION").to_string(),
                     allowed_ports: self.allowed_ports.clone(),
+                    server_key,
+                    protocol_info: Some(ProtocolInfo::current()),
                 };
-                if let Ok(msg) = Message::from_payload(Command::ServerInfoResponse, &res) {
-                    if let Ok(bin) = msg.to_vec() {
-                        ctx.binary(bin);
-                    }
+                if let Ok(msg) = Message::from_payload(Command::ServerInfoResponse, &res)
+                    && let Ok(bin) = msg.to_vec()
+                {
+                    ctx.binary(bin);
                 }
             }
             Command::Ping => {
                 self.send_packet(ctx, Command::Pong, packet.payload);
             }
+            Command::Rekey => {
+                info!("{}", t!("gateway.client_rekeyed"));
+            }
+            Command::Unknown => {
+                // 新しいバージョンのクライアントが追加したコマンド。接続は維持して読み飛ばす
+                warn!("{}", t!("gateway.unknown_command"));
+            }
             _ => {
                 warn!(
-                    "未実装または未知のコマンドを受信しました: {:?}",
-                    packet.command
+                    "{}",
+                    t!(
+                        "gateway.unhandled_command",
+                        command = format!("{:?}", packet.command)
+                    )
                 );
             }
         }
@@ -117,34 +153,64 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsProxySession {
 }
 
 impl WsProxySession {
+    /// クライアントから届いたデータをターゲットの TCP 接続へ渡します。
+    fn forward_to_target(&mut self, data: Vec<u8>, ctx: &mut ws::WebsocketContext<Self>) {
+        if let Some(tx) = &self.tcp_tx {
+            let len = data.len() as u64;
+            self.stats.upload_total += len;
+            self.stats.queued_bytes.fetch_add(len, Ordering::Relaxed);
+            self.record_traffic(len);
+            if tx.send(data).is_err() {
+                error!("{}", t!("gateway.target_forward_failed"));
+                self.audit.set_close_reason(AuditReason::TargetWriteFailed);
+                ctx.stop();
+            }
+        } else {
+            warn!("{}", t!("gateway.data_before_connect"));
+        }
+    }
+
     pub(super) fn handle_secure_connect(
         &mut self,
         packet: Message,
         ctx: &mut ws::WebsocketContext<Self>,
     ) {
-        info!("SecureConnect リクエストを解析中...");
+        info!("{}", t!("gateway.secure_connect_parsing"));
 
         // 1. ハンドシェイク処理
-        let (secure_context, protocol, port) = match handle_server_handshake(
-            packet,
-            self.server_key.as_ref(),
-        ) {
+        let handshake = match server_handshake(packet, self.server_keys.as_ref()) {
             Ok(res) => res,
             Err(e) => {
-                error!(
-                    "セキュアハンドシェイクに失敗: {}. クライアントの鍵が古い可能性があります。",
-                    e
+                // 詳細な理由は監査ログにのみ記録し、クライアントには理由によらず同じ応答を返す
+                error!("{}", t!("gateway.handshake_failed", error = e));
+                self.audit.handshake_failed(e.to_string());
+                self.stop_with_error(
+                    ctx,
+                    ConnectResponsePayload::failure(ErrorCode::HandshakeFailed, &[]),
                 );
-                self.stop_with_error(ctx, format!("Handshake failed: {}", e));
                 return;
             }
         };
 
+        let (protocol, port) = (handshake.protocol, handshake.port);
         info!(
-            "ハンドシェイクに成功しました。プロトコル: {:?}, ポート: {}",
-            protocol, port
+            "{}",
+            t!(
+                "gateway.handshake_succeeded",
+                protocol = format!("{:?}", protocol),
+                port = port,
+                version = handshake.protocol_info.version
+            )
         );
-        self.secure_context = secure_context;
+        self.secure_context = handshake.context;
+        self.key_rotation = handshake.key_rotation;
+        self.protocol_info = handshake.protocol_info;
+        self.algorithm = handshake.algorithm;
+        self.key_fingerprint = handshake.key_fingerprint;
+        self.target_port = port;
+        self.target_protocol = protocol.clone();
+        self.audit
+            .handshake_succeeded(&self.key_fingerprint, port, protocol.clone());
 
         // 2. 許可されたポート/プロトコルかチェック
         let is_allowed = self
@@ -154,46 +220,71 @@ impl WsProxySession {
 
         if !is_allowed {
             error!(
-                "不許可なポートへのアクセス要求をブロックしました: {}:{:?}",
-                port, protocol
+                "{}",
+                t!(
+                    "gateway.port_blocked",
+                    port = port,
+                    protocol = format!("{:?}", protocol)
+                )
             );
-            self.stop_with_error(
-                ctx,
-                format!("Unauthorized access to port {}: {:?}", port, protocol),
+            let res = ConnectResponsePayload::failure(
+                ErrorCode::PortNotAllowed,
+                &[
+                    ("port", port.to_string()),
+                    ("protocol", format!("{:?}", protocol)),
+                ],
             );
+            self.audit
+                .handshake_rejected(ErrorCode::PortNotAllowed, res.message.clone());
+            self.stop_with_error(ctx, res);
             return;
         }
 
-        info!(
-            "ターゲット (127.0.0.1:{}) への TCP 接続を試行します...",
-            port
-        );
+        // 3. 転送量の上限を超えていないかチェック
+        if let Some(Err(exceeded)) = self.traffic.as_ref().map(|t| t.check_quota()) {
+            warn!("{}", t!("gateway.quota_rejected", reason = exceeded));
+            let res = ConnectResponsePayload::failure(ErrorCode::QuotaExceeded, &exceeded.params());
+            self.audit
+                .handshake_rejected(ErrorCode::QuotaExceeded, res.message.clone());
+            self.stop_with_error(ctx, res);
+            return;
+        }
+
+        info!("{}", t!("gateway.target_connecting", port = port));
         let target_addr = format!("127.0.0.1:{}", port);
         let session_addr = ctx.address();
+        let connect_started = Instant::now();
 
-        // 3. 非同期接続の実行
+        // 4. 非同期接続の実行
         let fut =
             actix::fut::wrap_future::<_, Self>(
                 async move { TcpStream::connect(&target_addr).await },
             )
-            .map(move |res, _act, ctx| {
+            .map(move |res, act, ctx| {
                 match res {
                     Ok(stream) => {
-                        info!("Successfully connected to target TCP server.");
+                        info!("{}", t!("gateway.target_connected"));
                         // 接続に成功したら、自分自身に TcpConnected メッセージを送って転送ループを開始
-                        session_addr.do_send(TcpConnected { stream });
+                        session_addr.do_send(TcpConnected {
+                            stream,
+                            connect_latency: connect_started.elapsed(),
+                        });
                     }
                     Err(e) => {
-                        error!("Failed to connect to target: {}", e);
-                        let res = ConnectResponsePayload {
-                            success: false,
-                            message: e.to_string(),
-                        };
+                        error!("{}", t!("gateway.target_connect_failed", error = e));
+                        let res = ConnectResponsePayload::failure(
+                            ErrorCode::TargetUnreachable,
+                            &[("reason", e.to_string())],
+                        );
+                        act.audit.set_close_reason_with_detail(
+                            AuditReason::TargetUnreachable,
+                            Some(e.to_string()),
+                        );
                         // 応答を暗号化して送信 (send_packet を使用)
-                        _act.send_packet(
+                        act.send_packet(
                             ctx,
                             Command::ConnectResponse,
-                            rmp_serde::to_vec(&res).unwrap(),
+                            encode_payload(&res).unwrap(),
                         );
                         ctx.stop();
                     }
@@ -211,6 +302,8 @@ impl WsProxySession {
 #[rtype(result = "()")]
 pub struct TcpConnected {
     pub stream: TcpStream,
+    /// ターゲットへの接続にかかった時間
+    pub connect_latency: Duration,
 }
 
 impl Handler<TcpConnected> for WsProxySession {
@@ -220,14 +313,17 @@ impl Handler<TcpConnected> for WsProxySession {
         let (mut reader, mut writer) = msg.stream.into_split();
         let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
         self.tcp_tx = Some(tx);
+        self.stats.connect_latency = Some(msg.connect_latency);
 
         // TCP への書き込みタスク
+        let queued_bytes = self.stats.queued_bytes.clone();
         tokio::spawn(async move {
             while let Some(data) = rx.recv().await {
                 if let Err(e) = writer.write_all(&data).await {
-                    error!("TCP Target write error: {}", e);
+                    error!("{}", t!("gateway.target_write_error", error = e));
                     break;
                 }
+                queued_bytes.fetch_sub(data.len() as u64, Ordering::Relaxed);
             }
         });
 
@@ -242,26 +338,63 @@ impl Handler<TcpConnected> for WsProxySession {
                         session_addr.do_send(TcpStatusMsg::Data(buf[..n].to_vec()));
                     }
                     Err(e) => {
-                        error!("TCP Target read error: {}", e);
+                        error!("{}", t!("gateway.target_read_error", error = e));
                         break;
                     }
                 }
             }
-            let _ = session_addr.do_send(TcpStatusMsg::Disconnected);
+            session_addr.do_send(TcpStatusMsg::Disconnected);
         });
 
         // 最後に、クライアントへ「準備完了 (ConnectResponse: success=true)」を送信
+        // 旧鍵で接続してきたクライアントには、新しい公開鍵もあわせて通知します
         let res = ConnectResponsePayload {
             success: true,
             message: "OK".to_string(),
+            key_rotation: self.key_rotation.take(),
+            protocol_info: Some(self.protocol_info.clone()),
+            algorithm: self.algorithm.map(|a| a.name().to_string()),
+            code: None,
+            params: Default::default(),
         };
         // この時点では SecureContext が確立されているため、暗号化されて送信されます
-        self.send_packet(
-            ctx,
-            Command::ConnectResponse,
-            rmp_serde::to_vec(&res).unwrap(),
-        );
-        info!("Handshake completed. Secure bridge established.");
+        self.send_packet(ctx, Command::ConnectResponse, encode_payload(&res).unwrap());
+        // クライアントは ConnectResponse を受信した直後に切り替えるため、以降の送受信は選んだ共通鍵暗号で行う
+        if let Some(algorithm) = self.algorithm
+            && let Err(e) = self.secure_context.switch_algorithm(algorithm)
+        {
+            error!("{}", t!("gateway.algorithm_switch_failed", error = e));
+            self.audit.set_close_reason_with_detail(
+                AuditReason::AlgorithmSwitchFailed,
+                Some(e.to_string()),
+            );
+            ctx.stop();
+            return;
+        }
+        // 対応しているクライアントには、ゲートウェイから見た統計情報を定期的に送る
+        if self.protocol_info.supports(capability::SESSION_STATS) {
+            ctx.run_interval(STATS_INTERVAL, |act, ctx| act.send_stats(ctx));
+        }
+        // 中継中のセッションの一覧へ登録し、通信量を定期的に反映する
+        if let Some(sessions) = &self.sessions {
+            sessions.open(
+                self.audit.session_id(),
+                self.peer_addr,
+                &self.key_fingerprint,
+                self.target_port,
+                self.target_protocol.clone(),
+            );
+            ctx.run_interval(STATS_INTERVAL, |act, _| {
+                if let Some(sessions) = &act.sessions {
+                    sessions.update(
+                        act.audit.session_id(),
+                        act.stats.upload_total,
+                        act.stats.download_total,
+                    );
+                }
+            });
+        }
+        info!("{}", t!("gateway.bridge_established"));
     }
 }
 
@@ -280,11 +413,14 @@ impl Handler<TcpStatusMsg> for WsProxySession {
     fn handle(&mut self, msg: TcpStatusMsg, ctx: &mut Self::Context) {
         match msg {
             TcpStatusMsg::Data(data) => {
+                self.stats.download_total += data.len() as u64;
+                self.record_traffic(data.len() as u64);
                 // send_packet を通じて暗号化して WS へ送信
                 self.send_packet(ctx, Command::Data, data);
             }
             TcpStatusMsg::Disconnected => {
-                info!("TCP Target disconnected. Closing session.");
+                info!("{}", t!("gateway.target_disconnected"));
+                self.audit.set_close_reason(AuditReason::TargetClosed);
                 ctx.stop();
             }
         }
@@ -1,4 +1,12 @@
 pub mod session;
 pub mod handlers;
+pub mod traffic;
+pub mod audit;
+pub mod registry;
 
 pub use session::WsProxySession;
+pub use audit::{AuditLog, AuditReason, AuditRecord, HandshakeOutcome, SessionAudit};
+pub use registry::{SessionEvent, SessionEventKind, SessionInfo, SessionRegistry};
+pub use traffic::{
+    QuotaExceeded, QuotaPeriod, TrafficCounter, TrafficLedger, TrafficQuota, TrafficReport,
+};
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::models::packet::Protocol;
+use crate::time::unix_now;
+
+/// 購読者が受け取りきれずに保持しておくイベントの数
+const EVENT_CAPACITY: usize = 256;
+
+/// [SessionInfo]
+/// ゲートウェイで中継中のセッション 1 つ分のスナップショットです。UI の表示に使用します。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    /// セッション ID (監査ログの `session_id` と同じ値)
+    pub id: u64,
+    /// 接続元のアドレス
+    pub peer_addr: String,
+    /// 中継を開始した時刻 (UNIX 秒)
+    pub started_at: u64,
+    /// クライアントが使用したサーバー鍵のフィンガープリント
+    pub key_fingerprint: String,
+    /// 中継先のポート
+    pub port: u16,
+    pub protocol: Protocol,
+    /// クライアントから受信し、ターゲットへ渡したバイト数
+    pub upload_total: u64,
+    /// ターゲットから受信し、クライアントへ送ったバイト数
+    pub download_total: u64,
+}
+
+/// [SessionEventKind]
+/// [SessionEvent] の種類です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionEventKind {
+    /// 中継を開始した
+    Connected,
+    /// 通信量が更新された
+    Traffic,
+    /// セッションが終了した
+    Disconnected,
+}
+
+/// [SessionEvent]
+/// セッションの開始・通信量の更新・終了の通知です。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEvent {
+    pub kind: SessionEventKind,
+    pub session: SessionInfo,
+    /// 終了した理由 (監査ログの `close_reason` と同じ値)。終了時のみ設定します。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// [SessionRegistry]
+/// ゲートウェイで中継中のセッションの一覧です。
+///
+/// セッションはターゲットとの中継を開始した時点で登録され、終了すると一覧から外れます。
+/// 通信量は [STATS_INTERVAL](super::session::STATS_INTERVAL) ごとに反映するため、一覧の値は最大でその分だけ遅れます。
+/// 変化は [SessionRegistry::subscribe] でイベントとして受け取れます。
+pub struct SessionRegistry {
+    sessions: Mutex<HashMap<u64, SessionInfo>>,
+    events: broadcast::Sender<SessionEvent>,
+}
+
+impl Default for SessionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// [open]
+    /// 中継を開始したセッションを登録します。
+    pub fn open(
+        &self,
+        id: u64,
+        peer_addr: Option<SocketAddr>,
+        key_fingerprint: &str,
+        port: u16,
+        protocol: Protocol,
+    ) {
+        let info = SessionInfo {
+            id,
+            peer_addr: peer_addr
+                .map(|a| a.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            started_at: unix_now(),
+            key_fingerprint: key_fingerprint.to_string(),
+            port,
+            protocol,
+            upload_total: 0,
+            download_total: 0,
+        };
+        self.sessions.lock().unwrap().insert(id, info.clone());
+        self.publish(SessionEventKind::Connected, info, None);
+    }
+
+    /// [update]
+    /// セッションの通信量を反映します。前回から変化がない場合はイベントを送りません。
+    pub fn update(&self, id: u64, upload_total: u64, download_total: u64) {
+        let info = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let Some(info) = sessions.get_mut(&id) else {
+                return;
+            };
+            if info.upload_total == upload_total && info.download_total == download_total {
+                return;
+            }
+            info.upload_total = upload_total;
+            info.download_total = download_total;
+            info.clone()
+        };
+        self.publish(SessionEventKind::Traffic, info, None);
+    }
+
+    /// [close]
+    /// 終了したセッションを最終的な通信量とともに一覧から外します。登録されていないセッションの場合は何もしません。
+    pub fn close(&self, id: u64, upload_total: u64, download_total: u64, reason: &str) {
+        let Some(mut info) = self.sessions.lock().unwrap().remove(&id) else {
+            return;
+        };
+        info.upload_total = upload_total;
+        info.download_total = download_total;
+        self.publish(
+            SessionEventKind::Disconnected,
+            info,
+            Some(reason.to_string()),
+        );
+    }
+
+    /// [list]
+    /// 中継中のセッションの一覧を、セッション ID の順に返します。
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut list: Vec<SessionInfo> = self.sessions.lock().unwrap().values().cloned().collect();
+        list.sort_by_key(|s| s.id);
+        list
+    }
+
+    /// 中継中のセッション数を返します。
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// [subscribe]
+    /// セッションのイベントを購読します。購読を開始した後のイベントのみ受け取ります。
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, kind: SessionEventKind, session: SessionInfo, reason: Option<String>) {
+        // 購読者がいない場合の送信エラーは無視する
+        let _ = self.events.send(SessionEvent {
+            kind,
+            session,
+            reason,
+        });
+    }
+}
@@ -1,11 +1,62 @@
 use actix::prelude::*;
 use actix_web_actors::ws;
 use tokio::sync::mpsc;
-use crate::models::packet::{AllowedPort, Message, Command, ConnectResponsePayload};
-use crate::encryption::{SecureContext, RsaKeyPair};
+use crate::models::packet::{
+    AllowedPort, Message, Command, ConnectResponsePayload, KeyRotation, Protocol, ProtocolInfo, StatsPayload,
+    encode_payload,
+};
+use crate::encryption::{SecureContext, ServerKeyring, SymmetricAlgorithm};
+use crate::t;
+use super::audit::{AuditLog, AuditReason, SessionAudit};
+use super::registry::SessionRegistry;
+use super::traffic::TrafficLedger;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
+/// セッションの統計情報をクライアントへ送る間隔
+pub const STATS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// [SessionStats]
+/// ゲートウェイが 1 セッションについて記録する通信量と遅延です。
+/// 向きはクライアントから見た名前に揃えており、クライアントの `TunnelStats` と直接比較できます。
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    /// クライアントから受信し、ターゲットへ渡したバイト数
+    pub upload_total: u64,
+    /// ターゲットから受信し、クライアントへ送ったバイト数
+    pub download_total: u64,
+    /// ターゲットへの TCP 接続にかかった時間
+    pub connect_latency: Option<Duration>,
+    /// ターゲットへの書き込みを待っているバイト数。書き込みタスクと共有します。
+    pub queued_bytes: Arc<AtomicU64>,
+    /// 前回の報告時点の累計 (速度の計算に使用)
+    last_upload: u64,
+    last_download: u64,
+}
+
+impl SessionStats {
+    /// [report]
+    /// 前回の報告からの経過時間で速度を計算し、クライアントへ送るペイロードを作成します。
+    pub fn report(&mut self, elapsed: Duration) -> StatsPayload {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let upload_speed = ((self.upload_total - self.last_upload) as f64 / secs) as u64;
+        let download_speed = ((self.download_total - self.last_download) as f64 / secs) as u64;
+        self.last_upload = self.upload_total;
+        self.last_download = self.download_total;
+        StatsPayload {
+            upload_total: self.upload_total,
+            download_total: self.download_total,
+            upload_speed,
+            download_speed,
+            rtt_ms: None,
+            connect_latency_ms: self.connect_latency.map(|d| d.as_millis() as u64),
+            queue_depth: Some(self.queued_bytes.load(Ordering::Relaxed)),
+        }
+    }
+}
+
 /// [WsProxySession]
 /// ゲートウェイ（サーバー）側で、WebSocket接続1つにつき、1つ生成されるアクターです。
 /// 
@@ -23,55 +74,156 @@ pub struct WsProxySession {
 
     /// セッションの暗号化状態を管理するコンテキスト
     pub secure_context: SecureContext,
-    /// サーバー自身のキーペア（秘密鍵を使用してクライアントからの共通鍵を復号する）
-    pub server_key: Arc<RsaKeyPair>,
+    /// サーバー自身の鍵（秘密鍵を使用してクライアントからの共通鍵を復号する）。
+    /// 猶予期間中の旧鍵も含みます。
+    pub server_keys: Arc<ServerKeyring>,
+    /// クライアントが旧鍵で接続してきた場合に、ConnectResponse で返す新しい公開鍵の通知
+    pub key_rotation: Option<KeyRotation>,
+    /// クライアントと合意したプロトコルバージョンと機能。ハンドシェイク前は旧実装として扱います。
+    pub protocol_info: ProtocolInfo,
+    /// ConnectResponse の送信後に切り替える共通鍵暗号。クライアントが候補を送ってこなかった場合は None です。
+    pub algorithm: Option<SymmetricAlgorithm>,
     /// トンネルの初期化（ターゲットへの接続確立）が完了しているかどうか。
     pub initialized: bool,
+    /// このセッションの通信量と遅延
+    pub stats: SessionStats,
+    /// ゲートウェイ全体の転送量の記録。None の場合は記録も上限の確認も行いません。
+    pub traffic: Option<Arc<TrafficLedger>>,
+    /// クライアントが使用したサーバー鍵のフィンガープリント (ハンドシェイク後に設定)
+    pub key_fingerprint: String,
+    /// クライアントが要求したターゲットポート (ハンドシェイク後に設定)
+    pub target_port: u16,
+    /// クライアントが要求したプロトコル (ハンドシェイク後に設定)
+    pub target_protocol: Protocol,
+    /// このセッションの監査記録。セッションの終了時に監査ログへ書き出します。
+    pub audit: SessionAudit,
+    /// 中継中のセッションの一覧。None の場合は登録しません。
+    pub sessions: Option<Arc<SessionRegistry>>,
+    /// 接続元のアドレス
+    pub peer_addr: Option<SocketAddr>,
 }
 
 impl WsProxySession {
     /// 許可ポート情報とサーバーキーを保持した新しいセッションアクターを作成します。
-    pub fn new(allowed_ports: Vec<AllowedPort>, server_key: Arc<RsaKeyPair>) -> Self {
+    /// `traffic` を指定すると、転送量を記録し、上限を超えている場合は接続を拒否します。
+    pub fn new(
+        allowed_ports: Vec<AllowedPort>,
+        server_keys: Arc<ServerKeyring>,
+        traffic: Option<Arc<TrafficLedger>>,
+    ) -> Self {
         Self {
             tcp_tx: None,
             allowed_ports,
             secure_context: SecureContext::new(),
-            server_key,
+            server_keys,
+            key_rotation: None,
+            protocol_info: ProtocolInfo::legacy(),
+            algorithm: None,
             initialized: false,
+            stats: SessionStats::default(),
+            traffic,
+            key_fingerprint: String::new(),
+            target_port: 0,
+            target_protocol: Protocol::TCP,
+            audit: SessionAudit::default(),
+            sessions: None,
+            peer_addr: None,
+        }
+    }
+
+    /// [with_audit]
+    /// セッションの終了時に、接続元やハンドシェイクの結果などを `audit` へ書き出すようにします。
+    pub fn with_audit(
+        mut self,
+        audit: Option<Arc<AuditLog>>,
+        peer_addr: Option<SocketAddr>,
+    ) -> Self {
+        self.audit = SessionAudit::new(audit, peer_addr);
+        self
+    }
+
+    /// [with_sessions]
+    /// 中継を開始した時点で `sessions` へ登録し、通信量と終了を反映するようにします。
+    pub fn with_sessions(
+        mut self,
+        sessions: Option<Arc<SessionRegistry>>,
+        peer_addr: Option<SocketAddr>,
+    ) -> Self {
+        self.sessions = sessions;
+        self.peer_addr = peer_addr;
+        self
+    }
+
+    /// [record_traffic]
+    /// このセッションの転送量をゲートウェイ全体の記録に加えます。
+    pub fn record_traffic(&self, bytes: u64) {
+        if let Some(traffic) = &self.traffic {
+            traffic.record(&self.key_fingerprint, self.target_port, bytes);
         }
     }
 
     /// [send_packet]
     /// コンテンツ（コマンドとデータ）を受け取り、必要に応じて暗号化して
     /// WebSocket クライアントへバイナリデータとして送信します。
-    pub fn send_packet(&self, ctx: &mut ws::WebsocketContext<Self>, command: Command, payload: Vec<u8>) {
+    pub fn send_packet(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        command: Command,
+        payload: Vec<u8>,
+    ) {
         let msg = Message::new(command, payload);
-        // コンテキストを使用してペイロードを暗号化
-        let msg = match self.secure_context.seal_message(msg) {
-            Ok(m) => m,
+        // コンテキストを使用してペイロードを暗号化 (Data はコンパクトなフレームが有効ならフレーム形式になる)
+        match self.secure_context.seal_to_bytes(msg) {
+            Ok(bin) => ctx.binary(bin),
             Err(e) => {
-                log::error!("Packet encryption error: {}", e);
+                log::error!("{}", t!("gateway.packet_encrypt_error", error = e));
                 return;
             }
-        };
-        
-        match msg.to_vec() {
-            Ok(bin) => ctx.binary(bin),
-            Err(e) => log::error!("Packet serialization error: {}", e),
+        }
+
+        // 送信量・経過時間が上限に達していれば、セッションを維持したまま共通鍵を更新する
+        match self.secure_context.take_rekey() {
+            Ok(Some(bin)) => ctx.binary(bin),
+            Ok(None) => {}
+            Err(e) => {
+                log::error!("{}", t!("gateway.rekey_error", error = e));
+                self.audit
+                    .set_close_reason_with_detail(AuditReason::RekeyFailed, Some(e.to_string()));
+                ctx.stop();
+            }
+        }
+    }
+
+    /// [send_stats]
+    /// 記録している統計情報を暗号化してクライアントへ送信します。
+    pub fn send_stats(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let report = self.stats.report(STATS_INTERVAL);
+        match encode_payload(&report) {
+            Ok(payload) => self.send_packet(ctx, Command::Stats, payload),
+            Err(e) => log::error!("{}", t!("gateway.stats_encode_error", error = e)),
         }
     }
 
     /// [stop_with_error]
     /// 接続失敗などの致命的なエラーが発生した際に、
     /// クライアントへ失敗パケットを送信した上で、セッション（アクター）を終了します。
-    pub fn stop_with_error(&self, ctx: &mut ws::WebsocketContext<Self>, message: String) {
-        let res = ConnectResponsePayload { success: false, message: message.clone() };
-        if let Ok(msg) = Message::from_payload(Command::ConnectResponse, &res) {
-            if let Ok(bin) = msg.to_vec() {
-                ctx.binary(bin);
-            }
+    /// `res` は [ConnectResponsePayload::failure] で作成し、クライアントが理由を判断・翻訳できるようにします。
+    pub fn stop_with_error(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        res: ConnectResponsePayload,
+    ) {
+        // ハンドシェイク後であれば暗号化して送信し、クライアントが拒否理由を読めるようにする
+        if let Ok(payload) = encode_payload(&res) {
+            self.send_packet(ctx, Command::ConnectResponse, payload);
         }
-        log::error!("Closing session due to error: {}", message);
+        log::error!("{}", t!("gateway.closing_with_error", reason = res.message));
+        let reason = res
+            .code
+            .map(AuditReason::from)
+            .unwrap_or(AuditReason::Unknown);
+        self.audit
+            .set_close_reason_with_detail(reason, Some(res.message));
         ctx.stop();
     }
 }
@@ -82,12 +234,13 @@ impl Actor for WsProxySession {
 
     /// アクター（接続）が開始された時に呼ばれます。
     fn started(&mut self, ctx: &mut Self::Context) {
-        log::info!("WebSocket session started. Waiting for SecureConnect packet...");
+        log::info!("{}", t!("gateway.session_started", id = self.audit.session_id()));
         
         // 30秒以内にハンドシェイクが完了しない場合は強制切断
         ctx.run_later(Duration::from_secs(30), |act, ctx| {
             if !act.initialized {
-                log::warn!("Handshake timeout (30s). Closing connection.");
+                log::warn!("{}", t!("gateway.handshake_timeout"));
+                act.audit.set_close_reason(AuditReason::HandshakeTimeout);
                 ctx.stop();
             }
         });
@@ -95,8 +248,24 @@ impl Actor for WsProxySession {
 
     /// アクターが停止する直前に呼ばれます。
     fn stopped(&mut self, _ctx: &mut Self::Context) {
-        log::info!("WebSocket session stopped. Cleaning up resources...");
+        log::info!(
+            "{}",
+            t!("gateway.session_stopped", id = self.audit.session_id())
+        );
         // 備考: tcp_tx がここでドロップされることで、TCP書き込みループの rx 側が閉じ、
         // 関連する tokio タスクも自動的に終了する仕組みになっています。
+        if let Some(traffic) = &self.traffic {
+            traffic.flush();
+        }
+        self.audit
+            .finish(self.stats.upload_total, self.stats.download_total);
+        if let Some(sessions) = &self.sessions {
+            sessions.close(
+                self.audit.session_id(),
+                self.stats.upload_total,
+                self.stats.download_total,
+                self.audit.close_reason().as_str(),
+            );
+        }
     }
 }
@@ -0,0 +1,382 @@
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::encryption::CryptoError;
+use crate::i18n;
+use crate::models::packet::ErrorCode;
+use crate::t;
+
+/// 転送量をファイルへ書き出す最短の間隔
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// [TrafficQuota]
+/// ゲートウェイ全体で 1 日・1 か月に転送できるバイト数の上限です。日付は UTC で区切ります。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficQuota {
+    /// 1 日あたりの上限。None の場合は無制限です。
+    pub daily_bytes: Option<u64>,
+    /// 1 か月あたりの上限。None の場合は無制限です。
+    pub monthly_bytes: Option<u64>,
+}
+
+/// [QuotaExceeded]
+/// 転送量が上限に達していることを表すエラーです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    /// 上限に達した期間
+    pub period: QuotaPeriod,
+    /// 期間内の転送バイト数
+    pub used: u64,
+    /// 期間内の上限
+    pub limit: u64,
+}
+
+/// [QuotaPeriod]
+/// 転送量の上限の期間です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+impl QuotaExceeded {
+    /// [params]
+    /// クライアントへ返す ConnectResponse の引数を返します。
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let period = match self.period {
+            QuotaPeriod::Daily => "daily",
+            QuotaPeriod::Monthly => "monthly",
+        };
+        vec![
+            ("period", period.to_string()),
+            ("used", self.used.to_string()),
+            ("limit", self.limit.to_string()),
+        ]
+    }
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params = self.params();
+        let key = ErrorCode::QuotaExceeded.message_key(&params);
+        f.write_str(&i18n::tr(key, &params))
+    }
+}
+
+/// [TrafficCounter]
+/// 累計と、当日・当月分の転送量 (送受信の合計) です。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficCounter {
+    /// 記録を開始してからの累計バイト数
+    pub total_bytes: u64,
+    /// `day` (YYYY-MM-DD) の転送バイト数
+    pub day: String,
+    pub day_bytes: u64,
+    /// `month` (YYYY-MM) の転送バイト数
+    pub month: String,
+    pub month_bytes: u64,
+}
+
+impl TrafficCounter {
+    fn add(&mut self, bytes: u64, today: &str, this_month: &str) {
+        self.roll_over(today, this_month);
+        self.total_bytes += bytes;
+        self.day_bytes += bytes;
+        self.month_bytes += bytes;
+    }
+
+    /// 日付・月が変わっていれば当日・当月分をリセットします。
+    fn roll_over(&mut self, today: &str, this_month: &str) {
+        if self.day != today {
+            self.day = today.to_string();
+            self.day_bytes = 0;
+        }
+        if self.month != this_month {
+            self.month = this_month.to_string();
+            self.month_bytes = 0;
+        }
+    }
+}
+
+/// [TrafficReport]
+/// 永続化する転送量の記録です。CLI や管理用エンドポイントでもこの形式で返します。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrafficReport {
+    /// ゲートウェイ全体の転送量
+    #[serde(default)]
+    pub total: TrafficCounter,
+    /// クライアントが使用したサーバー鍵 (フィンガープリント) ごとの転送量
+    ///
+    /// クライアントは個別の鍵を持たず、同じサーバー鍵を使うクライアントはすべて同じ項目に合算されます。
+    /// クライアントごとの内訳ではなく、鍵のローテーション前後でどちらの鍵が使われたかを確認するためのものです。
+    #[serde(default)]
+    pub keys: BTreeMap<String, TrafficCounter>,
+    /// 許可ポートごとの転送量
+    #[serde(default)]
+    pub ports: BTreeMap<u16, TrafficCounter>,
+    /// 上限の設定 (表示用。読み込み時は無視します)
+    #[serde(default)]
+    pub quota: TrafficQuota,
+}
+
+struct LedgerState {
+    report: TrafficReport,
+    dirty: bool,
+    last_saved: Instant,
+}
+
+/// [TrafficLedger]
+/// ゲートウェイの転送量を、クライアントの鍵ごと・許可ポートごとに記録します。
+///
+/// 記録は JSON ファイルへ定期的に書き出され、再起動後も引き継がれます。書き出しは専用のスレッドで行うため、
+/// [TrafficLedger::record] や [TrafficLedger::flush] を呼び出したセッションのアクターはファイル I/O を待ちません。
+/// 上限 ([TrafficQuota]) を超えている間は、新しい接続を [TrafficLedger::check_quota] で拒否します。
+///
+/// 鍵ごとの転送量はサーバー鍵単位で、クライアントを区別しません ([TrafficReport::keys])。
+pub struct TrafficLedger {
+    quota: TrafficQuota,
+    state: Mutex<LedgerState>,
+    /// ファイルへの書き出しを行うスレッド。ファイルへ保存しない場合は None です。
+    writer: Option<LedgerWriter>,
+}
+
+/// [LedgerWriter]
+/// 記録のスナップショットを受け取り、ファイルへ書き出すスレッドです。
+/// 書き込みが追いつかない間に届いたスナップショットは、最新のものだけを書き出します。
+struct LedgerWriter {
+    tx: Option<mpsc::Sender<TrafficReport>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LedgerWriter {
+    fn spawn(path: PathBuf) -> Result<Self, CryptoError> {
+        let (tx, rx) = mpsc::channel::<TrafficReport>();
+        let handle = std::thread::Builder::new()
+            .name("traffic-ledger".to_string())
+            .spawn(move || {
+                while let Ok(mut report) = rx.recv() {
+                    while let Ok(newer) = rx.try_recv() {
+                        report = newer;
+                    }
+                    write_report(&path, &report);
+                }
+            })?;
+        Ok(Self {
+            tx: Some(tx),
+            handle: Some(handle),
+        })
+    }
+
+    fn send(&self, report: TrafficReport) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(report);
+        }
+    }
+}
+
+impl Drop for LedgerWriter {
+    /// 送信済みのスナップショットを書き終えるまで待ちます。
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl TrafficLedger {
+    /// [open]
+    /// ファイルから記録を読み込みます。ファイルがない場合は空の記録から始めます。
+    pub fn open(path: impl AsRef<Path>, quota: TrafficQuota) -> Result<Self, CryptoError> {
+        let path = path.as_ref().to_path_buf();
+        let report = Self::load(&path)?;
+        let writer = LedgerWriter::spawn(path)?;
+        Ok(Self::with_report(Some(writer), quota, report))
+    }
+
+    /// [in_memory]
+    /// ファイルへ保存しない記録を作成します。テストや一時的なゲートウェイで使用します。
+    pub fn in_memory(quota: TrafficQuota) -> Self {
+        Self::with_report(None, quota, TrafficReport::default())
+    }
+
+    /// [load]
+    /// 保存済みの記録を読み込みます。ファイルがない場合は空の記録を返します。
+    pub fn load(path: impl AsRef<Path>) -> Result<TrafficReport, CryptoError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(TrafficReport::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        let report =
+            serde_json::from_str(&content).map_err(|e| t!("traffic.parse_failed", error = e))?;
+        Ok(report)
+    }
+
+    fn with_report(
+        writer: Option<LedgerWriter>,
+        quota: TrafficQuota,
+        report: TrafficReport,
+    ) -> Self {
+        Self {
+            writer,
+            quota,
+            state: Mutex::new(LedgerState {
+                report,
+                dirty: false,
+                last_saved: Instant::now(),
+            }),
+        }
+    }
+
+    /// [record]
+    /// `key` (サーバー鍵のフィンガープリント) と `port` への転送を記録します。
+    /// 前回の保存から一定時間が経っていれば、書き出し用のスレッドへ記録を渡します。
+    pub fn record(&self, key: &str, port: u16, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let (today, this_month) = utc_period(SystemTime::now());
+        let mut state = self.state.lock().unwrap();
+        let report = &mut state.report;
+        report.total.add(bytes, &today, &this_month);
+        report
+            .keys
+            .entry(key.to_string())
+            .or_default()
+            .add(bytes, &today, &this_month);
+        report
+            .ports
+            .entry(port)
+            .or_default()
+            .add(bytes, &today, &this_month);
+        state.dirty = true;
+
+        if state.last_saved.elapsed() >= SAVE_INTERVAL {
+            self.save_locked(&mut state);
+        }
+    }
+
+    /// [check_quota]
+    /// 当日・当月の転送量が上限に達していないかを確認します。
+    /// 達している場合は、どの期間の上限に達したかをエラーとして返します。
+    pub fn check_quota(&self) -> Result<(), QuotaExceeded> {
+        let (today, this_month) = utc_period(SystemTime::now());
+        let mut total = self.state.lock().unwrap().report.total.clone();
+        total.roll_over(&today, &this_month);
+
+        if let Some(limit) = self.quota.daily_bytes
+            && total.day_bytes >= limit
+        {
+            return Err(QuotaExceeded {
+                period: QuotaPeriod::Daily,
+                used: total.day_bytes,
+                limit,
+            });
+        }
+        if let Some(limit) = self.quota.monthly_bytes
+            && total.month_bytes >= limit
+        {
+            return Err(QuotaExceeded {
+                period: QuotaPeriod::Monthly,
+                used: total.month_bytes,
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// [report]
+    /// 現在の記録を返します。日付が変わっている項目は当日・当月分を 0 として返します。
+    pub fn report(&self) -> TrafficReport {
+        let (today, this_month) = utc_period(SystemTime::now());
+        let mut report = self.state.lock().unwrap().report.clone();
+        report.total.roll_over(&today, &this_month);
+        for counter in report.keys.values_mut().chain(report.ports.values_mut()) {
+            counter.roll_over(&today, &this_month);
+        }
+        report.quota = self.quota;
+        report
+    }
+
+    /// [flush]
+    /// 未保存の記録があれば、書き出し用のスレッドへ渡します。書き込みの完了は待ちません。
+    pub fn flush(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.dirty {
+            self.save_locked(&mut state);
+        }
+    }
+
+    fn save_locked(&self, state: &mut LedgerState) {
+        state.last_saved = Instant::now();
+        state.dirty = false;
+        if let Some(writer) = &self.writer {
+            writer.send(state.report.clone());
+        }
+    }
+}
+
+/// 記録を JSON としてファイルへ書き出します。書き出し用のスレッドで呼び出します。
+fn write_report(path: &Path, report: &TrafficReport) {
+    let json = match serde_json::to_string_pretty(report) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("{}", t!("traffic.encode_failed", error = e));
+            return;
+        }
+    };
+    // 書き込み途中で終了しても記録が壊れないよう、一時ファイルに書いてから置き換える
+    let tmp = path.with_extension("json.tmp");
+    let result = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&tmp, json))
+        .and_then(|_| std::fs::rename(&tmp, path));
+    if let Err(e) = result {
+        warn!(
+            "{}",
+            t!("traffic.save_failed", path = path.display(), error = e)
+        );
+    }
+}
+
+impl Drop for TrafficLedger {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// 時刻を UTC の日付 (YYYY-MM-DD) と月 (YYYY-MM) に変換します。
+fn utc_period(time: SystemTime) -> (String, String) {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+    let (year, month, day) = civil_from_days(days);
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!("{:04}-{:02}", year, month),
+    )
+}
+
+/// 1970-01-01 からの日数をグレゴリオ暦の年月日に変換します。
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,347 @@
+use actix::prelude::*;
+use actix_web_actors::ws;
+use log::{error, info, warn};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::client_session::{CloseStream, RelayClientSession, RelayDeliver};
+use super::registry::{AgentStats, RelayRegistry, agent_id};
+use crate::encryption::{
+    RsaPublicKey, SERVER_KEY_CHALLENGE_LEN, fingerprint, verify_agent_registration,
+};
+use crate::models::packet::{Command, ConnectResponsePayload, Message};
+use crate::models::relay::{
+    AgentChallengePayload, AgentRegisterPayload, RelayStreamPayload, new_relay_frame,
+    parse_relay_frame,
+};
+use crate::t;
+
+/// [RelayAgentSession]
+/// リレー側で、ホストエージェントの WebSocket 接続 1 つにつき 1 つ生成されるアクターです。
+///
+/// 接続直後にリレー ID とチャレンジを `AgentChallenge` で送り、エージェントが登録用の署名付きの公開鍵
+/// (`AgentRegister`) を返したらそのフィンガープリントで登録します。以降はクライアントのストリームをこの接続上に多重化します。
+/// クライアントとエージェントの間のバイナリは暗号化されたまま中継し、復号はしません。
+pub struct RelayAgentSession {
+    registry: Arc<RelayRegistry>,
+    /// 鍵の所持を確認するために送ったチャレンジ
+    challenge: Vec<u8>,
+    /// 登録済みのエージェント ID。登録前は None です。
+    agent_id: Option<String>,
+    /// 登録後の中継状況
+    stats: Option<Arc<AgentStats>>,
+    /// 中継中のクライアントのストリーム
+    streams: HashMap<u32, Addr<RelayClientSession>>,
+}
+
+impl RelayAgentSession {
+    pub fn new(registry: Arc<RelayRegistry>) -> Self {
+        let mut challenge = vec![0u8; SERVER_KEY_CHALLENGE_LEN];
+        OsRng.fill_bytes(&mut challenge);
+        Self {
+            registry,
+            challenge,
+            agent_id: None,
+            stats: None,
+            streams: HashMap::new(),
+        }
+    }
+
+    fn send_message(
+        &self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        msg: Result<Message, crate::encryption::CryptoError>,
+    ) {
+        match msg.and_then(|m| m.to_vec()) {
+            Ok(bin) => ctx.binary(bin),
+            Err(e) => error!("{}", t!("relay.encode_failed", error = e)),
+        }
+    }
+
+    /// エージェントが返した署名付きの公開鍵を検証し、登録します。
+    fn handle_registration(&mut self, packet: Message, ctx: &mut ws::WebsocketContext<Self>) {
+        let result = packet
+            .deserialize_payload::<AgentRegisterPayload>()
+            .and_then(|req| {
+                verify_agent_registration(
+                    &req.signed_key,
+                    self.registry.relay_id(),
+                    &self.challenge,
+                )
+            });
+        let public_key = match result {
+            Ok(key) => key,
+            Err(e) => {
+                warn!("{}", t!("relay.registration_invalid", error = e));
+                self.reject_registration(ctx, t!("relay.registration_failed"));
+                return;
+            }
+        };
+        // 使い捨ての短い鍵で登録を繰り返せないよう、鍵長の下限を確認する
+        let key_check = RsaPublicKey::from_der(&public_key)
+            .map_err(|e| t!("relay.registration_invalid", error = e))
+            .and_then(|key| self.registry.check_agent_key(&key));
+        if let Err(reason) = key_check {
+            warn!(
+                "{}",
+                t!(
+                    "relay.registration_rejected",
+                    agent = agent_id(&fingerprint(&public_key)),
+                    reason = reason
+                )
+            );
+            self.reject_registration(ctx, reason);
+            return;
+        }
+
+        let id = agent_id(&fingerprint(&public_key));
+        let (stats, previous) = match self.registry.register(id.clone(), ctx.address()) {
+            Ok(v) => v,
+            Err(reason) => {
+                warn!(
+                    "{}",
+                    t!("relay.registration_rejected", agent = id, reason = reason)
+                );
+                self.reject_registration(ctx, reason);
+                return;
+            }
+        };
+        if let Some(previous) = previous {
+            info!("{}", t!("relay.agent_reconnected", agent = id));
+            previous.do_send(ReplacedByNewSession);
+        }
+        info!("{}", t!("relay.agent_registered", agent = id));
+        self.agent_id = Some(id.clone());
+        self.stats = Some(stats);
+
+        let res = ConnectResponsePayload {
+            success: true,
+            message: id,
+            key_rotation: None,
+            protocol_info: None,
+            algorithm: None,
+            code: None,
+            params: Default::default(),
+        };
+        self.send_message(ctx, Message::from_payload(Command::ConnectResponse, &res));
+    }
+
+    /// [enforce_byte_quota]
+    /// 転送量が上限に達していれば、ストリームをクライアントとエージェントの両方で閉じます。
+    fn enforce_byte_quota(&mut self, stream_id: u32, ctx: &mut ws::WebsocketContext<Self>) {
+        let Some(stats) = &self.stats else {
+            return;
+        };
+        if !self.registry.byte_quota_exhausted(stats) {
+            return;
+        }
+        if let Some(client) = self.streams.remove(&stream_id) {
+            warn!(
+                "{}",
+                t!(
+                    "relay.byte_quota_exceeded",
+                    agent = self.agent_id.as_deref().unwrap_or_default(),
+                    stream = stream_id
+                )
+            );
+            client.do_send(CloseStream);
+            let payload = RelayStreamPayload { stream_id };
+            self.send_message(ctx, Message::from_payload(Command::RelayClose, &payload));
+        }
+    }
+
+    /// 登録の失敗をエージェントへ通知し、接続を閉じます。
+    fn reject_registration(&self, ctx: &mut ws::WebsocketContext<Self>, message: String) {
+        let res = ConnectResponsePayload {
+            success: false,
+            message,
+            key_rotation: None,
+            protocol_info: None,
+            algorithm: None,
+            code: None,
+            params: Default::default(),
+        };
+        self.send_message(ctx, Message::from_payload(Command::ConnectResponse, &res));
+        ctx.stop();
+    }
+}
+
+impl Actor for RelayAgentSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("{}", t!("relay.agent_connected"));
+        let req = AgentChallengePayload {
+            relay_id: self.registry.relay_id().to_string(),
+            challenge: self.challenge.clone(),
+        };
+        self.send_message(ctx, Message::from_payload(Command::AgentChallenge, &req));
+
+        // 30秒以内に登録が完了しない場合は強制切断
+        ctx.run_later(Duration::from_secs(30), |act, ctx| {
+            if act.agent_id.is_none() {
+                warn!("{}", t!("relay.registration_timeout"));
+                ctx.stop();
+            }
+        });
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        if let Some(id) = &self.agent_id {
+            info!("{}", t!("relay.agent_disconnected", agent = id));
+            self.registry.unregister(id, &ctx.address());
+        }
+        // エージェントがいなくなったストリームはすべて閉じる
+        for (_, client) in self.streams.drain() {
+            client.do_send(CloseStream);
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for RelayAgentSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let bin = match msg {
+            Ok(ws::Message::Binary(bin)) => bin,
+            Ok(ws::Message::Ping(p)) => {
+                ctx.pong(&p);
+                return;
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+                return;
+            }
+            _ => return,
+        };
+
+        // クライアント宛てのバイナリは中身を見ずにそのまま渡す
+        if let Some((stream_id, data)) = parse_relay_frame(&bin) {
+            let Some(stats) = &self.stats else {
+                return;
+            };
+            if let Some(client) = self.streams.get(&stream_id) {
+                stats
+                    .bytes_to_clients
+                    .fetch_add(data.len() as u64, Ordering::Relaxed);
+                client.do_send(RelayDeliver(data.to_vec()));
+                self.enforce_byte_quota(stream_id, ctx);
+            }
+            return;
+        }
+
+        let packet = match Message::from_slice(&bin) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("{}", t!("relay.decode_failed", error = e));
+                return;
+            }
+        };
+        match packet.command {
+            Command::AgentRegister if self.agent_id.is_none() => {
+                self.handle_registration(packet, ctx);
+            }
+            Command::RelayClose => {
+                if let Ok(payload) = packet.deserialize_payload::<RelayStreamPayload>()
+                    && let Some(client) = self.streams.remove(&payload.stream_id)
+                {
+                    client.do_send(CloseStream);
+                }
+            }
+            _ => {
+                warn!(
+                    "{}",
+                    t!(
+                        "relay.unexpected_command",
+                        command = format!("{:?}", packet.command)
+                    )
+                );
+            }
+        }
+    }
+}
+
+/// [OpenStream]
+/// クライアントがリレーに接続したことをエージェントのセッションへ伝えます。
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct OpenStream {
+    pub stream_id: u32,
+    pub client: Addr<RelayClientSession>,
+}
+
+impl Handler<OpenStream> for RelayAgentSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: OpenStream, ctx: &mut Self::Context) {
+        self.streams.insert(msg.stream_id, msg.client);
+        let payload = RelayStreamPayload {
+            stream_id: msg.stream_id,
+        };
+        self.send_message(ctx, Message::from_payload(Command::RelayOpen, &payload));
+    }
+}
+
+/// [ClientData]
+/// クライアントから届いたバイナリ (暗号化済み) をエージェントへ転送します。
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ClientData {
+    pub stream_id: u32,
+    pub data: Vec<u8>,
+}
+
+impl Handler<ClientData> for RelayAgentSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: ClientData, ctx: &mut Self::Context) {
+        if self.streams.contains_key(&msg.stream_id) {
+            if let Some(stats) = &self.stats {
+                stats
+                    .bytes_to_agent
+                    .fetch_add(msg.data.len() as u64, Ordering::Relaxed);
+            }
+            ctx.binary(new_relay_frame(msg.stream_id, &msg.data));
+            self.enforce_byte_quota(msg.stream_id, ctx);
+        }
+    }
+}
+
+/// [ClientClosed]
+/// クライアントの WebSocket 接続が終了したことを伝えます。
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ClientClosed {
+    pub stream_id: u32,
+}
+
+impl Handler<ClientClosed> for RelayAgentSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: ClientClosed, ctx: &mut Self::Context) {
+        // エージェント側から閉じたストリームは既に取り除かれている
+        if self.streams.remove(&msg.stream_id).is_some() {
+            let payload = RelayStreamPayload {
+                stream_id: msg.stream_id,
+            };
+            self.send_message(ctx, Message::from_payload(Command::RelayClose, &payload));
+        }
+    }
+}
+
+/// [ReplacedByNewSession]
+/// 同じ鍵のエージェントが新しく登録されたため、このセッションを終了させます。
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReplacedByNewSession;
+
+impl Handler<ReplacedByNewSession> for RelayAgentSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ReplacedByNewSession, ctx: &mut Self::Context) {
+        ctx.stop();
+    }
+}
@@ -0,0 +1,99 @@
+use actix::prelude::*;
+use actix_web_actors::ws;
+use log::info;
+use std::sync::Arc;
+
+use super::agent_session::{ClientClosed, ClientData, OpenStream, RelayAgentSession};
+use super::registry::AgentStats;
+use crate::t;
+
+/// [RelayClientSession]
+/// リレー側で、クライアントの WebSocket 接続 1 つにつき 1 つ生成されるアクターです。
+///
+/// クライアントから届いたバイナリをストリーム ID 付きでエージェントへ、
+/// エージェントから届いたバイナリをそのままクライアントへ渡します。
+/// クライアントにとっては、通常のゲートウェイに接続しているのと区別がつきません。
+pub struct RelayClientSession {
+    agent: Addr<RelayAgentSession>,
+    stream_id: u32,
+    /// エージェントの中継状況。終了時にストリーム数を戻します。
+    stats: Arc<AgentStats>,
+}
+
+impl RelayClientSession {
+    /// [RelayRegistry::open_stream](super::RelayRegistry::open_stream) で予約したストリームのセッションを作成します。
+    pub fn new(agent: Addr<RelayAgentSession>, stream_id: u32, stats: Arc<AgentStats>) -> Self {
+        Self {
+            agent,
+            stream_id,
+            stats,
+        }
+    }
+}
+
+impl Actor for RelayClientSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("{}", t!("relay.stream_started", stream = self.stream_id));
+        // メールボックスは順番に処理されるため、クライアントのデータより先にエージェントへ届く
+        self.agent.do_send(OpenStream {
+            stream_id: self.stream_id,
+            client: ctx.address(),
+        });
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        info!("{}", t!("relay.stream_finished", stream = self.stream_id));
+        self.stats.close_stream();
+        self.agent.do_send(ClientClosed {
+            stream_id: self.stream_id,
+        });
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for RelayClientSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Binary(bin)) => self.agent.do_send(ClientData {
+                stream_id: self.stream_id,
+                data: bin.to_vec(),
+            }),
+            Ok(ws::Message::Ping(p)) => ctx.pong(&p),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// [RelayDeliver]
+/// エージェントから届いたバイナリをクライアントへ送信します。
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RelayDeliver(pub Vec<u8>);
+
+impl Handler<RelayDeliver> for RelayClientSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: RelayDeliver, ctx: &mut Self::Context) {
+        ctx.binary(msg.0);
+    }
+}
+
+/// [CloseStream]
+/// エージェント側のセッションが終了したため、クライアントとの接続を閉じます。
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseStream;
+
+impl Handler<CloseStream> for RelayClientSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: CloseStream, ctx: &mut Self::Context) {
+        ctx.close(None);
+        ctx.stop();
+    }
+}
@@ -0,0 +1,10 @@
+pub mod agent_session;
+pub mod client_session;
+pub mod registry;
+
+pub use agent_session::RelayAgentSession;
+pub use client_session::RelayClientSession;
+pub use registry::{
+    AgentMetrics, AgentStats, MAX_RELEASED_AGENTS, MIN_AGENT_KEY_BITS, RelayLimits, RelayMetrics,
+    RelayRegistry, StreamRejection, agent_id,
+};
@@ -0,0 +1,381 @@
+use actix::Addr;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::agent_session::RelayAgentSession;
+use crate::encryption::RsaPublicKey;
+use crate::t;
+use crate::time::unix_now;
+
+/// [agent_id]
+/// 公開鍵のフィンガープリントから、リレー上でエージェントを識別する ID を作成します。
+///
+/// `aa:bb:...` 形式と区切りなしの 16 進数のどちらも受け付け、区切りなしの小文字に揃えます。
+/// クライアントはこの ID を含む URL (`/ws/{agent_id}`) でリレーに接続します。
+pub fn agent_id(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| *c != ':')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// 登録を受け付けるエージェントの RSA 鍵長の既定の下限 (ビット)
+pub const MIN_AGENT_KEY_BITS: usize = 2048;
+
+/// 転送量の上限を引き継ぐために保持する、登録を解除したエージェントの数の上限。
+/// 超えた場合は、登録を解除した時刻が最も古いものから破棄します。
+pub const MAX_RELEASED_AGENTS: usize = 4096;
+
+/// [RelayLimits]
+/// リレーが受け付けるエージェントとストリームの上限です。
+#[derive(Debug, Clone)]
+pub struct RelayLimits {
+    /// 同時に登録できるエージェントの数。None の場合は無制限です。
+    pub max_agents: Option<usize>,
+    /// エージェントごとに同時に中継できるストリーム (クライアント接続) の数。None の場合は無制限です。
+    pub max_streams_per_agent: Option<u32>,
+    /// 登録を許可するエージェント ID の一覧。空の場合はすべてのエージェントを受け付けます。
+    pub allowed_agents: Vec<String>,
+    /// エージェントごとに中継できる転送量 (両方向の合計バイト数)。None の場合は無制限です。
+    ///
+    /// リレーの起動中の累計で、エージェントが接続し直しても引き継ぎます。
+    /// 上限に達すると新しいストリームを拒否し、中継中のストリームも閉じます。
+    /// 接続していないエージェントの累計は [MAX_RELEASED_AGENTS] 件まで保持します。
+    pub max_bytes_per_agent: Option<u64>,
+    /// 登録を受け付けるエージェントの RSA 鍵長の下限 (ビット)。既定は [MIN_AGENT_KEY_BITS] です。
+    pub min_agent_key_bits: usize,
+}
+
+impl Default for RelayLimits {
+    fn default() -> Self {
+        Self {
+            max_agents: None,
+            max_streams_per_agent: None,
+            allowed_agents: Vec::new(),
+            max_bytes_per_agent: None,
+            min_agent_key_bits: MIN_AGENT_KEY_BITS,
+        }
+    }
+}
+
+/// [AgentStats]
+/// 登録中のエージェント 1 つ分の中継状況です。
+#[derive(Debug)]
+pub struct AgentStats {
+    /// 中継中のストリーム数
+    pub active_streams: AtomicU32,
+    /// 登録後に受け付けたストリームの累計
+    pub total_streams: AtomicU64,
+    /// 上限により拒否したストリームの累計
+    pub rejected_streams: AtomicU64,
+    /// クライアントからエージェントへ中継した累計バイト数
+    pub bytes_to_agent: AtomicU64,
+    /// エージェントからクライアントへ中継した累計バイト数
+    pub bytes_to_clients: AtomicU64,
+    /// 最後に登録した時刻 (UNIX 秒)
+    pub registered_at: AtomicU64,
+}
+
+impl AgentStats {
+    fn new() -> Self {
+        Self {
+            active_streams: AtomicU32::new(0),
+            total_streams: AtomicU64::new(0),
+            rejected_streams: AtomicU64::new(0),
+            bytes_to_agent: AtomicU64::new(0),
+            bytes_to_clients: AtomicU64::new(0),
+            registered_at: AtomicU64::new(unix_now()),
+        }
+    }
+
+    /// 両方向に中継した累計バイト数を返します。
+    pub fn bytes_relayed(&self) -> u64 {
+        self.bytes_to_agent.load(Ordering::Relaxed) + self.bytes_to_clients.load(Ordering::Relaxed)
+    }
+
+    /// ストリームの終了を記録します。
+    pub fn close_stream(&self) {
+        self.active_streams.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// [AgentMetrics]
+/// `/metrics` で返すエージェントごとの中継状況
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentMetrics {
+    pub agent_id: String,
+    pub active_streams: u32,
+    pub total_streams: u64,
+    pub rejected_streams: u64,
+    pub bytes_to_agent: u64,
+    pub bytes_to_clients: u64,
+    pub registered_at: u64,
+}
+
+/// [RelayMetrics]
+/// `/metrics` で返すリレー全体の状況
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayMetrics {
+    /// 登録中のエージェント数
+    pub agent_count: usize,
+    /// 拒否したエージェント登録の累計
+    pub rejected_registrations: u64,
+    /// 未登録のエージェントへの接続要求の累計
+    pub unknown_agent_requests: u64,
+    pub agents: Vec<AgentMetrics>,
+}
+
+/// [StreamRejection]
+/// クライアントのストリームを受け付けなかった理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamRejection {
+    /// 指定したエージェントが登録されていない
+    UnknownAgent,
+    /// エージェントのストリーム数が上限に達している
+    QuotaExceeded,
+    /// エージェントの転送量が上限に達している
+    ByteQuotaExceeded,
+}
+
+struct AgentEntry {
+    session: Addr<RelayAgentSession>,
+    stats: Arc<AgentStats>,
+}
+
+/// [RelayRegistry]
+/// リレーに登録中のホストエージェントの一覧です。
+/// エージェントは公開鍵のフィンガープリント ([agent_id]) で識別します。
+#[derive(Default)]
+pub struct RelayRegistry {
+    /// このリレーの識別子。エージェントの登録の署名に含めます。
+    relay_id: String,
+    limits: RelayLimits,
+    agents: Mutex<HashMap<String, AgentEntry>>,
+    /// 登録を解除したエージェントの中継状況と、解除した時刻 (UNIX 秒)。
+    /// 転送量の上限を接続し直しても引き継ぐため、上限を設定している場合のみ保持します。
+    released: Mutex<HashMap<String, (Arc<AgentStats>, u64)>>,
+    next_stream_id: AtomicU32,
+    rejected_registrations: AtomicU64,
+    unknown_agent_requests: AtomicU64,
+}
+
+impl RelayRegistry {
+    pub fn new(limits: RelayLimits) -> Self {
+        let limits = RelayLimits {
+            allowed_agents: limits.allowed_agents.iter().map(|a| agent_id(a)).collect(),
+            ..limits
+        };
+        let mut relay_id = [0u8; 16];
+        OsRng.fill_bytes(&mut relay_id);
+        Self {
+            relay_id: relay_id.iter().map(|b| format!("{:02x}", b)).collect(),
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// [relay_id]
+    /// このリレーの識別子を返します。起動ごとにランダムに生成し、エージェントの登録の署名に含めます。
+    pub fn relay_id(&self) -> &str {
+        &self.relay_id
+    }
+
+    /// [register]
+    /// エージェントを登録し、そのエージェントの中継状況を返します。
+    ///
+    /// 中継状況は同じエージェントの以前の登録から引き継ぎます。
+    ///
+    /// 同じ鍵のエージェントが既に登録されていれば置き換え、以前のセッションを閉じます。
+    /// 接続が切れたまま残っている古いセッションより、鍵の所持を証明し直した新しいセッションを優先します。
+    /// 許可リストにない場合や、エージェント数が上限に達している場合はエラーになります。
+    pub fn register(
+        &self,
+        agent_id: String,
+        session: Addr<RelayAgentSession>,
+    ) -> Result<(Arc<AgentStats>, Option<Addr<RelayAgentSession>>), String> {
+        let mut agents = self.agents.lock().unwrap();
+        let rejection = if !self.limits.allowed_agents.is_empty()
+            && !self.limits.allowed_agents.contains(&agent_id)
+        {
+            Some(t!("relay.agent_not_allowed", agent = agent_id))
+        } else if !agents.contains_key(&agent_id)
+            && self
+                .limits
+                .max_agents
+                .is_some_and(|max| agents.len() >= max)
+        {
+            Some(t!("relay.too_many_agents"))
+        } else {
+            None
+        };
+        if let Some(reason) = rejection {
+            self.rejected_registrations.fetch_add(1, Ordering::Relaxed);
+            return Err(reason);
+        }
+
+        let stats = agents
+            .get(&agent_id)
+            .map(|e| Arc::clone(&e.stats))
+            .or_else(|| {
+                self.released
+                    .lock()
+                    .unwrap()
+                    .remove(&agent_id)
+                    .map(|(stats, _)| stats)
+            })
+            .unwrap_or_else(|| Arc::new(AgentStats::new()));
+        stats.registered_at.store(unix_now(), Ordering::Relaxed);
+        let previous = agents.insert(
+            agent_id,
+            AgentEntry {
+                session,
+                stats: Arc::clone(&stats),
+            },
+        );
+        Ok((stats, previous.map(|p| p.session)))
+    }
+
+    /// [check_agent_key]
+    /// エージェントの公開鍵の長さが [RelayLimits::min_agent_key_bits] 以上かを確認します。
+    /// 短い鍵は登録の拒否として数え、理由を返します。
+    pub fn check_agent_key(&self, public_key: &RsaPublicKey) -> Result<(), String> {
+        let bits = public_key.bits();
+        if bits >= self.limits.min_agent_key_bits {
+            return Ok(());
+        }
+        self.rejected_registrations.fetch_add(1, Ordering::Relaxed);
+        Err(t!(
+            "relay.agent_key_too_short",
+            bits = bits,
+            min = self.limits.min_agent_key_bits
+        ))
+    }
+
+    /// [unregister]
+    /// エージェントの登録を解除します。既に新しいセッションに置き換えられている場合は何もしません。
+    ///
+    /// 転送量の上限を設定している場合は、接続し直したときに引き継げるよう中継状況を保持します。
+    /// 保持する数は [MAX_RELEASED_AGENTS] までで、超えた分は解除した時刻が古いものから破棄します。
+    pub fn unregister(&self, agent_id: &str, session: &Addr<RelayAgentSession>) {
+        let mut agents = self.agents.lock().unwrap();
+        if agents.get(agent_id).map(|e| &e.session) != Some(session) {
+            return;
+        }
+        let Some(entry) = agents.remove(agent_id) else {
+            return;
+        };
+        if self.limits.max_bytes_per_agent.is_none() {
+            return;
+        }
+        let mut released = self.released.lock().unwrap();
+        while released.len() >= MAX_RELEASED_AGENTS {
+            let Some(oldest) = released
+                .iter()
+                .min_by_key(|(_, (_, at))| *at)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            released.remove(&oldest);
+        }
+        released.insert(agent_id.to_string(), (entry.stats, unix_now()));
+    }
+
+    /// 転送量の上限を引き継ぐために保持している、登録を解除したエージェントの数を返します。
+    pub fn released_count(&self) -> usize {
+        self.released.lock().unwrap().len()
+    }
+
+    /// 登録中のエージェントのセッションを取得します。
+    pub fn get(&self, agent_id: &str) -> Option<Addr<RelayAgentSession>> {
+        self.agents
+            .lock()
+            .unwrap()
+            .get(&self::agent_id(agent_id))
+            .map(|e| e.session.clone())
+    }
+
+    /// [open_stream]
+    /// エージェントへの新しいストリームを予約し、エージェントのセッションと中継状況を返します。
+    /// ストリームが終了したら [AgentStats::close_stream] を呼び出してください。
+    pub fn open_stream(
+        &self,
+        agent_id: &str,
+    ) -> Result<(Addr<RelayAgentSession>, Arc<AgentStats>), StreamRejection> {
+        let agents = self.agents.lock().unwrap();
+        let Some(entry) = agents.get(&self::agent_id(agent_id)) else {
+            self.unknown_agent_requests.fetch_add(1, Ordering::Relaxed);
+            return Err(StreamRejection::UnknownAgent);
+        };
+
+        let stats = &entry.stats;
+        if self.byte_quota_exhausted(stats) {
+            stats.rejected_streams.fetch_add(1, Ordering::Relaxed);
+            return Err(StreamRejection::ByteQuotaExceeded);
+        }
+        let max = self.limits.max_streams_per_agent.unwrap_or(u32::MAX);
+        let reserved =
+            stats
+                .active_streams
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                    (n < max).then_some(n + 1)
+                });
+        if reserved.is_err() {
+            stats.rejected_streams.fetch_add(1, Ordering::Relaxed);
+            return Err(StreamRejection::QuotaExceeded);
+        }
+        stats.total_streams.fetch_add(1, Ordering::Relaxed);
+        Ok((entry.session.clone(), Arc::clone(stats)))
+    }
+
+    /// [byte_quota_exhausted]
+    /// エージェントの転送量が [RelayLimits::max_bytes_per_agent] に達しているかを返します。
+    pub fn byte_quota_exhausted(&self, stats: &AgentStats) -> bool {
+        self.limits
+            .max_bytes_per_agent
+            .is_some_and(|max| stats.bytes_relayed() >= max)
+    }
+
+    /// 登録中のエージェントの ID 一覧を返します。
+    pub fn agent_ids(&self) -> Vec<String> {
+        self.agents.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// 新しいストリーム ID を払い出します。
+    pub fn next_stream_id(&self) -> u32 {
+        self.next_stream_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// [metrics]
+    /// リレー全体とエージェントごとの中継状況を返します。
+    pub fn metrics(&self) -> RelayMetrics {
+        let agents = self.agents.lock().unwrap();
+        let mut agent_metrics: Vec<AgentMetrics> = agents
+            .iter()
+            .map(|(id, entry)| {
+                let stats = &entry.stats;
+                AgentMetrics {
+                    agent_id: id.clone(),
+                    active_streams: stats.active_streams.load(Ordering::Relaxed),
+                    total_streams: stats.total_streams.load(Ordering::Relaxed),
+                    rejected_streams: stats.rejected_streams.load(Ordering::Relaxed),
+                    bytes_to_agent: stats.bytes_to_agent.load(Ordering::Relaxed),
+                    bytes_to_clients: stats.bytes_to_clients.load(Ordering::Relaxed),
+                    registered_at: stats.registered_at.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        agent_metrics.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        RelayMetrics {
+            agent_count: agent_metrics.len(),
+            rejected_registrations: self.rejected_registrations.load(Ordering::Relaxed),
+            unknown_agent_requests: self.unknown_agent_requests.load(Ordering::Relaxed),
+            agents: agent_metrics,
+        }
+    }
+}
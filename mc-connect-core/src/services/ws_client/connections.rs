@@ -0,0 +1,197 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::time::unix_now;
+
+/// [ConnectionState]
+/// ローカル接続 1 つ分の状態です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// ゲートウェイとのハンドシェイク中
+    Connecting,
+    /// トンネルを通して中継中
+    Active,
+    /// 切断要求を受けて終了処理中
+    Closing,
+}
+
+/// [ConnectionInfo]
+/// ローカル接続 1 つ分の統計情報のスナップショットです。UI や CLI の表示に使用します。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInfo {
+    /// マッピング内で接続を識別する ID
+    pub id: u64,
+    /// 接続元のアドレス
+    pub peer_addr: String,
+    /// 接続を受け付けた時刻 (UNIX 秒)
+    pub started_at: u64,
+    /// この接続で送信したバイト数
+    pub upload_total: u64,
+    /// この接続で受信したバイト数
+    pub download_total: u64,
+    /// この接続で最後に計測した RTT (ミリ秒)。まだ計測していない場合は None
+    pub rtt_ms: Option<u64>,
+    pub state: ConnectionState,
+}
+
+/// [ConnectionStats]
+/// ローカル接続 1 つ分の通信量と状態を記録します。
+#[derive(Debug)]
+pub struct ConnectionStats {
+    pub id: u64,
+    pub peer_addr: Option<SocketAddr>,
+    pub started_at: u64,
+    pub upload_total: AtomicU64,
+    pub download_total: AtomicU64,
+    pub last_rtt_ms: AtomicU64,
+    active: AtomicBool,
+    close_requested: AtomicBool,
+    close: Notify,
+}
+
+impl ConnectionStats {
+    /// ハンドシェイクが完了し、中継を開始したことを記録します。
+    pub fn set_active(&self) {
+        self.active.store(true, Ordering::Relaxed);
+    }
+
+    /// [closed]
+    /// [ConnectionRegistry::close] で切断が要求されるまで待ちます。
+    pub async fn closed(&self) {
+        self.close.notified().await;
+    }
+
+    /// [info]
+    /// 現在の統計情報のスナップショットを取得します。
+    pub fn info(&self) -> ConnectionInfo {
+        let state = if self.close_requested.load(Ordering::Relaxed) {
+            ConnectionState::Closing
+        } else if self.active.load(Ordering::Relaxed) {
+            ConnectionState::Active
+        } else {
+            ConnectionState::Connecting
+        };
+        let rtt_ms = self.last_rtt_ms.load(Ordering::Relaxed);
+        ConnectionInfo {
+            id: self.id,
+            peer_addr: self
+                .peer_addr
+                .map(|a| a.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            started_at: self.started_at,
+            upload_total: self.upload_total.load(Ordering::Relaxed),
+            download_total: self.download_total.load(Ordering::Relaxed),
+            rtt_ms: (rtt_ms != u64::MAX).then_some(rtt_ms),
+            state,
+        }
+    }
+}
+
+type ConnectionMap = Arc<Mutex<HashMap<u64, Arc<ConnectionStats>>>>;
+
+/// [ConnectionRegistry]
+/// 1 つのマッピングで中継中のローカル接続の一覧です。
+///
+/// 接続は [ConnectionRegistry::open] で登録し、返された [ConnectionGuard] を drop すると一覧から外れます。
+#[derive(Debug, Default)]
+pub struct ConnectionRegistry {
+    connections: ConnectionMap,
+    next_id: AtomicU64,
+}
+
+impl ConnectionRegistry {
+    /// [open]
+    /// 新しいローカル接続を登録します。
+    pub fn open(&self, peer_addr: Option<SocketAddr>) -> ConnectionGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let stats = Arc::new(ConnectionStats {
+            id,
+            peer_addr,
+            started_at: unix_now(),
+            upload_total: AtomicU64::new(0),
+            download_total: AtomicU64::new(0),
+            last_rtt_ms: AtomicU64::new(u64::MAX),
+            active: AtomicBool::new(false),
+            close_requested: AtomicBool::new(false),
+            close: Notify::new(),
+        });
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(id, Arc::clone(&stats));
+        ConnectionGuard {
+            connections: Arc::clone(&self.connections),
+            stats,
+        }
+    }
+
+    /// [list]
+    /// 中継中の接続の一覧を、接続を受け付けた順に返します。
+    pub fn list(&self) -> Vec<ConnectionInfo> {
+        let mut list: Vec<ConnectionInfo> = self
+            .connections
+            .lock()
+            .unwrap()
+            .values()
+            .map(|c| c.info())
+            .collect();
+        list.sort_by_key(|c| c.id);
+        list
+    }
+
+    /// 中継中の接続数を返します。
+    pub fn len(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// [close]
+    /// 指定した接続だけを切断します。接続が見つからない場合は false を返します。
+    pub fn close(&self, id: u64) -> bool {
+        let Some(stats) = self.connections.lock().unwrap().get(&id).cloned() else {
+            return false;
+        };
+        stats.close_requested.store(true, Ordering::Relaxed);
+        // 待機中でなくても通知が残るため、切断要求を取りこぼさない
+        stats.close.notify_one();
+        true
+    }
+}
+
+/// [ConnectionGuard]
+/// 登録中のローカル接続です。drop すると [ConnectionRegistry] から外れます。
+pub struct ConnectionGuard {
+    connections: ConnectionMap,
+    stats: Arc<ConnectionStats>,
+}
+
+impl ConnectionGuard {
+    /// 別タスクで記録するための共有参照を取得します。
+    pub fn stats(&self) -> Arc<ConnectionStats> {
+        Arc::clone(&self.stats)
+    }
+}
+
+impl Deref for ConnectionGuard {
+    type Target = ConnectionStats;
+
+    fn deref(&self) -> &ConnectionStats {
+        &self.stats
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.connections.lock().unwrap().remove(&self.stats.id);
+    }
+}
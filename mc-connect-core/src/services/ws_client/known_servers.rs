@@ -0,0 +1,112 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::encryption::{CryptoError, fingerprint};
+use crate::t;
+use crate::time::unix_now;
+
+/// 既知サーバーとして記録された公開鍵の情報
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KnownServer {
+    /// 記録済みの公開鍵（Base64）
+    pub public_key: String,
+    /// 公開鍵の SHA-256 フィンガープリント (表示用)
+    pub fingerprint: String,
+    /// 初めて記録した時刻 (UNIX 秒)
+    pub first_seen: u64,
+}
+
+/// [TrustStatus]
+/// TOFU による照合結果です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustStatus {
+    /// 初めて接続したサーバーで、公開鍵を新たに記録した
+    FirstUse,
+    /// 記録済みの公開鍵と一致した
+    Trusted,
+}
+
+/// [KnownServers]
+/// TOFU (Trust On First Use) で記録したゲートウェイの公開鍵を管理します。
+///
+/// WebSocket URL ごとに初回接続時の公開鍵を JSON ファイルへ保存し、
+/// 以降の接続で提示された公開鍵が異なる場合は接続を拒否するために使用します。
+#[derive(Debug, Default)]
+pub struct KnownServers {
+    path: PathBuf,
+    servers: BTreeMap<String, KnownServer>,
+}
+
+impl KnownServers {
+    /// 指定されたファイルから既知サーバーの一覧を読み込みます。
+    /// ファイルが存在しない場合は空の一覧を返します。
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CryptoError> {
+        let path = path.as_ref().to_path_buf();
+        let servers = if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            serde_json::from_str(&content)
+                .map_err(|e| t!("known_servers.parse_failed", error = e))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { path, servers })
+    }
+
+    /// 現在の一覧をファイルへ保存します。
+    pub fn save(&self) -> Result<(), CryptoError> {
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.servers)?)?;
+        Ok(())
+    }
+
+    /// 記録済みの公開鍵 (DER) を取得します。
+    pub fn get(&self, ws_url: &str) -> Option<Vec<u8>> {
+        self.servers
+            .get(ws_url)
+            .and_then(|s| general_purpose::STANDARD.decode(&s.public_key).ok())
+    }
+
+    /// [trust]
+    /// 提示された公開鍵を記録と照合します。
+    ///
+    /// 未記録のサーバーであれば記録して `FirstUse` を返します (保存は呼び出し側で行います)。
+    /// 記録済みの公開鍵と異なる場合はエラーを返します。
+    pub fn trust(&mut self, ws_url: &str, public_der: &[u8]) -> Result<TrustStatus, CryptoError> {
+        if let Some(known) = self.servers.get(ws_url) {
+            let presented = fingerprint(public_der);
+            if known.fingerprint != presented {
+                return Err(t!(
+                    "known_servers.fingerprint_mismatch",
+                    url = ws_url,
+                    known = known.fingerprint,
+                    presented = presented
+                )
+                .into());
+            }
+            return Ok(TrustStatus::Trusted);
+        }
+
+        self.replace(ws_url, public_der);
+        Ok(TrustStatus::FirstUse)
+    }
+
+    /// 公開鍵の記録を置き換えます。
+    /// 旧鍵の署名を検証済みの鍵ローテーション通知を受け取った場合にのみ使用してください。
+    pub fn replace(&mut self, ws_url: &str, public_der: &[u8]) {
+        let first_seen = unix_now();
+        self.servers.insert(
+            ws_url.to_string(),
+            KnownServer {
+                public_key: general_purpose::STANDARD.encode(public_der),
+                fingerprint: fingerprint(public_der),
+                first_seen,
+            },
+        );
+    }
+}
@@ -1,6 +1,12 @@
 pub mod stats;
+pub mod connections;
 pub mod tunnel;
+pub mod proxy_frontend;
 pub mod service;
+pub mod known_servers;
 
-pub use stats::TunnelStats;
+pub use stats::{SpeedSample, SpeedSampler, TunnelStats};
+pub use connections::{ConnectionInfo, ConnectionRegistry, ConnectionState};
 pub use service::WsClientService;
+pub use known_servers::{KnownServers, TrustStatus};
+pub use proxy_frontend::{ProxyKind, ProxyReply, ProxyRequest};
@@ -0,0 +1,305 @@
+//! クライアント側の SOCKS5 / HTTP CONNECT フロントエンド
+//!
+//! ローカルのポート 1 つで SOCKS5 と HTTP CONNECT の両方を受け付け、
+//! 要求された宛先ポートに応じて、ゲートウェイの許可ポートへトンネルを張ります。
+//! ゲートウェイは常に自身のローカル (127.0.0.1) のポートへ接続するため、宛先ホストは使用せず、ポートのみで転送先を選びます。
+//!
+//! 先頭 1 バイトが `0x05` であれば SOCKS5、それ以外は HTTP として扱います。
+
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::stats::TunnelStats;
+use super::tunnel::{open_secure_tunnel, run_secure_tunnel};
+use crate::encryption::{CryptoError, RsaPublicKey};
+use crate::models::packet::{AllowedPort, Protocol};
+use crate::t;
+
+/// SOCKS のバージョン番号
+const SOCKS_VERSION: u8 = 0x05;
+/// 認証なし
+const SOCKS_NO_AUTH: u8 = 0x00;
+/// 使用できる認証方式がない
+const SOCKS_NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+/// CONNECT コマンド
+const SOCKS_CMD_CONNECT: u8 = 0x01;
+const SOCKS_ATYP_IPV4: u8 = 0x01;
+const SOCKS_ATYP_DOMAIN: u8 = 0x03;
+const SOCKS_ATYP_IPV6: u8 = 0x04;
+
+/// HTTP リクエストヘッダーの上限 (バイト)
+const MAX_HTTP_HEADER: usize = 8 * 1024;
+
+/// 接続要求を読み終えるまでの制限時間。
+/// 要求を少しずつしか送らないクライアントに接続を占有させないために使います。
+pub const PROXY_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// [ProxyKind]
+/// ローカルのクライアントが使用したプロキシプロトコル
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Socks5,
+    HttpConnect,
+}
+
+/// [ProxyReply]
+/// 接続要求に対する応答
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyReply {
+    /// トンネルを確立した
+    Success,
+    /// ゲートウェイで許可されていないポート
+    NotAllowed,
+    /// ゲートウェイへの接続、またはゲートウェイからターゲットへの接続に失敗した
+    Unreachable,
+    /// CONNECT 以外のコマンド
+    CommandNotSupported,
+    /// 未対応のアドレス形式
+    AddressNotSupported,
+}
+
+impl ProxyReply {
+    /// SOCKS5 の応答コード (RFC 1928)
+    fn socks_code(self) -> u8 {
+        match self {
+            Self::Success => 0x00,
+            Self::NotAllowed => 0x02,
+            Self::Unreachable => 0x05,
+            Self::CommandNotSupported => 0x07,
+            Self::AddressNotSupported => 0x08,
+        }
+    }
+
+    /// HTTP のステータス行
+    fn http_status(self) -> &'static str {
+        match self {
+            Self::Success => "200 Connection Established",
+            Self::NotAllowed => "403 Forbidden",
+            Self::Unreachable => "502 Bad Gateway",
+            Self::CommandNotSupported => "405 Method Not Allowed",
+            Self::AddressNotSupported => "400 Bad Request",
+        }
+    }
+}
+
+/// [ProxyRequest]
+/// ローカルのクライアントから受け取った接続要求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyRequest {
+    /// 使用されたプロキシプロトコル
+    pub kind: ProxyKind,
+    /// 要求された宛先ホスト (ログ用。転送先の選択には使用しません)
+    pub host: String,
+    /// 要求された宛先ポート。ゲートウェイ側のターゲットポートとして使用します。
+    pub port: u16,
+}
+
+impl ProxyRequest {
+    /// [reply]
+    /// 接続要求への応答を、要求と同じプロトコルで送信します。
+    pub async fn reply(&self, stream: &mut TcpStream, reply: ProxyReply) -> std::io::Result<()> {
+        send_reply(stream, self.kind, reply).await
+    }
+}
+
+async fn send_reply(
+    stream: &mut TcpStream,
+    kind: ProxyKind,
+    reply: ProxyReply,
+) -> std::io::Result<()> {
+    match kind {
+        ProxyKind::Socks5 => {
+            // BND.ADDR / BND.PORT は使用しないため 0.0.0.0:0 を返す
+            let mut res = vec![SOCKS_VERSION, reply.socks_code(), 0x00, SOCKS_ATYP_IPV4];
+            res.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+            stream.write_all(&res).await
+        }
+        ProxyKind::HttpConnect => {
+            let res = format!("HTTP/1.1 {}\r\n\r\n", reply.http_status());
+            stream.write_all(res.as_bytes()).await
+        }
+    }
+}
+
+/// [read_proxy_request]
+/// ローカルのクライアントから SOCKS5 または HTTP CONNECT の接続要求を読み取ります。
+///
+/// 対応していない要求 (CONNECT 以外のコマンドなど) には、この関数内でエラー応答を返してからエラーになります。
+/// [PROXY_REQUEST_TIMEOUT] 以内に要求を読み終えられない場合もエラーになります。
+pub async fn read_proxy_request(stream: &mut TcpStream) -> Result<ProxyRequest, CryptoError> {
+    match tokio::time::timeout(PROXY_REQUEST_TIMEOUT, read_request(stream)).await {
+        Ok(result) => result,
+        Err(_) => Err(t!(
+            "proxy.request_timeout",
+            seconds = PROXY_REQUEST_TIMEOUT.as_secs()
+        )
+        .into()),
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<ProxyRequest, CryptoError> {
+    let mut first = [0u8; 1];
+    stream.peek(&mut first).await?;
+    if first[0] == SOCKS_VERSION {
+        read_socks5_request(stream).await
+    } else {
+        read_http_connect_request(stream).await
+    }
+}
+
+async fn read_socks5_request(stream: &mut TcpStream) -> Result<ProxyRequest, CryptoError> {
+    // 挨拶: VER NMETHODS METHODS...
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&SOCKS_NO_AUTH) {
+        stream
+            .write_all(&[SOCKS_VERSION, SOCKS_NO_ACCEPTABLE_METHODS])
+            .await?;
+        return Err(t!("proxy.socks_no_auth_unsupported").into());
+    }
+    stream.write_all(&[SOCKS_VERSION, SOCKS_NO_AUTH]).await?;
+
+    // 要求: VER CMD RSV ATYP DST.ADDR DST.PORT
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[0] != SOCKS_VERSION {
+        return Err(t!("proxy.socks_bad_version", version = request[0]).into());
+    }
+    let host = match request[3] {
+        SOCKS_ATYP_IPV4 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            std::net::Ipv4Addr::from(addr).to_string()
+        }
+        SOCKS_ATYP_DOMAIN => {
+            let len = stream.read_u8().await? as usize;
+            let mut name = vec![0u8; len];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8_lossy(&name).into_owned()
+        }
+        SOCKS_ATYP_IPV6 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            std::net::Ipv6Addr::from(addr).to_string()
+        }
+        atyp => {
+            send_reply(stream, ProxyKind::Socks5, ProxyReply::AddressNotSupported).await?;
+            return Err(t!("proxy.socks_bad_address_type", atyp = atyp).into());
+        }
+    };
+    let port = stream.read_u16().await?;
+
+    if request[1] != SOCKS_CMD_CONNECT {
+        send_reply(stream, ProxyKind::Socks5, ProxyReply::CommandNotSupported).await?;
+        return Err(t!("proxy.socks_bad_command", command = request[1]).into());
+    }
+    Ok(ProxyRequest {
+        kind: ProxyKind::Socks5,
+        host,
+        port,
+    })
+}
+
+async fn read_http_connect_request(stream: &mut TcpStream) -> Result<ProxyRequest, CryptoError> {
+    // ヘッダーの終わり (空行) まで 1 バイトずつ読み、トンネル開始後のデータを読み込まないようにする
+    let mut header = Vec::new();
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() >= MAX_HTTP_HEADER {
+            return Err(t!("proxy.http_header_too_large").into());
+        }
+        header.push(stream.read_u8().await?);
+    }
+    let header = String::from_utf8_lossy(&header);
+    let request_line = header.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next());
+
+    if !method.eq_ignore_ascii_case("CONNECT") {
+        send_reply(
+            stream,
+            ProxyKind::HttpConnect,
+            ProxyReply::CommandNotSupported,
+        )
+        .await?;
+        return Err(t!("proxy.http_bad_method", method = method).into());
+    }
+    let parsed = target.and_then(|t| {
+        let (host, port) = t.rsplit_once(':')?;
+        Some((
+            host.trim_matches(['[', ']']).to_string(),
+            port.parse().ok()?,
+        ))
+    });
+    let Some((host, port)) = parsed else {
+        send_reply(
+            stream,
+            ProxyKind::HttpConnect,
+            ProxyReply::AddressNotSupported,
+        )
+        .await?;
+        return Err(t!("proxy.http_bad_target", request = request_line).into());
+    };
+    Ok(ProxyRequest {
+        kind: ProxyKind::HttpConnect,
+        host,
+        port,
+    })
+}
+
+/// [handle_proxy_connection]
+/// ローカルの接続 1 つについて、接続要求を読み取り、許可ポートであればゲートウェイへのトンネルを張って中継します。
+pub async fn handle_proxy_connection(
+    mut tcp_stream: TcpStream,
+    ws_url: String,
+    allowed_ports: Arc<Vec<AllowedPort>>,
+    stats: Arc<TunnelStats>,
+    manual_ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+    server_public_key: Arc<RsaPublicKey>,
+) -> Result<(), CryptoError> {
+    let conn = stats.connections.open(tcp_stream.peer_addr().ok());
+    let request = read_proxy_request(&mut tcp_stream).await?;
+    info!(
+        "{}",
+        t!(
+            "proxy.request",
+            kind = format!("{:?}", request.kind),
+            host = request.host,
+            port = request.port
+        )
+    );
+
+    let allowed = allowed_ports
+        .iter()
+        .any(|p| p.port == request.port && p.protocol == Protocol::TCP);
+    if !allowed {
+        warn!("{}", t!("proxy.port_not_allowed", port = request.port));
+        request
+            .reply(&mut tcp_stream, ProxyReply::NotAllowed)
+            .await?;
+        return Err(t!("proxy.port_rejected", port = request.port).into());
+    }
+
+    let tunnel = match open_secure_tunnel(
+        &ws_url,
+        request.port,
+        Protocol::TCP,
+        server_public_key.as_ref(),
+    )
+    .await
+    {
+        Ok(tunnel) => tunnel,
+        Err(e) => {
+            request
+                .reply(&mut tcp_stream, ProxyReply::Unreachable)
+                .await?;
+            return Err(e);
+        }
+    };
+    request.reply(&mut tcp_stream, ProxyReply::Success).await?;
+    run_secure_tunnel(tcp_stream, tunnel, stats, conn, manual_ping_rx).await
+}
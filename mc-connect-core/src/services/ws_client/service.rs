@@ -1,17 +1,27 @@
 use futures_util::{SinkExt, StreamExt};
-use log::{error, info};
+use log::{error, info, warn};
+use rand::RngCore;
+use rand::rngs::OsRng;
 use std::sync::Arc;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
 use url::Url;
 
+use super::known_servers::{KnownServers, TrustStatus};
+use super::proxy_frontend::handle_proxy_connection;
 use super::stats::TunnelStats;
 use super::tunnel::handle_tunnel;
-use crate::encryption::{CryptoError, RsaKeyPair, create_secure_connect_packet};
+use crate::encryption::{
+    CryptoError, RsaPublicKey, SERVER_KEY_CHALLENGE_LEN, create_secure_connect_packet, fingerprint,
+    short_fingerprint, verify_key_rotation, verify_server_key,
+};
+use crate::error::McConnectError;
 use crate::models::packet::{
-    Command, ConnectResponsePayload, Message, Protocol, ServerInfoResponsePayload,
+    AllowedPort, Command, ConnectResponsePayload, Message, Protocol, ServerInfoRequestPayload,
+    ServerInfoResponsePayload,
 };
+use crate::t;
 
 /// [WsClientService]
 /// WebSocket クライアント側のトンネル管理サービスです。
@@ -20,6 +30,7 @@ pub struct WsClientService;
 impl WsClientService {
     /// [start_tunnel_with_protocol]
     /// ローカルでポート待機を開始し、接続ごとにセッションを確立します。
+    #[allow(clippy::too_many_arguments)]
     pub async fn start_tunnel_with_protocol(
         bind_addr: String,
         local_port: u16,
@@ -28,10 +39,10 @@ impl WsClientService {
         protocol: Protocol,
         stats: Arc<TunnelStats>,
         ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
-        server_public_key: Arc<RsaKeyPair>,
+        server_public_key: Arc<RsaPublicKey>,
     ) -> Result<(), CryptoError> {
-        info!("ゲートウェイへのセキュア接続を確認中: {}...", ws_url);
-        Self::check_connectivity(
+        info!("{}", t!("client.checking_gateway", url = ws_url));
+        let rotated_key = Self::check_connectivity(
             &ws_url,
             remote_target_port,
             protocol.clone(),
@@ -39,7 +50,13 @@ impl WsClientService {
         )
         .await?;
 
-        info!("ゲートウェイとのセキュアハンドシェイクに成功しました。準備完了です。");
+        info!("{}", t!("client.gateway_ready"));
+
+        // ゲートウェイが鍵をローテーションしている場合は、以降の接続で新しい公開鍵を使用する
+        let server_public_key = match rotated_key {
+            Some(der) => Arc::new(RsaPublicKey::from_der(&der)?),
+            None => server_public_key,
+        };
 
         Self::run_tunnel_server(
             bind_addr,
@@ -56,6 +73,7 @@ impl WsClientService {
 
     /// [run_tunnel_server]
     /// ローカルでポート待機を開始し、接続ごとにセッションを確立します。
+    #[allow(clippy::too_many_arguments)]
     pub async fn run_tunnel_server(
         bind_addr: String,
         local_port: u16,
@@ -63,12 +81,129 @@ impl WsClientService {
         remote_target_port: u16,
         protocol: Protocol,
         stats: Arc<TunnelStats>,
-        mut ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
-        server_public_key: Arc<RsaKeyPair>,
+        ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+        server_public_key: Arc<RsaPublicKey>,
+    ) -> Result<(), CryptoError> {
+        let listener = TcpListener::bind(format!("{}:{}", bind_addr, local_port)).await?;
+        info!(
+            "{}",
+            t!(
+                "client.listener_started",
+                host = bind_addr,
+                port = local_port
+            )
+        );
+
+        Self::run_tunnel_listener(
+            listener,
+            ws_url,
+            remote_target_port,
+            protocol,
+            stats,
+            ping_rx,
+            server_public_key,
+        )
+        .await
+    }
+
+    /// [run_tunnel_listener]
+    /// バインド済みのリスナーで接続を受け付け、接続ごとにセッションを確立します。
+    /// ポート 0 でバインドしたリスナーを渡すことで、空きポートでトンネルを開始できます。
+    /// 待ち受けている間は `stats` の転送速度を [TunnelStats::start_sampler] で計測します。
+    pub async fn run_tunnel_listener(
+        listener: TcpListener,
+        ws_url: String,
+        remote_target_port: u16,
+        protocol: Protocol,
+        stats: Arc<TunnelStats>,
+        ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+        server_public_key: Arc<RsaPublicKey>,
     ) -> Result<(), CryptoError> {
+        let _sampler = stats.start_sampler();
+        Self::accept_sessions(listener, ping_rx, move |tcp_stream, session_ping_rx| {
+            handle_tunnel(
+                tcp_stream,
+                ws_url.clone(),
+                remote_target_port,
+                protocol.clone(),
+                Arc::clone(&stats),
+                session_ping_rx,
+                Arc::clone(&server_public_key),
+            )
+        })
+        .await
+    }
+
+    /// [run_proxy_server]
+    /// ローカルで SOCKS5 / HTTP CONNECT プロキシを開始します。
+    ///
+    /// 起動時にゲートウェイの許可ポートを問い合わせ、接続ごとに要求された宛先ポートへトンネルを張ります。
+    /// 固定のポート対応 (マッピング) を設定しなくても、許可されたすべての TCP ポートを利用できます。
+    pub async fn run_proxy_server(
+        bind_addr: String,
+        local_port: u16,
+        ws_url: String,
+        stats: Arc<TunnelStats>,
+        ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+        server_public_key: Arc<RsaPublicKey>,
+    ) -> Result<(), CryptoError> {
+        let info = Self::get_server_info(&ws_url).await?;
         let listener = TcpListener::bind(format!("{}:{}", bind_addr, local_port)).await?;
-        info!("TCP リスナーを開始しました: {}:{}", bind_addr, local_port);
+        info!(
+            "{}",
+            t!("client.proxy_started", host = bind_addr, port = local_port)
+        );
+
+        Self::run_proxy_listener(
+            listener,
+            ws_url,
+            info.allowed_ports,
+            stats,
+            ping_rx,
+            server_public_key,
+        )
+        .await
+    }
 
+    /// [run_proxy_listener]
+    /// バインド済みのリスナーで SOCKS5 / HTTP CONNECT の接続を受け付けます。
+    /// `allowed_ports` に含まれる TCP ポートへの要求のみトンネルを張ります。
+    /// 待ち受けている間は `stats` の転送速度を [TunnelStats::start_sampler] で計測します。
+    pub async fn run_proxy_listener(
+        listener: TcpListener,
+        ws_url: String,
+        allowed_ports: Vec<AllowedPort>,
+        stats: Arc<TunnelStats>,
+        ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+        server_public_key: Arc<RsaPublicKey>,
+    ) -> Result<(), CryptoError> {
+        let allowed_ports = Arc::new(allowed_ports);
+        let _sampler = stats.start_sampler();
+        Self::accept_sessions(listener, ping_rx, move |tcp_stream, session_ping_rx| {
+            handle_proxy_connection(
+                tcp_stream,
+                ws_url.clone(),
+                Arc::clone(&allowed_ports),
+                Arc::clone(&stats),
+                session_ping_rx,
+                Arc::clone(&server_public_key),
+            )
+        })
+        .await
+    }
+
+    /// [accept_sessions]
+    /// リスナーで接続を受け付け、接続ごとに `session` を実行します。
+    /// 手動 Ping の要求は、実行中のすべてのセッションへ転送します。
+    async fn accept_sessions<F, Fut>(
+        listener: TcpListener,
+        mut ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+        session: F,
+    ) -> Result<(), CryptoError>
+    where
+        F: Fn(TcpStream, tokio::sync::mpsc::UnboundedReceiver<()>) -> Fut,
+        Fut: Future<Output = Result<(), CryptoError>> + Send + 'static,
+    {
         let mut join_set = tokio::task::JoinSet::new();
         let mut session_ping_txs = Vec::<tokio::sync::mpsc::UnboundedSender<()>>::new();
 
@@ -77,32 +212,20 @@ impl WsClientService {
                 conn = listener.accept() => {
                     match conn {
                         Ok((tcp_stream, addr)) => {
-                            info!("新規ローカル接続: {}", addr);
-
-                            let ws_url_clone = ws_url.clone();
-                            let proto_clone = protocol.clone();
-                            let stats_clone = Arc::clone(&stats);
-                            let key_clone = Arc::clone(&server_public_key);
+                            info!("{}", t!("client.new_connection", addr = addr));
 
                             let (session_ping_tx, session_ping_rx) = tokio::sync::mpsc::unbounded_channel();
                             session_ping_txs.push(session_ping_tx);
 
+                            let fut = session(tcp_stream, session_ping_rx);
                             join_set.spawn(async move {
-                                if let Err(e) = handle_tunnel(
-                                    tcp_stream,
-                                    ws_url_clone,
-                                    remote_target_port,
-                                    proto_clone,
-                                    stats_clone,
-                                    session_ping_rx,
-                                    key_clone
-                                ).await {
-                                    error!("トンネルセッションが異常終了しました ({}): {}", addr, e);
+                                if let Err(e) = fut.await {
+                                    error!("{}", t!("client.session_failed", addr = addr, error = e));
                                 }
                             });
                         }
                         Err(e) => {
-                            error!("TCP accept エラー: {}", e);
+                            error!("{}", t!("client.accept_error", error = e));
                             break;
                         }
                     }
@@ -116,73 +239,79 @@ impl WsClientService {
         Ok(())
     }
 
+    /// [check_connectivity]
+    /// ゲートウェイとのセキュアハンドシェイクを 1 度だけ行い、接続可能かを確認します。
+    ///
+    /// ゲートウェイが鍵をローテーションしている場合は、手元の公開鍵で署名を検証した
+    /// 新しい公開鍵 (DER) を `Some` で返します。
+    /// 失敗した場合は、理由に応じた [McConnectError] を返します。
     pub async fn check_connectivity(
         ws_url: &str,
         remote_port: u16,
         protocol: Protocol,
-        server_public_key: Arc<RsaKeyPair>,
-    ) -> Result<(), CryptoError> {
-        info!("ゲートウェイへの接続テストを開始します: {}", ws_url);
+        server_public_key: Arc<RsaPublicKey>,
+    ) -> Result<Option<Vec<u8>>, CryptoError> {
+        info!("{}", t!("client.check_starting", url = ws_url));
         let url = match Url::parse(ws_url) {
             Ok(u) => u,
             Err(e) => {
-                error!("URLの解析に失敗しました ({}): {}", ws_url, e);
-                return Err(e.into());
+                error!("{}", t!("client.invalid_url", url = ws_url, error = e));
+                return Err(McConnectError::InvalidUrl(e.to_string()).into());
             }
         };
 
-        info!("WebSocket 接続中...");
+        info!("{}", t!("client.ws_connecting"));
         let (ws_stream, _) = match connect_async(url).await {
             Ok(v) => v,
             Err(e) => {
-                error!("WebSocket 接続自体に失敗しました: {}", e);
-                return Err(e.into());
+                error!("{}", t!("tunnel.ws_connect_failed", error = e));
+                return Err(McConnectError::WsConnectFailed(e.to_string()).into());
             }
         };
-        info!("WebSocket 接続に成功しました。セキュアハンドシェイクを試行します...");
+        info!("{}", t!("client.ws_connected"));
 
         let (mut ws_write, mut ws_read) = ws_stream.split();
 
-        let (secure_context, handshake_packet) =
+        let (mut secure_context, handshake_packet) =
             match create_secure_connect_packet(protocol, remote_port, server_public_key.as_ref()) {
                 Ok(v) => v,
                 Err(e) => {
-                    error!("ハンドシェイクパケットの生成に失敗: {}", e);
+                    error!("{}", t!("handshake.build_failed", error = e));
                     return Err(e);
                 }
             };
 
-        info!("SecureConnect パケットを送信中...");
+        info!("{}", t!("tunnel.sending_handshake"));
         let bin = match handshake_packet.to_vec() {
             Ok(b) => b,
             Err(e) => {
-                error!("ハンドシェイクパケットのシリアライズに失敗: {}", e);
+                error!("{}", t!("handshake.encode_failed", error = e));
                 return Err(e);
             }
         };
 
         if let Err(e) = ws_write.send(WsMessage::Binary(bin)).await {
-            error!("パケットの送信に失敗しました: {}", e);
+            error!("{}", t!("tunnel.send_failed", error = e));
             return Err(e.into());
         }
 
-        info!("ゲートウェイからの応答を待機しています...");
+        info!("{}", t!("tunnel.waiting_response"));
         match ws_read.next().await {
             Some(Ok(msg)) => {
                 let bin = msg.into_data();
-                info!("応答データを受信しました ({} bytes)。復号中...", bin.len());
+                info!("{}", t!("client.response_received", bytes = bin.len()));
                 let res_msg = match Message::from_slice(&bin) {
                     Ok(m) => m,
                     Err(e) => {
-                        error!("応答メッセージの MessagePack デコードに失敗: {}", e);
+                        error!("{}", t!("tunnel.response_decode_failed", error = e));
                         return Err(e);
                     }
                 };
                 let res_msg = match secure_context.unseal_message(res_msg) {
                     Ok(m) => m,
                     Err(e) => {
-                        error!("応答メッセージの復号に失敗: {}", e);
-                        return Err(e);
+                        error!("{}", t!("tunnel.response_decrypt_failed", error = e));
+                        return Err(McConnectError::DecryptFailed(e.to_string()).into());
                     }
                 };
 
@@ -190,51 +319,206 @@ impl WsClientService {
                     let res: ConnectResponsePayload = match res_msg.deserialize_payload() {
                         Ok(p) => p,
                         Err(e) => {
-                            error!("ConnectResponse ペイロードのデシリアライズに失敗: {}", e);
+                            error!("{}", t!("tunnel.response_payload_invalid", error = e));
                             return Err(e);
                         }
                     };
                     if res.success {
-                        info!("セキュア接続テストに成功しました。");
-                        return Ok(());
+                        let protocol_info = secure_context.apply_connect_response(&res)?;
+                        info!(
+                            "{}",
+                            t!(
+                                "client.check_succeeded",
+                                version = protocol_info.version,
+                                algorithm = secure_context.algorithm().unwrap_or("-")
+                            )
+                        );
+                        let Some(rotation) = res.key_rotation else {
+                            return Ok(None);
+                        };
+                        warn!("{}", t!("client.key_rotation_received"));
+                        return match verify_key_rotation(server_public_key.as_ref(), &rotation) {
+                            Ok(new_key) => {
+                                info!("{}", t!("client.key_rotation_verified"));
+                                Ok(Some(new_key))
+                            }
+                            Err(e) => {
+                                error!("{}", e);
+                                Ok(None)
+                            }
+                        };
                     }
-                    error!("サーバーにより接続が拒否されました: {}", res.message);
-                    return Err(res.message.into());
+                    error!(
+                        "{}",
+                        t!("tunnel.rejected", message = res.localized_message())
+                    );
+                    return Err(McConnectError::from_response(&res).into());
                 }
-                error!("予期しないコマンドを受信しました: {:?}", res_msg.command);
-                Err("予期しない応答です".into())
+                error!(
+                    "{}",
+                    t!(
+                        "tunnel.unexpected_response",
+                        command = format!("{:?}", res_msg.command)
+                    )
+                );
+                Err(McConnectError::ProtocolMismatch(t!("tunnel.expected_connect_response")).into())
             }
             Some(Err(e)) => {
-                error!("WebSocket でエラーが発生しました: {}", e);
-                Err(e.into())
+                error!("{}", t!("tunnel.ws_error", error = e));
+                Err(McConnectError::WsConnectFailed(e.to_string()).into())
             }
             None => {
-                error!("ゲートウェイによって接続が閉じられました。");
-                Err("接続終了".into())
+                error!("{}", t!("tunnel.closed_during_handshake"));
+                Err(McConnectError::ProtocolMismatch(t!("tunnel.closed_during_handshake")).into())
             }
         }
     }
 
+    /// [get_server_info]
+    /// ゲートウェイのバージョンと許可ポートを問い合わせます。
+    ///
+    /// 問い合わせにはランダムなチャレンジを添え、ゲートウェイが署名付きの公開鍵を
+    /// 返してきた場合はその署名を検証します。検証に失敗した場合はエラーになります。
     pub async fn get_server_info(ws_url: &str) -> Result<ServerInfoResponsePayload, CryptoError> {
-        info!("サーバー情報を取得しています: {}", ws_url);
-        let url = Url::parse(ws_url)?;
-        let (ws_stream, _) = connect_async(url).await?;
+        info!("{}", t!("client.fetching_info", url = ws_url));
+        let url = Url::parse(ws_url).map_err(|e| McConnectError::InvalidUrl(e.to_string()))?;
+        let (ws_stream, _) = connect_async(url)
+            .await
+            .map_err(|e| McConnectError::WsConnectFailed(e.to_string()))?;
         let (mut ws_write, mut ws_read) = ws_stream.split();
 
-        let packet = Message::new(Command::GetServerInfo, vec![]);
-        info!("GetServerInfo パケットを送信中...");
+        let mut challenge = vec![0u8; SERVER_KEY_CHALLENGE_LEN];
+        OsRng.fill_bytes(&mut challenge);
+        let packet = Message::from_payload(
+            Command::GetServerInfo,
+            &ServerInfoRequestPayload {
+                challenge: challenge.clone(),
+            },
+        )?;
+        info!("{}", t!("client.sending_info_request"));
         ws_write.send(WsMessage::Binary(packet.to_vec()?)).await?;
 
         if let Some(msg) = ws_read.next().await {
             let bin = msg?.into_data();
             let res_packet = Message::from_slice(&bin)?;
             if res_packet.command == Command::ServerInfoResponse {
-                info!("サーバー情報を正常に取得しました。");
-                return Ok(res_packet.deserialize_payload()?);
+                let info: ServerInfoResponsePayload = res_packet.deserialize_payload()?;
+                if let Some(server_key) = &info.server_key {
+                    verify_server_key(server_key, &challenge).inspect_err(|e| {
+                        error!("{}", e);
+                    })?;
+                    info!(
+                        "{}",
+                        t!(
+                            "client.server_key_verified",
+                            fingerprint = short_fingerprint(&server_key.public_key)
+                        )
+                    );
+                }
+                info!("{}", t!("client.info_received"));
+                return Ok(info);
+            }
+            error!(
+                "{}",
+                t!(
+                    "tunnel.unexpected_response",
+                    command = format!("{:?}", res_packet.command)
+                )
+            );
+        }
+        let message = t!("client.info_failed");
+        error!("{}", message);
+        Err(message.into())
+    }
+
+    /// [trust_on_first_use]
+    /// ゲートウェイが提示する署名付き公開鍵を取得し、既知サーバーの記録と照合します。
+    ///
+    /// 初回接続時は公開鍵を記録して保存し、2 回目以降は記録と一致しない場合にエラーを返します。
+    /// ただし、記録済みの鍵でハンドシェイクした際に、その鍵で署名された鍵ローテーション通知が
+    /// 提示された公開鍵を指している場合は、ローテーションとして記録を置き換えて保存します。
+    /// 成功した場合は、以降のハンドシェイクに使用する公開鍵 (DER) を返します。
+    pub async fn trust_on_first_use(
+        ws_url: &str,
+        known_servers: &mut KnownServers,
+    ) -> Result<Vec<u8>, CryptoError> {
+        let info = Self::get_server_info(ws_url).await?;
+        let server_key = info.server_key.ok_or_else(|| t!("client.no_signed_key"))?;
+
+        let status = match known_servers.trust(ws_url, &server_key.public_key) {
+            Ok(status) => status,
+            Err(mismatch) => {
+                let Some(pinned) = known_servers.get(ws_url) else {
+                    return Err(mismatch);
+                };
+                if let Err(e) = Self::confirm_rotation(
+                    ws_url,
+                    &info.allowed_ports,
+                    &pinned,
+                    &server_key.public_key,
+                )
+                .await
+                {
+                    warn!("{}", t!("client.rotation_not_confirmed", error = e));
+                    return Err(mismatch);
+                }
+                known_servers.replace(ws_url, &server_key.public_key);
+                known_servers.save()?;
+                warn!(
+                    "{}",
+                    t!(
+                        "client.known_key_rotated",
+                        old = fingerprint(&pinned),
+                        new = fingerprint(&server_key.public_key)
+                    )
+                );
+                return Ok(server_key.public_key);
             }
-            error!("想定外の応答です: {:?}", res_packet.command);
+        };
+        match status {
+            TrustStatus::FirstUse => {
+                warn!(
+                    "{}",
+                    t!(
+                        "client.first_use",
+                        fingerprint = fingerprint(&server_key.public_key)
+                    )
+                );
+                known_servers.save()?;
+            }
+            TrustStatus::Trusted => {
+                info!("{}", t!("client.key_trusted"));
+            }
+        }
+        Ok(server_key.public_key)
+    }
+
+    /// [confirm_rotation]
+    /// 記録済みの公開鍵 `pinned` でハンドシェイクし、返された鍵ローテーション通知が
+    /// `presented` の公開鍵を指しているかを確認します。
+    ///
+    /// 通知の署名は [check_connectivity](Self::check_connectivity) が `pinned` で検証します。
+    /// ハンドシェイクには、ゲートウェイが許可している最初のポートを使用します。
+    async fn confirm_rotation(
+        ws_url: &str,
+        allowed_ports: &[AllowedPort],
+        pinned: &[u8],
+        presented: &[u8],
+    ) -> Result<(), CryptoError> {
+        let port = allowed_ports
+            .first()
+            .ok_or_else(|| t!("client.rotation_no_port"))?;
+        let pinned_key = Arc::new(RsaPublicKey::from_der(pinned)?);
+        let rotated =
+            Self::check_connectivity(ws_url, port.port, port.protocol.clone(), pinned_key).await?;
+        match rotated {
+            Some(new_key) if new_key == presented => Ok(()),
+            Some(new_key) => Err(t!(
+                "client.rotation_key_differs",
+                rotated = fingerprint(&new_key)
+            )
+            .into()),
+            None => Err(t!("client.rotation_missing").into()),
         }
-        error!("サーバー情報の取得に失敗しました。");
-        Err("サーバー情報の取得に失敗しました".into())
     }
 }
@@ -1,12 +1,48 @@
+use super::connections::ConnectionRegistry;
 use crate::models::packet::StatsPayload;
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// 速度を計測する間隔
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 保持する速度の履歴の件数
+pub const SPEED_HISTORY_LEN: usize = 60;
+
+/// 速度の平滑化 (EWMA) に使用する係数。大きいほど直近の計測値を重視します。
+const SPEED_EWMA_ALPHA: f64 = 0.5;
+
+/// [SpeedSample]
+/// ある計測時点の平滑化済みの転送速度です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SpeedSample {
+    /// 送信速度 (bytes/sec)
+    pub upload_speed: u64,
+    /// 受信速度 (bytes/sec)
+    pub download_speed: u64,
+}
+
+/// 速度計測の途中経過
+#[derive(Debug, Default)]
+struct SamplerState {
+    last_upload: u64,
+    last_download: u64,
+    upload_ewma: Option<f64>,
+    download_ewma: Option<f64>,
+    history: VecDeque<SpeedSample>,
+}
 
 /// [TunnelStats]
 /// トンネル内の通信量やレイテンシ（RTT）をスレッドセーフに記録するための構造体です。
 ///
 /// 複数のスレッド（アップロード・タスク、ダウンロード・タスク、GUI更新タスク等）から
 /// 同時にアクセスされるため、全てのフィールドに `AtomicU64` を使用しています。
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct TunnelStats {
     /// 通算のアップロード転送量 (バイト単位)
     /// TCPから読み取ってWebSocketへ送る際に加算されます。
@@ -21,10 +57,38 @@ pub struct TunnelStats {
     pub last_rtt_ms: AtomicU64,
 
     /// 現在のアップロード速度 (bytes/sec)
+    /// [TunnelStats::sample] で、転送量の差分を EWMA で平滑化して更新されます。
     pub upload_speed: AtomicU64,
 
     /// 現在のダウンロード速度 (bytes/sec)
     pub download_speed: AtomicU64,
+
+    /// ゲートウェイから最後に届いた統計情報 (`Command::Stats`)
+    /// ゲートウェイ側で観測した転送量や接続遅延で、ローカルの数値と比較するために使用します。
+    pub gateway: Mutex<Option<StatsPayload>>,
+
+    /// 中継中のローカル接続ごとの統計情報
+    /// 上記の数値はすべての接続の合計で、接続ごとの内訳はこちらで確認できます。
+    pub connections: ConnectionRegistry,
+
+    sampler: Mutex<SamplerState>,
+    updates: watch::Sender<StatsPayload>,
+}
+
+impl Default for TunnelStats {
+    fn default() -> Self {
+        Self {
+            upload_total: AtomicU64::default(),
+            download_total: AtomicU64::default(),
+            last_rtt_ms: AtomicU64::default(),
+            upload_speed: AtomicU64::default(),
+            download_speed: AtomicU64::default(),
+            gateway: Mutex::default(),
+            connections: ConnectionRegistry::default(),
+            sampler: Mutex::default(),
+            updates: watch::channel(StatsPayload::default()).0,
+        }
+    }
 }
 
 impl TunnelStats {
@@ -47,6 +111,117 @@ impl TunnelStats {
             upload_speed: self.upload_speed.load(Ordering::Relaxed),
             download_speed: self.download_speed.load(Ordering::Relaxed),
             rtt_ms: Some(self.last_rtt_ms.load(Ordering::Relaxed)),
+            connect_latency_ms: None,
+            queue_depth: None,
         }
     }
+
+    /// [set_gateway_stats]
+    /// ゲートウェイから届いた統計情報を記録します。
+    pub fn set_gateway_stats(&self, stats: StatsPayload) {
+        *self.gateway.lock().unwrap() = Some(stats);
+    }
+
+    /// [gateway_snapshot]
+    /// ゲートウェイから最後に届いた統計情報を取得します。まだ届いていない場合は None を返します。
+    pub fn gateway_snapshot(&self) -> Option<StatsPayload> {
+        self.gateway.lock().unwrap().clone()
+    }
+
+    /// [sample]
+    /// 前回の計測から `elapsed` の間の転送量で速度を計算し、EWMA で平滑化して記録します。
+    ///
+    /// 計測結果は履歴に追加され、[TunnelStats::subscribe] の購読者へ通知されます。
+    /// 通常は [TunnelStats::start_sampler] が [SAMPLE_INTERVAL] ごとに呼び出します。
+    pub fn sample(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let upload_total = self.upload_total.load(Ordering::Relaxed);
+        let download_total = self.download_total.load(Ordering::Relaxed);
+
+        let speed = {
+            let mut state = self.sampler.lock().unwrap();
+            let upload_rate = upload_total.saturating_sub(state.last_upload) as f64 / secs;
+            let download_rate = download_total.saturating_sub(state.last_download) as f64 / secs;
+            state.last_upload = upload_total;
+            state.last_download = download_total;
+
+            let upload = ewma(&mut state.upload_ewma, upload_rate);
+            let download = ewma(&mut state.download_ewma, download_rate);
+            let speed = SpeedSample {
+                upload_speed: upload.round() as u64,
+                download_speed: download.round() as u64,
+            };
+            if state.history.len() == SPEED_HISTORY_LEN {
+                state.history.pop_front();
+            }
+            state.history.push_back(speed);
+            speed
+        };
+
+        self.upload_speed
+            .store(speed.upload_speed, Ordering::Relaxed);
+        self.download_speed
+            .store(speed.download_speed, Ordering::Relaxed);
+        self.updates.send_replace(self.get_snapshot());
+    }
+
+    /// [speed_history]
+    /// 直近 [SPEED_HISTORY_LEN] 回分の速度を、古い順に返します。
+    pub fn speed_history(&self) -> Vec<SpeedSample> {
+        self.sampler
+            .lock()
+            .unwrap()
+            .history
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// [subscribe]
+    /// 速度を計測するたびに最新のスナップショットを受け取るための受信側を返します。
+    pub fn subscribe(&self) -> watch::Receiver<StatsPayload> {
+        self.updates.subscribe()
+    }
+
+    /// [start_sampler]
+    /// [SAMPLE_INTERVAL] ごとに [TunnelStats::sample] を呼び出すタスクを開始します。
+    /// 返された [SpeedSampler] を drop するか、統計情報が破棄されるとタスクも終了します。
+    pub fn start_sampler(self: &Arc<Self>) -> SpeedSampler {
+        let stats: Weak<Self> = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+            interval.tick().await;
+            let mut last = Instant::now();
+            loop {
+                interval.tick().await;
+                let Some(stats) = stats.upgrade() else {
+                    break;
+                };
+                stats.sample(last.elapsed());
+                last = Instant::now();
+            }
+        });
+        SpeedSampler(handle)
+    }
+}
+
+/// [SpeedSampler]
+/// [TunnelStats::start_sampler] で開始した速度計測タスクです。drop すると停止します。
+#[derive(Debug)]
+pub struct SpeedSampler(JoinHandle<()>);
+
+impl Drop for SpeedSampler {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// 指数移動平均を更新して返します。最初の計測値はそのまま使用します。
+fn ewma(current: &mut Option<f64>, value: f64) -> f64 {
+    let next = match *current {
+        Some(prev) => SPEED_EWMA_ALPHA * value + (1.0 - SPEED_EWMA_ALPHA) * prev,
+        None => value,
+    };
+    *current = Some(next);
+    next
 }
@@ -1,15 +1,34 @@
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
-use log::{error, info};
+use log::{error, info, warn};
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::time::{Duration, Instant, interval};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async, tungstenite::protocol::Message as WsMessage,
+};
 use url::Url;
 
+use super::connections::ConnectionGuard;
 use super::stats::TunnelStats;
-use crate::encryption::{RsaKeyPair, create_secure_connect_packet};
-use crate::models::packet::{Command, ConnectResponsePayload, Message, PingPayload, Protocol};
+use crate::encryption::{RsaPublicKey, SecureContext, create_secure_connect_packet};
+use crate::error::McConnectError;
+use crate::models::packet::{
+    Command, ConnectResponsePayload, Message, PingPayload, Protocol, StatsPayload,
+};
+use crate::t;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// [SecureTunnel]
+/// ゲートウェイとのセキュアハンドシェイクが完了した WebSocket 接続です。
+/// [run_secure_tunnel] でローカルの TCP 接続と中継します。
+pub struct SecureTunnel {
+    ws_write: SplitSink<WsStream, WsMessage>,
+    ws_read: SplitStream<WsStream>,
+    secure_context: SecureContext,
+}
 
 /// [handle_tunnel]
 /// セキュアなトンネル接続を確立し、データの送受信を行うメインロジックです。
@@ -19,77 +38,96 @@ pub async fn handle_tunnel(
     remote_port: u16,
     protocol: Protocol,
     stats: Arc<TunnelStats>,
-    mut manual_ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
-    server_public_key: Arc<RsaKeyPair>, // サーバーの公開鍵
+    manual_ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+    server_public_key: Arc<RsaPublicKey>, // サーバーの公開鍵
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let conn = stats.connections.open(tcp_stream.peer_addr().ok());
+    let tunnel =
+        open_secure_tunnel(&ws_url, remote_port, protocol, server_public_key.as_ref()).await?;
+    run_secure_tunnel(tcp_stream, tunnel, stats, conn, manual_ping_rx).await
+}
+
+/// [open_secure_tunnel]
+/// ゲートウェイへ WebSocket で接続し、`remote_port` へのセキュアハンドシェイクを行います。
+/// 接続やハンドシェイクに失敗した場合は、理由に応じた [McConnectError] を返します。
+pub async fn open_secure_tunnel(
+    ws_url: &str,
+    remote_port: u16,
+    protocol: Protocol,
+    server_public_key: &RsaPublicKey,
+) -> Result<SecureTunnel, Box<dyn std::error::Error + Send + Sync>> {
     // 1. WebSocket 接続の開始
-    let url = match Url::parse(&ws_url) {
+    let url = match Url::parse(ws_url) {
         Ok(u) => u,
         Err(e) => {
-            error!("URLの解析に失敗しました: {}", e);
-            return Err(e.into());
+            error!("{}", t!("tunnel.invalid_url", error = e));
+            return Err(McConnectError::InvalidUrl(e.to_string()).into());
         }
     };
-    info!("WebSocket 接続を開始します: {}", url);
+    info!("{}", t!("tunnel.connecting", url = url));
     let (ws_stream, _) = match connect_async(url).await {
         Ok(v) => v,
         Err(e) => {
-            error!("WebSocket 接続自体に失敗しました: {}", e);
-            return Err(e.into());
+            error!("{}", t!("tunnel.ws_connect_failed", error = e));
+            return Err(McConnectError::WsConnectFailed(e.to_string()).into());
         }
     };
-    info!("WebSocket 接続が確立されました。");
+    info!("{}", t!("tunnel.ws_connected"));
     let (mut ws_write, mut ws_read) = ws_stream.split();
 
     // 2. セキュアハンドシェイク (Handshake Phase)
     info!(
-        "セキュアハンドシェイクを開始します (Target Port: {}, Protocol: {:?})",
-        remote_port, protocol
+        "{}",
+        t!(
+            "tunnel.handshake_starting",
+            port = remote_port,
+            protocol = format!("{:?}", protocol)
+        )
     );
-    let (secure_context, handshake_packet) =
-        match create_secure_connect_packet(protocol, remote_port, server_public_key.as_ref()) {
+    let (mut secure_context, handshake_packet) =
+        match create_secure_connect_packet(protocol, remote_port, server_public_key) {
             Ok(v) => v,
             Err(e) => {
-                error!("ハンドシェイクパケットの生成に失敗: {}", e);
+                error!("{}", t!("handshake.build_failed", error = e));
                 return Err(e);
             }
         };
 
     // ハンドシェイクパケットを送信
-    info!("SecureConnect パケットを送信します...");
+    info!("{}", t!("tunnel.sending_handshake"));
     let bin = match handshake_packet.to_vec() {
         Ok(b) => b,
         Err(e) => {
-            error!("ハンドシェイクパケットのシリアライズに失敗: {}", e);
+            error!("{}", t!("handshake.encode_failed", error = e));
             return Err(e);
         }
     };
 
     if let Err(e) = ws_write.send(WsMessage::Binary(bin)).await {
-        error!("パケットの送信に失敗しました: {}", e);
+        error!("{}", t!("tunnel.send_failed", error = e));
         return Err(e.into());
     }
 
     // サーバーからの応答待ち
-    info!("ゲートウェイからの応答を待機中...");
+    info!("{}", t!("tunnel.waiting_response"));
     match ws_read.next().await {
         Some(Ok(msg)) => {
             let bin = msg.into_data();
             let res_packet = match Message::from_slice(&bin) {
                 Ok(m) => m,
                 Err(e) => {
-                    error!("応答メッセージのデコードに失敗: {}", e);
+                    error!("{}", t!("tunnel.response_decode_failed", error = e));
                     return Err(e);
                 }
             };
 
-            info!("応答パケットを受信しました。復号を試みます...");
+            info!("{}", t!("tunnel.response_received"));
             // 応答パケットを復号
             let res_packet = match secure_context.unseal_message(res_packet) {
                 Ok(p) => p,
                 Err(e) => {
-                    error!("応答メッセージの復号に失敗: {}", e);
-                    return Err(e.into());
+                    error!("{}", t!("tunnel.response_decrypt_failed", error = e));
+                    return Err(McConnectError::DecryptFailed(e.to_string()).into());
                 }
             };
 
@@ -97,44 +135,95 @@ pub async fn handle_tunnel(
                 let res: ConnectResponsePayload = match res_packet.deserialize_payload() {
                     Ok(p) => p,
                     Err(e) => {
-                        error!("ConnectResponse ペイロードのデシリアライズに失敗: {}", e);
+                        error!("{}", t!("tunnel.response_payload_invalid", error = e));
                         return Err(e);
                     }
                 };
                 if !res.success {
-                    error!("ゲートウェイが接続を拒否しました: {}", res.message);
-                    return Err(
-                        format!("Gateway rejected secure connection: {}", res.message).into(),
+                    error!(
+                        "{}",
+                        t!("tunnel.rejected", message = res.localized_message())
                     );
+                    return Err(McConnectError::from_response(&res).into());
                 }
-                info!("セキュアハンドシェイクに成功しました。暗号化トンネルが有効です。");
+                let protocol_info = match secure_context.apply_connect_response(&res) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        error!("{}", t!("tunnel.algorithm_unusable", error = e));
+                        return Err(e);
+                    }
+                };
+                info!(
+                    "{}",
+                    t!(
+                        "tunnel.established",
+                        version = protocol_info.version,
+                        algorithm = secure_context.algorithm().unwrap_or("-")
+                    )
+                );
             } else {
                 error!(
-                    "プロトコルエラー: ConnectResponse 以外のパケットを受信しました: {:?}",
-                    res_packet.command
+                    "{}",
+                    t!(
+                        "tunnel.unexpected_response",
+                        command = format!("{:?}", res_packet.command)
+                    )
                 );
-                return Err("Protocol error: Expected ConnectResponse after SecureConnect".into());
+                return Err(McConnectError::ProtocolMismatch(t!(
+                    "tunnel.expected_connect_response"
+                ))
+                .into());
             }
         }
         Some(Err(e)) => {
-            error!("WebSocket でエラーが発生しました: {}", e);
-            return Err(e.into());
+            error!("{}", t!("tunnel.ws_error", error = e));
+            return Err(McConnectError::WsConnectFailed(e.to_string()).into());
         }
         None => {
-            error!("ハンドシェイク中にサーバーによって接続が閉じられました。");
-            return Err("Connection closed by server during handshake".into());
+            error!("{}", t!("tunnel.closed_during_handshake"));
+            return Err(
+                McConnectError::ProtocolMismatch(t!("tunnel.closed_during_handshake")).into(),
+            );
         }
     }
 
+    Ok(SecureTunnel {
+        ws_write,
+        ws_read,
+        secure_context,
+    })
+}
+
+/// [run_secure_tunnel]
+/// ハンドシェイク済みのトンネルとローカルの TCP 接続の間でデータを中継します。
+/// どちらかが切断されるか、`conn` に切断が要求されるまで戻りません。
+pub async fn run_secure_tunnel(
+    tcp_stream: TcpStream,
+    tunnel: SecureTunnel,
+    stats: Arc<TunnelStats>,
+    conn: ConnectionGuard,
+    mut manual_ping_rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let SecureTunnel {
+        mut ws_write,
+        mut ws_read,
+        mut secure_context,
+    } = tunnel;
+
     // --- 以降、すべての通信は secure_context を通じて暗号化されます ---
 
     let (mut tcp_read, mut tcp_write) = tcp_stream.into_split();
     let (internal_tx, mut internal_rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
+    conn.set_active();
+
+    // Ping のタイムスタンプはこの時刻からの経過ミリ秒で、ゲートウェイは Pong でそのまま返してくる
+    let epoch = Instant::now();
 
     // タスク 1: TCP -> WS (Upload)
     let stats_up = Arc::clone(&stats);
+    let conn_up = conn.stats();
     let itx_up = internal_tx.clone();
-    tokio::spawn(async move {
+    let upload_task = tokio::spawn(async move {
         let mut buf = [0u8; 8192];
         loop {
             match tcp_read.read(&mut buf).await {
@@ -143,6 +232,9 @@ pub async fn handle_tunnel(
                     stats_up
                         .upload_total
                         .fetch_add(n as u64, std::sync::atomic::Ordering::Relaxed);
+                    conn_up
+                        .upload_total
+                        .fetch_add(n as u64, std::sync::atomic::Ordering::Relaxed);
                     // データを Data パケットとして送信キューへ
                     if itx_up
                         .send(Message::new(Command::Data, buf[..n].to_vec()))
@@ -152,7 +244,7 @@ pub async fn handle_tunnel(
                     }
                 }
                 Err(e) => {
-                    error!("TCP read error: {}", e);
+                    error!("{}", t!("tunnel.tcp_read_error", error = e));
                     break;
                 }
             }
@@ -167,12 +259,12 @@ pub async fn handle_tunnel(
         loop {
             interval.tick().await;
             let ping = PingPayload {
-                timestamp: Instant::now().elapsed().as_millis() as u64,
+                timestamp: epoch.elapsed().as_millis() as u64,
             };
-            if let Ok(p) = Message::from_payload(Command::Ping, &ping) {
-                if itx_ping.send(p).is_err() {
-                    break;
-                }
+            if let Ok(p) = Message::from_payload(Command::Ping, &ping)
+                && itx_ping.send(p).is_err()
+            {
+                break;
             }
         }
     });
@@ -185,13 +277,12 @@ pub async fn handle_tunnel(
                 match msg {
                     Ok(ws_msg) => {
                         let bin = ws_msg.into_data();
-                        let encrypted_packet = Message::from_slice(&bin)?;
 
-                        // パケットを復号
-                        let packet = match secure_context.unseal_message(encrypted_packet) {
+                        // パケットを復号 (Data フレームと MessagePack のどちらも受け付ける)
+                        let packet = match secure_context.open_bytes(&bin) {
                             Ok(p) => p,
                             Err(e) => {
-                                error!("Packet decryption error: {}. Closing tunnel.", e);
+                                error!("{}", t!("tunnel.decrypt_error", error = e));
                                 break;
                             }
                         };
@@ -199,65 +290,101 @@ pub async fn handle_tunnel(
                         match packet.command {
                             Command::Data => {
                                 stats.download_total.fetch_add(packet.payload.len() as u64, std::sync::atomic::Ordering::Relaxed);
+                                conn.download_total.fetch_add(packet.payload.len() as u64, std::sync::atomic::Ordering::Relaxed);
                                 if let Err(e) = tcp_write.write_all(&packet.payload).await {
-                                    error!("TCP write error: {}", e);
+                                    error!("{}", t!("tunnel.tcp_write_error", error = e));
                                     break;
                                 }
                             }
                             Command::Pong => {
                                 if let Ok(payload) = packet.deserialize_payload::<PingPayload>() {
-                                    let rtt = (Instant::now().elapsed().as_millis() as u64).saturating_sub(payload.timestamp);
+                                    let rtt = (epoch.elapsed().as_millis() as u64).saturating_sub(payload.timestamp);
                                     stats.last_rtt_ms.store(rtt, std::sync::atomic::Ordering::Relaxed);
+                                    conn.last_rtt_ms.store(rtt, std::sync::atomic::Ordering::Relaxed);
                                 }
                             }
                             Command::Disconnect => {
-                                info!("Gateway requested disconnection from secure tunnel.");
+                                info!("{}", t!("tunnel.gateway_disconnect"));
                                 break;
                             }
+                            Command::Rekey => {
+                                info!("{}", t!("tunnel.gateway_rekeyed"));
+                            }
+                            Command::Stats => {
+                                if let Ok(payload) = packet.deserialize_payload::<StatsPayload>() {
+                                    stats.set_gateway_stats(payload);
+                                }
+                            }
+                            Command::Unknown => {
+                                warn!("{}", t!("tunnel.unknown_command"));
+                            }
                             _ => {}
                         }
                     }
                     Err(e) => {
-                        error!("WebSocket read error: {}", e);
+                        error!("{}", t!("tunnel.ws_read_error", error = e));
                         break;
                     }
                 }
             }
 
             // [送信] 内部タスクからの送信要求を受け取り、暗号化して WS へ送る
-            Some(mut packet) = internal_rx.recv() => {
-                // パケットを暗号化
-                packet = match secure_context.seal_message(packet) {
-                    Ok(p) => p,
+            Some(packet) = internal_rx.recv() => {
+                let is_disconnect = packet.command == Command::Disconnect;
+                // パケットを暗号化 (Data はコンパクトなフレームが有効ならフレーム形式になる)
+                let bin = match secure_context.seal_to_bytes(packet) {
+                    Ok(b) => b,
                     Err(e) => {
-                        error!("Packet encryption error: {}", e);
+                        error!("{}", t!("tunnel.encrypt_error", error = e));
                         break;
                     }
                 };
 
-                if let Ok(bin) = packet.to_vec() {
-                    if let Err(e) = ws_write.send(WsMessage::Binary(bin)).await {
-                        error!("WebSocket send error: {}", e);
+                if let Err(e) = ws_write.send(WsMessage::Binary(bin)).await {
+                    error!("{}", t!("tunnel.ws_send_error", error = e));
+                    break;
+                }
+                if is_disconnect { break; }
+
+                // 送信量・経過時間が上限に達していれば、トンネルを維持したまま共通鍵を更新する
+                match secure_context.take_rekey() {
+                    Ok(Some(bin)) => {
+                        if let Err(e) = ws_write.send(WsMessage::Binary(bin)).await {
+                            error!("{}", t!("tunnel.ws_send_error", error = e));
+                            break;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!("{}", t!("tunnel.rekey_error", error = e));
                         break;
                     }
                 }
-                if packet.command == Command::Disconnect { break; }
             }
 
             // [手動Ping] 暗号化して送信
             Some(_) = manual_ping_rx.recv() => {
-                let ping = PingPayload { timestamp: Instant::now().elapsed().as_millis() as u64 };
-                if let Ok(p) = Message::from_payload(Command::Ping, &ping) {
-                    if let Ok(sealed) = secure_context.seal_message(p) {
-                        if let Ok(bin) = sealed.to_vec() {
-                            let _ = ws_write.send(WsMessage::Binary(bin)).await;
-                        }
-                    }
+                let ping = PingPayload { timestamp: epoch.elapsed().as_millis() as u64 };
+                if let Ok(p) = Message::from_payload(Command::Ping, &ping)
+                    && let Ok(bin) = secure_context.seal_to_bytes(p)
+                {
+                    let _ = ws_write.send(WsMessage::Binary(bin)).await;
+                }
+            }
+
+            // [切断要求] この接続だけを閉じる。ゲートウェイにも切断を通知する
+            _ = conn.closed() => {
+                info!("{}", t!("tunnel.closing_connection", id = conn.id));
+                if let Ok(bin) = secure_context.seal_to_bytes(Message::new(Command::Disconnect, vec![])) {
+                    let _ = ws_write.send(WsMessage::Binary(bin)).await;
                 }
+                break;
             }
         }
     }
 
-    info!("Secure tunnel session closed.");
+    // ローカル接続の読み取り側も閉じて、接続を確実に終了させる
+    upload_task.abort();
+    info!("{}", t!("tunnel.closed"));
     Ok(())
 }
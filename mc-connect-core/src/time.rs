@@ -0,0 +1,12 @@
+//! 時刻に関する共通の処理
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// [unix_now]
+/// 現在時刻を UNIX 秒で返します。システム時刻が UNIX エポックより前の場合は 0 を返します。
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,146 @@
+//! ゲートウェイのセッション監査ログの結合テスト
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use common::*;
+use mc_connect_core::encryption::{CryptoError, CryptoKeyPair, RsaPublicKey, ServerKeyring};
+use mc_connect_core::models::packet::Protocol;
+use mc_connect_core::services::proxy::{AuditLog, AuditReason, AuditRecord, HandshakeOutcome};
+use mc_connect_core::{GatewayOptions, WsClientService};
+use tokio::time::timeout;
+
+/// テストごとに別の監査ログのパスを返します。
+fn audit_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "mc-connect-audit-{}-{}.jsonl",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn start_gateway(allowed_port: u16, path: &Path) -> TestGateway {
+    TestGateway::start_with_options(
+        allow_tcp(allowed_port),
+        ServerKeyring::new(server_key()),
+        GatewayOptions {
+            audit: Some(Arc::new(AuditLog::open(path).unwrap())),
+            ..GatewayOptions::default()
+        },
+    )
+}
+
+/// ハンドシェイクまで進んだセッションの記録が書き出されるまで待ち、最初の 1 件を返します。
+/// サーバー情報の問い合わせだけのセッションは読み飛ばします。
+async fn wait_for_record(path: &Path) -> AuditRecord {
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    loop {
+        let records: Vec<AuditRecord> = std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("監査記録が JSON ではありません"))
+            .collect();
+        if let Some(record) = records
+            .into_iter()
+            .find(|r| r.handshake != HandshakeOutcome::None)
+        {
+            return record;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "監査記録が書き出されませんでした"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+}
+
+async fn check(
+    gateway: &TestGateway,
+    port: u16,
+    key: Arc<RsaPublicKey>,
+) -> Result<Option<Vec<u8>>, CryptoError> {
+    timeout(
+        TIMEOUT,
+        WsClientService::check_connectivity(&gateway.ws_url, port, Protocol::TCP, key),
+    )
+    .await
+    .expect("接続テストがタイムアウトしました")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn successful_session_is_recorded_with_traffic() {
+    let path = audit_path("success");
+    let echo = spawn_echo_server().await;
+    let gateway = start_gateway(echo.port(), &path);
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    let data = pattern(5_000, 7);
+    let received = timeout(TIMEOUT, round_trip(tunnel.connect().await, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+
+    let record = wait_for_record(&path).await;
+    assert_eq!(record.handshake, HandshakeOutcome::Success);
+    assert_eq!(record.handshake_reason, None);
+    assert_eq!(record.peer_ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(record.key_fingerprint, Some(server_key().fingerprint()));
+    assert_eq!(record.port, Some(echo.port()));
+    assert_eq!(record.protocol, Some(Protocol::TCP));
+    assert_eq!(record.bytes_up, data.len() as u64);
+    assert_eq!(record.bytes_down, data.len() as u64);
+    assert!(record.close_reason.is_some());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disallowed_port_is_recorded_as_rejected() {
+    let path = audit_path("rejected");
+    let echo = spawn_echo_server().await;
+    let gateway = start_gateway(echo.port(), &path);
+
+    let disallowed = echo.port().wrapping_add(1);
+    assert!(
+        check(&gateway, disallowed, server_public_key())
+            .await
+            .is_err()
+    );
+
+    let record = wait_for_record(&path).await;
+    assert_eq!(record.handshake, HandshakeOutcome::Rejected);
+    assert_eq!(record.port, Some(disallowed));
+    // 理由は言語や文面によらない固定のコードで、文面は詳細として別に残る
+    assert_eq!(record.handshake_reason, Some(AuditReason::PortNotAllowed));
+    let detail = record.handshake_detail.unwrap_or_default();
+    assert!(detail.contains("Unauthorized"), "{}", detail);
+    assert_eq!(record.close_reason, Some(AuditReason::PortNotAllowed));
+    assert_eq!(record.bytes_up, 0);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wrong_key_is_recorded_as_failed_handshake() {
+    let path = audit_path("failed");
+    let echo = spawn_echo_server().await;
+    let gateway = start_gateway(echo.port(), &path);
+
+    let wrong_key = generate_key();
+    let wrong_public = Arc::new(RsaPublicKey::from_der(&wrong_key.public_key_bytes()).unwrap());
+    assert!(check(&gateway, echo.port(), wrong_public).await.is_err());
+
+    // 詳細な理由は監査ログにのみ残り、フィンガープリントやポートは記録されない
+    let record = wait_for_record(&path).await;
+    assert_eq!(record.handshake, HandshakeOutcome::Failed);
+    assert_eq!(record.handshake_reason, Some(AuditReason::HandshakeFailed));
+    assert!(record.handshake_detail.is_some());
+    assert_eq!(record.key_fingerprint, None);
+    assert_eq!(record.port, None);
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,202 @@
+//! 共通鍵暗号エンジン (AES-256-GCM / ChaCha20-Poly1305) と、その選択・切り替えのテスト
+
+mod common;
+
+use common::*;
+use mc_connect_core::encryption::{
+    ChaCha20Poly1305Engine, Encryptor, KeyWrap, SecureContext, ServerKeyring, SymmetricAlgorithm,
+    SymmetricCrypto, handle_server_handshake,
+};
+use mc_connect_core::models::frame::Direction;
+use mc_connect_core::models::packet::{
+    Command, ConnectResponsePayload, Message, Protocol, ProtocolInfo, SecureConnectPayload,
+    encode_payload,
+};
+
+use SymmetricAlgorithm::{Aes256Gcm, ChaCha20Poly1305};
+
+/// 指定した共通鍵暗号でハンドシェイクする SecureConnect を作成します。
+fn secure_connect(algorithm: &str, offers: &[&str]) -> (Box<dyn SymmetricCrypto>, Message) {
+    let engine = algorithm
+        .parse::<SymmetricAlgorithm>()
+        .map(|a| a.generate())
+        .unwrap_or_else(|_| Aes256Gcm.generate());
+    let payload = SecureConnectPayload {
+        protocol: Protocol::TCP,
+        port: 25565,
+        encrypted_key: server_public_key().encrypt(&engine.key_bytes()).unwrap(),
+        algorithm: algorithm.to_string(),
+        algorithms: offers.iter().map(|o| o.to_string()).collect(),
+        protocol_info: Some(ProtocolInfo::current()),
+        key_wrap: Some(KeyWrap::OaepSha256.name().to_string()),
+    };
+    let msg = Message::from_payload(Command::SecureConnect, &payload).unwrap();
+    (engine, msg)
+}
+
+#[test]
+fn chacha_engine_round_trip_and_tamper_detection() {
+    let engine = ChaCha20Poly1305Engine::new_random();
+    assert_eq!(engine.algorithm_name(), "ChaCha20-Poly1305");
+    assert!(ChaCha20Poly1305Engine::from_key(&[0u8; 16]).is_err());
+
+    let sealed = engine.encrypt(b"minecraft").unwrap();
+    assert_eq!(engine.decrypt(&sealed).unwrap(), b"minecraft");
+    let mut tampered = sealed.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(engine.decrypt(&tampered).is_err());
+
+    let nonce = Direction::ClientToServer.nonce(7);
+    let mut buf = b"in place".to_vec();
+    let tag = engine
+        .encrypt_in_place_detached(&nonce, b"header", &mut buf)
+        .unwrap();
+    assert!(
+        engine
+            .decrypt_in_place_detached(&nonce, b"other", &mut buf.clone(), &tag)
+            .is_err()
+    );
+    engine
+        .decrypt_in_place_detached(&nonce, b"header", &mut buf, &tag)
+        .unwrap();
+    assert_eq!(buf, b"in place");
+
+    // 同じ鍵でも AES-256-GCM の暗号文は復号できない
+    let aes = Aes256Gcm.from_key(&engine.key_bytes()).unwrap();
+    assert!(aes.decrypt(&sealed).is_err());
+}
+
+#[test]
+fn algorithm_names_are_parsed_case_insensitively() {
+    for algorithm in SymmetricAlgorithm::ALL {
+        assert_eq!(
+            algorithm.name().parse::<SymmetricAlgorithm>().unwrap(),
+            algorithm
+        );
+        assert_eq!(algorithm.generate().algorithm_name(), algorithm.name());
+    }
+    assert_eq!(
+        "chacha20-poly1305".parse::<SymmetricAlgorithm>().unwrap(),
+        ChaCha20Poly1305
+    );
+    assert!("AES-128-CBC".parse::<SymmetricAlgorithm>().is_err());
+}
+
+#[test]
+fn negotiation_prefers_common_ranking_then_gateway() {
+    let aes_first = [Aes256Gcm, ChaCha20Poly1305];
+    let chacha_first = [ChaCha20Poly1305, Aes256Gcm];
+
+    assert_eq!(
+        SymmetricAlgorithm::negotiate(&aes_first, &aes_first),
+        Some(Aes256Gcm)
+    );
+    assert_eq!(
+        SymmetricAlgorithm::negotiate(&chacha_first, &chacha_first),
+        Some(ChaCha20Poly1305)
+    );
+    // 推奨が食い違う場合はゲートウェイ (AES 支援のない Pi など) の推奨を優先する
+    assert_eq!(
+        SymmetricAlgorithm::negotiate(&chacha_first, &aes_first),
+        Some(ChaCha20Poly1305)
+    );
+    assert_eq!(
+        SymmetricAlgorithm::negotiate(&aes_first, &chacha_first),
+        Some(Aes256Gcm)
+    );
+    // 片方しか対応していないものは選ばない
+    assert_eq!(
+        SymmetricAlgorithm::negotiate(&aes_first, &[ChaCha20Poly1305]),
+        Some(ChaCha20Poly1305)
+    );
+    assert_eq!(
+        SymmetricAlgorithm::negotiate(&[Aes256Gcm], &[ChaCha20Poly1305]),
+        None
+    );
+}
+
+#[test]
+fn handshake_rejects_unsupported_algorithm() {
+    let keys = ServerKeyring::new(server_key());
+
+    let (_, packet) = secure_connect("AES-128-CBC", &[]);
+    assert!(handle_server_handshake(packet, &keys).is_err());
+
+    let (_, packet) = secure_connect("AES-256-GCM", &["Serpent"]);
+    assert!(
+        handle_server_handshake(packet, &keys).is_err(),
+        "共通の共通鍵暗号がないハンドシェイクを受け付けました"
+    );
+}
+
+#[test]
+fn handshake_honors_requested_algorithm() {
+    let keys = ServerKeyring::new(server_key());
+    let (engine, packet) = secure_connect("ChaCha20-Poly1305", &[]);
+    let handshake = handle_server_handshake(packet, &keys).unwrap();
+    assert_eq!(handshake.algorithm, None);
+    assert_eq!(handshake.context.algorithm(), Some("ChaCha20-Poly1305"));
+
+    let mut client = SecureContext::with_crypto(engine, Direction::ClientToServer).unwrap();
+    client.apply_protocol(&handshake.protocol_info);
+    let mut server = handshake.context;
+    let ping = client
+        .seal_to_bytes(Message::new(Command::Ping, vec![1]))
+        .unwrap();
+    assert_eq!(server.open_bytes(&ping).unwrap().command, Command::Ping);
+}
+
+#[test]
+fn negotiated_cipher_is_switched_after_connect_response() {
+    let keys = ServerKeyring::new(server_key());
+    // 旧ゲートウェイとの互換のためハンドシェイクは AES で行い、セッションは ChaCha20-Poly1305 を希望する
+    let (engine, packet) = secure_connect("AES-256-GCM", &["ChaCha20-Poly1305", "Unknown-Cipher"]);
+    let handshake = handle_server_handshake(packet, &keys).unwrap();
+    assert_eq!(handshake.algorithm, Some(ChaCha20Poly1305));
+
+    let mut client = SecureContext::with_crypto(engine, Direction::ClientToServer).unwrap();
+    let mut server = handshake.context;
+
+    let res = ConnectResponsePayload {
+        success: true,
+        message: "OK".to_string(),
+        key_rotation: None,
+        protocol_info: Some(handshake.protocol_info.clone()),
+        algorithm: handshake.algorithm.map(|a| a.name().to_string()),
+        code: None,
+        params: Default::default(),
+    };
+    let bin = server
+        .seal_to_bytes(Message::new(
+            Command::ConnectResponse,
+            encode_payload(&res).unwrap(),
+        ))
+        .unwrap();
+    server.switch_algorithm(ChaCha20Poly1305).unwrap();
+
+    let res: ConnectResponsePayload = client
+        .open_bytes(&bin)
+        .unwrap()
+        .deserialize_payload()
+        .unwrap();
+    client.apply_connect_response(&res).unwrap();
+    assert_eq!(client.algorithm(), Some("ChaCha20-Poly1305"));
+    assert_eq!(server.algorithm(), Some("ChaCha20-Poly1305"));
+
+    let up = client
+        .seal_to_bytes(Message::new(Command::Data, b"up".to_vec()))
+        .unwrap();
+    assert_eq!(server.open_bytes(&up).unwrap().payload, b"up");
+    let down = server
+        .seal_to_bytes(Message::new(Command::Data, b"down".to_vec()))
+        .unwrap();
+    assert_eq!(client.open_bytes(&down).unwrap().payload, b"down");
+
+    // 切り替え後の鍵は更新も続けて行える
+    let rekey = client.rekey_message().unwrap();
+    server.open_bytes(&rekey).unwrap();
+    let after = client
+        .seal_to_bytes(Message::new(Command::Data, b"after".to_vec()))
+        .unwrap();
+    assert_eq!(server.open_bytes(&after).unwrap().payload, b"after");
+}
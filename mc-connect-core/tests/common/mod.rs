@@ -0,0 +1,257 @@
+//! 結合テスト用のハーネス
+//!
+//! ゲートウェイ (`bind_server`)、ターゲットとなる TCP エコーサーバー、
+//! クライアントトンネル (`WsClientService`) を同一プロセス内の空きポートで起動します。
+
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use mc_connect_core::WsClientService;
+use mc_connect_core::encryption::{
+    CryptoKeyPair, KeyGenerator, RsaKeyGenerator, RsaKeyPair, RsaPublicKey, ServerKeyring,
+};
+use mc_connect_core::models::packet::{AllowedPort, Protocol};
+use mc_connect_core::services::ws_client::TunnelStats;
+use mc_connect_core::{GatewayOptions, bind_server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// テストで使用する RSA の鍵長。デバッグビルドでも素早く生成できるよう小さめにしています。
+pub const TEST_KEY_BITS: usize = 1024;
+
+/// テスト 1 件あたりの待ち時間の上限
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// テストプロセス内で共有するサーバー秘密鍵 (DER)
+fn server_private_der() -> &'static [u8] {
+    static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+    KEY.get_or_init(|| generate_key().private_key_bytes())
+}
+
+/// テスト用の新しい鍵ペアを生成します。
+pub fn generate_key() -> Box<dyn CryptoKeyPair> {
+    RsaKeyGenerator {
+        bits: TEST_KEY_BITS,
+    }
+    .generate()
+    .expect("テスト鍵の生成に失敗しました")
+}
+
+/// ゲートウェイの秘密鍵ペアを返します。
+pub fn server_key() -> RsaKeyPair {
+    RsaKeyPair::from_private_der(server_private_der()).unwrap()
+}
+
+/// クライアントに配布する、ゲートウェイの公開鍵を返します。
+pub fn server_public_key() -> Arc<RsaPublicKey> {
+    Arc::new(server_key().public_key())
+}
+
+/// [TestGateway]
+/// 空きポートで起動したゲートウェイです。drop 時に停止します。
+pub struct TestGateway {
+    pub addr: SocketAddr,
+    pub ws_url: String,
+    handle: actix_web::dev::ServerHandle,
+}
+
+impl TestGateway {
+    /// 共有のサーバー鍵でゲートウェイを起動します。
+    pub fn start(allowed_ports: Vec<AllowedPort>) -> Self {
+        Self::start_with_keys(allowed_ports, ServerKeyring::new(server_key()))
+    }
+
+    /// 任意のキーリングでゲートウェイを起動します。
+    pub fn start_with_keys(allowed_ports: Vec<AllowedPort>, keys: ServerKeyring) -> Self {
+        Self::start_with_options(allowed_ports, keys, GatewayOptions::default())
+    }
+
+    /// 任意のキーリングと設定でゲートウェイを起動します。
+    pub fn start_with_options(
+        allowed_ports: Vec<AllowedPort>,
+        keys: ServerKeyring,
+        options: GatewayOptions,
+    ) -> Self {
+        let (server, addr) = bind_server("127.0.0.1", 0, allowed_ports, Arc::new(keys), options)
+            .expect("ゲートウェイのバインドに失敗しました");
+        let handle = server.handle();
+        tokio::spawn(server);
+        Self {
+            addr,
+            ws_url: format!("ws://{}/ws", addr),
+            handle,
+        }
+    }
+
+    /// ゲートウェイを停止し、同じアドレスで別のキーリングを使って起動し直します。
+    pub async fn restart_with_keys(
+        self,
+        allowed_ports: Vec<AllowedPort>,
+        keys: ServerKeyring,
+    ) -> Self {
+        let addr = self.addr;
+        self.handle.stop(true).await;
+        let (server, addr) = bind_server(
+            &addr.ip().to_string(),
+            addr.port(),
+            allowed_ports,
+            Arc::new(keys),
+            GatewayOptions::default(),
+        )
+        .expect("ゲートウェイの再バインドに失敗しました");
+        let handle = server.handle();
+        tokio::spawn(server);
+        Self {
+            addr,
+            ws_url: format!("ws://{}/ws", addr),
+            handle,
+        }
+    }
+}
+
+impl Drop for TestGateway {
+    fn drop(&mut self) {
+        let handle = self.handle.clone();
+        tokio::spawn(async move { handle.stop(false).await });
+    }
+}
+
+/// TCP のみを許可するポート設定を作成します。
+pub fn allow_tcp(port: u16) -> Vec<AllowedPort> {
+    vec![AllowedPort {
+        port,
+        protocol: Protocol::TCP,
+    }]
+}
+
+/// [spawn_echo_server]
+/// 受信したデータをそのまま送り返す TCP サーバーを空きポートで起動します。
+pub async fn spawn_echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = stream.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    addr
+}
+
+/// [spawn_fake_minecraft]
+/// Minecraft サーバーのように、クライアントからのハンドシェイク (可変長の長さ付きパケット) を
+/// 1 つ受け取ってから固定の応答を返す TCP サーバーを空きポートで起動します。
+pub async fn spawn_fake_minecraft(response: &'static [u8]) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let Ok(len) = read_varint(&mut stream).await else {
+                    return;
+                };
+                let mut packet = vec![0u8; len as usize];
+                if stream.read_exact(&mut packet).await.is_ok() {
+                    let _ = stream.write_all(response).await;
+                }
+            });
+        }
+    });
+    addr
+}
+
+async fn read_varint(stream: &mut TcpStream) -> std::io::Result<u32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = stream.read_u8().await?;
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(std::io::Error::other("VarInt が長すぎます"))
+}
+
+/// [TestTunnel]
+/// 空きポートで待ち受けるクライアントトンネルです。drop 時に停止します。
+pub struct TestTunnel {
+    pub addr: SocketAddr,
+    pub stats: Arc<TunnelStats>,
+    task: JoinHandle<()>,
+}
+
+impl TestTunnel {
+    /// ゲートウェイ経由で `remote_port` へ転送するトンネルを起動します。
+    pub async fn start(
+        ws_url: &str,
+        remote_port: u16,
+        server_public_key: Arc<RsaPublicKey>,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stats = Arc::new(TunnelStats::new());
+        let (_ping_tx, ping_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let ws_url = ws_url.to_string();
+        let stats_clone = Arc::clone(&stats);
+        let task = tokio::spawn(async move {
+            let _ = WsClientService::run_tunnel_listener(
+                listener,
+                ws_url,
+                remote_port,
+                Protocol::TCP,
+                stats_clone,
+                ping_rx,
+                server_public_key,
+            )
+            .await;
+        });
+        Self { addr, stats, task }
+    }
+
+    /// トンネルのローカル側に接続します。
+    pub async fn connect(&self) -> TcpStream {
+        TcpStream::connect(self.addr).await.unwrap()
+    }
+}
+
+impl Drop for TestTunnel {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 決まったシードで再現可能な疑似乱数データを生成します。
+pub fn pattern(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+    (0..len)
+        .map(|_| {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// `data` を送信しながら同じ長さだけ受信し、受信したバイト列を返します。
+pub async fn round_trip(stream: TcpStream, data: &[u8]) -> Vec<u8> {
+    let (mut reader, mut writer) = stream.into_split();
+    let to_send = data.to_vec();
+    let writer_task = tokio::spawn(async move {
+        writer.write_all(&to_send).await.unwrap();
+        writer
+    });
+
+    let mut received = vec![0u8; data.len()];
+    reader.read_exact(&mut received).await.unwrap();
+    drop(writer_task.await.unwrap());
+    received
+}
@@ -0,0 +1,357 @@
+//! プロトコル互換性のテスト
+//!
+//! `tests/fixtures/protocol` のシリアライズ済みフィクスチャと、旧バージョン (0.1.0) の
+//! ペイロード構造体を再現した `legacy` モジュールを使い、新旧の組み合わせを検証します。
+
+mod common;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use mc_connect_core::encryption::{
+    AesGcmEngine, HANDSHAKE_FAILED, KeyWrap, ServerKeyring, SymmetricCrypto,
+};
+use mc_connect_core::models::packet::{
+    Command, ConnectResponsePayload, ErrorCode, KeyRotation, Message, PingPayload, Protocol,
+    ProtocolInfo, SecureConnectPayload, ServerInfoResponsePayload, StatsPayload, capability,
+    encode_payload,
+};
+use tokio::time::timeout;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+
+/// 0.1.0 のペイロード構造体。旧バージョンの相手を再現するために使用します。
+mod legacy {
+    use mc_connect_core::encryption::{AesGcmEngine, SymmetricCrypto};
+    use mc_connect_core::models::packet::{AllowedPort, Message, Protocol};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SecureConnectPayload {
+        pub protocol: Protocol,
+        pub port: u16,
+        pub encrypted_key: Vec<u8>,
+        pub algorithm: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct ConnectResponsePayload {
+        pub success: bool,
+        pub message: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct ServerInfoResponsePayload {
+        pub server_version: String,
+        pub allowed_ports: Vec<AllowedPort>,
+    }
+
+    /// 0.1.0 の暗号化セッション。
+    /// ペイロードを毎回ランダムな Nonce で暗号化し (`Nonce || 暗号文 || 認証タグ`)、
+    /// カウンターや連番の検証、鍵の更新は行いません。
+    pub struct Session {
+        pub engine: AesGcmEngine,
+    }
+
+    impl Session {
+        pub fn seal(&self, mut msg: Message) -> Message {
+            msg.payload = self.engine.encrypt(&msg.payload).unwrap();
+            msg
+        }
+
+        pub fn unseal(&self, mut msg: Message) -> Message {
+            msg.payload = self.engine.decrypt(&msg.payload).unwrap();
+            msg
+        }
+    }
+}
+
+macro_rules! fixture {
+    ($path:literal) => {
+        Message::from_slice(include_bytes!(concat!("fixtures/protocol/", $path)))
+            .expect(concat!("フィクスチャのデコードに失敗しました: ", $path))
+    };
+}
+
+#[test]
+fn v0_messages_decode_with_current_types() {
+    let msg = fixture!("v0/secure_connect.msgpack");
+    assert_eq!(msg.command, Command::SecureConnect);
+    let payload: SecureConnectPayload = msg.deserialize_payload().unwrap();
+    assert_eq!(payload.protocol, Protocol::TCP);
+    assert_eq!(payload.port, 25565);
+    assert_eq!(payload.encrypted_key, (0u8..16).collect::<Vec<_>>());
+    assert_eq!(payload.algorithm, "AES-256-GCM");
+    assert!(payload.protocol_info.is_none());
+
+    let msg = fixture!("v0/connect_response.msgpack");
+    assert_eq!(msg.command, Command::ConnectResponse);
+    let payload: ConnectResponsePayload = msg.deserialize_payload().unwrap();
+    assert!(payload.success);
+    assert_eq!(payload.message, "OK");
+    assert!(payload.key_rotation.is_none());
+    assert!(payload.protocol_info.is_none());
+
+    let payload: ConnectResponsePayload = fixture!("v0/connect_response_rejected.msgpack")
+        .deserialize_payload()
+        .unwrap();
+    assert!(!payload.success);
+    assert!(payload.message.contains("Unauthorized"));
+
+    let msg = fixture!("v0/server_info_response.msgpack");
+    assert_eq!(msg.command, Command::ServerInfoResponse);
+    let payload: ServerInfoResponsePayload = msg.deserialize_payload().unwrap();
+    assert_eq!(payload.server_version, "0.1.0");
+    assert_eq!(payload.allowed_ports.len(), 2);
+    assert_eq!(payload.allowed_ports[1].protocol, Protocol::UDP);
+    assert!(payload.server_key.is_none());
+    assert!(payload.protocol_info.is_none());
+
+    let msg = fixture!("v0/ping.msgpack");
+    assert_eq!(msg.command, Command::Ping);
+    let payload: PingPayload = msg.deserialize_payload().unwrap();
+    assert_eq!(payload.timestamp, 1234);
+
+    let msg = fixture!("v0/data.msgpack");
+    assert_eq!(msg.command, Command::Data);
+    assert_eq!(msg.payload, vec![1, 2, 3]);
+
+    let msg = fixture!("v0/stats.msgpack");
+    assert_eq!(msg.command, Command::Stats);
+    let payload: StatsPayload = msg.deserialize_payload().unwrap();
+    assert_eq!(payload.upload_total, 100);
+    assert_eq!(payload.rtt_ms, Some(15));
+}
+
+#[test]
+fn v1_messages_decode_with_current_types() {
+    let payload: SecureConnectPayload = fixture!("v1/secure_connect.msgpack")
+        .deserialize_payload()
+        .unwrap();
+    assert_eq!(payload.port, 25565);
+    let info = payload.protocol_info.expect("プロトコル情報がありません");
+    assert_eq!(info.version, 1);
+    assert!(info.supports(capability::KEY_ROTATION));
+
+    let payload: ConnectResponsePayload = fixture!("v1/connect_response.msgpack")
+        .deserialize_payload()
+        .unwrap();
+    assert!(payload.success);
+    let rotation: KeyRotation = payload
+        .key_rotation
+        .expect("鍵ローテーション通知がありません");
+    assert_eq!(rotation.new_public_key, vec![1, 2, 3]);
+    assert_eq!(rotation.expires_at, 1_700_000_000);
+    assert_eq!(payload.protocol_info.unwrap().version, 1);
+
+    let payload: ServerInfoResponsePayload = fixture!("v1/server_info_response.msgpack")
+        .deserialize_payload()
+        .unwrap();
+    assert_eq!(payload.server_key.unwrap().public_key, vec![7, 8]);
+    assert!(
+        payload
+            .protocol_info
+            .unwrap()
+            .supports(capability::SIGNED_SERVER_KEY)
+    );
+}
+
+#[test]
+fn v1_messages_decode_on_legacy_peer() {
+    let payload: legacy::SecureConnectPayload = fixture!("v1/secure_connect.msgpack")
+        .deserialize_payload()
+        .expect("旧サーバーが新しい SecureConnect を読めません");
+    assert_eq!(payload.port, 25565);
+    assert_eq!(payload.algorithm, "AES-256-GCM");
+
+    let payload: legacy::ConnectResponsePayload = fixture!("v1/connect_response.msgpack")
+        .deserialize_payload()
+        .expect("旧クライアントが新しい ConnectResponse を読めません");
+    assert!(payload.success);
+    assert_eq!(payload.message, "OK");
+
+    let payload: legacy::ServerInfoResponsePayload = fixture!("v1/server_info_response.msgpack")
+        .deserialize_payload()
+        .expect("旧クライアントが新しい ServerInfoResponse を読めません");
+    assert_eq!(payload.allowed_ports.len(), 1);
+}
+
+#[test]
+fn current_encoding_decodes_on_legacy_peer() {
+    let res = ConnectResponsePayload {
+        protocol_info: Some(ProtocolInfo::current()),
+        ..ConnectResponsePayload::failure(
+            ErrorCode::PortNotAllowed,
+            &[("port", "1".to_string()), ("protocol", "TCP".to_string())],
+        )
+    };
+    let payload: legacy::ConnectResponsePayload =
+        rmp_serde::from_slice(&encode_payload(&res).unwrap()).unwrap();
+    assert!(!payload.success);
+    // 旧クライアントは英語のメッセージをそのまま表示する
+    assert_eq!(payload.message, "Unauthorized access to port 1: TCP");
+}
+
+#[test]
+fn future_messages_are_tolerated() {
+    let payload: SecureConnectPayload = fixture!("future/secure_connect.msgpack")
+        .deserialize_payload()
+        .expect("未知のフィールドを含むペイロードを読めません");
+    assert_eq!(payload.port, 25565);
+
+    // 新しい相手とは、こちらのバージョンと共通の機能で合意する
+    let negotiated = ProtocolInfo::current().negotiate(payload.protocol_info.as_ref());
+    assert_eq!(negotiated.version, 1);
+    assert_eq!(negotiated.capabilities, vec![capability::KEY_ROTATION]);
+
+    let msg = fixture!("future/future_command.msgpack");
+    assert_eq!(msg.command, Command::Unknown);
+    assert_eq!(msg.payload, vec![9, 9, 9]);
+
+    // プロトコルバージョン 1 の時点で未知だった Rekey は、対応した現在のバージョンでは Rekey として読む
+    let msg = fixture!("future/unknown_command.msgpack");
+    assert_eq!(msg.command, Command::Rekey);
+    assert_eq!(msg.payload, vec![9, 9, 9]);
+
+    // 新しいゲートウェイが追加した理由コードは Unknown として読む
+    let code: ErrorCode =
+        rmp_serde::from_slice(&rmp_serde::to_vec("future_reason").unwrap()).unwrap();
+    assert_eq!(code, ErrorCode::Unknown);
+}
+
+#[test]
+fn negotiation_with_legacy_peer_disables_capabilities() {
+    let negotiated = ProtocolInfo::current().negotiate(None);
+    assert_eq!(negotiated, ProtocolInfo::legacy());
+    assert!(!negotiated.supports(capability::KEY_ROTATION));
+
+    let current = ProtocolInfo::current();
+    assert_eq!(current.negotiate(Some(&current)), current);
+}
+
+/// PKCS#1 v1.5 でラップされた共通鍵 (旧クライアント) を受け付けるゲートウェイを起動します。
+fn start_legacy_gateway(port: u16) -> TestGateway {
+    let mut keys = ServerKeyring::new(server_key());
+    keys.set_allow_legacy_key_wrap(true);
+    TestGateway::start_with_keys(allow_tcp(port), keys)
+}
+
+/// 0.1.0 のクライアントと同じ形式 (配列形式のペイロード、PKCS#1 v1.5) の SecureConnect を作成します。
+fn legacy_secure_connect(engine: &AesGcmEngine, port: u16) -> Message {
+    let payload = legacy::SecureConnectPayload {
+        protocol: Protocol::TCP,
+        port,
+        encrypted_key: server_public_key()
+            .encrypt_with(&engine.key_bytes(), KeyWrap::Pkcs1v15)
+            .unwrap(),
+        algorithm: "AES-256-GCM".to_string(),
+    };
+    Message::new(Command::SecureConnect, rmp_serde::to_vec(&payload).unwrap())
+}
+
+/// 0.1.0 のクライアントと同じ形式 (配列形式のペイロード、バージョン情報なし、ランダムな Nonce) で
+/// ゲートウェイに接続し、旧クライアントの暗号化セッションと WebSocket を返します。
+async fn connect_as_legacy_client(
+    ws_url: &str,
+    port: u16,
+) -> (
+    legacy::Session,
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    legacy::ConnectResponsePayload,
+) {
+    let (mut ws, _) = connect_async(ws_url).await.unwrap();
+
+    let engine = AesGcmEngine::new_random();
+    let msg = legacy_secure_connect(&engine, port);
+    ws.send(WsMessage::Binary(msg.to_vec().unwrap()))
+        .await
+        .unwrap();
+
+    let session = legacy::Session { engine };
+
+    let bin = timeout(TIMEOUT, ws.next())
+        .await
+        .expect("ConnectResponse がタイムアウトしました")
+        .unwrap()
+        .unwrap()
+        .into_data();
+    let res = session.unseal(Message::from_slice(&bin).unwrap());
+    assert_eq!(res.command, Command::ConnectResponse);
+    let res = res.deserialize_payload().unwrap();
+    (session, ws, res)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legacy_client_is_accepted_by_gateway() {
+    let echo = spawn_echo_server().await;
+    let gateway = start_legacy_gateway(echo.port());
+
+    let (session, mut ws, res) = connect_as_legacy_client(&gateway.ws_url, echo.port()).await;
+    assert!(
+        res.success,
+        "旧クライアントが拒否されました: {}",
+        res.message
+    );
+
+    // 新しいバージョンで追加されたコマンドを送っても、ゲートウェイは読み飛ばして接続を維持する
+    let sealed = session.seal(Message::new(Command::Unknown, Vec::new()));
+    let future_command = rmp_serde::to_vec(&("FutureCommand", sealed.payload)).unwrap();
+    ws.send(WsMessage::Binary(future_command)).await.unwrap();
+
+    let data = pattern(1024, 7);
+    let msg = session.seal(Message::new(Command::Data, data.clone()));
+    ws.send(WsMessage::Binary(msg.to_vec().unwrap()))
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < data.len() {
+        let bin = timeout(TIMEOUT, ws.next())
+            .await
+            .expect("エコーの受信がタイムアウトしました")
+            .expect("ゲートウェイが接続を閉じました")
+            .unwrap()
+            .into_data();
+        let msg = session.unseal(Message::from_slice(&bin).unwrap());
+        if msg.command == Command::Data {
+            received.extend(msg.payload);
+        }
+    }
+    assert_eq!(received, data);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legacy_client_reads_rejection() {
+    let echo = spawn_echo_server().await;
+    let other = spawn_echo_server().await;
+    let gateway = start_legacy_gateway(other.port());
+
+    let (_, _, res) = connect_as_legacy_client(&gateway.ws_url, echo.port()).await;
+    assert!(!res.success);
+    assert!(res.message.contains("Unauthorized"), "{}", res.message);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legacy_key_wrap_is_rejected_by_default() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+
+    let (mut ws, _) = connect_async(&gateway.ws_url).await.unwrap();
+    let msg = legacy_secure_connect(&AesGcmEngine::new_random(), echo.port());
+    ws.send(WsMessage::Binary(msg.to_vec().unwrap()))
+        .await
+        .unwrap();
+
+    // ハンドシェイク前の拒否は平文で届く
+    let bin = timeout(TIMEOUT, ws.next())
+        .await
+        .expect("ConnectResponse がタイムアウトしました")
+        .unwrap()
+        .unwrap()
+        .into_data();
+    let res: legacy::ConnectResponsePayload = Message::from_slice(&bin)
+        .unwrap()
+        .deserialize_payload()
+        .unwrap();
+    assert!(!res.success);
+    assert_eq!(res.message, HANDSHAKE_FAILED);
+}
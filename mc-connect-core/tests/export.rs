@@ -0,0 +1,88 @@
+//! クライアント配布用設定 (ClientExportConfig) のテスト
+
+mod common;
+
+use base64::{Engine as _, engine::general_purpose};
+use common::*;
+use mc_connect_core::models::packet::{AllowedPort, ClientExportConfig, Protocol};
+
+fn export_config() -> ClientExportConfig {
+    ClientExportConfig {
+        name: "survival".to_string(),
+        ws_url: "wss://example.com/ws".to_string(),
+        mappings: vec![
+            AllowedPort {
+                port: 25565,
+                protocol: Protocol::TCP,
+            },
+            AllowedPort {
+                port: 19132,
+                protocol: Protocol::UDP,
+            },
+        ],
+        public_key: general_purpose::STANDARD.encode(server_public_key().to_der()),
+        encryption_type: "RSA".to_string(),
+    }
+}
+
+#[test]
+fn gateway_url_scheme_follows_explicit_scheme_or_tls_flag() {
+    let url = ClientExportConfig::gateway_ws_url;
+    assert_eq!(url("example.com", 8080, false), "ws://example.com:8080/ws");
+    assert_eq!(url("example.com", 80, false), "ws://example.com/ws");
+    // ポートが 443 でも TLS を指定しなければ ws のまま
+    assert_eq!(url("example.com", 443, false), "ws://example.com:443/ws");
+    assert_eq!(url("example.com", 443, true), "wss://example.com/ws");
+    assert_eq!(url("example.com", 8443, true), "wss://example.com:8443/ws");
+    assert_eq!(
+        url("wss://example.com/", 8443, false),
+        "wss://example.com:8443/ws"
+    );
+    assert_eq!(
+        url("https://example.com", 443, false),
+        "wss://example.com/ws"
+    );
+    assert_eq!(
+        url("ws://example.com", 443, false),
+        "ws://example.com:443/ws"
+    );
+    assert_eq!(
+        url(" 203.0.113.5 ", 25580, false),
+        "ws://203.0.113.5:25580/ws"
+    );
+    assert_eq!(
+        url("2001:db8::1", 8080, false),
+        "ws://[2001:db8::1]:8080/ws"
+    );
+    assert_eq!(
+        url("[2001:db8::1]", 8080, false),
+        "ws://[2001:db8::1]:8080/ws"
+    );
+}
+
+#[test]
+fn valid_export_config_yields_server_public_key() {
+    let key = export_config().validate().expect("検証に失敗しました");
+    assert_eq!(key.fingerprint(), server_public_key().fingerprint());
+}
+
+#[test]
+fn invalid_export_config_is_rejected() {
+    let cases: [fn(&mut ClientExportConfig); 5] = [
+        |c| c.ws_url = "https://example.com/ws".to_string(),
+        |c| c.ws_url = "not a url".to_string(),
+        |c| c.public_key = "not base64!".to_string(),
+        |c| c.mappings.clear(),
+        |c| c.mappings[1].port = 0,
+    ];
+    for (i, modify) in cases.iter().enumerate() {
+        let mut config = export_config();
+        modify(&mut config);
+        assert!(config.validate().is_err(), "ケース {} が受理されました", i);
+    }
+
+    // 公開鍵として読めないバイト列
+    let mut config = export_config();
+    config.public_key = general_purpose::STANDARD.encode(b"garbage");
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,102 @@
+//! コンパクトな Data フレームのテスト
+
+use mc_connect_core::encryption::{AesGcmEngine, SecureContext, SymmetricCrypto, TAG_LEN};
+use mc_connect_core::models::frame::{Direction, FRAME_DATA, FRAME_HEADER_LEN, is_data_frame};
+use mc_connect_core::models::packet::{Command, Message};
+
+/// 同じ共通鍵を持つクライアントとゲートウェイのコンテキストを作成します。
+fn contexts() -> (SecureContext, SecureContext) {
+    let client = AesGcmEngine::new_random();
+    let server = AesGcmEngine::from_key(&client.key_bytes()).unwrap();
+    let mut client =
+        SecureContext::with_crypto(Box::new(client), Direction::ClientToServer).unwrap();
+    let mut server =
+        SecureContext::with_crypto(Box::new(server), Direction::ServerToClient).unwrap();
+    client.compact_data = true;
+    server.compact_data = true;
+    (client, server)
+}
+
+#[test]
+fn frame_layout_and_overhead() {
+    let (mut client, mut server) = contexts();
+    let data: Vec<u8> = (0..=255).collect();
+
+    let first = client.seal_data_frame(&data).unwrap();
+    let second = client.seal_data_frame(&data).unwrap();
+    assert_eq!(first[0], FRAME_DATA);
+    assert_eq!(first[1..FRAME_HEADER_LEN], 0u64.to_be_bytes());
+    assert_eq!(second[1..FRAME_HEADER_LEN], 1u64.to_be_bytes());
+    assert_eq!(first.len(), FRAME_HEADER_LEN + data.len() + TAG_LEN);
+    assert_ne!(first[FRAME_HEADER_LEN..], second[FRAME_HEADER_LEN..]);
+
+    // 従来の Message 形式よりも小さい
+    let message = client
+        .seal_message(Message::new(Command::Data, data.clone()))
+        .unwrap()
+        .to_vec()
+        .unwrap();
+    assert!(!is_data_frame(&message));
+    assert!(first.len() < message.len());
+
+    assert_eq!(server.open_data_frame(&first).unwrap(), data);
+    assert_eq!(server.open_data_frame(&second).unwrap(), data);
+}
+
+#[test]
+fn seal_to_bytes_uses_frame_only_when_enabled() {
+    let (mut client, mut server) = contexts();
+
+    let bin = client
+        .seal_to_bytes(Message::new(Command::Data, b"hello".to_vec()))
+        .unwrap();
+    assert!(is_data_frame(&bin));
+    let msg = server.open_bytes(&bin).unwrap();
+    assert_eq!(msg.command, Command::Data);
+    assert_eq!(msg.payload, b"hello");
+
+    // 制御メッセージは常に MessagePack で送る
+    let bin = client
+        .seal_to_bytes(Message::new(Command::Ping, vec![1]))
+        .unwrap();
+    assert!(!is_data_frame(&bin));
+    assert_eq!(server.open_bytes(&bin).unwrap().command, Command::Ping);
+
+    client.compact_data = false;
+    let bin = client
+        .seal_to_bytes(Message::new(Command::Data, b"legacy".to_vec()))
+        .unwrap();
+    assert!(!is_data_frame(&bin));
+    assert_eq!(server.open_bytes(&bin).unwrap().payload, b"legacy");
+}
+
+#[test]
+fn tampered_or_truncated_frames_are_rejected() {
+    let (mut client, mut server) = contexts();
+    let frame = client.seal_data_frame(b"minecraft").unwrap();
+
+    // ヘッダー (カウンター) は AAD として認証される
+    let mut tampered = frame.clone();
+    tampered[FRAME_HEADER_LEN - 1] ^= 1;
+    assert!(server.open_data_frame(&tampered).is_err());
+
+    let mut tampered = frame.clone();
+    tampered[FRAME_HEADER_LEN] ^= 1;
+    assert!(server.open_data_frame(&tampered).is_err());
+
+    assert!(
+        server
+            .open_data_frame(&frame[..FRAME_HEADER_LEN + TAG_LEN - 1])
+            .is_err()
+    );
+}
+
+#[test]
+fn frame_reflected_to_sender_is_rejected() {
+    let (mut client, mut server) = contexts();
+    let frame = client.seal_data_frame(b"ping").unwrap();
+
+    // 送信方向ごとに Nonce が異なるため、送り返されたフレームは送信者自身には復号できない
+    assert!(client.open_data_frame(&frame).is_err());
+    assert!(server.open_data_frame(&frame).is_ok());
+}
@@ -0,0 +1,21 @@
+//! 秘密鍵を含むファイルの書き込みのテスト
+
+use mc_connect_core::fs::write_private_file;
+
+#[cfg(unix)]
+#[tokio::test]
+async fn private_file_is_owner_only() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join(format!("mc-connect-private-{}.json", std::process::id()));
+
+    // 既存のファイルが他のユーザーから読める権限でも、書き込み後は所有者のみになること
+    std::fs::write(&path, b"old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    write_private_file(&path, b"secret").await.unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600, "権限が 0600 になっていません");
+    assert_eq!(std::fs::read(&path).unwrap(), b"secret");
+    std::fs::remove_file(&path).unwrap();
+}
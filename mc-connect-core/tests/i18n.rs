@@ -0,0 +1,162 @@
+//! メッセージカタログ (英語 / 日本語) のテスト
+//!
+//! カタログの整合性と、コア・CLI・アプリのソースで使用しているキーがすべて
+//! カタログに登録されていることを検証します。
+
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mc_connect_core::i18n::{self, Locale, catalog};
+use mc_connect_core::models::packet::{ConnectResponsePayload, ErrorCode};
+
+/// テンプレート中の `{name}` の名前を集めます。
+fn placeholders(template: &str) -> BTreeSet<&str> {
+    let mut names = BTreeSet::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                names.insert(&after[..end]);
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+/// ディレクトリ以下の `.rs` ファイルを再帰的に集めます。
+fn rust_sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            rust_sources(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+/// ソース中の `"領域.名前"` 形式の文字列リテラルのうち、カタログの領域で始まるものを集めます。
+fn message_keys_in(source: &str, areas: &HashSet<&str>) -> Vec<String> {
+    source
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .filter(|literal| {
+            let Some((area, name)) = literal.split_once('.') else {
+                return false;
+            };
+            // `server.json` などのファイル名は除く
+            areas.contains(area)
+                && !name.is_empty()
+                && name != "json"
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn catalog_entries_are_complete_and_consistent() {
+    let mut keys = HashSet::new();
+    for entry in catalog::entries() {
+        assert!(
+            keys.insert(entry.key),
+            "キーが重複しています: {}",
+            entry.key
+        );
+        assert!(!entry.en.is_empty(), "英語が空です: {}", entry.key);
+        assert!(!entry.ja.is_empty(), "日本語が空です: {}", entry.key);
+        assert_eq!(
+            placeholders(entry.en),
+            placeholders(entry.ja),
+            "英語と日本語で引数が異なります: {}",
+            entry.key
+        );
+        assert!(catalog::lookup(entry.key).is_some());
+    }
+    assert!(catalog::lookup("no.such_key").is_none());
+}
+
+#[test]
+fn every_message_key_in_sources_is_in_catalog() {
+    let areas: HashSet<&str> = catalog::entries()
+        .filter_map(|entry| entry.key.split_once('.').map(|(area, _)| area))
+        .collect();
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut files = Vec::new();
+    for dir in [
+        "src",
+        "../mc-connect-cli/src",
+        "../mc-connect-app/src-tauri/src",
+    ] {
+        rust_sources(&root.join(dir), &mut files);
+    }
+    // カタログ自身は検査の対象外
+    files.retain(|path| !path.components().any(|c| c.as_os_str() == "catalog"));
+    assert!(!files.is_empty());
+
+    let mut missing = BTreeSet::new();
+    for path in &files {
+        let source = fs::read_to_string(path).unwrap();
+        for key in message_keys_in(&source, &areas) {
+            if catalog::lookup(&key).is_none() {
+                missing.insert(format!("{} ({})", key, path.display()));
+            }
+        }
+    }
+    assert!(missing.is_empty(), "カタログにないキー: {:?}", missing);
+}
+
+#[test]
+fn locale_is_parsed_from_common_notations() {
+    assert_eq!(Locale::parse("ja"), Some(Locale::Ja));
+    assert_eq!(Locale::parse("ja_JP.UTF-8"), Some(Locale::Ja));
+    assert_eq!(Locale::parse("EN-us"), Some(Locale::En));
+    assert_eq!(Locale::parse("C"), Some(Locale::En));
+    assert_eq!(Locale::parse("fr_FR"), None);
+    assert_eq!(Locale::Ja.code(), "ja");
+    assert_eq!(Locale::default(), Locale::En);
+}
+
+#[test]
+fn templates_are_rendered_with_arguments() {
+    let args = [("port", "25565".to_string())];
+    assert_eq!(i18n::render("port {port}", &args), "port 25565");
+    // 対応する引数がない部分はそのまま残す
+    assert_eq!(
+        i18n::render("{port}/{protocol} {", &args),
+        "25565/{protocol} {"
+    );
+    // カタログにないキーはキーをそのまま返す
+    assert_eq!(i18n::tr_in(Locale::Ja, "no.such_key", &[]), "no.such_key");
+}
+
+#[test]
+fn failure_response_is_sent_in_english_and_localized_on_the_client() {
+    let params = [
+        ("port", "25565".to_string()),
+        ("protocol", "TCP".to_string()),
+    ];
+    let res = ConnectResponsePayload::failure(ErrorCode::PortNotAllowed, &params);
+    let en = i18n::tr_in(Locale::En, "response.port_not_allowed", &params);
+    let ja = i18n::tr_in(Locale::Ja, "response.port_not_allowed", &params);
+    assert_eq!(res.message, en);
+    assert_ne!(en, ja);
+    assert!(ja.contains("25565"));
+
+    i18n::set_locale(Locale::Ja);
+    assert_eq!(i18n::locale(), Locale::Ja);
+    assert_eq!(res.localized_message(), ja);
+    i18n::set_locale(Locale::En);
+    assert_eq!(res.localized_message(), en);
+}
@@ -0,0 +1,126 @@
+//! 秘密鍵のパスフレーズによる暗号化 (Argon2id + AES-256-GCM) のテスト
+
+mod common;
+
+use base64::{Engine as _, engine::general_purpose};
+use common::*;
+use mc_connect_core::encryption::{
+    CryptoKeyPair, RsaKeyPair, decrypt_private_key, decrypt_server_config, encrypt_private_key,
+    encrypt_server_config,
+};
+use mc_connect_core::models::packet::{RetiredKey, ServerConfig};
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+/// 現在の鍵と旧鍵を 1 つずつ持つ、平文の設定を作成します。
+fn plain_config() -> ServerConfig {
+    let current = server_key();
+    let retired = generate_key();
+    ServerConfig {
+        bind_host: "0.0.0.0".to_string(),
+        public_host: "example.com".to_string(),
+        port: 8080,
+        public_key: general_purpose::STANDARD.encode(current.public_key_bytes()),
+        private_key: general_purpose::STANDARD.encode(current.private_key_bytes()),
+        allowed_ports: "25565".to_string(),
+        retired_keys: vec![RetiredKey {
+            public_key: general_purpose::STANDARD.encode(retired.public_key_bytes()),
+            private_key: general_purpose::STANDARD.encode(retired.private_key_bytes()),
+            expires_at: u64::MAX,
+        }],
+        key_encryption: None,
+        allow_legacy_key_wrap: false,
+    }
+}
+
+/// 暗号化した設定を JSON として保存し、読み込み直したものを返します。
+fn saved_and_loaded(config: &ServerConfig) -> ServerConfig {
+    let json = serde_json::to_string(config).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn server_config_round_trips_through_encryption() {
+    let plain = plain_config();
+    let mut config = plain.clone();
+    encrypt_server_config(&mut config, PASSPHRASE).unwrap();
+
+    // 保存される内容に平文の秘密鍵が含まれないこと
+    assert!(config.key_encryption.is_some());
+    let json = serde_json::to_string(&config).unwrap();
+    assert!(!json.contains(&plain.private_key));
+    assert!(!json.contains(&plain.retired_keys[0].private_key));
+
+    let mut loaded = saved_and_loaded(&config);
+    decrypt_server_config(&mut loaded, PASSPHRASE).unwrap();
+    assert!(loaded.key_encryption.is_none());
+    assert_eq!(loaded.private_key, plain.private_key);
+    assert_eq!(
+        loaded.retired_keys[0].private_key,
+        plain.retired_keys[0].private_key
+    );
+    let der = general_purpose::STANDARD
+        .decode(&loaded.private_key)
+        .unwrap();
+    assert!(RsaKeyPair::from_private_der(&der).is_ok());
+}
+
+#[test]
+fn wrong_passphrase_is_rejected() {
+    let mut config = plain_config();
+    encrypt_server_config(&mut config, PASSPHRASE).unwrap();
+    let encrypted = config.clone();
+
+    assert!(decrypt_server_config(&mut config, "wrong passphrase").is_err());
+    // 失敗しても暗号化されたままで、正しいパスフレーズで復号できること
+    assert_eq!(config.private_key, encrypted.private_key);
+    assert!(decrypt_server_config(&mut config, PASSPHRASE).is_ok());
+}
+
+#[test]
+fn tampered_file_is_rejected() {
+    let mut config = plain_config();
+    encrypt_server_config(&mut config, PASSPHRASE).unwrap();
+
+    // 暗号文の 1 バイトを書き換えると認証タグの検証に失敗する
+    let mut sealed = general_purpose::STANDARD
+        .decode(&config.private_key)
+        .unwrap();
+    let last = sealed.len() - 1;
+    sealed[last] ^= 0x01;
+    let mut tampered = saved_and_loaded(&config);
+    tampered.private_key = general_purpose::STANDARD.encode(&sealed);
+    assert!(decrypt_server_config(&mut tampered, PASSPHRASE).is_err());
+
+    // KDF のパラメータを書き換えると別の鍵が導出され、復号できない
+    let mut tampered = saved_and_loaded(&config);
+    tampered.key_encryption.as_mut().unwrap().iterations += 1;
+    assert!(decrypt_server_config(&mut tampered, PASSPHRASE).is_err());
+
+    // 未知の KDF は受け付けない
+    let mut tampered = saved_and_loaded(&config);
+    tampered.key_encryption.as_mut().unwrap().kdf = "scrypt".to_string();
+    assert!(decrypt_server_config(&mut tampered, PASSPHRASE).is_err());
+}
+
+#[test]
+fn empty_passphrase_and_double_encryption_are_rejected() {
+    let mut config = plain_config();
+    assert!(encrypt_server_config(&mut config, "").is_err());
+    encrypt_server_config(&mut config, PASSPHRASE).unwrap();
+    assert!(encrypt_server_config(&mut config, PASSPHRASE).is_err());
+}
+
+#[test]
+fn single_private_key_round_trips() {
+    let private_key = plain_config().private_key;
+    let (sealed, params) = encrypt_private_key(&private_key, PASSPHRASE).unwrap();
+    assert_ne!(sealed, private_key);
+
+    assert_eq!(
+        decrypt_private_key(&sealed, &params, PASSPHRASE).unwrap(),
+        private_key
+    );
+    assert!(decrypt_private_key(&sealed, &params, "wrong passphrase").is_err());
+    assert!(encrypt_private_key(&private_key, "").is_err());
+}
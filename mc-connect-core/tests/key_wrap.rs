@@ -0,0 +1,125 @@
+//! 共通鍵のラップ方式 (RSA-OAEP / PKCS#1 v1.5) と、ハンドシェイク失敗時の応答のテスト
+
+mod common;
+
+use common::*;
+use mc_connect_core::encryption::{
+    CryptoKeyPair, Decryptor, Encryptor, HANDSHAKE_FAILED, KeyWrap, RsaKeyPair, RsaPublicKey,
+    ServerKeyring, Signer, SymmetricAlgorithm, Verifier, create_secure_connect_packet,
+    handle_server_handshake,
+};
+use mc_connect_core::models::packet::{Command, Message, Protocol, SecureConnectPayload};
+
+/// 共通鍵と、そのラップ方式を指定した SecureConnect を作成します。
+fn secure_connect(encrypted_key: Vec<u8>, key_wrap: Option<&str>) -> Message {
+    let payload = SecureConnectPayload {
+        protocol: Protocol::TCP,
+        port: 25565,
+        encrypted_key,
+        algorithm: SymmetricAlgorithm::Aes256Gcm.name().to_string(),
+        algorithms: Vec::new(),
+        protocol_info: None,
+        key_wrap: key_wrap.map(str::to_string),
+    };
+    Message::from_payload(Command::SecureConnect, &payload).unwrap()
+}
+
+fn handshake_error(packet: Message, keys: &ServerKeyring) -> String {
+    match handle_server_handshake(packet, keys) {
+        Ok(_) => panic!("不正なハンドシェイクを受け付けました"),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn oaep_is_the_default_key_wrap() {
+    let key = server_key();
+    let wrapped = server_public_key().encrypt(&[7u8; 32]).unwrap();
+    assert_eq!(
+        key.decrypt_with(&wrapped, KeyWrap::OaepSha256).unwrap(),
+        [7u8; 32]
+    );
+    assert!(key.decrypt_with(&wrapped, KeyWrap::Pkcs1v15).is_err());
+
+    let keys = ServerKeyring::new(server_key());
+    let (_, packet) =
+        create_secure_connect_packet(Protocol::TCP, 25565, server_public_key().as_ref()).unwrap();
+    let payload: SecureConnectPayload = packet.deserialize_payload().unwrap();
+    assert_eq!(payload.key_wrap.as_deref(), Some("RSA-OAEP-SHA256"));
+    assert!(handle_server_handshake(packet, &keys).is_ok());
+}
+
+#[test]
+fn legacy_key_wrap_requires_opt_in() {
+    let legacy_key = || {
+        server_public_key()
+            .encrypt_with(&[1u8; 32], KeyWrap::Pkcs1v15)
+            .unwrap()
+    };
+
+    let mut keys = ServerKeyring::new(server_key());
+    assert_eq!(
+        handshake_error(secure_connect(legacy_key(), None), &keys),
+        HANDSHAKE_FAILED
+    );
+    // パディング方式を明示しても、許可されていなければ受け付けない
+    assert_eq!(
+        handshake_error(secure_connect(legacy_key(), Some("RSA-PKCS1-v1_5")), &keys),
+        HANDSHAKE_FAILED
+    );
+
+    keys.set_allow_legacy_key_wrap(true);
+    assert!(handle_server_handshake(secure_connect(legacy_key(), None), &keys).is_ok());
+}
+
+#[test]
+fn handshake_failures_are_indistinguishable() {
+    let mut keys = ServerKeyring::new(server_key());
+    keys.set_allow_legacy_key_wrap(true);
+    let public_key = server_public_key();
+    let other_key = RsaKeyPair::from_private_der(&generate_key().private_key_bytes()).unwrap();
+    let oaep = Some("RSA-OAEP-SHA256");
+
+    let failures = [
+        // 復号自体に失敗する (パディング不正)
+        secure_connect(vec![0u8; 128], oaep),
+        secure_connect(vec![0u8; 128], None),
+        // 復号には成功するが、共通鍵の長さが不正
+        secure_connect(public_key.encrypt(&[0u8; 16]).unwrap(), oaep),
+        secure_connect(
+            public_key
+                .encrypt_with(&[0u8; 16], KeyWrap::Pkcs1v15)
+                .unwrap(),
+            None,
+        ),
+        // 別の鍵でラップされている
+        secure_connect(other_key.encrypt(&[0u8; 32]).unwrap(), oaep),
+        // 未知のパディング方式
+        secure_connect(
+            public_key.encrypt(&[0u8; 32]).unwrap(),
+            Some("RSA-OAEP-MD5"),
+        ),
+        Message::new(Command::SecureConnect, vec![0xc1]),
+    ];
+    for packet in failures {
+        assert_eq!(handshake_error(packet, &keys), HANDSHAKE_FAILED);
+    }
+}
+
+#[test]
+fn public_key_only_type_encrypts_and_verifies() {
+    let key = server_key();
+    let public_key = RsaPublicKey::from_der(&key.public_key_bytes()).unwrap();
+    assert_eq!(public_key.to_der(), key.public_key_bytes());
+    assert_eq!(public_key.fingerprint(), key.fingerprint());
+
+    let wrapped = public_key.encrypt(b"session key").unwrap();
+    assert_eq!(key.decrypt(&wrapped).unwrap(), b"session key");
+
+    let signature = key.sign(b"challenge").unwrap();
+    assert!(public_key.verify(b"challenge", &signature).unwrap());
+    assert!(!public_key.verify(b"other", &signature).unwrap());
+
+    // 秘密鍵の DER は公開鍵として読み込めない
+    assert!(RsaPublicKey::from_der(&key.private_key_bytes()).is_err());
+}
@@ -0,0 +1,183 @@
+//! クライアント側の SOCKS5 / HTTP CONNECT フロントエンドの結合テスト
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::*;
+use mc_connect_core::WsClientService;
+use mc_connect_core::models::packet::AllowedPort;
+use mc_connect_core::services::ws_client::TunnelStats;
+use mc_connect_core::services::ws_client::proxy_frontend::PROXY_REQUEST_TIMEOUT;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+/// 空きポートで待ち受けるプロキシフロントエンドです。drop 時に停止します。
+struct TestProxy {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl TestProxy {
+    async fn start(ws_url: &str, allowed_ports: Vec<AllowedPort>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_ping_tx, ping_rx) = tokio::sync::mpsc::unbounded_channel();
+        let ws_url = ws_url.to_string();
+        let task = tokio::spawn(async move {
+            let _ = WsClientService::run_proxy_listener(
+                listener,
+                ws_url,
+                allowed_ports,
+                Arc::new(TunnelStats::new()),
+                ping_rx,
+                server_public_key(),
+            )
+            .await;
+        });
+        Self { addr, task }
+    }
+
+    async fn connect(&self) -> TcpStream {
+        TcpStream::connect(self.addr).await.unwrap()
+    }
+}
+
+impl Drop for TestProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// SOCKS5 の挨拶と要求を送り、応答コード (REP) を返します。
+async fn socks5_request(stream: &mut TcpStream, command: u8, port: u16) -> u8 {
+    stream.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+    let mut method = [0u8; 2];
+    stream.read_exact(&mut method).await.unwrap();
+    assert_eq!(method, [0x05, 0x00], "認証なしが選択されていません");
+
+    let host = b"localhost";
+    let mut request = vec![0x05, command, 0x00, 0x03, host.len() as u8];
+    request.extend_from_slice(host);
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await.unwrap();
+
+    let mut reply = [0u8; 10];
+    timeout(TIMEOUT, stream.read_exact(&mut reply))
+        .await
+        .expect("SOCKS5 の応答がタイムアウトしました")
+        .unwrap();
+    assert_eq!(reply[0], 0x05);
+    reply[1]
+}
+
+/// HTTP CONNECT 要求を送り、ステータス行を返します。
+async fn http_connect(stream: &mut TcpStream, port: u16) -> String {
+    let request = format!("CONNECT localhost:{port} HTTP/1.1\r\nHost: localhost:{port}\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = Vec::new();
+    timeout(TIMEOUT, async {
+        while !response.ends_with(b"\r\n\r\n") {
+            response.push(stream.read_u8().await.unwrap());
+        }
+    })
+    .await
+    .expect("HTTP の応答がタイムアウトしました");
+    let response = String::from_utf8(response).unwrap();
+    response.lines().next().unwrap().to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn socks5_connect_tunnels_to_requested_port() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let proxy = TestProxy::start(&gateway.ws_url, allow_tcp(echo.port())).await;
+
+    let mut stream = proxy.connect().await;
+    assert_eq!(socks5_request(&mut stream, 0x01, echo.port()).await, 0x00);
+
+    let data = pattern(16_384, 1);
+    let received = timeout(TIMEOUT, round_trip(stream, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn http_connect_tunnels_to_requested_port() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let proxy = TestProxy::start(&gateway.ws_url, allow_tcp(echo.port())).await;
+
+    let mut stream = proxy.connect().await;
+    let status = http_connect(&mut stream, echo.port()).await;
+    assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+
+    let data = pattern(4096, 2);
+    let received = timeout(TIMEOUT, round_trip(stream, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disallowed_port_is_refused_without_contacting_gateway() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let proxy = TestProxy::start(&gateway.ws_url, allow_tcp(echo.port())).await;
+    let other_port = echo.port().wrapping_add(1);
+
+    let mut socks = proxy.connect().await;
+    assert_eq!(socks5_request(&mut socks, 0x01, other_port).await, 0x02);
+
+    let mut http = proxy.connect().await;
+    let status = http_connect(&mut http, other_port).await;
+    assert!(status.starts_with("HTTP/1.1 403"), "{}", status);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsupported_requests_are_rejected() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let proxy = TestProxy::start(&gateway.ws_url, allow_tcp(echo.port())).await;
+
+    // SOCKS5 BIND
+    let mut socks = proxy.connect().await;
+    assert_eq!(socks5_request(&mut socks, 0x02, echo.port()).await, 0x07);
+
+    // CONNECT 以外の HTTP メソッド
+    let mut http = proxy.connect().await;
+    http.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    timeout(TIMEOUT, http.read_to_string(&mut response))
+        .await
+        .expect("HTTP の応答がタイムアウトしました")
+        .unwrap();
+    assert!(response.starts_with("HTTP/1.1 405"), "{}", response);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn slow_request_is_closed_after_timeout() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let proxy = TestProxy::start(&gateway.ws_url, allow_tcp(echo.port())).await;
+
+    // ヘッダーを最後まで送らずに待ち続けるクライアント
+    let mut http = proxy.connect().await;
+    http.write_all(b"CONNECT localhost:").await.unwrap();
+    let mut buf = Vec::new();
+    let closed = timeout(
+        PROXY_REQUEST_TIMEOUT + Duration::from_secs(5),
+        http.read_to_end(&mut buf),
+    )
+    .await
+    .expect("制限時間を過ぎても接続が閉じられていません");
+    assert!(closed.is_err() || buf.is_empty(), "{:?}", buf);
+}
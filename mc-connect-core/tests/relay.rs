@@ -0,0 +1,538 @@
+//! リレーとホストエージェントを通したリバーストンネルの結合テスト
+
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use mc_connect_core::encryption::{AGENT_REGISTRATION_CONTEXT, CryptoKeyPair, ServerKeyring};
+use mc_connect_core::models::packet::{
+    AllowedPort, Command, ConnectResponsePayload, Message, Protocol, ServerInfoRequestPayload,
+    ServerInfoResponsePayload,
+};
+use mc_connect_core::models::relay::{AgentChallengePayload, AgentRegisterPayload};
+use mc_connect_core::services::relay::{RelayLimits, RelayRegistry, agent_id};
+use mc_connect_core::{HostAgentService, RelayOptions, WsClientService, bind_relay};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+/// [TestRelay]
+/// 空きポートで起動したリレーと、そこへ登録したホストエージェントです。drop 時に停止します。
+struct TestRelay {
+    relay_url: String,
+    registry: Arc<RelayRegistry>,
+    handle: actix_web::dev::ServerHandle,
+    agent: Option<JoinHandle<()>>,
+}
+
+/// テスト用の短い鍵のエージェントも登録できるようにした上限の設定
+fn test_limits() -> RelayLimits {
+    RelayLimits {
+        min_agent_key_bits: TEST_KEY_BITS,
+        ..RelayLimits::default()
+    }
+}
+
+impl TestRelay {
+    fn start() -> Self {
+        Self::start_with_limits(test_limits())
+    }
+
+    fn start_with_limits(limits: RelayLimits) -> Self {
+        let options = RelayOptions {
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+        };
+        let (server, addr, registry) =
+            bind_relay("127.0.0.1", 0, limits, options).expect("リレーのバインドに失敗しました");
+        let handle = server.handle();
+        tokio::spawn(server);
+        Self {
+            relay_url: format!("ws://{}", addr),
+            registry,
+            handle,
+            agent: None,
+        }
+    }
+
+    /// 共有のサーバー鍵でホストエージェントを起動し、登録が完了するまで待ちます。
+    async fn start_agent(&mut self, allowed_ports: Vec<AllowedPort>) {
+        self.spawn_agent(allowed_ports);
+        let deadline = Instant::now() + TIMEOUT;
+        while self.registry.get(&self.agent_id()).is_none() {
+            assert!(
+                Instant::now() < deadline,
+                "エージェントが登録されませんでした"
+            );
+            sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// 共有のサーバー鍵でホストエージェントを起動します。登録の完了は待ちません。
+    fn spawn_agent(&mut self, allowed_ports: Vec<AllowedPort>) {
+        let relay_url = self.relay_url.clone();
+        self.agent = Some(tokio::spawn(async move {
+            let _ = HostAgentService::run(
+                relay_url,
+                allowed_ports,
+                Arc::new(ServerKeyring::new(server_key())),
+            )
+            .await;
+        }));
+    }
+
+    fn agent_id(&self) -> String {
+        agent_id(&server_key().fingerprint())
+    }
+
+    fn client_url(&self) -> String {
+        HostAgentService::client_url(&self.relay_url, &self.agent_id())
+    }
+
+    /// `/metrics` へ GET を送り、ステータス行と本文を返します。
+    async fn metrics_get(&self, token: Option<&str>) -> (String, String) {
+        let addr = self.relay_url.trim_start_matches("ws://").to_string();
+        let mut stream = TcpStream::connect(&addr).await.unwrap();
+        let auth = token
+            .map(|t| format!("Authorization: Bearer {}\r\n", t))
+            .unwrap_or_default();
+        stream
+            .write_all(
+                format!(
+                    "GET /metrics HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+                    addr, auth
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        timeout(TIMEOUT, stream.read_to_string(&mut response))
+            .await
+            .expect("応答がタイムアウトしました")
+            .unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        (
+            head.lines().next().unwrap_or("").to_string(),
+            body.to_string(),
+        )
+    }
+}
+
+impl Drop for TestRelay {
+    fn drop(&mut self) {
+        if let Some(agent) = self.agent.take() {
+            agent.abort();
+        }
+        let handle = self.handle.clone();
+        tokio::spawn(async move { handle.stop(false).await });
+    }
+}
+
+/// `Message` を 1 つ送信します。
+async fn send_message<T: serde::Serialize>(ws: &mut WsStream, command: Command, payload: &T) {
+    let bin = Message::from_payload(command, payload)
+        .unwrap()
+        .to_vec()
+        .unwrap();
+    ws.send(WsMessage::Binary(bin)).await.unwrap();
+}
+
+/// 次の `Message` を受信し、ペイロードをデコードします。
+async fn next_payload<T: serde::de::DeserializeOwned>(ws: &mut WsStream, command: Command) -> T {
+    loop {
+        let msg = timeout(TIMEOUT, ws.next())
+            .await
+            .expect("受信がタイムアウトしました")
+            .expect("接続が閉じられました")
+            .unwrap();
+        if let WsMessage::Binary(bin) = msg {
+            let msg = Message::from_slice(&bin).unwrap();
+            assert_eq!(msg.command, command);
+            return msg.deserialize_payload().unwrap();
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tcp_round_trip_through_relay() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+    let tunnel = TestTunnel::start(&relay.client_url(), echo.port(), server_public_key()).await;
+
+    // 複数のクライアントが 1 本のエージェント接続上で混ざらずに中継されること
+    let mut tasks = Vec::new();
+    for seed in 0..4 {
+        let stream = tunnel.connect().await;
+        tasks.push(tokio::spawn(async move {
+            let data = pattern(32_768, seed);
+            let received = round_trip(stream, &data).await;
+            assert_eq!(received, data, "ストリーム {} のデータが一致しません", seed);
+        }));
+    }
+    for task in tasks {
+        timeout(TIMEOUT, task)
+            .await
+            .expect("往復がタイムアウトしました")
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn server_info_through_relay_is_signed_by_agent() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+
+    let info = timeout(
+        TIMEOUT,
+        WsClientService::get_server_info(&relay.client_url()),
+    )
+    .await
+    .expect("問い合わせがタイムアウトしました")
+    .unwrap();
+    assert_eq!(info.allowed_ports.len(), 1);
+    assert_eq!(info.allowed_ports[0].port, echo.port());
+    // リレーではなくエージェントの鍵で署名されていること
+    assert_eq!(
+        info.server_key.unwrap().public_key,
+        server_key().public_key_bytes()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disallowed_port_through_relay_is_rejected() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+
+    let result = timeout(
+        TIMEOUT,
+        WsClientService::check_connectivity(
+            &relay.client_url(),
+            echo.port().wrapping_add(1),
+            Protocol::TCP,
+            server_public_key(),
+        ),
+    )
+    .await
+    .expect("接続テストがタイムアウトしました");
+    assert!(result.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unknown_agent_is_not_found() {
+    let relay = TestRelay::start();
+    let url = HostAgentService::client_url(&relay.relay_url, "00ff");
+
+    let result = timeout(TIMEOUT, tokio_tungstenite::connect_async(url))
+        .await
+        .expect("接続がタイムアウトしました");
+    assert!(
+        result.is_err(),
+        "未登録のエージェントに接続できてしまいました"
+    );
+}
+
+/// エージェントを止め、登録が解除されるまで待ちます。
+async fn stop_agent(relay: &mut TestRelay) {
+    relay.agent.take().unwrap().abort();
+    let deadline = Instant::now() + TIMEOUT;
+    while relay.registry.get(&relay.agent_id()).is_some() {
+        assert!(
+            Instant::now() < deadline,
+            "エージェントの登録が解除されませんでした"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn agent_is_unregistered_when_it_disconnects() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+    let tunnel = TestTunnel::start(&relay.client_url(), echo.port(), server_public_key()).await;
+
+    let data = pattern(1024, 7);
+    let received = timeout(TIMEOUT, round_trip(tunnel.connect().await, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+
+    // エージェントを止めると登録が解除される
+    stop_agent(&mut relay).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streams_beyond_agent_quota_are_rejected() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start_with_limits(RelayLimits {
+        max_streams_per_agent: Some(1),
+        ..test_limits()
+    });
+    relay.start_agent(allow_tcp(echo.port())).await;
+    let tunnel = TestTunnel::start(&relay.client_url(), echo.port(), server_public_key()).await;
+
+    // 1 本目は中継され、接続している間は 2 本目を受け付けない
+    let data = pattern(1024, 3);
+    let first = tunnel.connect().await;
+    let (mut reader, mut writer) = first.into_split();
+    writer.write_all(&data).await.unwrap();
+    let mut received = vec![0u8; data.len()];
+    timeout(TIMEOUT, reader.read_exact(&mut received))
+        .await
+        .expect("往復がタイムアウトしました")
+        .unwrap();
+    assert_eq!(received, data);
+
+    let result = timeout(
+        TIMEOUT,
+        tokio_tungstenite::connect_async(relay.client_url()),
+    )
+    .await
+    .expect("接続がタイムアウトしました");
+    assert!(
+        result.is_err(),
+        "上限を超えたストリームが受け付けられました"
+    );
+
+    let metrics = relay.registry.metrics();
+    assert_eq!(metrics.agents[0].active_streams, 1);
+    assert_eq!(metrics.agents[0].rejected_streams, 1);
+    assert!(metrics.agents[0].bytes_to_agent > data.len() as u64);
+
+    // 1 本目を閉じれば再び受け付ける
+    drop((reader, writer));
+    let deadline = Instant::now() + TIMEOUT;
+    while relay.registry.metrics().agents[0].active_streams > 0 {
+        assert!(Instant::now() < deadline, "ストリーム数が戻りませんでした");
+        sleep(Duration::from_millis(20)).await;
+    }
+    let received = timeout(TIMEOUT, round_trip(tunnel.connect().await, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn agents_outside_allow_list_are_not_registered() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start_with_limits(RelayLimits {
+        allowed_agents: vec![agent_id(&generate_key().fingerprint())],
+        ..test_limits()
+    });
+    relay.spawn_agent(allow_tcp(echo.port()));
+
+    let deadline = Instant::now() + TIMEOUT;
+    while relay.registry.metrics().rejected_registrations == 0 {
+        assert!(
+            Instant::now() < deadline,
+            "エージェントの登録が拒否されませんでした"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert!(relay.registry.get(&relay.agent_id()).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn agents_with_short_keys_are_not_registered() {
+    let echo = spawn_echo_server().await;
+    // 既定の下限 (2048 ビット) では、テスト用の短い鍵は登録できない
+    let mut relay = TestRelay::start_with_limits(RelayLimits::default());
+    relay.spawn_agent(allow_tcp(echo.port()));
+
+    let deadline = Instant::now() + TIMEOUT;
+    while relay.registry.metrics().rejected_registrations == 0 {
+        assert!(
+            Instant::now() < deadline,
+            "短い鍵のエージェントの登録が拒否されませんでした"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert!(relay.registry.get(&relay.agent_id()).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn released_stats_are_kept_only_with_byte_quota() {
+    let echo = spawn_echo_server().await;
+
+    // 転送量の上限がなければ、切断したエージェントの中継状況は残さない
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+    stop_agent(&mut relay).await;
+    assert_eq!(relay.registry.released_count(), 0);
+
+    // 上限がある場合は、接続し直したときに引き継げるよう残す
+    let mut relay = TestRelay::start_with_limits(RelayLimits {
+        max_bytes_per_agent: Some(1 << 20),
+        ..test_limits()
+    });
+    relay.start_agent(allow_tcp(echo.port())).await;
+    stop_agent(&mut relay).await;
+    assert_eq!(relay.registry.released_count(), 1);
+    relay.start_agent(allow_tcp(echo.port())).await;
+    assert_eq!(relay.registry.released_count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn metrics_endpoint_reports_registered_agents() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+
+    let (status, body) = relay.metrics_get(Some(ADMIN_TOKEN)).await;
+    assert!(status.contains("200"), "{}", status);
+    let metrics: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(metrics["agent_count"], 1);
+    assert_eq!(metrics["agents"][0]["agent_id"], relay.agent_id());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn metrics_endpoint_requires_token() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+
+    // トークンがない、または誤っている場合はエージェント ID を返さない
+    let (status, body) = relay.metrics_get(None).await;
+    assert!(status.contains("401"), "{}", status);
+    assert!(!body.contains(&relay.agent_id()));
+    let (status, body) = relay.metrics_get(Some("wrong-token")).await;
+    assert!(status.contains("401"), "{}", status);
+    assert!(!body.contains(&relay.agent_id()));
+
+    // 管理用トークンを設定していないリレーでは無効
+    let (server, addr, _) = bind_relay(
+        "127.0.0.1",
+        0,
+        RelayLimits::default(),
+        RelayOptions::default(),
+    )
+    .expect("リレーのバインドに失敗しました");
+    let handle = server.handle();
+    tokio::spawn(server);
+    let disabled = TestRelay {
+        relay_url: format!("ws://{}", addr),
+        registry: Arc::new(RelayRegistry::default()),
+        handle,
+        agent: None,
+    };
+    let (status, _) = disabled.metrics_get(Some(ADMIN_TOKEN)).await;
+    assert!(status.contains("404"), "{}", status);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streams_beyond_agent_byte_quota_are_closed() {
+    const QUOTA: u64 = 64 * 1024;
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start_with_limits(RelayLimits {
+        max_bytes_per_agent: Some(QUOTA),
+        ..test_limits()
+    });
+    relay.start_agent(allow_tcp(echo.port())).await;
+    let tunnel = TestTunnel::start(&relay.client_url(), echo.port(), server_public_key()).await;
+
+    // 上限を超える量を送ると、中継中のストリームが途中で閉じられる
+    let data = pattern(QUOTA as usize * 4, 5);
+    let (mut reader, mut writer) = tunnel.connect().await.into_split();
+    let writing = tokio::spawn(async move {
+        let _ = writer.write_all(&data).await;
+        writer
+    });
+    let mut received = Vec::new();
+    let _ = timeout(TIMEOUT, reader.read_to_end(&mut received))
+        .await
+        .expect("ストリームが閉じられませんでした");
+    assert!(received.len() < QUOTA as usize * 4);
+    writing.abort();
+
+    let metrics = relay.registry.metrics();
+    assert!(metrics.agents[0].bytes_to_agent + metrics.agents[0].bytes_to_clients >= QUOTA);
+
+    // 以降のストリームは受け付けない
+    let result = timeout(TIMEOUT, connect_async(relay.client_url()))
+        .await
+        .expect("接続がタイムアウトしました");
+    assert!(
+        result.is_err(),
+        "転送量の上限を超えたエージェントへのストリームが受け付けられました"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_signature_cannot_register_as_agent() {
+    let echo = spawn_echo_server().await;
+    let mut relay = TestRelay::start();
+    relay.start_agent(allow_tcp(echo.port())).await;
+
+    // 攻撃者としてリレーの /agent に接続し、登録のチャレンジを受け取る
+    let (mut attacker, _) = connect_async(HostAgentService::agent_url(&relay.relay_url))
+        .await
+        .unwrap();
+    let challenge: AgentChallengePayload =
+        next_payload(&mut attacker, Command::AgentChallenge).await;
+
+    // 登録済みのエージェントのゲートウェイに、リレーのチャレンジを GetServerInfo として署名させる
+    let (mut victim, _) = connect_async(relay.client_url()).await.unwrap();
+    send_message(
+        &mut victim,
+        Command::GetServerInfo,
+        &ServerInfoRequestPayload {
+            challenge: challenge.challenge.clone(),
+        },
+    )
+    .await;
+    let info: ServerInfoResponsePayload =
+        next_payload(&mut victim, Command::ServerInfoResponse).await;
+    let signed_key = info
+        .server_key
+        .expect("署名付きの公開鍵が返されませんでした");
+
+    // ゲートウェイの署名を登録に流用しても拒否される
+    send_message(
+        &mut attacker,
+        Command::AgentRegister,
+        &AgentRegisterPayload { signed_key },
+    )
+    .await;
+    let res: ConnectResponsePayload = next_payload(&mut attacker, Command::ConnectResponse).await;
+    assert!(!res.success, "ゲートウェイの署名で登録できてしまいました");
+
+    // 登録用の内容をチャレンジとして送っても、ゲートウェイは署名しない
+    let crafted = [
+        AGENT_REGISTRATION_CONTEXT,
+        &(challenge.relay_id.len() as u32).to_be_bytes(),
+        challenge.relay_id.as_bytes(),
+        &challenge.challenge,
+    ]
+    .concat();
+    let (mut victim, _) = connect_async(relay.client_url()).await.unwrap();
+    send_message(
+        &mut victim,
+        Command::GetServerInfo,
+        &ServerInfoRequestPayload { challenge: crafted },
+    )
+    .await;
+    let info: ServerInfoResponsePayload =
+        next_payload(&mut victim, Command::ServerInfoResponse).await;
+    assert!(info.server_key.is_none());
+
+    // 本物のエージェントは登録されたままで、中継も続けられる
+    assert_eq!(relay.registry.metrics().agent_count, 1);
+    let tunnel = TestTunnel::start(&relay.client_url(), echo.port(), server_public_key()).await;
+    let data = pattern(1024, 11);
+    let received = timeout(TIMEOUT, round_trip(tunnel.connect().await, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+}
@@ -0,0 +1,193 @@
+//! 連番 Nonce によるリプレイ検出と、鍵更新 (Rekey) のテスト
+
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use mc_connect_core::encryption::{
+    AesGcmEngine, RekeyPolicy, SecureContext, ServerKeyring, SymmetricCrypto,
+    create_secure_connect_packet, handle_server_handshake,
+};
+use mc_connect_core::models::frame::Direction;
+use mc_connect_core::models::packet::{Command, Message, Protocol, ProtocolInfo};
+
+/// 現在のプロトコルで合意した状態のクライアントとゲートウェイのコンテキストを作成します。
+fn contexts() -> (SecureContext, SecureContext) {
+    let client = AesGcmEngine::new_random();
+    let server = AesGcmEngine::from_key(&client.key_bytes()).unwrap();
+    let mut client =
+        SecureContext::with_crypto(Box::new(client), Direction::ClientToServer).unwrap();
+    let mut server =
+        SecureContext::with_crypto(Box::new(server), Direction::ServerToClient).unwrap();
+    client.apply_protocol(&ProtocolInfo::current());
+    server.apply_protocol(&ProtocolInfo::current());
+    (client, server)
+}
+
+fn data(payload: &[u8]) -> Message {
+    Message::new(Command::Data, payload.to_vec())
+}
+
+#[test]
+fn replayed_messages_are_rejected() {
+    let (mut client, mut server) = contexts();
+
+    let frame = client.seal_to_bytes(data(b"first")).unwrap();
+    assert_eq!(server.open_bytes(&frame).unwrap().payload, b"first");
+    assert!(
+        server.open_bytes(&frame).is_err(),
+        "Data フレームのリプレイを受け付けました"
+    );
+
+    let ping = client
+        .seal_to_bytes(Message::new(Command::Ping, vec![1]))
+        .unwrap();
+    assert!(server.open_bytes(&ping).is_ok());
+    assert!(
+        server.open_bytes(&ping).is_err(),
+        "制御メッセージのリプレイを受け付けました"
+    );
+}
+
+#[test]
+fn tampered_command_is_rejected() {
+    let (mut client, mut server) = contexts();
+
+    // 暗号文はそのままに、平文のコマンドだけを書き換える
+    let mut sealed = client
+        .seal_message(Message::new(Command::Stats, vec![1, 2, 3]))
+        .unwrap();
+    sealed.command = Command::Disconnect;
+    assert!(
+        server.open_bytes(&sealed.to_vec().unwrap()).is_err(),
+        "コマンドを書き換えたメッセージを受け付けました"
+    );
+
+    // 書き換えていなければ同じカウンターのメッセージとして受け付ける
+    sealed.command = Command::Stats;
+    let opened = server.open_bytes(&sealed.to_vec().unwrap()).unwrap();
+    assert_eq!(opened.command, Command::Stats);
+    assert_eq!(opened.payload, vec![1, 2, 3]);
+}
+
+#[test]
+fn reordered_or_dropped_messages_are_rejected() {
+    let (mut client, mut server) = contexts();
+
+    let first = client.seal_to_bytes(data(b"1")).unwrap();
+    let second = client.seal_to_bytes(data(b"2")).unwrap();
+    assert!(
+        server.open_bytes(&second).is_err(),
+        "欠落・入れ替えを検出できませんでした"
+    );
+    assert!(server.open_bytes(&first).is_ok());
+    assert!(server.open_bytes(&second).is_ok());
+}
+
+#[test]
+fn sequence_check_is_disabled_for_legacy_peers() {
+    let (mut client, mut server) = contexts();
+    client.apply_protocol(&ProtocolInfo::legacy());
+    server.apply_protocol(&ProtocolInfo::legacy());
+
+    let frame = client
+        .seal_message(data(b"legacy"))
+        .unwrap()
+        .to_vec()
+        .unwrap();
+    assert!(server.open_bytes(&frame).is_ok());
+    assert!(server.open_bytes(&frame).is_ok());
+}
+
+#[test]
+fn rekey_rotates_keys_without_interrupting_the_stream() {
+    let (mut client, mut server) = contexts();
+
+    let before = client.seal_to_bytes(data(b"before")).unwrap();
+    let rekey = client.rekey_message().unwrap();
+    let after = client.seal_to_bytes(data(b"after")).unwrap();
+    assert_eq!(client.send_generation(), Some(1));
+
+    assert_eq!(server.open_bytes(&before).unwrap().payload, b"before");
+    assert_eq!(server.open_bytes(&rekey).unwrap().command, Command::Rekey);
+    assert_eq!(server.recv_generation(), Some(1));
+    assert_eq!(server.open_bytes(&after).unwrap().payload, b"after");
+
+    // 逆方向は影響を受けない
+    assert_eq!(server.send_generation(), Some(0));
+    let reply = server.seal_to_bytes(data(b"reply")).unwrap();
+    assert_eq!(client.open_bytes(&reply).unwrap().payload, b"reply");
+
+    // 旧鍵で暗号化されたフレームは、連番検証がなくても更新後は復号できない
+    let (mut client2, mut server2) = contexts();
+    server2.strict_sequence = false;
+    let old = client2.seal_to_bytes(data(b"old")).unwrap();
+    let rekey = client2.rekey_message().unwrap();
+    server2.open_bytes(&rekey).unwrap();
+    assert!(server2.open_bytes(&old).is_err());
+}
+
+#[test]
+fn take_rekey_follows_policy() {
+    let (mut client, mut server) = contexts();
+    client.rekey_policy = Some(RekeyPolicy {
+        max_bytes: 100,
+        max_age: Duration::from_secs(3600),
+    });
+
+    let bin = client.seal_to_bytes(data(&[0u8; 60])).unwrap();
+    server.open_bytes(&bin).unwrap();
+    assert!(client.take_rekey().unwrap().is_none());
+
+    let bin = client.seal_to_bytes(data(&[0u8; 60])).unwrap();
+    server.open_bytes(&bin).unwrap();
+    let rekey = client
+        .take_rekey()
+        .unwrap()
+        .expect("送信量の上限で鍵が更新されませんでした");
+    server.open_bytes(&rekey).unwrap();
+    assert!(client.take_rekey().unwrap().is_none());
+
+    client.rekey_policy = Some(RekeyPolicy {
+        max_bytes: u64::MAX,
+        max_age: Duration::ZERO,
+    });
+    assert!(
+        client.take_rekey().unwrap().is_some(),
+        "経過時間の上限で鍵が更新されませんでした"
+    );
+
+    // 鍵更新に対応していない相手とは更新しない
+    client.apply_protocol(&ProtocolInfo::legacy());
+    assert!(client.take_rekey().unwrap().is_none());
+}
+
+#[test]
+fn negotiated_handshake_enables_sequence_and_rekey() {
+    let server_keys = ServerKeyring::new(server_key());
+    let (mut client, packet) =
+        create_secure_connect_packet(Protocol::TCP, 25565, server_public_key().as_ref()).unwrap();
+    let handshake = handle_server_handshake(packet, &server_keys).unwrap();
+    let mut server = handshake.context;
+    assert!(server.strict_sequence && server.compact_data);
+    assert!(server.rekey_policy.is_some());
+
+    // クライアントは ConnectResponse を受け取ってから機能を有効にする
+    let response = server
+        .seal_to_bytes(Message::new(Command::ConnectResponse, vec![]))
+        .unwrap();
+    client.open_bytes(&response).unwrap();
+    client.apply_protocol(&handshake.protocol_info);
+
+    let pong = server
+        .seal_to_bytes(Message::new(Command::Pong, vec![]))
+        .unwrap();
+    assert!(client.open_bytes(&pong).is_ok());
+    assert!(client.open_bytes(&pong).is_err());
+
+    let rekey = server.rekey_message().unwrap();
+    client.open_bytes(&rekey).unwrap();
+    let frame = server.seal_to_bytes(data(b"tunnel")).unwrap();
+    assert_eq!(client.open_bytes(&frame).unwrap().payload, b"tunnel");
+}
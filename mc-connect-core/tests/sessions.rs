@@ -0,0 +1,113 @@
+//! ゲートウェイの中継中セッションの一覧とイベントの結合テスト
+
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use mc_connect_core::GatewayOptions;
+use mc_connect_core::encryption::{CryptoKeyPair, ServerKeyring};
+use mc_connect_core::models::packet::Protocol;
+use mc_connect_core::services::proxy::{SessionEvent, SessionEventKind, SessionRegistry};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::broadcast;
+use tokio::time::timeout;
+
+fn start_gateway(port: u16, sessions: &Arc<SessionRegistry>) -> TestGateway {
+    TestGateway::start_with_options(
+        allow_tcp(port),
+        ServerKeyring::new(server_key()),
+        GatewayOptions {
+            sessions: Some(Arc::clone(sessions)),
+            ..GatewayOptions::default()
+        },
+    )
+}
+
+/// 指定した種類のイベントが届くまで待ち、そのイベントを返します。
+async fn wait_for(
+    events: &mut broadcast::Receiver<SessionEvent>,
+    kind: SessionEventKind,
+) -> SessionEvent {
+    timeout(TIMEOUT, async {
+        loop {
+            let event = events.recv().await.expect("イベントを受信できませんでした");
+            if event.kind == kind {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("イベントが届きませんでした")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn relayed_session_is_listed_until_closed() {
+    let sessions = Arc::new(SessionRegistry::new());
+    let mut events = sessions.subscribe();
+    let echo = spawn_echo_server().await;
+    let gateway = start_gateway(echo.port(), &sessions);
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    let data = pattern(3_000, 11);
+    let mut stream = tunnel.connect().await;
+    stream.write_all(&data).await.unwrap();
+    let mut received = vec![0u8; data.len()];
+    timeout(TIMEOUT, stream.read_exact(&mut received))
+        .await
+        .expect("往復がタイムアウトしました")
+        .unwrap();
+    assert_eq!(received, data);
+
+    let connected = wait_for(&mut events, SessionEventKind::Connected).await;
+    assert_eq!(connected.session.port, echo.port());
+    assert_eq!(connected.session.protocol, Protocol::TCP);
+    assert_eq!(
+        connected.session.key_fingerprint,
+        server_key().fingerprint()
+    );
+    assert!(connected.session.peer_addr.starts_with("127.0.0.1:"));
+    assert_eq!(connected.reason, None);
+
+    let listed = sessions.list();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, connected.session.id);
+
+    // 通信量は一定間隔で反映される
+    let traffic = wait_for(&mut events, SessionEventKind::Traffic).await;
+    assert_eq!(traffic.session.id, connected.session.id);
+    assert_eq!(traffic.session.upload_total, data.len() as u64);
+
+    drop(stream);
+    let closed = wait_for(&mut events, SessionEventKind::Disconnected).await;
+    assert_eq!(closed.session.id, connected.session.id);
+    assert_eq!(closed.session.upload_total, data.len() as u64);
+    assert_eq!(closed.session.download_total, data.len() as u64);
+    assert!(closed.reason.is_some_and(|r| !r.is_empty()));
+    assert!(sessions.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejected_session_is_not_listed() {
+    let sessions = Arc::new(SessionRegistry::new());
+    let mut events = sessions.subscribe();
+    let echo = spawn_echo_server().await;
+    let gateway = start_gateway(echo.port(), &sessions);
+
+    let disallowed = echo.port().wrapping_add(1);
+    let result = timeout(
+        TIMEOUT,
+        mc_connect_core::WsClientService::check_connectivity(
+            &gateway.ws_url,
+            disallowed,
+            Protocol::TCP,
+            server_public_key(),
+        ),
+    )
+    .await
+    .expect("接続テストがタイムアウトしました");
+    assert!(result.is_err());
+
+    assert!(sessions.is_empty());
+    assert!(events.try_recv().is_err());
+}
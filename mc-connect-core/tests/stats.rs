@@ -0,0 +1,158 @@
+//! クライアント側の転送速度 (EWMA と履歴) と RTT の計測のテスト
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use mc_connect_core::encryption::{ServerKeyring, handle_server_handshake};
+use mc_connect_core::models::packet::{Command, ConnectResponsePayload, Message, encode_payload};
+use mc_connect_core::services::ws_client::stats::SPEED_HISTORY_LEN;
+use mc_connect_core::services::ws_client::{SpeedSample, TunnelStats};
+use tokio::net::TcpListener;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+
+#[test]
+fn speed_is_smoothed_with_ewma() {
+    let stats = TunnelStats::new();
+
+    // 最初の計測値はそのまま使用する
+    stats.upload_total.store(1000, Ordering::Relaxed);
+    stats.sample(Duration::from_secs(1));
+    assert_eq!(stats.upload_speed.load(Ordering::Relaxed), 1000);
+
+    // 以降は直近の値と過去の値を平滑化する
+    stats.upload_total.store(4000, Ordering::Relaxed);
+    stats.download_total.store(2000, Ordering::Relaxed);
+    stats.sample(Duration::from_secs(2));
+    let upload = stats.upload_speed.load(Ordering::Relaxed);
+    assert!(upload > 1000 && upload < 1500, "{}", upload);
+    assert_eq!(stats.download_speed.load(Ordering::Relaxed), 500);
+
+    // 転送が止まると速度は徐々に 0 へ近づく
+    for _ in 0..20 {
+        stats.sample(Duration::from_secs(1));
+    }
+    assert_eq!(stats.upload_speed.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn history_keeps_latest_samples() {
+    let stats = TunnelStats::new();
+    for i in 1..=(SPEED_HISTORY_LEN as u64 + 5) {
+        stats.upload_total.store(i * 100, Ordering::Relaxed);
+        stats.sample(Duration::from_secs(1));
+    }
+    let history = stats.speed_history();
+    assert_eq!(history.len(), SPEED_HISTORY_LEN);
+    assert_eq!(
+        history.last(),
+        Some(&SpeedSample {
+            upload_speed: 100,
+            download_speed: 0
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tunnel_publishes_speed_updates() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+    let stats: Arc<TunnelStats> = Arc::clone(&tunnel.stats);
+    let mut updates = stats.subscribe();
+
+    let data = pattern(256 * 1024, 2);
+    let received = timeout(TIMEOUT, round_trip(tunnel.connect().await, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+
+    // 計測タスクが転送量を反映したスナップショットを通知すること
+    timeout(TIMEOUT, async {
+        loop {
+            updates.changed().await.unwrap();
+            let snapshot = updates.borrow_and_update().clone();
+            if snapshot.upload_total >= data.len() as u64 {
+                assert!(snapshot.upload_speed > 0);
+                break;
+            }
+        }
+    })
+    .await
+    .expect("速度の通知がタイムアウトしました");
+}
+
+/// Ping を受け取ってから `delay` だけ待って Pong を返す、最小限のゲートウェイを起動します。
+/// ターゲットへは接続せず、ハンドシェイクと Ping / Pong だけを処理します。
+async fn spawn_slow_pong_gateway(delay: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+        let bin = ws.next().await.unwrap().unwrap().into_data();
+        let keys = ServerKeyring::new(server_key());
+        let handshake = handle_server_handshake(Message::from_slice(&bin).unwrap(), &keys).unwrap();
+        let mut context = handshake.context;
+        let res = ConnectResponsePayload {
+            success: true,
+            message: "OK".to_string(),
+            key_rotation: None,
+            protocol_info: Some(handshake.protocol_info),
+            algorithm: None,
+            code: None,
+            params: Default::default(),
+        };
+        let msg = Message::new(Command::ConnectResponse, encode_payload(&res).unwrap());
+        let bin = context.seal_to_bytes(msg).unwrap();
+        ws.send(WsMessage::Binary(bin)).await.unwrap();
+
+        while let Some(Ok(WsMessage::Binary(bin))) = ws.next().await {
+            let packet = context.open_bytes(&bin).unwrap();
+            if packet.command == Command::Ping {
+                sleep(delay).await;
+                let pong = Message::new(Command::Pong, packet.payload);
+                let bin = context.seal_to_bytes(pong).unwrap();
+                ws.send(WsMessage::Binary(bin)).await.unwrap();
+            }
+        }
+    });
+    format!("ws://{}/ws", addr)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rtt_is_measured_against_delayed_pong() {
+    let delay = Duration::from_millis(200);
+    let ws_url = spawn_slow_pong_gateway(delay).await;
+    let tunnel = TestTunnel::start(&ws_url, 25565, server_public_key()).await;
+    let _stream = tunnel.connect().await;
+
+    // トンネルは接続直後に最初の Ping を送る
+    let rtt = timeout(TIMEOUT, async {
+        loop {
+            let rtt = tunnel.stats.last_rtt_ms.load(Ordering::Relaxed);
+            if rtt > 0 {
+                break rtt;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("RTT が計測されませんでした");
+    assert!(rtt >= delay.as_millis() as u64, "{}", rtt);
+    assert!(rtt < TIMEOUT.as_millis() as u64, "{}", rtt);
+
+    // 接続ごとの統計にも同じ RTT が記録される
+    let connections = tunnel.stats.connections.list();
+    assert_eq!(connections.len(), 1);
+    let conn_rtt = connections[0]
+        .rtt_ms
+        .expect("接続の RTT が記録されていません");
+    assert!(conn_rtt >= delay.as_millis() as u64, "{}", conn_rtt);
+}
@@ -0,0 +1,186 @@
+//! ゲートウェイの転送量の記録と上限の結合テスト
+
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use mc_connect_core::encryption::{CryptoKeyPair, ServerKeyring};
+use mc_connect_core::models::packet::Protocol;
+use mc_connect_core::services::proxy::{TrafficLedger, TrafficQuota};
+use mc_connect_core::{ErrorCode, GatewayOptions, McConnectError, WsClientService};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+fn start_gateway(port: u16, ledger: &Arc<TrafficLedger>) -> TestGateway {
+    TestGateway::start_with_options(
+        allow_tcp(port),
+        ServerKeyring::new(server_key()),
+        GatewayOptions {
+            traffic: Some(Arc::clone(ledger)),
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+            ..GatewayOptions::default()
+        },
+    )
+}
+
+/// 管理用エンドポイントへ GET を送り、ステータス行と本文を返します。
+async fn admin_get(gateway: &TestGateway, token: Option<&str>) -> (String, String) {
+    let mut stream = TcpStream::connect(gateway.addr).await.unwrap();
+    let auth = token
+        .map(|t| format!("Authorization: Bearer {}\r\n", t))
+        .unwrap_or_default();
+    stream
+        .write_all(
+            format!(
+                "GET /admin/traffic HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+                gateway.addr, auth
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    let mut response = String::new();
+    timeout(TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .expect("応答がタイムアウトしました")
+        .unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    (
+        head.lines().next().unwrap_or("").to_string(),
+        body.to_string(),
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn traffic_is_recorded_per_key_and_port() {
+    let echo = spawn_echo_server().await;
+    let ledger = Arc::new(TrafficLedger::in_memory(TrafficQuota::default()));
+    let gateway = start_gateway(echo.port(), &ledger);
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    let data = pattern(10_000, 4);
+    let received = timeout(TIMEOUT, round_trip(tunnel.connect().await, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+
+    // 送受信の合計が、使用した鍵と許可ポートの両方に記録される
+    let expected = 2 * data.len() as u64;
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    while ledger.report().total.total_bytes < expected {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "転送量が記録されませんでした"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let report = ledger.report();
+    assert_eq!(report.total.day_bytes, expected);
+    assert_eq!(
+        report.keys[&server_key().fingerprint()].total_bytes,
+        expected
+    );
+    assert_eq!(report.ports[&echo.port()].month_bytes, expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connections_are_rejected_over_quota() {
+    let echo = spawn_echo_server().await;
+    let ledger = Arc::new(TrafficLedger::in_memory(TrafficQuota {
+        daily_bytes: Some(1000),
+        monthly_bytes: None,
+    }));
+    ledger.record(&server_key().fingerprint(), echo.port(), 1000);
+    let gateway = start_gateway(echo.port(), &ledger);
+
+    let result = timeout(
+        TIMEOUT,
+        WsClientService::check_connectivity(
+            &gateway.ws_url,
+            echo.port(),
+            Protocol::TCP,
+            server_public_key(),
+        ),
+    )
+    .await
+    .expect("接続テストがタイムアウトしました");
+    let err = result.expect_err("上限を超えた接続が受け付けられました");
+    assert!(err.to_string().contains("quota"), "{}", err);
+    assert_eq!(
+        McConnectError::from_boxed(&err).map(McConnectError::code),
+        Some(ErrorCode::QuotaExceeded)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ledger_persists_across_restarts() {
+    let path = std::env::temp_dir().join(format!("mc-connect-traffic-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let ledger = TrafficLedger::open(&path, TrafficQuota::default()).unwrap();
+    ledger.record("aa:bb", 25565, 1234);
+    ledger.record("cc:dd", 25566, 766);
+    drop(ledger);
+
+    let reopened = TrafficLedger::open(&path, TrafficQuota::default()).unwrap();
+    let report = reopened.report();
+    assert_eq!(report.total.total_bytes, 2000);
+    assert_eq!(report.keys["aa:bb"].total_bytes, 1234);
+    assert_eq!(report.ports[&25566].day_bytes, 766);
+
+    drop(reopened);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn flush_writes_in_background() {
+    let path = std::env::temp_dir().join(format!(
+        "mc-connect-traffic-flush-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+
+    // flush は書き出し用のスレッドへ渡すだけで、記録を開いたまま書き出される
+    let ledger = TrafficLedger::open(&path, TrafficQuota::default()).unwrap();
+    ledger.record("aa:bb", 25565, 4321);
+    ledger.flush();
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    loop {
+        if let Ok(report) = TrafficLedger::load(&path)
+            && report.total.total_bytes == 4321
+        {
+            break;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "記録が書き出されませんでした"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    drop(ledger);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_endpoint_requires_token() {
+    let echo = spawn_echo_server().await;
+    let ledger = Arc::new(TrafficLedger::in_memory(TrafficQuota::default()));
+    ledger.record("aa:bb", echo.port(), 42);
+    let gateway = start_gateway(echo.port(), &ledger);
+
+    let (status, _) = admin_get(&gateway, None).await;
+    assert!(status.contains("401"), "{}", status);
+    let (status, _) = admin_get(&gateway, Some("wrong-token")).await;
+    assert!(status.contains("401"), "{}", status);
+
+    let (status, body) = admin_get(&gateway, Some(ADMIN_TOKEN)).await;
+    assert!(status.contains("200"), "{}", status);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["total"]["total_bytes"], 42);
+    assert_eq!(report["keys"]["aa:bb"]["total_bytes"], 42);
+}
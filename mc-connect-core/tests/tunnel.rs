@@ -0,0 +1,526 @@
+//! ゲートウェイとクライアントトンネルを通した結合テスト
+
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use mc_connect_core::encryption::{
+    CryptoKeyPair, RsaKeyPair, RsaPublicKey, ServerKeyring, Signer, sign_key_rotation,
+    verify_key_rotation, verify_server_key,
+};
+use mc_connect_core::models::packet::{AllowedPort, KeyRotation, Protocol};
+use mc_connect_core::services::ws_client::{ConnectionState, KnownServers};
+use mc_connect_core::{ErrorCode, McConnectError, WsClientService};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tcp_round_trip_is_byte_exact() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    let mut stream = tunnel.connect().await;
+    for (i, size) in [1usize, 7, 255, 4096, 8192, 8193, 65_536]
+        .into_iter()
+        .enumerate()
+    {
+        let data = pattern(size, i as u32);
+        stream.write_all(&data).await.unwrap();
+
+        let mut received = vec![0u8; size];
+        timeout(TIMEOUT, stream.read_exact(&mut received))
+            .await
+            .expect("応答がタイムアウトしました")
+            .unwrap();
+        assert_eq!(
+            received, data,
+            "{} バイトの往復でデータが一致しません",
+            size
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn large_transfer_round_trip() {
+    const SIZE: usize = 4 * 1024 * 1024;
+
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    let data = pattern(SIZE, 42);
+    let received = timeout(TIMEOUT, round_trip(tunnel.connect().await, &data))
+        .await
+        .expect("大容量転送がタイムアウトしました");
+    assert!(received == data, "大容量転送でデータが一致しません");
+
+    let stats = tunnel.stats.get_snapshot();
+    assert_eq!(stats.upload_total, SIZE as u64);
+    assert_eq!(stats.download_total, SIZE as u64);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_connections_are_isolated() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    let mut tasks = Vec::new();
+    for seed in 0..8 {
+        let stream = tunnel.connect().await;
+        tasks.push(tokio::spawn(async move {
+            let data = pattern(256 * 1024, seed);
+            let received = round_trip(stream, &data).await;
+            assert!(received == data, "接続 {} のデータが混線しています", seed);
+        }));
+    }
+    for task in tasks {
+        timeout(TIMEOUT, task).await.unwrap().unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fake_minecraft_handshake_is_forwarded() {
+    const STATUS: &[u8] = b"\x0f\x00\x0d{\"motd\":\"hi\"}";
+
+    let target = spawn_fake_minecraft(STATUS).await;
+    let gateway = TestGateway::start(allow_tcp(target.port()));
+    let tunnel = TestTunnel::start(&gateway.ws_url, target.port(), server_public_key()).await;
+
+    // 長さ 6 のハンドシェイクパケット (ID 0x00, プロトコル 765, 空ホスト, ポート, 次状態 1)
+    let mut stream = tunnel.connect().await;
+    stream
+        .write_all(&[0x06, 0x00, 0xfd, 0x05, 0x00, 0x63, 0xdd])
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    timeout(TIMEOUT, stream.read_to_end(&mut received))
+        .await
+        .expect("ターゲットからの応答がタイムアウトしました")
+        .unwrap();
+    assert_eq!(received, STATUS);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn handshake_succeeds_with_correct_key() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+
+    let rotated = WsClientService::check_connectivity(
+        &gateway.ws_url,
+        echo.port(),
+        Protocol::TCP,
+        server_public_key(),
+    )
+    .await
+    .expect("正しい公開鍵でのハンドシェイクに失敗しました");
+    assert!(rotated.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn handshake_fails_with_wrong_server_key() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+
+    let wrong_key = generate_key();
+    let wrong_public = Arc::new(RsaPublicKey::from_der(&wrong_key.public_key_bytes()).unwrap());
+    let result = WsClientService::check_connectivity(
+        &gateway.ws_url,
+        echo.port(),
+        Protocol::TCP,
+        wrong_public,
+    )
+    .await;
+    assert!(
+        result.is_err(),
+        "異なる公開鍵でハンドシェイクが成功してしまいました"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tunnel_with_wrong_key_closes_local_connection() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+
+    let wrong_key = generate_key();
+    let wrong_public = Arc::new(RsaPublicKey::from_der(&wrong_key.public_key_bytes()).unwrap());
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), wrong_public).await;
+
+    let mut stream = tunnel.connect().await;
+    let _ = stream.write_all(b"hello").await;
+    let mut buf = Vec::new();
+    let read = timeout(TIMEOUT, stream.read_to_end(&mut buf))
+        .await
+        .expect("ローカル接続が閉じられませんでした");
+    assert!(
+        read.is_err() || buf.is_empty(),
+        "データが転送されてしまいました"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disallowed_port_is_rejected() {
+    let echo = spawn_echo_server().await;
+    let other = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(other.port()));
+
+    let result = WsClientService::check_connectivity(
+        &gateway.ws_url,
+        echo.port(),
+        Protocol::TCP,
+        server_public_key(),
+    )
+    .await;
+    let err = result.expect_err("許可されていないポートへの接続が成功してしまいました");
+    assert!(
+        err.to_string().contains("Unauthorized"),
+        "想定外のエラー: {}",
+        err
+    );
+    let err = McConnectError::from_boxed(&err).expect("McConnectError ではありません");
+    assert_eq!(err.code(), ErrorCode::PortNotAllowed);
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connection_failures_carry_error_codes() {
+    let code_of = |result: Result<Option<Vec<u8>>, _>| {
+        let err = result.expect_err("接続が成功してしまいました");
+        McConnectError::from_boxed(&err)
+            .map(McConnectError::code)
+            .unwrap_or_else(|| panic!("McConnectError ではありません: {}", err))
+    };
+
+    let result =
+        WsClientService::check_connectivity("not a url", 25565, Protocol::TCP, server_public_key())
+            .await;
+    assert_eq!(code_of(result), ErrorCode::InvalidUrl);
+
+    // 待ち受けていないポートへの接続は、時間をおいて再試行できる失敗として扱う
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let result = WsClientService::check_connectivity(
+        &format!("ws://{}/ws", closed),
+        25565,
+        Protocol::TCP,
+        server_public_key(),
+    )
+    .await;
+    let code = code_of(result);
+    assert_eq!(code, ErrorCode::WsConnectFailed);
+    assert!(code.is_retryable());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disallowed_protocol_is_rejected() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(vec![AllowedPort {
+        port: echo.port(),
+        protocol: Protocol::UDP,
+    }]);
+
+    let result = WsClientService::check_connectivity(
+        &gateway.ws_url,
+        echo.port(),
+        Protocol::TCP,
+        server_public_key(),
+    )
+    .await;
+    assert!(
+        result.is_err(),
+        "UDP のみ許可のポートに TCP で接続できてしまいました"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unreachable_target_is_reported() {
+    // 一度バインドしてすぐ閉じ、誰も待ち受けていないポートを得る
+    let closed_port = {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let gateway = TestGateway::start(allow_tcp(closed_port));
+
+    let result = WsClientService::check_connectivity(
+        &gateway.ws_url,
+        closed_port,
+        Protocol::TCP,
+        server_public_key(),
+    )
+    .await;
+    assert!(
+        result.is_err(),
+        "到達できないターゲットへの接続が成功してしまいました"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_not_running_is_reported() {
+    let closed_port = {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let result = WsClientService::check_connectivity(
+        &format!("ws://127.0.0.1:{}/ws", closed_port),
+        25565,
+        Protocol::TCP,
+        server_public_key(),
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn server_info_lists_ports_and_signs_key() {
+    let gateway = TestGateway::start(vec![
+        AllowedPort {
+            port: 25565,
+            protocol: Protocol::TCP,
+        },
+        AllowedPort {
+            port: 19132,
+            protocol: Protocol::UDP,
+        },
+    ]);
+
+    let info = WsClientService::get_server_info(&gateway.ws_url)
+        .await
+        .expect("サーバー情報の取得に失敗しました");
+    assert_eq!(info.allowed_ports.len(), 2);
+
+    let signed = info.server_key.expect("署名付きの公開鍵がありません");
+    assert_eq!(signed.public_key, server_key().public_key_bytes());
+    // 別のチャレンジに対する署名としては検証に失敗する
+    assert!(verify_server_key(&signed, b"another challenge").is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retired_key_handshake_returns_rotation() {
+    let echo = spawn_echo_server().await;
+    let old_key = generate_key();
+    let old_public = Arc::new(RsaPublicKey::from_der(&old_key.public_key_bytes()).unwrap());
+
+    let mut keys = ServerKeyring::new(server_key());
+    keys.add_retired(
+        RsaKeyPair::from_private_der(&old_key.private_key_bytes()).unwrap(),
+        u64::MAX,
+    )
+    .unwrap();
+    let gateway = TestGateway::start_with_keys(allow_tcp(echo.port()), keys);
+
+    let rotated = WsClientService::check_connectivity(
+        &gateway.ws_url,
+        echo.port(),
+        Protocol::TCP,
+        old_public,
+    )
+    .await
+    .expect("旧鍵でのハンドシェイクに失敗しました");
+    assert_eq!(rotated, Some(server_key().public_key_bytes()));
+}
+
+/// テストごとに固有の既知サーバーファイルのパスを返します。
+fn known_servers_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "mc-connect-known-{}-{}.json",
+        name,
+        std::process::id()
+    ))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tofu_pin_follows_verified_rotation() {
+    let echo = spawn_echo_server().await;
+    let old_key = generate_key();
+    let old_der = old_key.public_key_bytes();
+    let old_pair = || RsaKeyPair::from_private_der(&old_key.private_key_bytes()).unwrap();
+    let gateway =
+        TestGateway::start_with_keys(allow_tcp(echo.port()), ServerKeyring::new(old_pair()));
+
+    let path = known_servers_path("rotation");
+    let _ = std::fs::remove_file(&path);
+    let mut known = KnownServers::load(&path).unwrap();
+    let pinned = WsClientService::trust_on_first_use(&gateway.ws_url, &mut known)
+        .await
+        .expect("初回接続での記録に失敗しました");
+    assert_eq!(pinned, old_der);
+
+    // 旧鍵を退役させ、同じアドレスで新しい鍵に切り替える
+    let mut keys = ServerKeyring::new(server_key());
+    keys.add_retired(old_pair(), u64::MAX).unwrap();
+    let gateway = gateway
+        .restart_with_keys(allow_tcp(echo.port()), keys)
+        .await;
+
+    let mut known = KnownServers::load(&path).unwrap();
+    let key = WsClientService::trust_on_first_use(&gateway.ws_url, &mut known)
+        .await
+        .expect("検証済みのローテーションが鍵の不一致として拒否されました");
+    let new_der = server_key().public_key_bytes();
+    assert_eq!(key, new_der);
+    assert_eq!(known.get(&gateway.ws_url), Some(new_der.clone()));
+    assert_eq!(
+        KnownServers::load(&path).unwrap().get(&gateway.ws_url),
+        Some(new_der),
+        "更新した記録が保存されていません"
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tofu_pin_rejects_unannounced_key_change() {
+    let echo = spawn_echo_server().await;
+    let old_key = generate_key();
+    let old_der = old_key.public_key_bytes();
+    let gateway = TestGateway::start_with_keys(
+        allow_tcp(echo.port()),
+        ServerKeyring::new(RsaKeyPair::from_private_der(&old_key.private_key_bytes()).unwrap()),
+    );
+
+    let path = known_servers_path("mismatch");
+    let _ = std::fs::remove_file(&path);
+    let mut known = KnownServers::load(&path).unwrap();
+    WsClientService::trust_on_first_use(&gateway.ws_url, &mut known)
+        .await
+        .expect("初回接続での記録に失敗しました");
+
+    // 旧鍵によるローテーション通知なしに鍵だけが変わった場合は受け付けない
+    let gateway = gateway
+        .restart_with_keys(allow_tcp(echo.port()), ServerKeyring::new(server_key()))
+        .await;
+    let mut known = KnownServers::load(&path).unwrap();
+    let result = WsClientService::trust_on_first_use(&gateway.ws_url, &mut known).await;
+    assert!(
+        result.is_err(),
+        "通知のない鍵の変更を受け付けてしまいました"
+    );
+    assert_eq!(known.get(&gateway.ws_url), Some(old_der));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn rotation_signature_is_bound_to_its_context() {
+    let old_key = RsaKeyPair::from_private_der(&generate_key().private_key_bytes()).unwrap();
+    let old_public = RsaPublicKey::from_der(&old_key.public_key_bytes()).unwrap();
+    let new_public_key = server_key().public_key_bytes();
+
+    let rotation = KeyRotation {
+        new_public_key: new_public_key.clone(),
+        signature: sign_key_rotation(&old_key, &new_public_key).unwrap(),
+        expires_at: u64::MAX,
+    };
+    assert_eq!(
+        verify_key_rotation(&old_public, &rotation).unwrap(),
+        new_public_key
+    );
+
+    // コンテキストを付けずに公開鍵そのものへ署名したもの (他の用途の署名) は受け付けない
+    let unbound = KeyRotation {
+        signature: old_key.sign(&new_public_key).unwrap(),
+        ..rotation
+    };
+    assert!(verify_key_rotation(&old_public, &unbound).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_pushes_session_stats() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    // 統計情報はセッションが続いている間だけ届くため、接続は開いたままにする
+    let mut stream = tunnel.connect().await;
+    let data = pattern(16_384, 5);
+    stream.write_all(&data).await.unwrap();
+    let mut received = vec![0u8; data.len()];
+    timeout(TIMEOUT, stream.read_exact(&mut received))
+        .await
+        .expect("応答がタイムアウトしました")
+        .unwrap();
+    assert_eq!(received, data);
+
+    // ゲートウェイから見た転送量と接続遅延が定期的に届くこと
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    loop {
+        if let Some(stats) = tunnel.stats.gateway_snapshot()
+            && stats.upload_total >= data.len() as u64
+            && stats.download_total >= data.len() as u64
+        {
+            assert!(stats.connect_latency_ms.is_some());
+            assert!(stats.queue_depth.is_some());
+            break;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "ゲートウェイの統計情報が届きませんでした"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connections_are_listed_and_closed_individually() {
+    let echo = spawn_echo_server().await;
+    let gateway = TestGateway::start(allow_tcp(echo.port()));
+    let tunnel = TestTunnel::start(&gateway.ws_url, echo.port(), server_public_key()).await;
+
+    let data = pattern(4096, 9);
+    let mut first = tunnel.connect().await;
+    let mut second = tunnel.connect().await;
+    for stream in [&mut first, &mut second] {
+        stream.write_all(&data).await.unwrap();
+        let mut received = vec![0u8; data.len()];
+        timeout(TIMEOUT, stream.read_exact(&mut received))
+            .await
+            .expect("応答がタイムアウトしました")
+            .unwrap();
+    }
+
+    let connections = tunnel.stats.connections.list();
+    assert_eq!(connections.len(), 2);
+    for conn in &connections {
+        assert_eq!(conn.state, ConnectionState::Active);
+        assert_eq!(conn.upload_total, data.len() as u64);
+        assert_eq!(conn.download_total, data.len() as u64);
+    }
+    let first_id = connections
+        .iter()
+        .find(|c| c.peer_addr == first.local_addr().unwrap().to_string())
+        .expect("接続元のアドレスが一覧にありません")
+        .id;
+
+    // 1 本だけ閉じると、その接続だけが切断される
+    assert!(tunnel.stats.connections.close(first_id));
+    let mut buf = [0u8; 1];
+    let read = timeout(TIMEOUT, first.read(&mut buf))
+        .await
+        .expect("切断がタイムアウトしました");
+    assert!(matches!(read, Ok(0) | Err(_)));
+
+    let received = timeout(TIMEOUT, round_trip(second, &data))
+        .await
+        .expect("往復がタイムアウトしました");
+    assert_eq!(received, data);
+
+    // 閉じた接続は一覧から外れる
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    while tunnel
+        .stats
+        .connections
+        .list()
+        .iter()
+        .any(|c| c.id == first_id)
+    {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "閉じた接続が一覧に残っています"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+}